@@ -0,0 +1,194 @@
+use serde::Deserialize;
+use std::fs::File;
+#[cfg(windows)]
+use std::fs::OpenOptions;
+use std::io::{self, Read};
+use std::path::Path;
+#[cfg(windows)]
+use std::process::Command;
+use tauri::AppHandle;
+
+use crate::blob_store::sha256_hex;
+use crate::patient;
+
+// The AutoHotkey installer is downloaded from a fixed release and only runs
+// once it's trusted, checked in this order against `autohotkey_installer`
+// in the app config:
+// - `sha256`: its SHA-256 is one of the list;
+// - `signer_thumbprint`: it has a valid Authenticode signature from the
+//   certificate with this thumbprint;
+// - `signer_subject` (SIGNER_SUBJECT by default): it has a valid Authenticode
+//   signature, chained to a root Windows trusts, issued to this organization.
+// The first one set decides; with all of them emptied the download is
+// refused. The default survives certificate renewals and new releases, so
+// INSTALLER_URL can move on its own.
+pub const INSTALLER_URL: &str = "https://www.autohotkey.com/download/2.0/AutoHotkey_2.0.19_setup.exe";
+const SIGNER_SUBJECT: &str = "AutoHotkey Foundation LLC";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct InstallerTrust {
+    pub sha256: Vec<String>,
+    pub signer_thumbprint: String,
+    pub signer_subject: String,
+}
+
+impl Default for InstallerTrust {
+    fn default() -> Self {
+        Self {
+            sha256: Vec::new(),
+            signer_thumbprint: String::new(),
+            signer_subject: SIGNER_SUBJECT.to_string(),
+        }
+    }
+}
+
+impl InstallerTrust {
+    // Whether a download can be trusted at all; the signature checks need
+    // Windows
+    pub fn can_verify(&self) -> bool {
+        !self.sha256.is_empty()
+            || (cfg!(windows) && !(self.signer_thumbprint.trim().is_empty() && self.signer_subject.trim().is_empty()))
+    }
+}
+
+// CN and O of a certificate subject such as "CN=Name, O=Name, L=City, C=US"
+fn subject_names(subject: &str) -> Vec<&str> {
+    subject
+        .split(',')
+        .filter_map(|part| part.trim().split_once('='))
+        .filter(|(key, _)| matches!(key.trim(), "CN" | "O"))
+        .map(|(_, value)| value.trim().trim_matches('"'))
+        .collect()
+}
+
+pub fn configured_trust(app_handle: &AppHandle) -> InstallerTrust {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get("autohotkey_installer").cloned())
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+const MANUAL_INSTALL: &str = "Instale manualmente o AutoHotkey V2 a partir de https://www.autohotkey.com \
+     ou copie o AutoHotkey64.exe para a pasta resources/AutoHotkey/v2.";
+
+// Status, signer subject and certificate thumbprint from
+// Get-AuthenticodeSignature
+#[cfg(windows)]
+fn authenticode(path: &Path) -> Result<(String, String, String), String> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let script = format!(
+        "$s = Get-AuthenticodeSignature -LiteralPath '{}'; $s.Status.ToString(); if ($s.SignerCertificate) {{ $s.SignerCertificate.Subject; $s.SignerCertificate.Thumbprint }}",
+        path.to_string_lossy().replace('\'', "''")
+    );
+    let output = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .map_err(|e| format!("Falha ao verificar a assinatura do instalador: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Falha ao verificar a assinatura do instalador: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut lines = text.lines().map(str::trim);
+    let status = lines.next().unwrap_or_default().to_string();
+    let subject = lines.next().unwrap_or_default().to_string();
+    let thumbprint = lines.next().unwrap_or_default().to_string();
+    Ok((status, subject, thumbprint))
+}
+
+#[cfg(not(windows))]
+fn authenticode(_path: &Path) -> Result<(String, String, String), String> {
+    Err("A assinatura Authenticode só pode ser verificada no Windows.".into())
+}
+
+// Opened so nothing can write to the file while the handle is held
+#[cfg(windows)]
+fn open_locked(path: &Path) -> io::Result<File> {
+    use std::os::windows::fs::OpenOptionsExt;
+    const FILE_SHARE_READ: u32 = 0x0000_0001;
+    OpenOptions::new().read(true).share_mode(FILE_SHARE_READ).open(path)
+}
+
+#[cfg(not(windows))]
+fn open_locked(path: &Path) -> io::Result<File> {
+    File::open(path)
+}
+
+// Checks the installer at `path`, hashing it as it is on disk. The returned
+// handle keeps the file from being replaced; hold it until the installer has
+// run, so what was checked is what runs.
+pub fn verify(app_handle: &AppHandle, path: &Path) -> Result<File, String> {
+    let trust = configured_trust(app_handle);
+    let mut file = open_locked(path).map_err(|e| format!("Falha ao abrir o instalador baixado: {}", e))?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)
+        .map_err(|e| format!("Falha ao ler o instalador baixado: {}", e))?;
+    let digest = sha256_hex(&data);
+
+    if !trust.sha256.is_empty() {
+        if trust.sha256.iter().any(|expected| expected.trim().eq_ignore_ascii_case(&digest)) {
+            return Ok(file);
+        }
+        return Err(format!(
+            "O instalador baixado não corresponde ao checksum configurado (SHA-256 {}). \
+             O download pode ter sido adulterado; confira o checksum em https://www.autohotkey.com \
+             antes de atualizar \"autohotkey_installer.sha256\". {}",
+            digest, MANUAL_INSTALL
+        ));
+    }
+
+    let expected = trust.signer_thumbprint.trim();
+    let expected_subject = trust.signer_subject.trim();
+    if expected.is_empty() && expected_subject.is_empty() {
+        return Err(format!(
+            "Nenhum checksum ou certificado confiável configurado para o instalador do AutoHotkey (SHA-256 {}). \
+             Preencha \"autohotkey_installer.sha256\", \"autohotkey_installer.signer_thumbprint\" \
+             ou \"autohotkey_installer.signer_subject\". {}",
+            digest, MANUAL_INSTALL
+        ));
+    }
+    let (status, subject, thumbprint) = authenticode(path).map_err(|e| format!("{} {}", e, MANUAL_INSTALL))?;
+    if status != "Valid" {
+        return Err(format!(
+            "O instalador baixado não tem assinatura digital válida ({}; SHA-256 {}). {}",
+            if status.is_empty() { "sem status" } else { status.as_str() },
+            digest,
+            MANUAL_INSTALL
+        ));
+    }
+    if !expected.is_empty() {
+        if !thumbprint.eq_ignore_ascii_case(expected) {
+            return Err(format!(
+                "O instalador baixado foi assinado por \"{}\" (certificado {}), não pelo certificado {} (SHA-256 {}). {}",
+                subject, thumbprint, expected, digest, MANUAL_INSTALL
+            ));
+        }
+        return Ok(file);
+    }
+    if !subject_names(&subject).iter().any(|name| name.eq_ignore_ascii_case(expected_subject)) {
+        return Err(format!(
+            "O instalador baixado foi assinado por \"{}\" (certificado {}), não por \"{}\" (SHA-256 {}). {}",
+            subject, thumbprint, expected_subject, digest, MANUAL_INSTALL
+        ));
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subject_names_reads_cn_and_o() {
+        let subject = "CN=AutoHotkey Foundation LLC, O=\"AutoHotkey Foundation LLC\", L=Dover, C=US";
+        assert_eq!(subject_names(subject), vec!["AutoHotkey Foundation LLC", "AutoHotkey Foundation LLC"]);
+        assert!(subject_names("OU=AutoHotkey Foundation LLC").is_empty());
+    }
+}
@@ -0,0 +1,163 @@
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use tauri::AppHandle;
+
+use crate::patient;
+
+// `ahk_script_template` in the app config names a file the AutoHotkey script
+// is rendered from instead of the built-in one, for extra hotstrings or window
+// conditions. A relative path is taken from the data directory. Placeholders
+// are `{{name}}`; an unknown one is an error rather than text AutoHotkey
+// would choke on.
+const DIRECTIVES: &str = "directives";
+const HOTKEYS: &str = "hotkeys";
+// Folder of the rendered script, e.g. for `#Include {{script_dir}}\...`
+const SCRIPT_DIR: &str = "script_dir";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateCheck {
+    pub path: String,
+    pub placeholders: Vec<String>,
+}
+
+fn configured_path(app_handle: &AppHandle) -> Result<Option<PathBuf>, String> {
+    let path = patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get("ahk_script_template").and_then(|v| v.as_str()).map(str::to_string))
+        .filter(|p| !p.trim().is_empty());
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let path = PathBuf::from(path.trim());
+    if path.is_absolute() {
+        return Ok(Some(path));
+    }
+    let data_dir = patient::ensure_data_dir(app_handle).map_err(|e| e.to_string())?;
+    Ok(Some(data_dir.join(path)))
+}
+
+// Names between `{{` and `}}`, in order of appearance
+fn placeholders(template: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            break;
+        };
+        let name = after[..end].trim();
+        if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            names.push(name.to_string());
+            rest = &after[end + 2..];
+        } else {
+            rest = after;
+        }
+    }
+    names
+}
+
+pub fn validate(template: &str) -> Result<Vec<String>, String> {
+    let found = placeholders(template);
+    let unknown: Vec<&str> = found
+        .iter()
+        .map(String::as_str)
+        .filter(|name| ![DIRECTIVES, HOTKEYS, SCRIPT_DIR].contains(name))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(format!("Marcadores desconhecidos no modelo de script: {{{{{}}}}}", unknown.join("}}, {{")));
+    }
+    for required in [DIRECTIVES, HOTKEYS] {
+        match found.iter().filter(|name| *name == required).count() {
+            0 => return Err(format!("O modelo de script precisa do marcador {{{{{}}}}}.", required)),
+            1 => {}
+            _ => return Err(format!("O marcador {{{{{}}}}} deve aparecer uma única vez no modelo de script.", required)),
+        }
+    }
+    Ok(found)
+}
+
+// The configured template, already validated; None uses the built-in script
+pub fn load(app_handle: &AppHandle) -> Result<Option<String>, String> {
+    let Some(path) = configured_path(app_handle)? else {
+        return Ok(None);
+    };
+    let template = fs::read_to_string(&path)
+        .map_err(|e| format!("Falha ao ler o modelo de script {}: {}", path.display(), e))?;
+    validate(&template).map_err(|e| format!("{} ({})", e, path.display()))?;
+    Ok(Some(template))
+}
+
+pub fn render(template: &str, directives: &str, hotkeys: &str, script_dir: &str) -> String {
+    // Same placeholder syntax the validation accepts, spaces included
+    let mut rendered = String::with_capacity(template.len() + hotkeys.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let replacement = after.find("}}").and_then(|end| {
+            let value = match after[..end].trim() {
+                DIRECTIVES => directives,
+                HOTKEYS => hotkeys,
+                SCRIPT_DIR => script_dir,
+                _ => return None,
+            };
+            Some((value, end))
+        });
+        match replacement {
+            Some((value, end)) => {
+                rendered.push_str(&rest[..start]);
+                rendered.push_str(value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                rendered.push_str(&rest[..start + 2]);
+                rest = after;
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+// Checks the template at `path`, or the configured one
+#[tauri::command]
+pub fn check_ahk_script_template(app_handle: AppHandle, path: Option<String>) -> Result<TemplateCheck, String> {
+    let path = match path.filter(|p| !p.trim().is_empty()) {
+        Some(path) => PathBuf::from(path.trim()),
+        None => configured_path(&app_handle)?.ok_or("Nenhum modelo de script configurado.")?,
+    };
+    let template = fs::read_to_string(&path)
+        .map_err(|e| format!("Falha ao ler o modelo de script {}: {}", path.display(), e))?;
+    Ok(TemplateCheck {
+        path: path.display().to_string(),
+        placeholders: validate(&template)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_are_listed_in_order_spaces_allowed() {
+        assert_eq!(placeholders("a {{directives}} b {{ hotkeys }} {{x y}} {{}}"), vec!["directives", "hotkeys"]);
+        assert_eq!(placeholders("{{hotkeys"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn validate_requires_each_placeholder_once() {
+        assert!(validate("{{directives}}\n{{hotkeys}}\n{{script_dir}}").is_ok());
+        assert!(validate("{{hotkeys}}").is_err());
+        assert!(validate("{{directives}}{{hotkeys}}{{hotkeys}}").is_err());
+        assert!(validate("{{directives}}{{hotkeys}}{{other}}").is_err());
+    }
+
+    #[test]
+    fn render_fills_placeholders_and_keeps_other_braces() {
+        let template = "{{ directives }}; {{other}} {{script_dir}}\\x.ahk\n{{hotkeys}}";
+        assert_eq!(
+            render(template, "#Requires", "^q::Send", "C:\\tmp"),
+            "#Requires; {{other}} C:\\tmp\\x.ahk\n^q::Send"
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use base64::{engine::general_purpose as b64, Engine};
+use image::{DynamicImage, ImageFormat, ImageOutputFormat};
+use serde::Serialize;
+use std::fs;
+use std::io::Cursor;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+use crate::beneficiary_import::finger_code;
+use crate::beneficiary_sync::iso_utc;
+use crate::fingerprint::{self, TemplateFormat};
+use crate::patient;
+use crate::wsq;
+
+// Traditional encoding separators
+const FS: u8 = 0x1C;
+const GS: u8 = 0x1D;
+const RS: u8 = 0x1E;
+const US: u8 = 0x1F;
+
+// ANSI/NIST-ITL 1-2011
+const VERSION: &str = "0500";
+// Criminal Ten-Print Submission, accepted by most EBTS tooling
+const DEFAULT_TRANSACTION_TYPE: &str = "CAR";
+// Originating and destination agencies; placeholders for test data
+const AGENCY: &str = "VIOH00000";
+// Fingerprints are assumed scanned at 500 ppi, the TOTVS readers' resolution
+const FINGER_PPI: u32 = 500;
+const FINGER_PPMM: &str = "19.69";
+const FACE_JPEG_QUALITY: u8 = 90;
+
+#[derive(Debug, Serialize)]
+pub struct AnsiNistExport {
+    pub path: String,
+    pub fingerprints: usize,
+    pub has_photo: bool,
+    // Fingers left out, with the reason
+    pub skipped: Vec<String>,
+}
+
+enum Value<'a> {
+    Text(String),
+    Binary(&'a [u8]),
+}
+
+// One logical record. Fields are written in the order added; LEN (field 1)
+// is computed on encoding.
+struct Record<'a> {
+    record_type: u8,
+    fields: Vec<(u16, Value<'a>)>,
+}
+
+impl<'a> Record<'a> {
+    fn new(record_type: u8) -> Self {
+        Self { record_type, fields: Vec::new() }
+    }
+
+    fn text(mut self, field: u16, value: impl Into<String>) -> Self {
+        self.fields.push((field, Value::Text(value.into())));
+        self
+    }
+
+    fn binary(mut self, field: u16, value: &'a [u8]) -> Self {
+        self.fields.push((field, Value::Binary(value)));
+        self
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (field, value) in &self.fields {
+            body.push(GS);
+            body.extend_from_slice(format!("{}.{:03}:", self.record_type, field).as_bytes());
+            match value {
+                Value::Text(text) => body.extend_from_slice(text.as_bytes()),
+                Value::Binary(data) => body.extend_from_slice(data),
+            }
+        }
+        body.push(FS);
+
+        // LEN counts itself, so grow it until the digits stop changing
+        let tag = format!("{}.001:", self.record_type);
+        let mut len = tag.len() + body.len();
+        while tag.len() + len.to_string().len() + body.len() != len {
+            len = tag.len() + len.to_string().len() + body.len();
+        }
+        let mut record = format!("{}{}", tag, len).into_bytes();
+        record.extend_from_slice(&body);
+        record
+    }
+}
+
+// TOTVS numbers fingers from the left little finger (1) to the right little
+// finger (10); ANSI/NIST from the right thumb (1) to the left little (10)
+fn finger_position(totvs_code: u32) -> u32 {
+    match totvs_code {
+        6..=10 => totvs_code - 5,
+        1..=5 => 11 - totvs_code,
+        _ => 0,
+    }
+}
+
+// WSQ as stored, or re-encoded from whatever image the template holds
+fn finger_image(template_b64: &str, bitrate: f32) -> Result<(Vec<u8>, u32, u32), String> {
+    let template = b64::STANDARD
+        .decode(template_b64.trim())
+        .map_err(|e| format!("Base64 inválido: {e}"))?;
+    let (format, image) = fingerprint::decode_template(&template, None)?;
+    let (width, height) = image.dimensions();
+    let wsq = match format {
+        TemplateFormat::Wsq => template,
+        _ => wsq::encode(&image, bitrate).map_err(|e| format!("Falha ao gerar WSQ: {e}"))?,
+    };
+    Ok((wsq, width, height))
+}
+
+// JPEG and PNG photos go as they are; anything else is re-encoded as JPEG.
+// Returns the data, the compression name, width and height.
+fn face_image(photo_b64: &str) -> Result<(Vec<u8>, &'static str, u32, u32), String> {
+    let photo = photo_b64.split_once("base64,").map_or(photo_b64, |(_, data)| data);
+    let data = b64::STANDARD
+        .decode(photo.split_whitespace().collect::<String>())
+        .map_err(|e| format!("Base64 inválido na foto: {e}"))?;
+    let format = image::guess_format(&data).map_err(|_| "Formato da foto não reconhecido.".to_string())?;
+    let image = image::load_from_memory_with_format(&data, format).map_err(|e| format!("Falha ao decodificar foto: {e}"))?;
+    let (width, height) = (image.width(), image.height());
+    match format {
+        ImageFormat::Jpeg => Ok((data, "JPEGB", width, height)),
+        ImageFormat::Png => Ok((data, "PNG", width, height)),
+        _ => {
+            let mut buf = Cursor::new(Vec::new());
+            DynamicImage::ImageRgb8(image.to_rgb8())
+                .write_to(&mut buf, ImageOutputFormat::Jpeg(FACE_JPEG_QUALITY))
+                .map_err(|e| format!("Falha ao gerar JPEG: {e}"))?;
+            Ok((buf.into_inner(), "JPEGB", width, height))
+        }
+    }
+}
+
+// Characters the Type-1 and Type-2 text fields accept
+fn printable(text: &str) -> String {
+    text.chars().filter(|c| c.is_ascii_graphic() || *c == ' ').collect::<String>().trim().to_string()
+}
+
+fn build(app_handle: &AppHandle, patient_id: u32, transaction_type: &str) -> Result<(Vec<u8>, usize, bool, Vec<String>), String> {
+    let patients = patient::load_patients_from_disk(app_handle).map_err(|e| format!("Falha ao ler pacientes: {e}"))?;
+    let patient = patients
+        .into_iter()
+        .find(|p| p.id == patient_id)
+        .ok_or_else(|| format!("Paciente {} não encontrado.", patient_id))?;
+
+    let bitrate = fingerprint::wsq_bitrate(app_handle);
+    let mut skipped = Vec::new();
+    let mut fingers = Vec::new();
+    for digital in &patient.digital_biometrics {
+        match finger_image(&digital.data, bitrate) {
+            Ok((wsq, width, height)) => {
+                fingers.push((finger_position(finger_code(&digital.finger).unwrap_or(0)), wsq, width, height))
+            }
+            Err(e) => skipped.push(format!("{}: {}", digital.finger, e)),
+        }
+    }
+    let face = if patient.main_photo().trim().is_empty() {
+        None
+    } else {
+        Some(face_image(patient.main_photo())?)
+    };
+    if fingers.is_empty() && face.is_none() {
+        return Err("O paciente não tem foto nem digitais exportáveis.".into());
+    }
+
+    let now = iso_utc(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0));
+    let date: String = now[..10].chars().filter(|c| c.is_ascii_digit()).collect();
+    let gmt: String = now.chars().filter(|c| c.is_ascii_digit()).chain(std::iter::once('Z')).collect();
+    let wallet = printable(&patient.wallet);
+
+    let mut records = vec![Record::new(2).text(2, "00").text(18, printable(&patient.name)).text(19, wallet.clone())];
+    if let Some((data, compression, width, height)) = &face {
+        records.push(
+            Record::new(10)
+                .text(2, "01")
+                .text(3, "FACE")
+                .text(4, AGENCY)
+                .text(5, date.clone())
+                .text(6, width.to_string())
+                .text(7, height.to_string())
+                .text(8, "0")
+                .text(9, "1")
+                .text(10, "1")
+                .text(11, *compression)
+                .text(12, "SRGB")
+                .text(20, "F")
+                .binary(999, data),
+        );
+    }
+    for (index, (position, wsq, width, height)) in fingers.iter().enumerate() {
+        records.push(
+            Record::new(14)
+                .text(2, format!("{:02}", index + 2))
+                .text(3, "0")
+                .text(4, AGENCY)
+                .text(5, date.clone())
+                .text(6, width.to_string())
+                .text(7, height.to_string())
+                .text(8, "1")
+                .text(9, FINGER_PPI.to_string())
+                .text(10, FINGER_PPI.to_string())
+                .text(11, "WSQ20")
+                .text(12, "8")
+                .text(13, position.to_string())
+                .binary(999, wsq),
+        );
+    }
+
+    // CNT: the number of records that follow, then type and IDC of each
+    let mut content = vec![format!("1{}{}", US as char, records.len())];
+    for record in &records {
+        let idc = record.fields.iter().find_map(|(field, value)| match (field, value) {
+            (2, Value::Text(idc)) => Some(idc.clone()),
+            _ => None,
+        });
+        content.push(format!("{}{}{}", record.record_type, US as char, idc.unwrap_or_default()));
+    }
+    let tcn: String = format!("{}{}", wallet, gmt).chars().filter(|c| c.is_ascii_alphanumeric()).take(40).collect();
+    let header = Record::new(1)
+        .text(2, VERSION)
+        .text(3, content.join(&(RS as char).to_string()))
+        .text(4, transaction_type)
+        .text(5, date)
+        .text(7, AGENCY)
+        .text(8, AGENCY)
+        .text(9, tcn)
+        .text(11, "00.00")
+        .text(12, FINGER_PPMM)
+        .text(14, gmt);
+
+    let mut file = header.encode();
+    for record in &records {
+        file.extend_from_slice(&record.encode());
+    }
+    Ok((file, fingers.len(), face.is_some(), skipped))
+}
+
+// Writes the patient's photo (Type-10) and fingerprints (Type-14, WSQ) to an
+// ANSI/NIST-ITL transaction file at `path`, for AFIS and matching tools.
+// Name and wallet go in the Type-2 record as EBTS fields 2.018 and 2.019.
+#[tauri::command]
+pub async fn export_ansi_nist(
+    app_handle: AppHandle,
+    patient_id: u32,
+    path: String,
+    transaction_type: Option<String>,
+) -> Result<AnsiNistExport, String> {
+    let transaction_type = transaction_type
+        .map(|t| printable(&t))
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| DEFAULT_TRANSACTION_TYPE.to_string());
+    tauri::async_runtime::spawn_blocking(move || {
+        let (data, fingerprints, has_photo, skipped) = build(&app_handle, patient_id, &transaction_type)?;
+        let path = path.trim().to_string();
+        fs::write(&path, data).map_err(|e| format!("Falha ao gravar arquivo ANSI/NIST: {e}"))?;
+        Ok(AnsiNistExport { path, fingerprints, has_photo, skipped })
+    })
+    .await
+    .map_err(|e| format!("Falha ao exportar ANSI/NIST: {e}"))?
+}
@@ -0,0 +1,167 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use base64::{engine::general_purpose as b64, Engine};
+use tauri::AppHandle;
+use tauri_plugin_opener::OpenerExt;
+
+use crate::blob_store;
+use crate::portable;
+use crate::workspaces;
+use crate::patient::{self, Attachment, Patient};
+
+const MAX_ATTACHMENT_BYTES: usize = 50 * 1024 * 1024;
+
+fn guess_mime_type(file_name: &str) -> &'static str {
+    let extension = Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("pdf") => "application/pdf",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("bmp") => "image/bmp",
+        Some("tif") | Some("tiff") => "image/tiff",
+        Some("txt") => "text/plain",
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("wsq") => "image/x-wsq",
+        _ => "application/octet-stream",
+    }
+}
+
+// File names come from the user; keep only the last component
+fn sanitize_file_name(name: &str) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    if name.is_empty() || name == "." || name == ".." {
+        "anexo".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+fn find_patient(patients: &mut [Patient], patient_id: u32) -> Result<&mut Patient, String> {
+    patients
+        .iter_mut()
+        .find(|p| p.id == patient_id)
+        .ok_or_else(|| format!("Paciente {} não encontrado.", patient_id))
+}
+
+fn load_patients(app_handle: &AppHandle) -> Result<Vec<Patient>, String> {
+    patient::load_patients_from_disk(app_handle).map_err(|e| format!("Falha ao ler pacientes: {e}"))
+}
+
+// Attachments are either read from a local path or sent inline as base64
+#[tauri::command]
+pub fn add_patient_attachment(
+    app_handle: AppHandle,
+    patient_id: u32,
+    file_path: Option<String>,
+    data_base64: Option<String>,
+    file_name: Option<String>,
+    mime_type: Option<String>,
+) -> Result<Attachment, String> {
+    let (data, default_name) = match (file_path, data_base64) {
+        (Some(path), _) => {
+            let data = fs::read(&path).map_err(|e| format!("Falha ao ler arquivo {}: {}", path, e))?;
+            (data, sanitize_file_name(&path))
+        }
+        (None, Some(encoded)) => {
+            let data = b64::STANDARD
+                .decode(encoded.trim())
+                .map_err(|e| format!("Base64 inválido: {e}"))?;
+            (data, "anexo".to_string())
+        }
+        (None, None) => return Err("Informe o arquivo ou o conteúdo do anexo.".into()),
+    };
+    if data.len() > MAX_ATTACHMENT_BYTES {
+        return Err(format!(
+            "Anexo excede o limite de {} MB.",
+            MAX_ATTACHMENT_BYTES / (1024 * 1024)
+        ));
+    }
+
+    let file_name = file_name.map(|n| sanitize_file_name(&n)).unwrap_or(default_name);
+    let mime_type = mime_type.unwrap_or_else(|| guess_mime_type(&file_name).to_string());
+
+    let mut patients = load_patients(&app_handle)?;
+    let patient = find_patient(&mut patients, patient_id)?;
+
+    let blob_key = blob_store::store(&app_handle, &data).map_err(|e| format!("Falha ao gravar anexo: {e}"))?;
+    let attachment = Attachment {
+        id: patient.attachments.iter().map(|a| a.id).max().unwrap_or(0) + 1,
+        file_name,
+        mime_type,
+        size: data.len() as u64,
+        blob_key,
+        added_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+    };
+    patient.attachments.push(attachment.clone());
+
+    patient::save_patients_to_disk(&app_handle, &patients).map_err(|e| e.to_string())?;
+    Ok(attachment)
+}
+
+#[tauri::command]
+pub fn list_patient_attachments(app_handle: AppHandle, patient_id: u32) -> Result<Vec<Attachment>, String> {
+    let mut patients = load_patients(&app_handle)?;
+    Ok(find_patient(&mut patients, patient_id)?.attachments.clone())
+}
+
+#[tauri::command]
+pub fn remove_patient_attachment(app_handle: AppHandle, patient_id: u32, attachment_id: u32) -> Result<bool, String> {
+    let mut patients = load_patients(&app_handle)?;
+    let patient = find_patient(&mut patients, patient_id)?;
+    let index = patient
+        .attachments
+        .iter()
+        .position(|a| a.id == attachment_id)
+        .ok_or_else(|| format!("Anexo {} não encontrado.", attachment_id))?;
+    let removed = patient.attachments.remove(index);
+
+    patient::save_patients_to_disk(&app_handle, &patients).map_err(|e| e.to_string())?;
+
+    // Blobs are shared between identical files and with cloned workspaces;
+    // only drop unreferenced ones
+    let still_used = patients
+        .iter()
+        .flat_map(|p| p.attachments.iter())
+        .any(|a| a.blob_key == removed.blob_key)
+        || workspaces::blob_used_elsewhere(&app_handle, &removed.blob_key);
+    if !still_used {
+        if let Err(e) = blob_store::remove(&app_handle, &removed.blob_key) {
+            eprintln!("Falha ao remover blob {}: {}", removed.blob_key, e);
+        }
+    }
+    Ok(true)
+}
+
+// Copies the blob to a temp file with its original name so the OS picks the
+// right application, then opens it.
+#[tauri::command]
+pub fn open_patient_attachment(app_handle: AppHandle, patient_id: u32, attachment_id: u32) -> Result<String, String> {
+    let mut patients = load_patients(&app_handle)?;
+    let patient = find_patient(&mut patients, patient_id)?;
+    let attachment = patient
+        .attachments
+        .iter()
+        .find(|a| a.id == attachment_id)
+        .ok_or_else(|| format!("Anexo {} não encontrado.", attachment_id))?;
+
+    let data = blob_store::load(&app_handle, &attachment.blob_key)
+        .map_err(|e| format!("Falha ao ler anexo: {e}"))?;
+
+    let dir: PathBuf = portable::temp_dir().join("virtual_io_hub_attachments");
+    fs::create_dir_all(&dir).map_err(|e| format!("Falha ao criar diretório temporário: {}", e))?;
+    let path = dir.join(format!("{}-{}-{}", patient_id, attachment.id, attachment.file_name));
+    fs::write(&path, data).map_err(|e| format!("Falha ao escrever arquivo temporário: {}", e))?;
+
+    let path = path.to_string_lossy().to_string();
+    app_handle
+        .opener()
+        .open_path(path.clone(), None::<&str>)
+        .map_err(|e| format!("Falha ao abrir anexo: {}", e))?;
+    Ok(path)
+}
@@ -0,0 +1,74 @@
+use tauri::AppHandle;
+
+use crate::totvs::error::TotvsError;
+use crate::totvs::models::{self, Authorization};
+use crate::totvs_endpoints;
+use crate::totvs_http;
+
+// Stops a server that always answers hasNext from paging forever; a list
+// cut there is logged as truncated
+const MAX_PAGES: u32 = 50;
+
+// Authorizations (guias) of the beneficiary being checked in, e.g. to show
+// which ones are still pending. `status` keeps only those whose status
+// matches, ignoring case.
+#[tauri::command]
+pub async fn list_authorizations(
+    app_handle: AppHandle,
+    card_number: String,
+    status: Option<String>,
+) -> Result<Vec<Authorization>, TotvsError> {
+    let card_number = card_number.trim();
+    if card_number.is_empty() {
+        return Err("Número da carteira não informado.".into());
+    }
+
+    let portal = crate::portal_context(&app_handle)?;
+    let client = totvs_http::client(&app_handle)?;
+    let mut authorizations = Vec::new();
+    let mut complete = false;
+
+    for page in 1..=MAX_PAGES {
+        let page_str = page.to_string();
+        let response = totvs_endpoints::send(&app_handle, "authorizations", &portal.credentials, Some(card_number), |url| {
+            tracing::debug!(target: "totvs", "URL Guias: {} (página {})", url, page);
+            client
+                .get(url)
+                .query(&portal.query())
+                .query(&[("page", page_str.as_str())])
+                .header("Accept", "application/json")
+                .header("x-totvs-hgp-portal-prestador-clinic", portal.clinic.as_str())
+        })
+        .await?;
+
+        if !response.status().is_success() {
+            return Err(TotvsError::status(response.status(), format!("Falha na requisição (página {}): {}", page, response.status())));
+        }
+        let json: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| TotvsError::parse(format!("Falha ao decodificar JSON: {e}")))?;
+
+        let items = json.get("items").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+        let path = format!("página {}: items", page);
+        authorizations.extend(models::parse_list(items, &path, Authorization::from_value)?);
+        if !json.get("hasNext").and_then(|v| v.as_bool()).unwrap_or(false) {
+            complete = true;
+            break;
+        }
+    }
+    if !complete {
+        tracing::warn!(
+            target: "totvs",
+            "Guias de {} truncadas em {} páginas ({} guias); o servidor ainda indicava mais páginas",
+            card_number,
+            MAX_PAGES,
+            authorizations.len()
+        );
+    }
+
+    if let Some(wanted) = status.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        authorizations.retain(|a| a.status.as_deref().is_some_and(|s| s.trim().eq_ignore_ascii_case(wanted)));
+    }
+    Ok(authorizations)
+}
@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+
+use crate::card_format;
+use crate::hotkey::HotkeyManager;
+use crate::keystroke::{self, KeystrokeMode};
+use crate::patient;
+
+// Unattended load tests: the card text is typed every `interval_secs` for
+// `iterations` times, without a key press. The first send waits an interval
+// too, leaving time to focus the target screen. Every send emits `auto-send`.
+pub struct AutoSendState {
+    status: AutoSendStatus,
+    // Dropping it stops the run
+    stop_tx: Option<mpsc::Sender<()>>,
+    // Tells a finished run's thread from the current one
+    run: u64,
+}
+
+impl AutoSendState {
+    pub fn new() -> Self {
+        Self {
+            status: AutoSendStatus::default(),
+            stop_tx: None,
+            run: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AutoSendStatus {
+    pub running: bool,
+    pub wallet: String,
+    pub interval_secs: u64,
+    pub iterations: u32,
+    pub sent: u32,
+    pub failed: u32,
+    pub last_error: Option<String>,
+    pub started_at: Option<u64>,
+    pub last_send_at: Option<u64>,
+}
+
+// Types the card of `patient_id`, or `card_text` without a patient
+#[derive(Debug, Clone, Deserialize)]
+pub struct AutoSendRequest {
+    pub patient_id: Option<u32>,
+    pub card_text: Option<String>,
+    pub format_id: Option<String>,
+    pub keystroke_mode: Option<KeystrokeMode>,
+    pub interval_secs: u64,
+    pub iterations: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoSendEvent {
+    // 1-based
+    pub iteration: u32,
+    pub iterations: u32,
+    pub wallet: String,
+    pub ok: bool,
+    pub error: Option<String>,
+    pub timestamp: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn lock(state: &Mutex<AutoSendState>) -> Result<std::sync::MutexGuard<'_, AutoSendState>, String> {
+    state.lock().map_err(|_| "Falha ao obter lock do envio automático".to_string())
+}
+
+#[tauri::command]
+pub fn start_auto_send(
+    app_handle: AppHandle,
+    request: AutoSendRequest,
+    state: tauri::State<'_, Arc<Mutex<AutoSendState>>>,
+) -> Result<AutoSendStatus, String> {
+    let AutoSendRequest { patient_id, card_text, format_id, keystroke_mode, interval_secs, iterations } = request;
+    if interval_secs == 0 {
+        return Err("O intervalo deve ser de ao menos 1 segundo.".into());
+    }
+    if iterations == 0 {
+        return Err("Informe ao menos um envio.".into());
+    }
+    let wallet = match patient_id {
+        Some(id) => patient::get_patient(&app_handle, id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Paciente {} não encontrado.", id))?
+            .wallet,
+        None => card_text.unwrap_or_default(),
+    };
+    let wallet = wallet.trim().to_string();
+    if wallet.is_empty() {
+        return Err("Texto para enviar não pode estar vazio".into());
+    }
+    let format = card_format::resolve_format(&app_handle, &wallet, format_id.as_deref())?;
+    let mode = keystroke::resolve_mode(&app_handle, keystroke_mode);
+
+    let (stop_tx, stop_rx) = mpsc::channel::<()>();
+    let (run, status) = {
+        let mut state = lock(&state)?;
+        if state.status.running {
+            return Err("O envio automático já está em execução.".into());
+        }
+        state.run += 1;
+        state.stop_tx = Some(stop_tx);
+        state.status = AutoSendStatus {
+            running: true,
+            wallet: wallet.clone(),
+            interval_secs,
+            iterations,
+            started_at: Some(now()),
+            ..Default::default()
+        };
+        (state.run, state.status.clone())
+    };
+
+    let shared = state.inner().clone();
+    thread::spawn(move || {
+        let interval = Duration::from_secs(interval_secs);
+        for iteration in 1..=iterations {
+            // A stop request or a dropped sender ends the run
+            if stop_rx.recv_timeout(interval) != Err(RecvTimeoutError::Timeout) {
+                break;
+            }
+            let result = HotkeyManager::send_once(&app_handle, &wallet, &format, mode);
+            let event = AutoSendEvent {
+                iteration,
+                iterations,
+                wallet: wallet.clone(),
+                ok: result.is_ok(),
+                error: result.err(),
+                timestamp: now(),
+            };
+            if let Ok(mut state) = shared.lock() {
+                if state.run != run {
+                    break;
+                }
+                if event.ok {
+                    state.status.sent += 1;
+                } else {
+                    state.status.failed += 1;
+                    state.status.last_error = event.error.clone();
+                }
+                state.status.last_send_at = Some(event.timestamp);
+            }
+            let _ = app_handle.emit("auto-send", &event);
+        }
+        if let Ok(mut state) = shared.lock() {
+            if state.run == run {
+                state.status.running = false;
+                state.stop_tx = None;
+            }
+        }
+    });
+
+    Ok(status)
+}
+
+// Returns false if nothing was running. A send already underway finishes.
+#[tauri::command]
+pub fn stop_auto_send(state: tauri::State<'_, Arc<Mutex<AutoSendState>>>) -> Result<bool, String> {
+    let mut state = lock(&state)?;
+    let running = state.status.running;
+    if let Some(stop_tx) = state.stop_tx.take() {
+        let _ = stop_tx.send(());
+    }
+    state.status.running = false;
+    Ok(running)
+}
+
+#[tauri::command]
+pub fn get_auto_send_status(state: tauri::State<'_, Arc<Mutex<AutoSendState>>>) -> Result<AutoSendStatus, String> {
+    Ok(lock(&state)?.status.clone())
+}
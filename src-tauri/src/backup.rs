@@ -0,0 +1,231 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use serde::Serialize;
+use serde_json::json;
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::beneficiary_sync::iso_utc;
+use crate::patient::{self, Patient};
+
+// File layout: MAGIC, salt, nonce, then the AES-256-GCM encrypted zip. The
+// key comes from the passphrase through Argon2id.
+const MAGIC: &[u8; 8] = b"VIOHBKP1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+// Patients go in as plain JSON: the database encrypts biometrics with a key
+// from this machine's keyring, which the destination machine doesn't have
+const PATIENTS_ENTRY: &str = "patients.json";
+const CONFIG_ENTRY: &str = "app_config.json";
+const MANIFEST_ENTRY: &str = "manifest.json";
+// Data dir folders copied as they are
+const FOLDERS: [&str; 2] = ["logs", "blobs"];
+
+#[derive(Debug, Serialize)]
+pub struct BackupSummary {
+    pub path: String,
+    pub patients: usize,
+    pub config: bool,
+    // Log and blob files
+    pub files: usize,
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key<Aes256Gcm>, String> {
+    let mut key = Key::<Aes256Gcm>::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Falha ao derivar chave do backup: {e}"))?;
+    Ok(key)
+}
+
+fn encrypt(passphrase: &str, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = Aes256Gcm::new(&derive_key(passphrase, &salt)?)
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| "Falha ao criptografar backup.".to_string())?;
+    let mut file = MAGIC.to_vec();
+    file.extend_from_slice(&salt);
+    file.extend_from_slice(&nonce);
+    file.extend_from_slice(&ciphertext);
+    Ok(file)
+}
+
+fn decrypt(passphrase: &str, file: &[u8]) -> Result<Vec<u8>, String> {
+    let rest = file
+        .strip_prefix(MAGIC.as_slice())
+        .filter(|rest| rest.len() > SALT_LEN + NONCE_LEN)
+        .ok_or_else(|| "O arquivo não é um backup do emulador.".to_string())?;
+    let (salt, rest) = rest.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    Aes256Gcm::new(&derive_key(passphrase, salt)?)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Senha incorreta ou backup corrompido.".to_string())
+}
+
+// Files under `dir`, as paths relative to the data dir
+fn collect_files(data_dir: &Path, dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.is_dir() {
+            collect_files(data_dir, &path, files);
+        } else if let Ok(relative) = path.strip_prefix(data_dir) {
+            files.push(relative.to_path_buf());
+        }
+    }
+}
+
+// Zip entry names always use '/'
+fn entry_name(relative: &Path) -> String {
+    relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+fn build_archive(app_handle: &AppHandle) -> Result<(Vec<u8>, usize, bool, usize), String> {
+    let data_dir = patient::ensure_data_dir(app_handle).map_err(|e| format!("Falha ao localizar diretório de dados: {e}"))?;
+    let patients = patient::load_patients_from_disk(app_handle).map_err(|e| format!("Falha ao ler pacientes: {e}"))?;
+    let config_path = patient::config_file_path(app_handle).map_err(|e| e.to_string())?;
+    let config = config_path
+        .exists()
+        .then(|| patient::load_config_from_disk(app_handle))
+        .transpose()
+        .map_err(|e| format!("Falha ao ler configuração: {e}"))?;
+
+    let mut files = Vec::new();
+    for folder in FOLDERS {
+        collect_files(&data_dir, &data_dir.join(folder), &mut files);
+    }
+
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let mut add = |name: &str, data: &[u8]| -> Result<(), String> {
+        zip.start_file(name, options).map_err(|e| format!("Falha ao montar backup: {e}"))?;
+        zip.write_all(data).map_err(|e| format!("Falha ao montar backup: {e}"))
+    };
+
+    let created_at = iso_utc(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0));
+    let manifest = json!({ "format": 1, "created_at": created_at, "patients": patients.len() });
+    add(MANIFEST_ENTRY, &serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?)?;
+    add(PATIENTS_ENTRY, &serde_json::to_vec(&patients).map_err(|e| e.to_string())?)?;
+    if let Some(config) = &config {
+        add(CONFIG_ENTRY, &serde_json::to_vec_pretty(config).map_err(|e| e.to_string())?)?;
+    }
+    let mut copied = 0;
+    for relative in &files {
+        // Files removed since listing (e.g. rotated logs) are left out
+        if let Ok(data) = fs::read(data_dir.join(relative)) {
+            add(&entry_name(relative), &data)?;
+            copied += 1;
+        }
+    }
+
+    let archive = zip.finish().map_err(|e| format!("Falha ao montar backup: {e}"))?.into_inner();
+    Ok((archive, patients.len(), config.is_some(), copied))
+}
+
+// Only plain relative paths into one of the copied folders are restored
+fn restore_target(data_dir: &Path, name: &str) -> Option<PathBuf> {
+    let relative = Path::new(name);
+    let safe = relative.components().all(|c| matches!(c, Component::Normal(_)));
+    let known = relative
+        .components()
+        .next()
+        .is_some_and(|c| FOLDERS.iter().any(|f| c.as_os_str() == *f));
+    (safe && known).then(|| data_dir.join(relative))
+}
+
+fn restore_archive(app_handle: &AppHandle, archive: Vec<u8>) -> Result<(usize, bool, usize), String> {
+    let data_dir = patient::ensure_data_dir(app_handle).map_err(|e| format!("Falha ao localizar diretório de dados: {e}"))?;
+    let mut zip = ZipArchive::new(Cursor::new(archive)).map_err(|e| format!("Backup corrompido: {e}"))?;
+
+    let mut read_entry = |name: &str| -> Result<Option<Vec<u8>>, String> {
+        match zip.by_name(name) {
+            Ok(mut entry) => {
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data).map_err(|e| format!("Backup corrompido: {e}"))?;
+                Ok(Some(data))
+            }
+            Err(zip::result::ZipError::FileNotFound) => Ok(None),
+            Err(e) => Err(format!("Backup corrompido: {e}")),
+        }
+    };
+    let patients: Vec<Patient> = match read_entry(PATIENTS_ENTRY)? {
+        Some(data) => serde_json::from_slice(&data).map_err(|e| format!("Pacientes inválidos no backup: {e}"))?,
+        None => return Err("O backup não contém pacientes.".into()),
+    };
+    let config: Option<serde_json::Value> = read_entry(CONFIG_ENTRY)?
+        .map(|data| serde_json::from_slice(&data))
+        .transpose()
+        .map_err(|e| format!("Configuração inválida no backup: {e}"))?;
+
+    let mut files = 0;
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index).map_err(|e| format!("Backup corrompido: {e}"))?;
+        let Some(target) = restore_target(&data_dir, entry.name()).filter(|_| entry.is_file()) else {
+            continue;
+        };
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).map_err(|e| format!("Backup corrompido: {e}"))?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Falha ao restaurar {}: {e}", entry.name()))?;
+        }
+        fs::write(&target, data).map_err(|e| format!("Falha ao restaurar {}: {e}", entry.name()))?;
+        files += 1;
+    }
+
+    patient::save_patients_from(app_handle, &patients, "restore_backup", patient::SaveMode::Restore).map_err(|e| e.to_string())?;
+    if let Some(config) = &config {
+        patient::save_config_to_disk(app_handle, config).map_err(|e| format!("Falha ao salvar configuração: {e}"))?;
+    }
+    Ok((patients.len(), config.is_some(), files))
+}
+
+fn check_passphrase(passphrase: &str) -> Result<(), String> {
+    if passphrase.is_empty() {
+        return Err("Informe a senha do backup.".into());
+    }
+    Ok(())
+}
+
+// Writes patients, app config, logs and blobs (attachments, previews) to one
+// file encrypted with `passphrase`, to move the emulator to another machine
+#[tauri::command]
+pub async fn create_backup(app_handle: AppHandle, path: String, passphrase: String) -> Result<BackupSummary, String> {
+    check_passphrase(&passphrase)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let (archive, patients, config, files) = build_archive(&app_handle)?;
+        let path = path.trim().to_string();
+        fs::write(&path, encrypt(&passphrase, &archive)?).map_err(|e| format!("Falha ao gravar backup: {e}"))?;
+        Ok(BackupSummary { path, patients, config, files })
+    })
+    .await
+    .map_err(|e| format!("Falha ao criar backup: {e}"))?
+}
+
+// Replaces the patients and config with those of the backup and puts its
+// logs and blobs back in the data dir. Nothing changes if the passphrase is
+// wrong.
+#[tauri::command]
+pub async fn restore_backup(app_handle: AppHandle, path: String, passphrase: String) -> Result<BackupSummary, String> {
+    check_passphrase(&passphrase)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let path = path.trim().to_string();
+        let file = fs::read(&path).map_err(|e| format!("Falha ao ler backup: {e}"))?;
+        let archive = decrypt(&passphrase, &file)?;
+        let (patients, config, files) = restore_archive(&app_handle, archive)?;
+        Ok(BackupSummary { path, patients, config, files })
+    })
+    .await
+    .map_err(|e| format!("Falha ao restaurar backup: {e}"))?
+}
@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+
+// Barcode scanner in keyboard-wedge mode: types what it decoded, formatted as
+// the symbology carries it, between a prefix and a suffix. A card format with
+// `barcode` types its rendered template this way, so a hotkey can "scan" a
+// wallet or any text.
+
+const CODE39_CHARS: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ-. $/+%";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Symbology {
+    // Any ASCII text
+    #[default]
+    Code128,
+    // Uppercase letters, digits and - . space $ / + %
+    Code39,
+    // 12 digits plus the check digit
+    Ean13,
+    // Interleaved 2 of 5: an even number of digits
+    Itf,
+    QrCode,
+}
+
+impl Symbology {
+    // AIM identifier scanners can type before the data
+    fn aim_id(self) -> &'static str {
+        match self {
+            Symbology::Code128 => "]C0",
+            Symbology::Code39 => "]A0",
+            Symbology::Ean13 => "]E0",
+            Symbology::Itf => "]I0",
+            Symbology::QrCode => "]Q1",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BarcodeLayout {
+    pub symbology: Symbology,
+    // Typed before the data, after the AIM identifier
+    pub prefix: String,
+    // "\n" is Enter, "\t" Tab
+    pub suffix: String,
+    pub aim_id: bool,
+    // Appends the symbology check character: mod 43 for Code 39, mod 10 for
+    // ITF. EAN-13 always has one.
+    pub check_digit: bool,
+}
+
+impl Default for BarcodeLayout {
+    fn default() -> Self {
+        Self {
+            symbology: Symbology::Code128,
+            prefix: String::new(),
+            suffix: "\n".into(),
+            aim_id: false,
+            check_digit: false,
+        }
+    }
+}
+
+fn digits(text: &str) -> Vec<u32> {
+    text.chars().filter_map(|c| c.to_digit(10)).collect()
+}
+
+// EAN and ITF: weights 3 and 1 alternating from the rightmost digit
+fn gs1_check_digit(body: &[u32]) -> u32 {
+    let sum: u32 = body
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| if i % 2 == 0 { d * 3 } else { d })
+        .sum();
+    (10 - sum % 10) % 10
+}
+
+fn code39_check_char(text: &str) -> char {
+    let sum: usize = text.chars().filter_map(|c| CODE39_CHARS.find(c)).sum();
+    CODE39_CHARS.chars().nth(sum % 43).unwrap_or('0')
+}
+
+impl BarcodeLayout {
+    // Data as the scanner would decode it, without prefix and suffix
+    fn data(&self, text: &str) -> String {
+        match self.symbology {
+            Symbology::Code128 | Symbology::QrCode => text.to_string(),
+            Symbology::Code39 => {
+                let data = text.to_uppercase();
+                if self.check_digit {
+                    format!("{}{}", data, code39_check_char(&data))
+                } else {
+                    data
+                }
+            }
+            Symbology::Ean13 => {
+                let mut data: String = text.chars().filter(|c| c.is_ascii_digit()).collect();
+                if data.len() == 12 {
+                    data.push_str(&gs1_check_digit(&digits(&data)).to_string());
+                }
+                data
+            }
+            Symbology::Itf => {
+                let mut data: String = text.chars().filter(|c| c.is_ascii_digit()).collect();
+                if self.check_digit {
+                    data.push_str(&gs1_check_digit(&digits(&data)).to_string());
+                }
+                // Digits are encoded in pairs; a leading zero completes them
+                if data.len() % 2 == 1 {
+                    data.insert(0, '0');
+                }
+                data
+            }
+        }
+    }
+
+    pub fn render(&self, text: &str) -> String {
+        let aim_id = if self.aim_id { self.symbology.aim_id() } else { "" };
+        format!("{}{}{}{}", aim_id, self.prefix, self.data(text), self.suffix)
+    }
+
+    pub fn validate(&self, text: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        match self.symbology {
+            Symbology::Code128 if !text.is_ascii() => {
+                errors.push("Code 128 aceita apenas caracteres ASCII.".to_string());
+            }
+            Symbology::Code39 => {
+                let invalid: String = text.to_uppercase().chars().filter(|&c| !CODE39_CHARS.contains(c)).collect();
+                if !invalid.is_empty() {
+                    errors.push(format!("Caracteres não suportados pelo Code 39: {}", invalid));
+                }
+            }
+            Symbology::Ean13 | Symbology::Itf if !text.chars().all(|c| c.is_ascii_digit()) => {
+                errors.push("O código de barras aceita apenas dígitos.".to_string());
+            }
+            Symbology::Ean13 => match text.len() {
+                12 => {}
+                13 => {
+                    let all = digits(text);
+                    if gs1_check_digit(&all[..12]) != all[12] {
+                        errors.push("Dígito verificador EAN-13 inválido.".to_string());
+                    }
+                }
+                len => errors.push(format!("EAN-13 precisa de 12 ou 13 dígitos (informado: {}).", len)),
+            },
+            _ => {}
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(symbology: Symbology, check_digit: bool) -> BarcodeLayout {
+        BarcodeLayout { symbology, suffix: String::new(), check_digit, ..BarcodeLayout::default() }
+    }
+
+    #[test]
+    fn gs1_check_digit_weights_from_the_right() {
+        assert_eq!(gs1_check_digit(&digits("400638133393")), 1);
+        assert_eq!(gs1_check_digit(&digits("12345")), 7);
+        assert_eq!(gs1_check_digit(&digits("000000000000")), 0);
+    }
+
+    #[test]
+    fn code39_check_char_is_mod_43() {
+        assert_eq!(code39_check_char("CODE39"), 'W');
+        assert_eq!(layout(Symbology::Code39, true).render("code39"), "CODE39W");
+    }
+
+    #[test]
+    fn ean13_and_itf_get_their_check_digits() {
+        assert_eq!(layout(Symbology::Ean13, false).render("400638133393"), "4006381333931");
+        assert_eq!(layout(Symbology::Itf, true).render("12345"), "123457");
+        assert_eq!(layout(Symbology::Itf, false).render("12345"), "012345");
+    }
+}
@@ -0,0 +1,394 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::import_jobs::ImportJob;
+use crate::import_report::{self, ImportReport, ImportReportEntry};
+use crate::patient::{self, DigitalBiometric, FaceAngle, Patient};
+use crate::photo_pipeline;
+use crate::streamed_download;
+use crate::totvs::error::TotvsError;
+use crate::totvs::models::{Fingerprint, Person};
+use crate::webhook::{self, JobReport};
+use crate::BeneficiarySearchParams;
+
+const DEFAULT_CONCURRENCY: usize = 3;
+const MAX_CONCURRENCY: usize = 8;
+
+// Optional search filters for the family import, same as the subscriber search
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct GuarantorFilters {
+    pub modality: Option<String>,
+    pub proposal: Option<String>,
+    pub contract: Option<String>,
+    pub concurrency: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GuarantorImportProgress {
+    pub job_id: String,
+    pub done: usize,
+    pub total: usize,
+    pub name: String,
+    pub wallet: String,
+    pub fingerprints: usize,
+    pub has_photo: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GuarantorImportSummary {
+    pub job_id: String,
+    pub total: usize,
+    pub patients: Vec<Patient>,
+    // Set when the job was cancelled; `patients` then holds the partial result
+    pub cancelled: bool,
+}
+
+// TOTVS finger codes, left little finger (1) to right little finger (10)
+pub(crate) fn finger_name(code: u32) -> String {
+    match code {
+        1 => "Mínimo Esquerdo".into(),
+        2 => "Anelar Esquerdo".into(),
+        3 => "Médio Esquerdo".into(),
+        4 => "Indicador Esquerdo".into(),
+        5 => "Polegar Esquerdo".into(),
+        6 => "Polegar Direito".into(),
+        7 => "Indicador Direito".into(),
+        8 => "Médio Direito".into(),
+        9 => "Anelar Direito".into(),
+        10 => "Mínimo Direito".into(),
+        other => format!("Dedo {}", other),
+    }
+}
+
+// Inverse of finger_name, for templates stored under a finger's name
+pub(crate) fn finger_code(name: &str) -> Option<u32> {
+    let name = name.trim();
+    (1..=10)
+        .find(|code| finger_name(*code).eq_ignore_ascii_case(name))
+        .or_else(|| name.strip_prefix("Dedo ")?.trim().parse().ok())
+}
+
+// Same rule as the importer screen: the complete card number when present,
+// otherwise the insurer (4 digits) followed by the card (13 digits)
+pub(crate) fn wallet_of(health_insurer: &Option<String>, card_number: &Option<String>, complete: &Option<String>) -> Option<String> {
+    if let Some(complete) = complete.as_deref().filter(|c| !c.trim().is_empty()) {
+        return Some(complete.trim().to_string());
+    }
+    let card = card_number.as_deref()?.trim();
+    let insurer = health_insurer.as_deref().unwrap_or_default().trim();
+    Some(format!("{:0>4}{:0>13}", insurer, card))
+}
+
+pub(crate) fn resolve_name(name: &Option<String>, person: &Option<Person>) -> String {
+    name.as_deref()
+        .or_else(|| person.as_ref().and_then(|p| p.name.as_deref()))
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+// The details endpoint wants the last 13 digits without leading zeros
+pub(crate) fn details_key(card_number: &str) -> String {
+    let digits: Vec<char> = card_number.trim().chars().collect();
+    let tail: String = digits[digits.len().saturating_sub(13)..].iter().collect();
+    let key = tail.trim_start_matches('0');
+    if key.is_empty() { "0".to_string() } else { key.to_string() }
+}
+
+pub(crate) fn to_digital_biometrics(fingerprints: Vec<Fingerprint>) -> Vec<DigitalBiometric> {
+    fingerprints
+        .into_iter()
+        .filter_map(|fp| {
+            let data = fp.biometry.filter(|b| !b.trim().is_empty())?;
+            Some(DigitalBiometric {
+                finger: fp.finger_code.map(finger_name).unwrap_or_else(|| "Dedo".into()),
+                data,
+            })
+        })
+        .collect()
+}
+
+// Fingerprints and photo for a wallet, fetched concurrently. Either may be
+// missing for a beneficiary, so failures are logged, yield empty data and
+// are returned for the import report.
+async fn fetch_biometrics(app_handle: &AppHandle, wallet: &str) -> (Vec<DigitalBiometric>, String, Vec<String>) {
+    let (fingerprints, photo) = tokio::join!(
+        crate::fetch_fingerprints(app_handle, wallet),
+        crate::fetch_facial_photo(app_handle, wallet),
+    );
+    let mut failures = Vec::new();
+    let fingerprints = fingerprints.unwrap_or_else(|e| {
+        tracing::warn!("Digitais indisponíveis para {}: {}", wallet, e);
+        failures.push(format!("Digitais: {}", e));
+        Vec::new()
+    });
+    let photo = match photo.map_err(String::from).and_then(|raw| streamed_download::photo_base64(&raw)) {
+        Ok(raw) => photo_pipeline::prepare(&photo_pipeline::settings(app_handle), raw).await,
+        Err(e) => {
+            tracing::warn!("Foto indisponível para {}: {}", wallet, e);
+            failures.push(format!("Foto: {}", e));
+            String::new()
+        }
+    };
+    (to_digital_biometrics(fingerprints), photo, failures)
+}
+
+// The TOTVS photo becomes the frontal one
+pub(crate) fn new_patient(name: String, wallet: String, digital_biometrics: Vec<DigitalBiometric>, facial_biometric: String) -> Patient {
+    let mut patient = Patient {
+        id: 0,
+        name,
+        wallet,
+        facial_biometrics: Vec::new(),
+        digital_biometrics,
+        imported: true,
+        tags: Vec::new(),
+        attachments: Vec::new(),
+        verification_history: Vec::new(),
+        notes: String::new(),
+        scenario: Default::default(),
+        version: 0,
+    };
+    patient.set_facial_photo(FaceAngle::Frontal, facial_biometric);
+    patient
+}
+
+// Adds the imported patients to the store in one save. A patient whose wallet
+// is already stored replaces that record's TOTVS data but keeps its id, tags,
+// attachments and history.
+pub(crate) fn store_imported(app_handle: &AppHandle, imported: Vec<Patient>) -> Result<Vec<Patient>, String> {
+    let mut patients = patient::load_patients_from_disk(app_handle)
+        .map_err(|e| format!("Falha ao ler pacientes: {e}"))?;
+    let mut next_id = patients.iter().map(|p| p.id).max().unwrap_or(0) + 1;
+    let mut stored = Vec::with_capacity(imported.len());
+
+    for incoming in imported {
+        match patients.iter_mut().find(|p| p.wallet == incoming.wallet) {
+            Some(existing) => {
+                // Photos of the other angles aren't in TOTVS and are kept
+                existing.set_facial_photo(FaceAngle::Frontal, incoming.main_photo().to_string());
+                if !incoming.name.is_empty() {
+                    existing.name = incoming.name;
+                }
+                existing.digital_biometrics = incoming.digital_biometrics;
+                existing.imported = true;
+                stored.push(existing.clone());
+            }
+            None => {
+                let patient = Patient { id: next_id, ..incoming };
+                next_id += 1;
+                stored.push(patient.clone());
+                patients.push(patient);
+            }
+        }
+    }
+
+    let skipped = patient::save_patients_from(app_handle, &patients, "import", patient::SaveMode::Import)
+        .map_err(|e| e.to_string())?;
+    for error in &skipped {
+        tracing::warn!("Paciente {} não importado: {} ({})", error.patient_id, error.message, error.field);
+    }
+    // Reloaded for the versions the save assigned; the ones left out are
+    // dropped, a stored one keeps its previous data
+    let saved = patient::load_patients_from_disk(app_handle).map_err(|e| format!("Falha ao ler pacientes: {e}"))?;
+    Ok(stored
+        .into_iter()
+        .filter(|p| !skipped.iter().any(|e| e.patient_id == p.id))
+        .filter_map(|p| saved.iter().find(|s| s.id == p.id).cloned())
+        .collect())
+}
+
+// Details, fingerprints and photo in one call, persisted as an imported patient
+#[tauri::command]
+pub async fn import_beneficiary(app_handle: AppHandle, card_number: String) -> Result<Patient, TotvsError> {
+    let card_number = card_number.trim().to_string();
+    if card_number.is_empty() {
+        return Err("Número da carteira não informado.".into());
+    }
+
+    let details_key = details_key(&card_number);
+    let (details, (digital_biometrics, photo, _)) = tokio::join!(
+        crate::fetch_beneficiary_details(&app_handle, &details_key),
+        fetch_biometrics(&app_handle, &card_number),
+    );
+    let details = details?;
+
+    let wallet = details
+        .complete_card_number
+        .clone()
+        .filter(|c| !c.trim().is_empty())
+        .unwrap_or(card_number);
+    let patient = new_patient(resolve_name(&details.name, &details.person), wallet, digital_biometrics, photo);
+
+    store_imported(&app_handle, vec![patient])?
+        .pop()
+        .ok_or_else(|| "Falha ao gravar paciente importado.".into())
+}
+
+// Imports every holder the search returns plus their dependents. Each
+// beneficiary's biometrics are downloaded with bounded concurrency and
+// reported through `guarantor-import-progress`; all are saved at the end.
+// If the job is cancelled, what was downloaded so far is kept. The outcome
+// per beneficiary is saved for `export_import_report`.
+pub(crate) async fn import_family(
+    app_handle: &AppHandle,
+    params: &BeneficiarySearchParams,
+    concurrency: Option<usize>,
+    job: &ImportJob,
+) -> Result<GuarantorImportSummary, TotvsError> {
+    let mut entries = Vec::new();
+    let result = download_family(app_handle, params, concurrency, job, &mut entries).await;
+    let mut report = ImportReport::new(job, result.as_ref().map_or(entries.len(), |s| s.total), entries);
+    report.error = result.as_ref().err().map(TotvsError::to_string);
+    import_report::save(app_handle, &report);
+    result
+}
+
+async fn download_family(
+    app_handle: &AppHandle,
+    params: &BeneficiarySearchParams,
+    concurrency: Option<usize>,
+    job: &ImportJob,
+    entries: &mut Vec<ImportReportEntry>,
+) -> Result<GuarantorImportSummary, TotvsError> {
+    let Some(holders) = job.token.run_until_cancelled(crate::fetch_beneficiaries(app_handle, params)).await else {
+        return Ok(GuarantorImportSummary {
+            job_id: job.id.clone(),
+            total: 0,
+            patients: Vec::new(),
+            cancelled: true,
+        });
+    };
+    let holders = holders?;
+
+    // Holder first, then dependents; a wallet listed twice is imported once
+    let mut seen = HashSet::new();
+    let mut targets = Vec::new();
+    for holder in &holders {
+        let family = std::iter::once((
+            resolve_name(&holder.name, &holder.person),
+            wallet_of(&holder.health_insurer, &holder.card_number, &holder.complete_card_number),
+        ))
+        .chain(holder.dependents.iter().map(|d| {
+            (
+                resolve_name(&d.name, &d.person),
+                wallet_of(&d.health_insurer, &d.card_number, &d.complete_card_number),
+            )
+        }));
+        for (name, wallet) in family {
+            if let Some(wallet) = wallet.filter(|w| seen.insert(w.clone())) {
+                targets.push((name, wallet));
+            }
+        }
+    }
+    let total = targets.len();
+
+    let semaphore = Arc::new(Semaphore::new(
+        concurrency.unwrap_or(DEFAULT_CONCURRENCY).clamp(1, MAX_CONCURRENCY),
+    ));
+    let mut tasks = JoinSet::new();
+    for (index, (name, wallet)) in targets.into_iter().enumerate() {
+        let app_handle = app_handle.clone();
+        let semaphore = semaphore.clone();
+        let token = job.token.clone();
+        tasks.spawn(async move {
+            token
+                .run_until_cancelled(async move {
+                    let _permit = semaphore.acquire_owned().await;
+                    let (digital_biometrics, photo, failures) = fetch_biometrics(&app_handle, &wallet).await;
+                    (index, new_patient(name, wallet, digital_biometrics, photo), failures)
+                })
+                .await
+        });
+    }
+
+    let mut imported = Vec::with_capacity(total);
+    while let Some(joined) = tasks.join_next().await {
+        let joined = joined.map_err(|e| format!("Falha na tarefa de importação: {e}"))?;
+        // None: cancelled before this beneficiary finished
+        let Some((index, patient, failures)) = joined else {
+            continue;
+        };
+        entries.push(ImportReportEntry {
+            name: patient.name.clone(),
+            wallet: patient.wallet.clone(),
+            photo: patient.has_facial_photo(),
+            fingerprints: patient.digital_biometrics.len(),
+            failures,
+        });
+        let _ = app_handle.emit(
+            "guarantor-import-progress",
+            GuarantorImportProgress {
+                job_id: job.id.clone(),
+                done: imported.len() + 1,
+                total,
+                name: patient.name.clone(),
+                wallet: patient.wallet.clone(),
+                fingerprints: patient.digital_biometrics.len(),
+                has_photo: patient.has_facial_photo(),
+            },
+        );
+        imported.push((index, patient));
+    }
+    // Keep the family order in the patient list
+    imported.sort_by_key(|(index, _)| *index);
+
+    let patients = if imported.is_empty() {
+        Vec::new()
+    } else {
+        store_imported(app_handle, imported.into_iter().map(|(_, p)| p).collect())?
+    };
+    Ok(GuarantorImportSummary {
+        job_id: job.id.clone(),
+        total,
+        patients,
+        cancelled: job.is_cancelled(),
+    })
+}
+
+// Webhook report for a family import or sync
+pub(crate) fn family_report(job: &ImportJob, result: &Result<GuarantorImportSummary, TotvsError>) -> JobReport {
+    match result {
+        Ok(summary) => JobReport {
+            total: summary.total,
+            succeeded: summary.patients.len(),
+            cancelled: summary.cancelled,
+            ..JobReport::new(&job.id, &job.kind)
+        },
+        Err(error) => JobReport::failed(&job.id, &job.kind, error.message()),
+    }
+}
+
+// Imports a guarantor's whole family; stoppable with `cancel_import`
+#[tauri::command]
+pub async fn import_guarantor(
+    app_handle: AppHandle,
+    guarantor: String,
+    filters: Option<GuarantorFilters>,
+    job_id: Option<String>,
+) -> Result<GuarantorImportSummary, TotvsError> {
+    let filters = filters.unwrap_or_default();
+    if guarantor.trim().is_empty() {
+        return Err("Contratante não informado.".into());
+    }
+
+    let params = BeneficiarySearchParams {
+        guarantor: guarantor.trim().to_string(),
+        modality: filters.modality,
+        proposal: filters.proposal,
+        contract: filters.contract,
+        page: None,
+        page_size: None,
+        all_pages: true,
+        changed_after: None,
+    };
+    let job = ImportJob::start(&app_handle, "guarantor", job_id)?;
+    let result = import_family(&app_handle, &params, filters.concurrency, &job).await;
+    webhook::send_job_report(&app_handle, family_report(&job, &result));
+    result
+}
@@ -0,0 +1,151 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::AppHandle;
+
+use crate::beneficiary_import::{family_report, import_family, GuarantorFilters};
+use crate::import_jobs::ImportJob;
+use crate::patient::{self, Patient};
+use crate::totvs::error::TotvsError;
+use crate::totvs_profiles;
+use crate::webhook;
+use crate::BeneficiarySearchParams;
+
+// Last successful sync per connection profile and guarantor:
+// { "<profile>": { "<guarantor>": "<ISO 8601 UTC>" } }
+const CURSORS_KEY: &str = "totvs_sync_cursors";
+// Cursor slot used when no connection profile is active
+const NO_PROFILE: &str = "default";
+
+#[derive(Debug, Serialize)]
+pub struct SyncSummary {
+    pub job_id: String,
+    pub profile: String,
+    // Cursor the search was filtered by; None for a full sync
+    pub since: Option<String>,
+    // Stored for the next run; unchanged when the sync didn't finish
+    pub cursor: Option<String>,
+    pub total: usize,
+    pub patients: Vec<Patient>,
+    pub cancelled: bool,
+}
+
+// Seconds since the epoch as `YYYY-MM-DDTHH:MM:SSZ` (civil-from-days algorithm)
+pub(crate) fn iso_utc(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+fn profile_name(config: &Value) -> String {
+    totvs_profiles::active_profile_name(config).unwrap_or(NO_PROFILE).to_string()
+}
+
+fn stored_cursor(config: &Value, profile: &str, guarantor: &str) -> Option<String> {
+    config
+        .get(CURSORS_KEY)?
+        .get(profile)?
+        .get(guarantor)?
+        .as_str()
+        .map(String::from)
+}
+
+fn save_cursor(app_handle: &AppHandle, profile: &str, guarantor: &str, cursor: &str) -> Result<(), String> {
+    // Reload: the import may have taken a while and the config changed meanwhile
+    let mut config = patient::load_config_from_disk(app_handle).map_err(|e| format!("Falha ao ler configurações: {e}"))?;
+    let root = config.as_object_mut().ok_or("Arquivo de configurações inválido.")?;
+    let cursors = root.entry(CURSORS_KEY).or_insert_with(|| json!({}));
+    if !cursors.is_object() {
+        *cursors = json!({});
+    }
+    let profile_cursors = cursors
+        .as_object_mut()
+        .unwrap()
+        .entry(profile)
+        .or_insert_with(|| json!({}));
+    if !profile_cursors.is_object() {
+        *profile_cursors = json!({});
+    }
+    profile_cursors
+        .as_object_mut()
+        .unwrap()
+        .insert(guarantor.to_string(), Value::String(cursor.to_string()));
+    patient::save_config_to_disk(app_handle, &config).map_err(|e| format!("Falha ao salvar configurações: {e}"))
+}
+
+// Imports only the guarantor's beneficiaries changed since the previous sync
+// of the active profile (all of them the first time, or with `full`). The
+// cursor advances to the moment this sync started, and only if it finished.
+#[tauri::command]
+pub async fn sync_beneficiaries(
+    app_handle: AppHandle,
+    guarantor: String,
+    filters: Option<GuarantorFilters>,
+    full: Option<bool>,
+    job_id: Option<String>,
+) -> Result<SyncSummary, TotvsError> {
+    let guarantor = guarantor.trim().to_string();
+    if guarantor.is_empty() {
+        return Err("Contratante não informado.".into());
+    }
+    let filters = filters.unwrap_or_default();
+
+    let config = patient::load_config_from_disk(&app_handle)
+        .map_err(|e| TotvsError::config(format!("Falha ao ler configurações: {e}")))?;
+    let profile = profile_name(&config);
+    let since = if full.unwrap_or(false) {
+        None
+    } else {
+        stored_cursor(&config, &profile, &guarantor)
+    };
+    let started_at = iso_utc(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0));
+
+    let params = BeneficiarySearchParams {
+        guarantor: guarantor.clone(),
+        modality: filters.modality,
+        proposal: filters.proposal,
+        contract: filters.contract,
+        page: None,
+        page_size: None,
+        all_pages: true,
+        changed_after: since.clone(),
+    };
+    let job = ImportJob::start(&app_handle, "sync", job_id)?;
+    let result = import_family(&app_handle, &params, filters.concurrency, &job).await;
+    webhook::send_job_report(&app_handle, family_report(&job, &result));
+    let summary = result?;
+
+    let cursor = if summary.cancelled {
+        None
+    } else {
+        save_cursor(&app_handle, &profile, &guarantor, &started_at)?;
+        Some(started_at)
+    };
+
+    Ok(SyncSummary {
+        job_id: summary.job_id,
+        profile,
+        since,
+        cursor,
+        total: summary.total,
+        patients: summary.patients,
+        cancelled: summary.cancelled,
+    })
+}
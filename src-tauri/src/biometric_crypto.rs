@@ -0,0 +1,320 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose as b64, Engine};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::patient::Patient;
+use crate::portable;
+
+// Photos and fingerprint templates are stored encrypted with AES-256-GCM.
+// The key is generated on first use and kept in the OS keyring (Keychain,
+// Credential Manager, Secret Service), never in the data dir. The exception
+// is portable mode, where the data must open on any machine and the key is
+// a file beside it (see `portable`).
+const KEYRING_SERVICE: &str = "com.lucashsilva.tauri-app";
+const KEYRING_USER: &str = "biometric-key";
+// Marks an encrypted value; anything else is legacy plaintext
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+// Files holding biometrics outside the database (TOTVS cache and mirror,
+// blobs, spilled downloads) start with this and are encrypted in chunks, so
+// a download can be written as it arrives. Each chunk is its length (u32 LE),
+// a random nonce and the ciphertext; its index and whether it's the last one
+// are authenticated along, so chunks can't be reordered or cut off.
+const FILE_MAGIC: &[u8] = b"enc:v1:";
+const CHUNK_LEN: usize = 64 * 1024;
+
+static KEY: OnceLock<Key<Aes256Gcm>> = OnceLock::new();
+
+fn keyring_error(error: keyring::Error) -> io::Error {
+    io::Error::other(format!("Falha ao acessar a chave de criptografia no chaveiro do sistema: {error}"))
+}
+
+fn decode_key(encoded: &str, source: &str) -> io::Result<Key<Aes256Gcm>> {
+    let bytes = b64::STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| io::Error::other(format!("Chave de criptografia inválida {}: {e}", source)))?;
+    if bytes.len() != 32 {
+        return Err(io::Error::other(format!("Chave de criptografia inválida {}: tamanho incorreto.", source)));
+    }
+    Ok(*Key::<Aes256Gcm>::from_slice(&bytes))
+}
+
+fn load_or_create_key() -> io::Result<Key<Aes256Gcm>> {
+    match portable::key_file() {
+        Some(path) => load_or_create_key_file(&path),
+        None => load_or_create_keyring_key(),
+    }
+}
+
+fn load_or_create_key_file(path: &Path) -> io::Result<Key<Aes256Gcm>> {
+    if path.exists() {
+        return decode_key(&fs::read_to_string(path)?, "no arquivo de chave");
+    }
+    let key = Aes256Gcm::generate_key(OsRng);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, b64::STANDARD.encode(key))?;
+    Ok(key)
+}
+
+fn load_or_create_keyring_key() -> io::Result<Key<Aes256Gcm>> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER).map_err(keyring_error)?;
+    match entry.get_password() {
+        Ok(encoded) => decode_key(&encoded, "no chaveiro"),
+        Err(keyring::Error::NoEntry) => {
+            let key = Aes256Gcm::generate_key(OsRng);
+            entry.set_password(&b64::STANDARD.encode(key)).map_err(keyring_error)?;
+            Ok(key)
+        }
+        Err(e) => Err(keyring_error(e)),
+    }
+}
+
+fn key() -> io::Result<&'static Key<Aes256Gcm>> {
+    if let Some(key) = KEY.get() {
+        return Ok(key);
+    }
+    let key = load_or_create_key()?;
+    Ok(KEY.get_or_init(|| key))
+}
+
+// Writes the key in use to `path`, for data copied into portable mode
+pub fn export_key(path: &Path) -> io::Result<()> {
+    fs::write(path, b64::STANDARD.encode(key()?))
+}
+
+// The nonce is derived from the key and the plaintext, so the same value
+// always encrypts the same way and unchanged rows aren't rewritten on save.
+// This only reveals whether two stored values are equal.
+fn nonce_for(key: &Key<Aes256Gcm>, plaintext: &str) -> [u8; NONCE_LEN] {
+    let digest = Sha256::new().chain_update(key).chain_update(plaintext.as_bytes()).finalize();
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce.copy_from_slice(&digest[..NONCE_LEN]);
+    nonce
+}
+
+fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+pub fn encrypt(value: &str) -> io::Result<String> {
+    if value.is_empty() || is_encrypted(value) {
+        return Ok(value.to_string());
+    }
+    let key = key()?;
+    let nonce = nonce_for(key, value);
+    let ciphertext = Aes256Gcm::new(key)
+        .encrypt(Nonce::from_slice(&nonce), value.as_bytes())
+        .map_err(|_| io::Error::other("Falha ao criptografar biometria."))?;
+    let mut payload = nonce.to_vec();
+    payload.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", PREFIX, b64::STANDARD.encode(payload)))
+}
+
+// Plaintext left from before encryption is returned as is
+pub fn decrypt(value: &str) -> io::Result<String> {
+    let Some(encoded) = value.strip_prefix(PREFIX) else {
+        return Ok(value.to_string());
+    };
+    let payload = b64::STANDARD
+        .decode(encoded)
+        .map_err(|e| io::Error::other(format!("Biometria criptografada corrompida: {e}")))?;
+    if payload.len() < NONCE_LEN {
+        return Err(io::Error::other("Biometria criptografada corrompida."));
+    }
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+    let plaintext = Aes256Gcm::new(key()?)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| io::Error::other("Falha ao descriptografar biometria: a chave do chaveiro não confere."))?;
+    String::from_utf8(plaintext).map_err(|e| io::Error::other(format!("Biometria criptografada corrompida: {e}")))
+}
+
+// Copy of the patient as stored: photo and fingerprint data encrypted
+pub fn encrypt_patient(patient: &Patient) -> io::Result<Patient> {
+    let mut stored = patient.clone();
+    for image in &mut stored.facial_biometrics {
+        image.data = encrypt(&image.data)?;
+        image.thumbnail = encrypt(&image.thumbnail)?;
+    }
+    for digital in &mut stored.digital_biometrics {
+        digital.data = encrypt(&digital.data)?;
+    }
+    Ok(stored)
+}
+
+pub fn decrypt_patient(mut patient: Patient) -> io::Result<Patient> {
+    for image in &mut patient.facial_biometrics {
+        image.data = decrypt(&image.data)?;
+        image.thumbnail = decrypt(&image.thumbnail)?;
+    }
+    for digital in &mut patient.digital_biometrics {
+        digital.data = decrypt(&digital.data)?;
+    }
+    Ok(patient)
+}
+
+fn chunk_aad(index: u64, last: bool) -> [u8; 9] {
+    let mut aad = [0u8; 9];
+    aad[..8].copy_from_slice(&index.to_le_bytes());
+    aad[8] = last as u8;
+    aad
+}
+
+pub struct EncryptedWriter<W: Write> {
+    inner: W,
+    cipher: Aes256Gcm,
+    buffer: Vec<u8>,
+    index: u64,
+}
+
+impl<W: Write> EncryptedWriter<W> {
+    pub fn new(mut inner: W) -> io::Result<Self> {
+        let cipher = Aes256Gcm::new(key()?);
+        inner.write_all(FILE_MAGIC)?;
+        Ok(Self { inner, cipher, buffer: Vec::with_capacity(CHUNK_LEN), index: 0 })
+    }
+
+    pub fn write_all(&mut self, mut data: &[u8]) -> io::Result<()> {
+        while !data.is_empty() {
+            // A full chunk is only written once more data shows it isn't the last
+            if self.buffer.len() == CHUNK_LEN {
+                self.write_chunk(false)?;
+            }
+            let take = (CHUNK_LEN - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..take]);
+            data = &data[take..];
+        }
+        Ok(())
+    }
+
+    fn write_chunk(&mut self, last: bool) -> io::Result<()> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let aad = chunk_aad(self.index, last);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, Payload { msg: &self.buffer, aad: &aad })
+            .map_err(|_| io::Error::other("Falha ao criptografar arquivo."))?;
+        self.inner.write_all(&(ciphertext.len() as u32).to_le_bytes())?;
+        self.inner.write_all(&nonce)?;
+        self.inner.write_all(&ciphertext)?;
+        self.buffer.clear();
+        self.index += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_chunk(true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+fn corrupted() -> io::Error {
+    io::Error::other("Arquivo criptografado corrompido.")
+}
+
+// Length of the next chunk; None at the end of the file
+fn read_chunk_len<R: Read>(inner: &mut R) -> io::Result<Option<usize>> {
+    let mut len = [0u8; 4];
+    let mut filled = 0;
+    while filled < len.len() {
+        match inner.read(&mut len[filled..])? {
+            0 if filled == 0 => return Ok(None),
+            0 => return Err(corrupted()),
+            n => filled += n,
+        }
+    }
+    Ok(Some(u32::from_le_bytes(len) as usize))
+}
+
+// Reads back what `EncryptedWriter` wrote, a chunk at a time
+pub struct EncryptedReader<R: Read> {
+    inner: R,
+    cipher: Aes256Gcm,
+    index: u64,
+    next_len: Option<usize>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> EncryptedReader<R> {
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut magic = [0u8; FILE_MAGIC.len()];
+        inner.read_exact(&mut magic).map_err(|_| corrupted())?;
+        if magic != FILE_MAGIC {
+            return Err(corrupted());
+        }
+        // Even an empty file has its last chunk
+        let next_len = read_chunk_len(&mut inner)?.ok_or_else(corrupted)?;
+        Ok(Self {
+            inner,
+            cipher: Aes256Gcm::new(key()?),
+            index: 0,
+            next_len: Some(next_len),
+            chunk: Vec::new(),
+            pos: 0,
+        })
+    }
+
+    fn read_chunk(&mut self, len: usize) -> io::Result<()> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.inner.read_exact(&mut nonce).map_err(|_| corrupted())?;
+        // A chunk plus the GCM tag
+        if len > CHUNK_LEN + 16 {
+            return Err(corrupted());
+        }
+        let mut ciphertext = vec![0u8; len];
+        self.inner.read_exact(&mut ciphertext).map_err(|_| corrupted())?;
+        self.next_len = read_chunk_len(&mut self.inner)?;
+        let aad = chunk_aad(self.index, self.next_len.is_none());
+        self.chunk = self
+            .cipher
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: &aad })
+            .map_err(|_| io::Error::other("Falha ao descriptografar arquivo: a chave do chaveiro não confere ou o arquivo foi alterado."))?;
+        self.pos = 0;
+        self.index += 1;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for EncryptedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.next_len {
+                Some(len) => self.read_chunk(len)?,
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+// Contents of a file written by `EncryptedWriter`; anything else is legacy
+// plaintext and returned as is
+pub fn decrypt_file(data: Vec<u8>) -> io::Result<Vec<u8>> {
+    if !data.starts_with(FILE_MAGIC) {
+        return Ok(data);
+    }
+    let mut plaintext = Vec::with_capacity(data.len());
+    EncryptedReader::new(data.as_slice())?.read_to_end(&mut plaintext)?;
+    Ok(plaintext)
+}
+
+pub fn write_encrypted_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut writer = EncryptedWriter::new(File::create(path)?)?;
+    writer.write_all(data)?;
+    writer.finish()?.sync_all()
+}
+
+pub fn read_encrypted_file(path: &Path) -> io::Result<Vec<u8>> {
+    decrypt_file(fs::read(path)?)
+}
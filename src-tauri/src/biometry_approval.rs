@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::oneshot;
+
+use crate::patient;
+
+const DEFAULT_APPROVAL_TIMEOUT_SECS: u64 = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    Approved,
+    Rejected,
+    TimedOut,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingRequest {
+    pub id: u64,
+    // "capture" or "verify"
+    pub kind: String,
+    pub detail: serde_json::Value,
+    pub received_at: u64,
+}
+
+struct Pending {
+    request: PendingRequest,
+    responder: oneshot::Sender<bool>,
+}
+
+// Manual-approval mode: while enabled, every capture/verify hitting the
+// biometry server waits until the operator approves or rejects it in the UI.
+pub struct ApprovalQueue {
+    enabled: bool,
+    next_id: u64,
+    pending: HashMap<u64, Pending>,
+}
+
+impl ApprovalQueue {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            next_id: 1,
+            pending: HashMap::new(),
+        }
+    }
+
+    fn reject_all(&mut self) {
+        for (_, pending) in self.pending.drain() {
+            let _ = pending.responder.send(false);
+        }
+    }
+}
+
+fn approval_timeout(app_handle: &AppHandle) -> Duration {
+    let secs = patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get("biometry_approval_timeout_secs").and_then(|v| v.as_u64()))
+        .unwrap_or(DEFAULT_APPROVAL_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+// Holds the request until the operator decides. Returns None when the mode is
+// off, so the server follows its normal behavior.
+pub async fn decide(app_handle: &AppHandle, kind: &str, detail: serde_json::Value) -> Option<Decision> {
+    let (request, rx) = {
+        let queue = app_handle.state::<Arc<Mutex<ApprovalQueue>>>();
+        let mut queue = queue.lock().unwrap();
+        if !queue.enabled {
+            return None;
+        }
+
+        let id = queue.next_id;
+        queue.next_id += 1;
+        let request = PendingRequest {
+            id,
+            kind: kind.to_string(),
+            detail,
+            received_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        };
+        let (tx, rx) = oneshot::channel();
+        queue.pending.insert(id, Pending { request: request.clone(), responder: tx });
+        (request, rx)
+    };
+
+    let _ = app_handle.emit("biometry-approval-requested", &request);
+
+    let decision = match tokio::time::timeout(approval_timeout(app_handle), rx).await {
+        Ok(Ok(true)) => Decision::Approved,
+        Ok(Ok(false)) | Ok(Err(_)) => Decision::Rejected,
+        Err(_) => Decision::TimedOut,
+    };
+
+    app_handle
+        .state::<Arc<Mutex<ApprovalQueue>>>()
+        .lock()
+        .unwrap()
+        .pending
+        .remove(&request.id);
+    let _ = app_handle.emit(
+        "biometry-approval-resolved",
+        serde_json::json!({ "id": request.id, "decision": decision }),
+    );
+
+    Some(decision)
+}
+
+#[tauri::command]
+pub fn set_biometry_manual_approval(enabled: bool, queue: tauri::State<'_, Arc<Mutex<ApprovalQueue>>>) -> Result<bool, String> {
+    let mut queue = queue.lock().map_err(|_| "Falha ao obter lock da fila de aprovação".to_string())?;
+    queue.enabled = enabled;
+    if !enabled {
+        // Don't leave clients hanging once the mode is turned off
+        queue.reject_all();
+    }
+    Ok(queue.enabled)
+}
+
+#[tauri::command]
+pub fn list_pending_biometry_requests(queue: tauri::State<'_, Arc<Mutex<ApprovalQueue>>>) -> Result<Vec<PendingRequest>, String> {
+    let queue = queue.lock().map_err(|_| "Falha ao obter lock da fila de aprovação".to_string())?;
+    let mut requests: Vec<PendingRequest> = queue.pending.values().map(|p| p.request.clone()).collect();
+    requests.sort_by_key(|r| r.id);
+    Ok(requests)
+}
+
+#[tauri::command]
+pub fn resolve_biometry_request(id: u64, approve: bool, queue: tauri::State<'_, Arc<Mutex<ApprovalQueue>>>) -> Result<bool, String> {
+    let mut queue = queue.lock().map_err(|_| "Falha ao obter lock da fila de aprovação".to_string())?;
+    let pending = queue
+        .pending
+        .remove(&id)
+        .ok_or_else(|| format!("Requisição {} não está mais pendente.", id))?;
+    pending
+        .responder
+        .send(approve)
+        .map_err(|_| format!("Requisição {} já foi encerrada pelo cliente.", id))?;
+    Ok(true)
+}
@@ -1,20 +1,155 @@
+use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
 use std::net::SocketAddr;
+use std::time::{Duration, Instant};
 use axum::{
-    routing::post,
+    routing::{get, post},
     Router,
     Json,
-    extract::State,
-    http::StatusCode,
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
 };
 use tower_http::cors::{Any, CorsLayer};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tokio::sync::oneshot;
+use tauri::AppHandle;
+
+use crate::biometry_approval::{self, Decision};
+use crate::notifications;
+use crate::openapi::{self, OPENAPI_PATH};
+use crate::patient;
+use crate::verification_audit;
+
+const DEFAULT_HOST: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 21004;
+
+// Route paths of the emulated agent. Sites whose middleware expects another
+// agent (`/api/capture`, `/biometria/ler`...) remap them via `biometry_routes`
+// in the app config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BiometryRoutes {
+    pub root: String,
+    pub capture: String,
+    pub verify: String,
+    pub shutdown: String,
+}
+
+impl Default for BiometryRoutes {
+    fn default() -> Self {
+        Self {
+            root: "/".into(),
+            capture: "/capture".into(),
+            verify: "/verify".into(),
+            shutdown: "/shutdown".into(),
+        }
+    }
+}
+
+impl BiometryRoutes {
+    // axum panics on malformed or duplicated routes, so check them up front
+    fn validate(&self) -> Result<(), String> {
+        let paths = [&self.root, &self.capture, &self.verify, &self.shutdown];
+        for (i, path) in paths.iter().enumerate() {
+            if !path.starts_with('/') {
+                return Err(format!("Rota '{}' do servidor de biometria deve começar com '/'.", path));
+            }
+            if path.contains(['*', ':', '{', '}', ' ']) {
+                return Err(format!("Rota '{}' do servidor de biometria contém caracteres inválidos.", path));
+            }
+            if path.as_str() == OPENAPI_PATH {
+                return Err(format!("Rota '{}' é reservada para a especificação OpenAPI.", path));
+            }
+            if paths[..i].contains(path) {
+                return Err(format!("Rota '{}' configurada para mais de um endpoint.", path));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn load_routes(app_handle: &AppHandle) -> Result<BiometryRoutes, String> {
+    let routes = patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get("biometry_routes").cloned())
+        .map(serde_json::from_value::<BiometryRoutes>)
+        .transpose()
+        .map_err(|e| format!("Configuração 'biometry_routes' inválida: {}", e))?
+        .unwrap_or_default();
+    routes.validate()?;
+    Ok(routes)
+}
+
+fn load_rate_limit(app_handle: &AppHandle) -> Option<RateLimit> {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get("biometry_rate_limit").cloned())
+        .and_then(|v| serde_json::from_value(v).ok())
+}
+
+async fn throttle_requests(
+    State(state): State<Arc<Mutex<BiometryServerState>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let retry_after = state.lock().unwrap().throttle();
+    match retry_after {
+        Some(secs) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, secs.to_string())],
+            Json(json!({
+                "success": false,
+                "message": "Muitas requisições ao agente de biometria. Tente novamente mais tarde."
+            })),
+        )
+            .into_response(),
+        None => next.run(request).await,
+    }
+}
+
+// Shutdown and the spec are added after the throttling layer so they are
+// never refused
+fn build_router(routes: &BiometryRoutes, server_url: String, state: Arc<Mutex<BiometryServerState>>) -> Router {
+    let spec_routes = routes.clone();
+    let serve_spec = move |State(state): State<Arc<Mutex<BiometryServerState>>>| {
+        let routes = spec_routes.clone();
+        let server_url = server_url.clone();
+        async move {
+            let throttled = state.lock().unwrap().rate_limit.is_some();
+            Json(openapi::biometry_server_spec(&routes, &server_url, throttled))
+        }
+    };
+
+    Router::new()
+        .route(&routes.root, post(handle_root))
+        .route(&routes.capture, post(handle_capture))
+        .route(&routes.verify, post(handle_verify))
+        .route_layer(middleware::from_fn_with_state(state.clone(), throttle_requests))
+        .route(&routes.shutdown, post(handle_shutdown))
+        .route(OPENAPI_PATH, get(serve_spec))
+        .with_state(state)
+}
+
+// Throttling simulation: past `max_requests` within `window_secs` the server
+// answers 429 with Retry-After, to exercise the portal's backoff.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub max_requests: u32,
+    pub window_secs: u64,
+    #[serde(default)]
+    pub retry_after_secs: Option<u64>,
+}
 
 pub struct BiometryServerState {
     biometry_data: Vec<String>,
     shutdown_tx: Option<oneshot::Sender<()>>,
+    started_at: Option<Instant>,
+    app_handle: Option<AppHandle>,
+    rate_limit: Option<RateLimit>,
+    recent_requests: VecDeque<Instant>,
 }
 
 impl BiometryServerState {
@@ -22,12 +157,67 @@ impl BiometryServerState {
         Self {
             biometry_data: Vec::new(),
             shutdown_tx: None,
+            started_at: None,
+            app_handle: None,
+            rate_limit: None,
+            recent_requests: VecDeque::new(),
+        }
+    }
+
+    pub fn set_rate_limit(&mut self, rate_limit: Option<RateLimit>) {
+        self.rate_limit = rate_limit;
+        self.recent_requests.clear();
+    }
+
+    // Records the request and returns the Retry-After seconds when over the limit
+    fn throttle(&mut self) -> Option<u64> {
+        let limit = self.rate_limit.as_ref()?;
+        let window = Duration::from_secs(limit.window_secs.max(1));
+        let now = Instant::now();
+        while self.recent_requests.front().is_some_and(|t| now.duration_since(*t) >= window) {
+            self.recent_requests.pop_front();
+        }
+
+        if self.recent_requests.len() >= limit.max_requests as usize {
+            let retry_after = limit.retry_after_secs.unwrap_or_else(|| {
+                let oldest = self.recent_requests.front().copied().unwrap_or(now);
+                window.saturating_sub(now.duration_since(oldest)).as_secs().max(1)
+            });
+            return Some(retry_after);
         }
+        self.recent_requests.push_back(now);
+        None
+    }
+
+    pub fn uptime_secs(&self) -> Option<u64> {
+        self.shutdown_tx.as_ref()?;
+        self.started_at.map(|t| t.elapsed().as_secs())
     }
 
     pub fn set_biometry_data(&mut self, data: Vec<String>) {
         self.biometry_data = data;
     }
+
+    fn is_exact_match(&self, code: &str) -> bool {
+        let code = code.trim();
+        self.biometry_data.iter().any(|d| d.trim() == code)
+    }
+
+    // Exact template matches score 100; anything else accepted by the
+    // permissive verify (or the operator) scores 0.
+    fn audit(&self, endpoint: &str, code: &str, matched: bool) {
+        if let Some(app_handle) = self.app_handle.clone() {
+            let score = if self.is_exact_match(code) { 100 } else { 0 };
+            verification_audit::record(
+                app_handle,
+                endpoint,
+                code.trim().to_string(),
+                self.biometry_data.clone(),
+                matched,
+                score,
+            );
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -69,6 +259,27 @@ pub struct RootRequest {
     code: Option<Vec<String>>,
 }
 
+// Waits for the operator when manual approval is on (None otherwise)
+async fn request_approval(
+    state: &Arc<Mutex<BiometryServerState>>,
+    kind: &str,
+    detail: serde_json::Value,
+) -> Option<Decision> {
+    let app_handle = state.lock().unwrap().app_handle.clone()?;
+    biometry_approval::decide(&app_handle, kind, detail).await
+}
+
+fn rejection_message(decision: Decision) -> &'static str {
+    match decision {
+        Decision::TimedOut => "Tempo de aprovação do operador esgotado.",
+        _ => "Requisição recusada pelo operador.",
+    }
+}
+
+fn code_preview(code: &str) -> String {
+    code.trim().chars().take(16).collect()
+}
+
 async fn handle_root(
     State(state): State<Arc<Mutex<BiometryServerState>>>,
     payload: Result<Json<RootRequest>, axum::extract::rejection::JsonRejection>,
@@ -89,6 +300,20 @@ async fn handle_root(
                         );
                     }
 
+                    let detail = json!({ "route": "root", "code": code_preview(&codes[0]) });
+                    if let Some(decision) = request_approval(&state, "verify", detail).await {
+                        let approved = decision == Decision::Approved;
+                        state.lock().unwrap().audit("root", &codes[0], approved);
+                        return (
+                            StatusCode::OK,
+                            Json(json!({
+                                "success": true,
+                                "match": approved,
+                                "message": if approved { "Verificação aprovada pelo operador." } else { rejection_message(decision) }
+                            })),
+                        );
+                    }
+
                     let state = state.lock().unwrap();
                     let biometric_to_verify = codes[0].trim().to_string();
                     let exact_match = state
@@ -96,6 +321,7 @@ async fn handle_root(
                         .iter()
                         .any(|d| d.trim() == biometric_to_verify);
                     let match_found = exact_match;
+                    state.audit("root", &biometric_to_verify, match_found);
 
                     return (
                         StatusCode::OK,
@@ -111,6 +337,19 @@ async fn handle_root(
     }
 
     // Default behavior: capture semantics
+    if let Some(decision) = request_approval(&state, "capture", json!({ "route": "root" })).await {
+        if decision != Decision::Approved {
+            return (
+                StatusCode::FORBIDDEN,
+                Json(json!({
+                    "success": false,
+                    "code": null,
+                    "message": rejection_message(decision)
+                })),
+            );
+        }
+    }
+
     let state = state.lock().unwrap();
     if state.biometry_data.is_empty() {
         return (
@@ -136,6 +375,19 @@ async fn handle_root(
 async fn handle_capture(
     State(state): State<Arc<Mutex<BiometryServerState>>>,
 ) -> (StatusCode, Json<CaptureResponse>) {
+    if let Some(decision) = request_approval(&state, "capture", json!({ "route": "capture" })).await {
+        if decision != Decision::Approved {
+            return (
+                StatusCode::FORBIDDEN,
+                Json(CaptureResponse {
+                    success: false,
+                    code: None,
+                    message: Some(rejection_message(decision).to_string()),
+                }),
+            );
+        }
+    }
+
     let state = state.lock().unwrap();
     
     if state.biometry_data.is_empty() {
@@ -175,6 +427,20 @@ async fn handle_verify(
         );
     }
     
+    let detail = json!({ "route": "verify", "code": code_preview(&payload.code[0]) });
+    if let Some(decision) = request_approval(&state, "verify", detail).await {
+        let approved = decision == Decision::Approved;
+        state.lock().unwrap().audit("verify", &payload.code[0], approved);
+        return (
+            StatusCode::OK,
+            Json(VerifyResponse {
+                success: true,
+                r#match: approved,
+                message: Some(if approved { "Verificação aprovada pelo operador." } else { rejection_message(decision) }.to_string()),
+            }),
+        );
+    }
+
     let state = state.lock().unwrap();
     // Compatível com Python: se houver qualquer biometria carregada, considerar match.
     // Mantém compatibilidade com teste estrito por igualdade.
@@ -185,6 +451,7 @@ async fn handle_verify(
         .iter()
         .any(|d| d.trim() == biometric_to_verify);
     let match_found = has_any && (exact_match || true);
+    state.audit("verify", &biometric_to_verify, match_found);
     
     (
         StatusCode::OK,
@@ -273,15 +540,19 @@ pub async fn run_server(
     Ok(())
 }
 
-#[tauri::command]
-pub async fn start_biometry_server(
-    host: String,
+// Binds the listener up front (so a port in use is reported immediately) and
+// serves the emulator in a background task.
+pub async fn spawn_server(
+    app_handle: AppHandle,
+    host: &str,
     port: u16,
     biometry_data: Vec<String>,
-    state: tauri::State<'_, Arc<Mutex<BiometryServerState>>>,
+    server_state: Arc<Mutex<BiometryServerState>>,
 ) -> Result<bool, String> {
+    let routes = load_routes(&app_handle)?;
+
     // Tenta vincular antes para retornar erro imediato se a porta estiver em uso
-    let addr: SocketAddr = format!("{}:{}", &host, port)
+    let addr: SocketAddr = format!("{}:{}", host, port)
         .parse()
         .map_err(|e| format!("Endereço inválido: {}", e))?;
     let listener = match tokio::net::TcpListener::bind(&addr).await {
@@ -289,12 +560,12 @@ pub async fn start_biometry_server(
         Err(e) => return Err(format!("Falha ao vincular servidor em {}: {}", addr, e)),
     };
 
-    // Clone o estado para a thread do servidor
-    let server_state = state.inner().clone();
     // Configurar estado inicial antes de servir
     {
         let mut s = server_state.lock().unwrap();
-        s.set_biometry_data(biometry_data.clone());
+        s.set_biometry_data(biometry_data);
+        s.app_handle = Some(app_handle.clone());
+        s.set_rate_limit(load_rate_limit(&app_handle));
     }
 
     tokio::spawn(async move {
@@ -303,14 +574,10 @@ pub async fn start_biometry_server(
         {
             let mut s = server_state.lock().unwrap();
             s.shutdown_tx = Some(tx);
+            s.started_at = Some(Instant::now());
         }
 
-        let app = Router::new()
-            .route("/", post(handle_root))
-            .route("/capture", post(handle_capture))
-            .route("/verify", post(handle_verify))
-            .route("/shutdown", post(handle_shutdown))
-            .with_state(server_state.clone());
+        let app = build_router(&routes, format!("http://{}", addr), server_state.clone());
 
         println!("Servidor de biometria iniciado em http://{}:{}", addr.ip(), addr.port());
 
@@ -321,21 +588,55 @@ pub async fn start_biometry_server(
         });
         if let Err(e) = graceful.await {
             eprintln!("Erro no servidor: {}", e);
+            server_state.lock().unwrap().shutdown_tx = None;
+            notifications::notify(
+                &app_handle,
+                "Servidor de biometria parou",
+                &format!("O servidor em {} foi encerrado com erro: {}", addr, e),
+            );
         }
     });
 
     Ok(true)
 }
 
+// Signals the running server to shut down; returns false if none is running.
+pub fn request_shutdown(server_state: &Arc<Mutex<BiometryServerState>>) -> bool {
+    let mut state = server_state.lock().unwrap();
+    match state.shutdown_tx.take() {
+        Some(tx) => tx.send(()).is_ok(),
+        None => false,
+    }
+}
+
+pub fn is_running(server_state: &Arc<Mutex<BiometryServerState>>) -> bool {
+    let state = server_state.lock().unwrap();
+    state.shutdown_tx.is_some()
+}
+
+#[tauri::command]
+pub async fn start_biometry_server(
+    app_handle: AppHandle,
+    host: String,
+    port: u16,
+    biometry_data: Vec<String>,
+    state: tauri::State<'_, Arc<Mutex<BiometryServerState>>>,
+) -> Result<bool, String> {
+    // Clone o estado para a thread do servidor
+    spawn_server(app_handle, &host, port, biometry_data, state.inner().clone()).await
+}
+
 #[tauri::command]
 pub async fn stop_biometry_server(
+    app_handle: AppHandle,
     host: String,
     port: u16,
     state: tauri::State<'_, Arc<Mutex<BiometryServerState>>>,
 ) -> Result<bool, String> {
     // Try to send a shutdown request to the server
     let client = reqwest::Client::new();
-    let url = format!("http://{}:{}/shutdown", host, port);
+    let shutdown_path = load_routes(&app_handle).map(|r| r.shutdown).unwrap_or_else(|_| BiometryRoutes::default().shutdown);
+    let url = format!("http://{}:{}{}", host, port, shutdown_path);
     
     match client.post(&url).send().await {
         Ok(_) => Ok(true),
@@ -357,6 +658,40 @@ pub async fn stop_biometry_server(
 pub fn check_biometry_server_status(
     state: tauri::State<'_, Arc<Mutex<BiometryServerState>>>,
 ) -> bool {
-    let state = state.inner().lock().unwrap();
-    state.shutdown_tx.is_some()
-}
\ No newline at end of file
+    is_running(state.inner())
+}
+
+// Changes the throttling of the running server; None turns it off
+#[tauri::command]
+pub fn set_biometry_rate_limit(
+    rate_limit: Option<RateLimit>,
+    state: tauri::State<'_, Arc<Mutex<BiometryServerState>>>,
+) -> Result<bool, String> {
+    if rate_limit.as_ref().is_some_and(|l| l.max_requests == 0) {
+        return Err("O limite de requisições deve ser maior que zero.".into());
+    }
+    let mut state = state.lock().map_err(|_| "Falha ao obter lock do servidor de biometria".to_string())?;
+    state.set_rate_limit(rate_limit);
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn get_biometry_openapi(
+    app_handle: AppHandle,
+    host: Option<String>,
+    port: Option<u16>,
+    state: tauri::State<'_, Arc<Mutex<BiometryServerState>>>,
+) -> Result<serde_json::Value, String> {
+    let routes = load_routes(&app_handle)?;
+    let server_url = format!(
+        "http://{}:{}",
+        host.unwrap_or_else(|| DEFAULT_HOST.to_string()),
+        port.unwrap_or(DEFAULT_PORT)
+    );
+    let throttled = state
+        .lock()
+        .map_err(|_| "Falha ao obter lock do servidor de biometria".to_string())?
+        .rate_limit
+        .is_some();
+    Ok(openapi::biometry_server_spec(&routes, &server_url, throttled))
+}
@@ -0,0 +1,71 @@
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::biometric_crypto;
+use crate::patient;
+
+// Binary files (previews, attachments...) kept out of the patient records, stored
+// under <data dir>/blobs and addressed by key. They're encrypted like the
+// biometrics, as previews are fingerprint images.
+fn blobs_dir(app_handle: &tauri::AppHandle) -> io::Result<PathBuf> {
+    let mut dir = patient::ensure_data_dir(app_handle)?;
+    dir.push("blobs");
+    if !dir.exists() {
+        fs::create_dir_all(&dir)?;
+    }
+    Ok(dir)
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+// Keys become file names, so only a conservative character set is accepted
+fn validate_key(key: &str) -> io::Result<()> {
+    let valid = !key.is_empty()
+        && !key.starts_with('.')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if valid {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid blob key: {key}")))
+    }
+}
+
+pub fn blob_path(app_handle: &tauri::AppHandle, key: &str) -> io::Result<PathBuf> {
+    validate_key(key)?;
+    Ok(blobs_dir(app_handle)?.join(key))
+}
+
+// Stores content under a caller-derived key (e.g. caches keyed by their source)
+pub fn store_as(app_handle: &tauri::AppHandle, key: &str, data: &[u8]) -> io::Result<()> {
+    let path = blob_path(app_handle, key)?;
+    biometric_crypto::write_encrypted_file(&path, data)
+}
+
+// Content-addressed: identical files share one blob
+pub fn store(app_handle: &tauri::AppHandle, data: &[u8]) -> io::Result<String> {
+    let key = format!("{}.bin", sha256_hex(data));
+    let path = blob_path(app_handle, &key)?;
+    if !path.exists() {
+        biometric_crypto::write_encrypted_file(&path, data)?;
+    }
+    Ok(key)
+}
+
+pub fn remove(app_handle: &tauri::AppHandle, key: &str) -> io::Result<()> {
+    match fs::remove_file(blob_path(app_handle, key)?) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        other => other,
+    }
+}
+
+pub fn load(app_handle: &tauri::AppHandle, key: &str) -> io::Result<Vec<u8>> {
+    biometric_crypto::read_encrypted_file(&blob_path(app_handle, key)?)
+}
+
+pub fn exists(app_handle: &tauri::AppHandle, key: &str) -> bool {
+    blob_path(app_handle, key).map(|p| p.exists()).unwrap_or(false)
+}
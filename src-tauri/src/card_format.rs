@@ -0,0 +1,360 @@
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+use crate::barcode::BarcodeLayout;
+use crate::beneficiary_sync::iso_utc;
+use crate::magstripe::MagstripeLayout;
+use crate::patient;
+use crate::totvs_profiles;
+
+pub const DEFAULT_FORMAT_ID: &str = "padrao";
+
+// Length of the operator (insurer) code at the start of a complete wallet number
+const INSURER_CODE_LEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckDigitRule {
+    #[default]
+    None,
+    // Luhn
+    Mod10,
+    // Weights 2..9 from the right, remainders 0/1 give digit 0
+    Mod11,
+}
+
+// Layout of the text a card reader types for a wallet. The template accepts the
+// placeholders {wallet} (complete number), {insurer} or {operator_code} (first
+// 4 digits), {card} (the remaining digits) and {expiry} (YYMM). With
+// `magstripe`, the tracks of a magnetic card are typed instead; with
+// `barcode`, the rendered template as a barcode scanner types it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CardFormat {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub insurer_codes: Vec<String>,
+    pub template: String,
+    #[serde(default)]
+    pub wallet_length: Option<usize>,
+    #[serde(default)]
+    pub check_digit: CheckDigitRule,
+    // YYMM typed for {expiry}; without it, December of next year, so the
+    // card is always valid
+    #[serde(default)]
+    pub expiry: Option<String>,
+    #[serde(default)]
+    pub magstripe: Option<MagstripeLayout>,
+    #[serde(default)]
+    pub barcode: Option<BarcodeLayout>,
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CardValidation {
+    pub valid: bool,
+    pub format_id: String,
+    pub errors: Vec<String>,
+}
+
+impl CardFormat {
+    // `holder` is the name on track 1 of a magnetic layout
+    pub fn render(&self, wallet: &str, holder: Option<&str>) -> String {
+        if let Some(layout) = &self.magstripe {
+            return layout.render(&self.pan(layout, wallet), &self.expiry(), holder);
+        }
+        let text = self.render_template(wallet);
+        match &self.barcode {
+            Some(layout) => layout.render(&text),
+            None => text,
+        }
+    }
+
+    fn render_template(&self, wallet: &str) -> String {
+        let (insurer, card) = split_wallet(wallet);
+        let mut text = self
+            .template
+            .replace("{wallet}", wallet)
+            .replace("{insurer}", insurer)
+            .replace("{operator_code}", insurer)
+            .replace("{card}", card);
+        if text.contains("{expiry}") {
+            text = text.replace("{expiry}", &self.expiry());
+        }
+        text
+    }
+
+    fn expiry(&self) -> String {
+        self.expiry.clone().unwrap_or_else(default_expiry)
+    }
+
+    // Wallet with the check digit the magnetic layout appends
+    fn pan(&self, layout: &MagstripeLayout, wallet: &str) -> String {
+        match check_digit(layout.pan_check_digit, &digits(wallet)) {
+            Some(digit) => format!("{}{}", wallet, digit),
+            None => wallet.to_string(),
+        }
+    }
+
+    pub fn validate(&self, wallet: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if wallet.is_empty() {
+            errors.push("Número da carteira vazio.".to_string());
+            return errors;
+        }
+        if !wallet.chars().all(|c| c.is_ascii_digit()) {
+            errors.push("A carteira deve conter apenas dígitos.".to_string());
+            return errors;
+        }
+        if let Some(expected) = self.wallet_length {
+            if wallet.len() != expected {
+                errors.push(format!(
+                    "A carteira deve ter {} dígitos (informado: {}).",
+                    expected,
+                    wallet.len()
+                ));
+            }
+        }
+        if !self.insurer_codes.is_empty() {
+            let (insurer, _) = split_wallet(wallet);
+            if !self.insurer_codes.iter().any(|c| c == insurer) {
+                errors.push(format!(
+                    "Operadora {} não pertence ao formato {}.",
+                    insurer, self.name
+                ));
+            }
+        }
+        if !check_digit_matches(self.check_digit, wallet) {
+            errors.push("Dígito verificador inválido.".to_string());
+        }
+        if let Some(layout) = &self.magstripe {
+            errors.extend(layout.validate(&self.pan(layout, wallet), &self.expiry()));
+        }
+        if let Some(layout) = &self.barcode {
+            errors.extend(layout.validate(&self.render_template(wallet)));
+        }
+
+        errors
+    }
+}
+
+fn default_expiry() -> String {
+    let now = iso_utc(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0));
+    let year: u32 = now[..4].parse().unwrap_or(2000);
+    format!("{:02}12", (year + 1) % 100)
+}
+
+fn split_wallet(wallet: &str) -> (&str, &str) {
+    if wallet.len() > INSURER_CODE_LEN && wallet.is_char_boundary(INSURER_CODE_LEN) {
+        wallet.split_at(INSURER_CODE_LEN)
+    } else {
+        ("", wallet)
+    }
+}
+
+fn digits(s: &str) -> Vec<u32> {
+    s.chars().filter_map(|c| c.to_digit(10)).collect()
+}
+
+fn check_digit_matches(rule: CheckDigitRule, wallet: &str) -> bool {
+    let all = digits(wallet);
+    let Some((&check, body)) = all.split_last() else {
+        return rule == CheckDigitRule::None;
+    };
+    check_digit(rule, body).is_none_or(|expected| expected == check)
+}
+
+// Digit the rule expects after `body`; None without a rule
+fn check_digit(rule: CheckDigitRule, body: &[u32]) -> Option<u32> {
+    match rule {
+        CheckDigitRule::None => None,
+        CheckDigitRule::Mod10 => {
+            let sum: u32 = body
+                .iter()
+                .rev()
+                .enumerate()
+                .map(|(i, &d)| {
+                    if i % 2 == 0 {
+                        let doubled = d * 2;
+                        if doubled > 9 { doubled - 9 } else { doubled }
+                    } else {
+                        d
+                    }
+                })
+                .sum();
+            Some((10 - sum % 10) % 10)
+        }
+        CheckDigitRule::Mod11 => {
+            let sum: u32 = body
+                .iter()
+                .rev()
+                .enumerate()
+                .map(|(i, &d)| d * (2 + (i as u32 % 8)))
+                .sum();
+            let remainder = sum % 11;
+            Some(if remainder < 2 { 0 } else { 11 - remainder })
+        }
+    }
+}
+
+fn builtin_formats() -> Vec<CardFormat> {
+    vec![
+        CardFormat {
+            id: DEFAULT_FORMAT_ID.into(),
+            name: "Padrão TOTVS (trilha 2)".into(),
+            insurer_codes: Vec::new(),
+            template: ";{wallet}=011903=004105713104?".into(),
+            wallet_length: None,
+            check_digit: CheckDigitRule::None,
+            expiry: None,
+            magstripe: None,
+            barcode: None,
+            builtin: true,
+        },
+        CardFormat {
+            id: "trilha2-simples".into(),
+            name: "Trilha 2 somente carteira".into(),
+            insurer_codes: Vec::new(),
+            template: ";{wallet}?".into(),
+            wallet_length: None,
+            check_digit: CheckDigitRule::None,
+            expiry: None,
+            magstripe: None,
+            barcode: None,
+            builtin: true,
+        },
+        CardFormat {
+            id: "carteira17-mod11".into(),
+            name: "Carteira 17 dígitos com DV módulo 11".into(),
+            insurer_codes: Vec::new(),
+            template: ";{wallet}=011903=004105713104?".into(),
+            wallet_length: Some(17),
+            check_digit: CheckDigitRule::Mod11,
+            expiry: None,
+            magstripe: None,
+            barcode: None,
+            builtin: true,
+        },
+        CardFormat {
+            id: "magnetico-trilhas-1-2".into(),
+            name: "Leitor magnético (trilhas 1 e 2)".into(),
+            insurer_codes: Vec::new(),
+            template: String::new(),
+            wallet_length: None,
+            check_digit: CheckDigitRule::None,
+            expiry: None,
+            magstripe: Some(MagstripeLayout::default()),
+            barcode: None,
+            builtin: true,
+        },
+        CardFormat {
+            id: "codigo-barras".into(),
+            name: "Leitor de código de barras (Code 128 + Enter)".into(),
+            insurer_codes: Vec::new(),
+            template: "{wallet}".into(),
+            wallet_length: None,
+            check_digit: CheckDigitRule::None,
+            expiry: None,
+            magstripe: None,
+            barcode: Some(BarcodeLayout::default()),
+            builtin: true,
+        },
+    ]
+}
+
+// Built-in formats plus user-defined ones from `card_formats` in the app config.
+// A user format with the same id as a built-in replaces it.
+pub fn load_formats(app_handle: &AppHandle) -> Vec<CardFormat> {
+    let mut formats = builtin_formats();
+
+    let config = patient::load_config_from_disk(app_handle).unwrap_or_else(|_| serde_json::json!({}));
+    if let Some(custom) = config.get("card_formats").and_then(|v| v.as_array()) {
+        for value in custom {
+            match serde_json::from_value::<CardFormat>(value.clone()) {
+                Ok(mut format) => {
+                    format.builtin = false;
+                    formats.retain(|f| f.id != format.id);
+                    formats.push(format);
+                }
+                Err(e) => tracing::warn!("Formato de cartão inválido na configuração: {}", e),
+            }
+        }
+    }
+
+    formats
+}
+
+// Picks the explicit format, otherwise the first one registered for the wallet's
+// insurer, otherwise the configured default (`default_card_format` of the
+// active TOTVS profile, then of the app config).
+pub fn resolve_format(app_handle: &AppHandle, wallet: &str, format_id: Option<&str>) -> Result<CardFormat, String> {
+    pick_format(&load_formats(app_handle), &default_format_id(app_handle), wallet, format_id)
+}
+
+pub fn default_format_id(app_handle: &AppHandle) -> String {
+    let config = patient::load_config_from_disk(app_handle).unwrap_or_default();
+    totvs_profiles::profile_setting(&config, "default_card_format")
+        .or_else(|| config.get("default_card_format"))
+        .and_then(|v| v.as_str())
+        .map(String::from)
+        .unwrap_or_else(|| DEFAULT_FORMAT_ID.to_string())
+}
+
+// `resolve_format` over formats already loaded
+pub fn pick_format(formats: &[CardFormat], default_id: &str, wallet: &str, format_id: Option<&str>) -> Result<CardFormat, String> {
+    if let Some(id) = format_id.filter(|id| !id.is_empty()) {
+        return formats
+            .iter()
+            .find(|f| f.id == id)
+            .cloned()
+            .ok_or_else(|| format!("Formato de cartão '{}' não encontrado.", id));
+    }
+
+    let (insurer, _) = split_wallet(wallet);
+    if let Some(format) = formats.iter().find(|f| f.insurer_codes.iter().any(|c| c == insurer)) {
+        return Ok(format.clone());
+    }
+
+    formats
+        .iter()
+        .find(|f| f.id == default_id)
+        .cloned()
+        .ok_or_else(|| format!("Formato de cartão padrão '{}' não encontrado.", default_id))
+}
+
+// Text typed for `wallet`, with the name of its patient on track 1 when the
+// format has one
+pub fn render_card(app_handle: &AppHandle, format: &CardFormat, wallet: &str) -> String {
+    let holder = format
+        .magstripe
+        .as_ref()
+        .filter(|layout| layout.uses_name())
+        .and_then(|_| patient::find_patient_by_wallet(app_handle, wallet).ok().flatten())
+        .map(|p| p.name);
+    format.render(wallet, holder.as_deref())
+}
+
+#[tauri::command]
+pub fn list_card_formats(app_handle: AppHandle) -> Vec<CardFormat> {
+    load_formats(&app_handle)
+}
+
+#[tauri::command]
+pub fn validate_card(app_handle: AppHandle, wallet: String, format_id: Option<String>) -> Result<CardValidation, String> {
+    let format = resolve_format(&app_handle, &wallet, format_id.as_deref())?;
+    let errors = format.validate(&wallet);
+    Ok(CardValidation {
+        valid: errors.is_empty(),
+        format_id: format.id,
+        errors,
+    })
+}
+
+#[tauri::command]
+pub fn preview_card_text(app_handle: AppHandle, wallet: String, format_id: Option<String>) -> Result<String, String> {
+    let format = resolve_format(&app_handle, &wallet, format_id.as_deref())?;
+    Ok(render_card(&app_handle, &format, &wallet))
+}
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::AppHandle;
+
+use crate::totvs::error::TotvsError;
+use crate::totvs::models::Checkin;
+use crate::totvs_endpoints;
+use crate::totvs_http;
+
+// How the beneficiary's identity was confirmed at the reception
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationMethod {
+    Fingerprint,
+    Facial,
+    Token,
+    Card,
+}
+
+// TOTVS explains rejected check-ins in `message`/`detailedMessage`
+fn error_message(body: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(body).ok()?;
+    ["detailedMessage", "message"]
+        .iter()
+        .find_map(|k| value.get(*k).and_then(|v| v.as_str()))
+        .filter(|m| !m.trim().is_empty())
+        .map(String::from)
+}
+
+// Registers an attendance for the beneficiary on the Portal do Prestador,
+// exactly as the reception would after validating the patient on a device
+#[tauri::command]
+pub async fn perform_checkin(
+    app_handle: AppHandle,
+    card_number: String,
+    validation_method: ValidationMethod,
+) -> Result<Checkin, TotvsError> {
+    let card_number = card_number.trim();
+    if card_number.is_empty() {
+        return Err("Número da carteira não informado.".into());
+    }
+
+    let portal = crate::portal_context(&app_handle)?;
+    let body = json!({
+        "cardNumber": card_number,
+        "validationMethod": validation_method,
+        "provider": portal.provider_code,
+        "providerHealthInsurer": portal.health_insurer_code,
+        "clinic": portal.clinic,
+    });
+
+    let client = totvs_http::client(&app_handle)?;
+    let response = totvs_endpoints::send(&app_handle, "checkin", &portal.credentials, Some(card_number), |url| {
+        tracing::debug!(target: "totvs", "URL Check-in: {}", url);
+        client
+            .post(url)
+            .query(&portal.query())
+            .json(&body)
+            .header("Accept", "application/json")
+            .header("x-totvs-hgp-portal-prestador-clinic", portal.clinic.as_str())
+    })
+    .await?;
+
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| TotvsError::network(format!("Falha ao ler resposta do check-in: {e}")))?;
+    if !status.is_success() {
+        let message = match error_message(&text) {
+            Some(message) => format!("Check-in recusado ({}): {}", status, message),
+            None => format!("Falha na requisição: {}", status),
+        };
+        return Err(TotvsError::status(status, message));
+    }
+
+    let json: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| TotvsError::parse(format!("Falha ao decodificar JSON: {e}")))?;
+    Ok(Checkin::from_value(json, "check-in")?)
+}
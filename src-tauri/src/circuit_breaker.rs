@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::patient;
+use crate::totvs::error::TotvsError;
+use crate::totvs_http;
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+const DEFAULT_OPEN_SECS: u64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+struct CircuitBreakerConfig {
+    failure_threshold: u32,
+    open_secs: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            open_secs: DEFAULT_OPEN_SECS,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Circuit {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    // A half-open circuit lets a single probe through at a time
+    probe_in_flight: bool,
+    last_error: Option<String>,
+}
+
+impl Circuit {
+    fn new() -> Self {
+        Self {
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            opened_at: None,
+            probe_in_flight: false,
+            last_error: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitStatus {
+    pub endpoint: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub retry_in_secs: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+// One circuit per TOTVS endpoint: after `failure_threshold` consecutive
+// failures requests fail fast for `open_secs` instead of each waiting for the
+// full timeout, then a probe decides whether to close it again. Tunable via
+// `circuit_breaker` in the app config.
+pub struct CircuitBreakerState {
+    circuits: HashMap<String, Circuit>,
+}
+
+impl CircuitBreakerState {
+    pub fn new() -> Self {
+        Self {
+            circuits: HashMap::new(),
+        }
+    }
+
+    fn status(&self, open_for: Duration) -> Vec<CircuitStatus> {
+        let mut statuses: Vec<CircuitStatus> = self
+            .circuits
+            .iter()
+            .map(|(endpoint, circuit)| CircuitStatus {
+                endpoint: endpoint.clone(),
+                state: circuit.state,
+                consecutive_failures: circuit.consecutive_failures,
+                retry_in_secs: match (circuit.state, circuit.opened_at) {
+                    (CircuitState::Open, Some(at)) => Some(open_for.saturating_sub(at.elapsed()).as_secs()),
+                    _ => None,
+                },
+                last_error: circuit.last_error.clone(),
+            })
+            .collect();
+        statuses.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+        statuses
+    }
+}
+
+fn load_config(app_handle: &AppHandle) -> CircuitBreakerConfig {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get("circuit_breaker").cloned())
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn emit_change(app_handle: &AppHandle, endpoint: &str, circuit: &Circuit) {
+    let _ = app_handle.emit(
+        "totvs-circuit-changed",
+        serde_json::json!({
+            "endpoint": endpoint,
+            "state": circuit.state,
+            "last_error": circuit.last_error,
+        }),
+    );
+}
+
+// Ok(true) when this request is the probe of a half-open circuit
+fn before_request(app_handle: &AppHandle, endpoint: &str, config: &CircuitBreakerConfig) -> Result<bool, String> {
+    let state = app_handle.state::<Arc<Mutex<CircuitBreakerState>>>();
+    let mut state = state.lock().unwrap();
+    let circuit = state.circuits.entry(endpoint.to_string()).or_insert_with(Circuit::new);
+    let open_for = Duration::from_secs(config.open_secs);
+
+    match circuit.state {
+        CircuitState::Closed => Ok(false),
+        CircuitState::Open => {
+            let elapsed = circuit.opened_at.map(|t| t.elapsed()).unwrap_or(open_for);
+            if elapsed < open_for {
+                return Err(format!(
+                    "TOTVS indisponível: requisições suspensas por {}s após falhas consecutivas.",
+                    (open_for - elapsed).as_secs().max(1)
+                ));
+            }
+            circuit.state = CircuitState::HalfOpen;
+            circuit.probe_in_flight = true;
+            emit_change(app_handle, endpoint, circuit);
+            Ok(true)
+        }
+        CircuitState::HalfOpen if circuit.probe_in_flight => {
+            Err("TOTVS indisponível: aguardando resultado da tentativa de reconexão.".into())
+        }
+        CircuitState::HalfOpen => {
+            circuit.probe_in_flight = true;
+            Ok(true)
+        }
+    }
+}
+
+fn record_result(app_handle: &AppHandle, endpoint: &str, config: &CircuitBreakerConfig, error: Option<String>) {
+    let state = app_handle.state::<Arc<Mutex<CircuitBreakerState>>>();
+    let mut state = state.lock().unwrap();
+    let circuit = state.circuits.entry(endpoint.to_string()).or_insert_with(Circuit::new);
+    let previous = circuit.state;
+    circuit.probe_in_flight = false;
+
+    match error {
+        None => {
+            circuit.state = CircuitState::Closed;
+            circuit.consecutive_failures = 0;
+            circuit.opened_at = None;
+            circuit.last_error = None;
+        }
+        Some(error) => {
+            circuit.consecutive_failures += 1;
+            circuit.last_error = Some(error);
+            if previous == CircuitState::HalfOpen || circuit.consecutive_failures >= config.failure_threshold.max(1) {
+                circuit.state = CircuitState::Open;
+                circuit.opened_at = Some(Instant::now());
+            }
+        }
+    }
+
+    if circuit.state != previous {
+        emit_change(app_handle, endpoint, circuit);
+    }
+}
+
+// Frees the half-open probe slot when the request future is dropped before
+// its result is recorded (a cancelled import, a closed window); otherwise the
+// circuit would refuse every request until reset
+struct ProbeGuard<'a> {
+    app_handle: &'a AppHandle,
+    endpoint: &'a str,
+    armed: bool,
+}
+
+impl Drop for ProbeGuard<'_> {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        let state = self.app_handle.state::<Arc<Mutex<CircuitBreakerState>>>();
+        if let Ok(mut state) = state.lock() {
+            if let Some(circuit) = state.circuits.get_mut(self.endpoint) {
+                circuit.probe_in_flight = false;
+            }
+        };
+    }
+}
+
+// Sends a TOTVS request through the endpoint's circuit. The circuit sees the
+// outcome after retries: transport errors and 5xx count as failures, other
+// statuses are left to the caller.
+pub async fn send(app_handle: &AppHandle, endpoint: &str, request: reqwest::RequestBuilder) -> Result<reqwest::Response, TotvsError> {
+    let config = load_config(app_handle);
+    let probe = before_request(app_handle, endpoint, &config).map_err(TotvsError::network)?;
+    let mut guard = ProbeGuard { app_handle, endpoint, armed: probe };
+
+    let result = totvs_http::send_with_retry(app_handle, endpoint, request).await;
+    record_result(app_handle, endpoint, &config, result.as_ref().err().map(|e| e.to_string()));
+    guard.armed = false;
+    result
+}
+
+#[tauri::command]
+pub fn get_totvs_circuit_status(
+    app_handle: AppHandle,
+    state: tauri::State<'_, Arc<Mutex<CircuitBreakerState>>>,
+) -> Result<Vec<CircuitStatus>, String> {
+    let open_for = Duration::from_secs(load_config(&app_handle).open_secs);
+    let state = state.lock().map_err(|_| "Falha ao obter lock do circuit breaker".to_string())?;
+    Ok(state.status(open_for))
+}
+
+#[tauri::command]
+pub fn reset_totvs_circuits(state: tauri::State<'_, Arc<Mutex<CircuitBreakerState>>>) -> Result<bool, String> {
+    let mut state = state.lock().map_err(|_| "Falha ao obter lock do circuit breaker".to_string())?;
+    state.circuits.clear();
+    Ok(true)
+}
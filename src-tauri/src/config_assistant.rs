@@ -0,0 +1,259 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::patient;
+use crate::totvs::error::TotvsError;
+use crate::totvs_auth::{self, AuthMode};
+use crate::totvs_http;
+use crate::totvs_profiles;
+
+pub(crate) const PORTPREST_BASE: &str = "/dts/datasul-rest/resources/prg/portprest/v1";
+const PAGE_SIZE: &str = "100";
+// Safety net against endpoints that always report hasNext
+const MAX_PAGES: u32 = 20;
+
+// Field names vary between Datasul releases, so codes and names are taken from
+// the first key present.
+const CODE_KEYS: [&str; 6] = ["code", "id", "healthInsurerCode", "providerCode", "clinicCode", "clinic"];
+const NAME_KEYS: [&str; 6] = ["name", "description", "shortName", "healthInsurerName", "providerName", "clinicName"];
+
+#[derive(Debug, Deserialize)]
+pub struct TotvsCredentials {
+    pub base_url: String,
+    pub user: String,
+    pub password: String,
+    // Overrides `auth_mode` from the config, e.g. while testing the settings form
+    pub auth_mode: Option<AuthMode>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LookupOption {
+    pub code: String,
+    pub name: String,
+}
+
+// A list that could not be fetched carries the error instead of failing the
+// whole lookup, so the settings screen can fall back to free text for it.
+#[derive(Debug, Default, Serialize)]
+pub struct LookupList {
+    pub items: Vec<LookupOption>,
+    pub error: Option<TotvsError>,
+}
+
+impl From<Result<Vec<LookupOption>, TotvsError>> for LookupList {
+    fn from(result: Result<Vec<LookupOption>, TotvsError>) -> Self {
+        match result {
+            Ok(items) => Self { items, error: None },
+            Err(error) => Self { items: Vec::new(), error: Some(error) },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TotvsOptions {
+    pub health_insurers: LookupList,
+    pub providers: LookupList,
+    pub clinics: LookupList,
+}
+
+fn value_as_string(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) if !s.is_empty() => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+pub(crate) fn parse_option(item: &serde_json::Value) -> Option<LookupOption> {
+    let code = CODE_KEYS.iter().find_map(|k| item.get(*k).and_then(value_as_string))?;
+    let name = NAME_KEYS
+        .iter()
+        .find_map(|k| item.get(*k).and_then(value_as_string))
+        .unwrap_or_else(|| code.clone());
+    Some(LookupOption { code, name })
+}
+
+async fn fetch_list(
+    app_handle: &AppHandle,
+    client: &reqwest::Client,
+    credentials: &TotvsCredentials,
+    circuit: &str,
+    endpoint: &str,
+    extra_query: &[(&str, &str)],
+) -> Result<Vec<LookupOption>, TotvsError> {
+    let url = format!("{}{}", credentials.base_url.trim_end_matches('/'), endpoint);
+    let mut options = Vec::new();
+
+    for page in 1..=MAX_PAGES {
+        let page = page.to_string();
+        let mut query = vec![("page", page.as_str()), ("pageSize", PAGE_SIZE)];
+        query.extend_from_slice(extra_query);
+
+        let request = client
+            .get(&url)
+            .query(&query)
+            .header("Accept", "application/json");
+        let response = totvs_auth::send(app_handle, circuit, credentials, request).await?;
+
+        if !response.status().is_success() {
+            return Err(TotvsError::status(response.status(), format!("Falha na requisição: {}", response.status())));
+        }
+
+        let json: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| TotvsError::parse(format!("Falha ao decodificar JSON: {e}")))?;
+
+        if let Some(items) = json.get("items").and_then(|v| v.as_array()) {
+            options.extend(items.iter().filter_map(parse_option));
+        }
+        if !json.get("hasNext").and_then(|v| v.as_bool()).unwrap_or(false) {
+            break;
+        }
+    }
+
+    let mut seen = HashSet::new();
+    options.retain(|o| seen.insert(o.code.clone()));
+    options.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(options)
+}
+
+// Lists what the settings screen needs for dropdowns. Credentials come from the
+// form (not the saved config) so they can be checked before saving; clinics
+// depend on the provider, so they're only listed once one is chosen.
+#[tauri::command]
+pub async fn fetch_totvs_options(
+    app_handle: AppHandle,
+    credentials: TotvsCredentials,
+    health_insurer_code: Option<String>,
+    provider_code: Option<String>,
+) -> Result<TotvsOptions, TotvsError> {
+    if credentials.base_url.trim().is_empty() {
+        return Err(TotvsError::config("Base URL não informada."));
+    }
+
+    let client = totvs_http::client(&app_handle)?;
+
+    let health_insurers = fetch_list(
+        &app_handle,
+        &client,
+        &credentials,
+        "lookup_health_insurers",
+        &format!("{}/healthInsurers", PORTPREST_BASE),
+        &[],
+    )
+    .await;
+
+    let insurer_query: Vec<(&str, &str)> = health_insurer_code
+        .as_deref()
+        .map(|code| vec![("healthInsurer", code)])
+        .unwrap_or_default();
+    let providers = fetch_list(
+        &app_handle,
+        &client,
+        &credentials,
+        "lookup_providers",
+        &format!("{}/providers", PORTPREST_BASE),
+        &insurer_query,
+    )
+    .await;
+
+    let clinics = match provider_code.as_deref().filter(|c| !c.is_empty()) {
+        Some(provider) => {
+            fetch_list(
+                &app_handle,
+                &client,
+                &credentials,
+                "lookup_clinics",
+                &format!("{}/providers/{}/clinics", PORTPREST_BASE, provider),
+                &insurer_query,
+            )
+            .await
+        }
+        None => Ok(Vec::new()),
+    };
+
+    // Both base lists failing almost always means bad URL or credentials
+    if let (Err(e), Err(_)) = (&health_insurers, &providers) {
+        return Err(e.clone().context("Não foi possível consultar o TOTVS"));
+    }
+
+    Ok(TotvsOptions {
+        health_insurers: health_insurers.into(),
+        providers: providers.into(),
+        clinics: clinics.into(),
+    })
+}
+
+// Saved credentials (active profile applied) plus the importer settings, for
+// the lookups below that default to the configured codes
+fn saved_settings(app_handle: &AppHandle) -> Result<(TotvsCredentials, serde_json::Value), TotvsError> {
+    let config = patient::load_config_from_disk(app_handle)
+        .map_err(|e| TotvsError::config(format!("Falha ao ler configurações: {e}")))?;
+    let settings = totvs_profiles::importer_settings(&config);
+    Ok((crate::totvs_credentials(&settings)?, settings))
+}
+
+fn code_or_saved(code: Option<String>, settings: &serde_json::Value, key: &str) -> Option<String> {
+    code.or_else(|| settings.get(key).and_then(|v| v.as_str()).map(String::from))
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+}
+
+#[tauri::command]
+pub async fn list_health_insurers(app_handle: AppHandle) -> Result<Vec<LookupOption>, TotvsError> {
+    let (credentials, _) = saved_settings(&app_handle)?;
+    let client = totvs_http::client(&app_handle)?;
+    fetch_list(
+        &app_handle,
+        &client,
+        &credentials,
+        "lookup_health_insurers",
+        &format!("{}/healthInsurers", PORTPREST_BASE),
+        &[],
+    )
+    .await
+}
+
+// Without a code, the health insurer saved in the settings filters the list
+#[tauri::command]
+pub async fn list_providers(app_handle: AppHandle, health_insurer_code: Option<String>) -> Result<Vec<LookupOption>, TotvsError> {
+    let (credentials, settings) = saved_settings(&app_handle)?;
+    let insurer = code_or_saved(health_insurer_code, &settings, "health_insurer_code");
+    let query: Vec<(&str, &str)> = insurer.as_deref().map(|c| vec![("healthInsurer", c)]).unwrap_or_default();
+    let client = totvs_http::client(&app_handle)?;
+    fetch_list(
+        &app_handle,
+        &client,
+        &credentials,
+        "lookup_providers",
+        &format!("{}/providers", PORTPREST_BASE),
+        &query,
+    )
+    .await
+}
+
+// Clinics of the given provider, or of the one saved in the settings
+#[tauri::command]
+pub async fn list_clinics(
+    app_handle: AppHandle,
+    provider_code: Option<String>,
+    health_insurer_code: Option<String>,
+) -> Result<Vec<LookupOption>, TotvsError> {
+    let (credentials, settings) = saved_settings(&app_handle)?;
+    let provider = code_or_saved(provider_code, &settings, "provider_code")
+        .ok_or_else(|| TotvsError::config("Código do prestador não definido nas configurações."))?;
+    let insurer = code_or_saved(health_insurer_code, &settings, "health_insurer_code");
+    let query: Vec<(&str, &str)> = insurer.as_deref().map(|c| vec![("healthInsurer", c)]).unwrap_or_default();
+    let client = totvs_http::client(&app_handle)?;
+    fetch_list(
+        &app_handle,
+        &client,
+        &credentials,
+        "lookup_clinics",
+        &format!("{}/providers/{}/clinics", PORTPREST_BASE, provider),
+        &query,
+    )
+    .await
+}
@@ -0,0 +1,124 @@
+use serde_json::{Map, Value};
+
+// app_config.json records the format it was written in under `schema_version`
+// (absent means 0). On load, each step after that version runs in order, so
+// an old file is brought up to date one format at a time. Steps must leave a
+// config that already has their change untouched.
+pub const VERSION_KEY: &str = "schema_version";
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[nest_importer_settings, move_leftover_importer_settings];
+pub const SCHEMA_VERSION: u64 = MIGRATIONS.len() as u64;
+
+// Settings the importer reads through `importer_config`; everything else
+// (biometry server, hotkey, keystroke...) stays at the root
+const IMPORTER_KEYS: [&str; 14] = [
+    "base_url",
+    "user",
+    "password",
+    "clinic",
+    "provider_code",
+    "health_insurer_code",
+    "portal_user",
+    "portal_password",
+    "proxy_host",
+    "proxy_port",
+    "proxy_user",
+    "proxy_password",
+    "sync_changed_after_param",
+    "endpoint_templates",
+];
+
+// Root importer keys are moved into `importer_config`, replacing what it has
+fn move_importer_keys(config: &mut Map<String, Value>) {
+    let moved: Vec<(String, Value)> = IMPORTER_KEYS
+        .iter()
+        .filter_map(|key| config.remove(*key).map(|value| (key.to_string(), value)))
+        .collect();
+    if moved.is_empty() {
+        return;
+    }
+    let importer = config
+        .entry("importer_config")
+        .or_insert_with(|| Value::Object(Map::new()));
+    if !importer.is_object() {
+        *importer = Value::Object(Map::new());
+    }
+    if let Some(importer) = importer.as_object_mut() {
+        importer.extend(moved);
+    }
+}
+
+// 0 -> 1: the importer settings used to live at the root
+fn nest_importer_settings(config: &mut Map<String, Value>) {
+    move_importer_keys(config);
+}
+
+// 1 -> 2: step 1 used to copy the keys and leave them at the root, where the
+// settings form kept editing them. Those root values are the current ones.
+fn move_leftover_importer_settings(config: &mut Map<String, Value>) {
+    move_importer_keys(config);
+}
+
+pub fn version(config: &Value) -> u64 {
+    config.get(VERSION_KEY).and_then(|v| v.as_u64()).unwrap_or(0)
+}
+
+// Runs the pending steps; a config from a newer version is left as is.
+// Returns whether it changed.
+pub fn migrate(config: &mut Value) -> bool {
+    let from = version(config);
+    let Some(map) = config.as_object_mut().filter(|_| from < SCHEMA_VERSION) else {
+        return false;
+    };
+    for step in &MIGRATIONS[from as usize..] {
+        step(map);
+    }
+    map.insert(VERSION_KEY.into(), Value::from(SCHEMA_VERSION));
+    true
+}
+
+// Configs written by this version without the key (e.g. a settings form that
+// builds the object from scratch) are already current
+pub fn stamp(config: &mut Value) {
+    if let Some(map) = config.as_object_mut() {
+        map.entry(VERSION_KEY).or_insert(Value::from(SCHEMA_VERSION));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn move_importer_keys_replaces_nested_values_and_keeps_the_rest() {
+        let mut config = json!({
+            "base_url": "http://novo",
+            "hotkey": "^q",
+            "importer_config": { "base_url": "http://antigo", "clinic": "7" },
+        });
+        move_importer_keys(config.as_object_mut().unwrap());
+        assert_eq!(
+            config,
+            json!({ "hotkey": "^q", "importer_config": { "base_url": "http://novo", "clinic": "7" } })
+        );
+    }
+
+    #[test]
+    fn move_importer_keys_replaces_an_importer_config_that_is_not_an_object() {
+        let mut config = json!({ "user": "ana", "importer_config": "quebrado" });
+        move_importer_keys(config.as_object_mut().unwrap());
+        assert_eq!(config, json!({ "importer_config": { "user": "ana" } }));
+    }
+
+    #[test]
+    fn migrate_runs_pending_steps_once() {
+        let mut config = json!({ "password": "x", "schema_version": 1 });
+        assert!(migrate(&mut config));
+        assert_eq!(config, json!({ "importer_config": { "password": "x" }, "schema_version": SCHEMA_VERSION }));
+        assert!(!migrate(&mut config));
+
+        let mut newer = json!({ "password": "x", "schema_version": SCHEMA_VERSION + 1 });
+        assert!(!migrate(&mut newer));
+        assert_eq!(newer["password"], "x");
+    }
+}
@@ -0,0 +1,243 @@
+use std::error::Error as _;
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::config_assistant::{parse_option, TotvsCredentials, PORTPREST_BASE};
+use crate::patient;
+use crate::totvs::error::TotvsError;
+use crate::totvs_auth;
+use crate::totvs_http;
+use crate::totvs_profiles;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Diagnosis {
+    Ok,
+    MissingSettings,
+    InvalidUrl,
+    DnsFailure,
+    ConnectionRefused,
+    Timeout,
+    SslError,
+    Unauthorized,
+    Forbidden,
+    WrongProvider,
+    WrongClinic,
+    ServerError,
+    Unexpected,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConnectionReport {
+    pub diagnosis: Diagnosis,
+    pub message: String,
+    pub hint: Option<String>,
+    pub status: Option<u16>,
+}
+
+impl ConnectionReport {
+    fn new(diagnosis: Diagnosis, message: impl Into<String>, hint: Option<&str>) -> Self {
+        Self {
+            diagnosis,
+            message: message.into(),
+            hint: hint.map(String::from),
+            status: None,
+        }
+    }
+
+    fn with_status(mut self, status: reqwest::StatusCode) -> Self {
+        self.status = Some(status.as_u16());
+        self
+    }
+}
+
+// Settings under test; when omitted the saved importer config is used
+#[derive(Debug, Deserialize)]
+pub struct ConnectionSettings {
+    #[serde(flatten)]
+    pub credentials: TotvsCredentials,
+    pub clinic: String,
+    pub provider_code: String,
+    pub health_insurer_code: String,
+}
+
+fn saved_settings(app_handle: &AppHandle) -> Result<ConnectionSettings, TotvsError> {
+    let config = patient::load_config_from_disk(app_handle)
+        .map_err(|e| TotvsError::config(format!("Falha ao ler configurações: {e}")))?;
+    let cfg = totvs_profiles::importer_settings(&config);
+    let field = |key: &str| cfg.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    Ok(ConnectionSettings {
+        credentials: TotvsCredentials {
+            base_url: field("base_url"),
+            user: field("user"),
+            password: field("password"),
+            auth_mode: None,
+        },
+        clinic: field("clinic"),
+        provider_code: field("provider_code"),
+        health_insurer_code: field("health_insurer_code"),
+    })
+}
+
+// reqwest only exposes timeout/connect flags; DNS and TLS failures have to be
+// recognized from the messages of the underlying errors.
+fn classify_transport_error(error: &reqwest::Error) -> ConnectionReport {
+    let mut chain = Vec::new();
+    let mut source = error.source();
+    while let Some(e) = source {
+        chain.push(e.to_string().to_lowercase());
+        source = e.source();
+    }
+    let chain = chain.join(" | ");
+    let mentions = |words: &[&str]| words.iter().any(|w| chain.contains(w));
+
+    if error.is_timeout() {
+        ConnectionReport::new(
+            Diagnosis::Timeout,
+            "Tempo esgotado aguardando o servidor.",
+            Some("Verifique VPN/firewall e se o servidor está no ar; aumente os timeouts em totvs_http se a rede for lenta."),
+        )
+    } else if mentions(&["dns", "lookup address", "no such host", "name or service not known", "nodename"]) {
+        ConnectionReport::new(
+            Diagnosis::DnsFailure,
+            format!("Não foi possível resolver o endereço do servidor: {}", error),
+            Some("Confira o nome do host na Base URL e o DNS/VPN desta máquina."),
+        )
+    } else if mentions(&["certificate", "tls", "ssl", "handshake"]) {
+        ConnectionReport::new(
+            Diagnosis::SslError,
+            format!("Falha na conexão segura (SSL/TLS): {}", error),
+            Some("O certificado do servidor não é confiável nesta máquina; instale a CA da empresa ou confirme se a URL usa http em vez de https."),
+        )
+    } else if mentions(&["refused", "actively refused"]) {
+        ConnectionReport::new(
+            Diagnosis::ConnectionRefused,
+            "Conexão recusada pelo servidor.",
+            Some("Confira a porta na Base URL e se o serviço Datasul está em execução."),
+        )
+    } else {
+        ConnectionReport::new(
+            Diagnosis::Unexpected,
+            format!("Falha ao conectar: {}", error),
+            Some("Confira a Base URL e a conexão de rede."),
+        )
+    }
+}
+
+async fn diagnose(app_handle: &AppHandle, settings: &ConnectionSettings) -> Result<ConnectionReport, TotvsError> {
+    let credentials = &settings.credentials;
+    let missing: Vec<&str> = [
+        ("base_url", credentials.base_url.as_str()),
+        ("user", credentials.user.as_str()),
+        ("password", credentials.password.as_str()),
+        ("clinic", settings.clinic.as_str()),
+        ("provider_code", settings.provider_code.as_str()),
+    ]
+    .iter()
+    .filter(|(_, value)| value.trim().is_empty())
+    .map(|(key, _)| *key)
+    .collect();
+    if !missing.is_empty() {
+        return Ok(ConnectionReport::new(
+            Diagnosis::MissingSettings,
+            format!("Configurações não preenchidas: {}", missing.join(", ")),
+            Some("Preencha os campos nas configurações do importador."),
+        ));
+    }
+
+    let base_url = credentials.base_url.trim().trim_end_matches('/');
+    if reqwest::Url::parse(base_url).is_err() {
+        return Ok(ConnectionReport::new(
+            Diagnosis::InvalidUrl,
+            format!("Base URL inválida: {}", base_url),
+            Some("Use o formato http(s)://servidor:porta."),
+        ));
+    }
+
+    let client = totvs_http::client(app_handle)?;
+
+    // Unauthenticated probe: any HTTP answer proves DNS, TCP and TLS are fine
+    if let Err(e) = client.get(base_url).send().await {
+        return Ok(classify_transport_error(&e));
+    }
+
+    let mut query = Vec::new();
+    if !settings.health_insurer_code.trim().is_empty() {
+        query.push(("healthInsurer", settings.health_insurer_code.trim()));
+    }
+    let request = client
+        .get(format!("{}{}/providers/{}/clinics", base_url, PORTPREST_BASE, settings.provider_code.trim()))
+        .query(&query)
+        .header("Accept", "application/json")
+        .header("x-totvs-hgp-portal-prestador-clinic", settings.clinic.trim());
+    // A 5xx left after the retries, or a refused token request, comes back
+    // as an error rather than a response
+    let response = match totvs_auth::send(app_handle, "connection_test", credentials, request).await {
+        Ok(response) => response,
+        Err(e @ TotvsError::Network { .. }) => return Ok(server_error(e.message())),
+        Err(e @ TotvsError::Unauthorized { .. }) => return Ok(unauthorized(e.message())),
+        Err(e) => return Ok(ConnectionReport::new(Diagnosis::Unexpected, e, None)),
+    };
+
+    let status = response.status();
+    let report = match status.as_u16() {
+        200..=299 => {
+            let json: serde_json::Value = response.json().await.unwrap_or_default();
+            let clinics: Vec<String> = json
+                .get("items")
+                .and_then(|v| v.as_array())
+                .map(|items| items.iter().filter_map(parse_option).map(|o| o.code).collect())
+                .unwrap_or_default();
+            if clinics.is_empty() || clinics.iter().any(|c| c == settings.clinic.trim()) {
+                ConnectionReport::new(Diagnosis::Ok, "Conexão e configurações válidas.", None)
+            } else {
+                ConnectionReport::new(
+                    Diagnosis::WrongClinic,
+                    format!("Clínica {} não pertence ao prestador {}.", settings.clinic.trim(), settings.provider_code.trim()),
+                    Some(&format!("Clínicas disponíveis: {}", clinics.join(", "))),
+                )
+            }
+        }
+        401 => unauthorized("Usuário ou senha recusados."),
+        403 => ConnectionReport::new(
+            Diagnosis::Forbidden,
+            "Acesso negado ao Portal do Prestador.",
+            Some("O usuário autenticou, mas não tem permissão para este prestador/clínica; confira o cabeçalho de clínica e o cadastro do usuário."),
+        ),
+        404 => ConnectionReport::new(
+            Diagnosis::WrongProvider,
+            format!("Prestador {} não encontrado.", settings.provider_code.trim()),
+            Some("Confira o código do prestador e da operadora, e se a Base URL aponta para a raiz do Datasul."),
+        ),
+        _ => ConnectionReport::new(Diagnosis::Unexpected, format!("Resposta inesperada: {}", status), None),
+    };
+    Ok(report.with_status(status))
+}
+
+fn unauthorized(message: &str) -> ConnectionReport {
+    ConnectionReport::new(
+        Diagnosis::Unauthorized,
+        message,
+        Some("Confira as credenciais e o modo de autenticação (basic/token)."),
+    )
+}
+
+fn server_error(message: &str) -> ConnectionReport {
+    ConnectionReport::new(
+        Diagnosis::ServerError,
+        format!("O servidor TOTVS não respondeu corretamente: {}", message),
+        Some("Tente novamente mais tarde; se persistir, verifique os logs do Datasul."),
+    )
+}
+
+#[tauri::command]
+pub async fn test_totvs_connection(
+    app_handle: AppHandle,
+    settings: Option<ConnectionSettings>,
+) -> Result<ConnectionReport, TotvsError> {
+    let settings = match settings {
+        Some(settings) => settings,
+        None => saved_settings(&app_handle)?,
+    };
+    diagnose(&app_handle, &settings).await
+}
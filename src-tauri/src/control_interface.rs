@@ -0,0 +1,381 @@
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::oneshot;
+
+use crate::biometry_server::{self, BiometryServerState};
+use crate::card_format;
+use crate::hotkey::HotkeyManager;
+use crate::keystroke;
+use crate::patient::{self, FaceAngle};
+use crate::webcam_emulator::{WebcamEmulator, WebcamSource};
+
+#[cfg(windows)]
+const DEFAULT_PIPE_NAME: &str = r"\\.\pipe\VirtualIOHub";
+
+const DEFAULT_SERVER_HOST: &str = "127.0.0.1";
+const DEFAULT_SERVER_PORT: u16 = 21004;
+
+// Local control channel for automation tools that can't use HTTP ports: a named
+// pipe on Windows, a Unix socket elsewhere. Requests and responses are one JSON
+// object per line.
+pub struct ControlInterfaceState {
+    shutdown_tx: Option<oneshot::Sender<()>>,
+    endpoint: Option<String>,
+    selected_patient_id: Option<u32>,
+}
+
+impl ControlInterfaceState {
+    pub fn new() -> Self {
+        Self {
+            shutdown_tx: None,
+            endpoint: None,
+            selected_patient_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum ControlRequest {
+    Status,
+    SelectPatient { patient_id: u32 },
+    TriggerSwipe { patient_id: Option<u32> },
+    StartHotkey { patient_id: Option<u32> },
+    StopHotkey,
+    StartBiometryServer { host: Option<String>, port: Option<u16> },
+    StopBiometryServer,
+    StartWebcam { source_type: String, source_data: Option<String> },
+    StopWebcam,
+}
+
+#[derive(Debug, Serialize)]
+struct ControlResponse {
+    success: bool,
+    message: Option<String>,
+    data: serde_json::Value,
+}
+
+type ControlState = Arc<Mutex<ControlInterfaceState>>;
+
+fn selected_patient(app: &AppHandle, patient_id: Option<u32>) -> Result<patient::Patient, String> {
+    let patient_id = match patient_id {
+        Some(id) => id,
+        None => app
+            .state::<ControlState>()
+            .lock()
+            .unwrap()
+            .selected_patient_id
+            .ok_or("Nenhum paciente selecionado.")?,
+    };
+
+    patient::load_patients_from_disk(app)
+        .map_err(|e| format!("Falha ao ler pacientes: {e}"))?
+        .into_iter()
+        .find(|p| p.id == patient_id)
+        .ok_or_else(|| format!("Paciente {} não encontrado.", patient_id))
+}
+
+fn patient_biometry_data(patient: &patient::Patient) -> Vec<String> {
+    patient.digital_biometrics.iter().map(|d| d.data.clone()).collect()
+}
+
+// Hotkey operations may download AutoHotkey or wait on the process, so they
+// run on the blocking pool.
+async fn run_hotkey<F>(app: &AppHandle, op: F) -> Result<bool, String>
+where
+    F: FnOnce(&AppHandle, &Mutex<HotkeyManager>) -> Result<bool, String> + Send + 'static,
+{
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let manager = app.state::<Mutex<HotkeyManager>>();
+        op(&app, manager.inner())
+    })
+    .await
+    .map_err(|e| format!("Falha ao executar operação de hotkey: {e}"))?
+}
+
+fn server_address(app: &AppHandle, host: Option<String>, port: Option<u16>) -> (String, u16) {
+    let config = patient::load_config_from_disk(app).unwrap_or_else(|_| json!({}));
+    let host = host
+        .or_else(|| config.get("server_host").and_then(|v| v.as_str()).map(String::from))
+        .unwrap_or_else(|| DEFAULT_SERVER_HOST.to_string());
+    let port = port
+        .or_else(|| {
+            config.get("server_port").and_then(|v| {
+                v.as_u64()
+                    .and_then(|n| u16::try_from(n).ok())
+                    .or_else(|| v.as_str().and_then(|s| s.parse().ok()))
+            })
+        })
+        .unwrap_or(DEFAULT_SERVER_PORT);
+    (host, port)
+}
+
+async fn execute(app: &AppHandle, request: ControlRequest) -> Result<serde_json::Value, String> {
+    match request {
+        ControlRequest::Status => {
+            let selected = app.state::<ControlState>().lock().unwrap().selected_patient_id;
+            let hotkey = app.state::<Mutex<HotkeyManager>>().lock().unwrap().is_running();
+            let biometry = biometry_server::is_running(app.state::<Arc<Mutex<BiometryServerState>>>().inner());
+            let webcam = app.state::<Arc<Mutex<WebcamEmulator>>>().lock().unwrap().is_running();
+            Ok(json!({
+                "selected_patient_id": selected,
+                "hotkey_running": hotkey,
+                "biometry_server_running": biometry,
+                "webcam_running": webcam,
+            }))
+        }
+        ControlRequest::SelectPatient { patient_id } => {
+            let patient = selected_patient(app, Some(patient_id))?;
+            app.state::<ControlState>().lock().unwrap().selected_patient_id = Some(patient.id);
+
+            // Running emulators follow the selection
+            let wallet = patient.wallet.clone();
+            run_hotkey(app, move |app, manager| {
+                let mut manager = manager.lock().map_err(|_| "Falha ao obter lock do HotkeyManager".to_string())?;
+                if manager.is_running() {
+                    let format = card_format::resolve_format(app, &wallet, None)?;
+                    manager.start(app, &wallet, &format, keystroke::configured_mode(app))?;
+                }
+                Ok(true)
+            })
+            .await?;
+
+            let biometry_state = app.state::<Arc<Mutex<BiometryServerState>>>();
+            if biometry_server::is_running(biometry_state.inner()) {
+                biometry_state.lock().unwrap().set_biometry_data(patient_biometry_data(&patient));
+            }
+
+            let _ = app.emit("control-patient-selected", patient.id);
+            Ok(json!({ "patient_id": patient.id, "name": patient.name }))
+        }
+        ControlRequest::TriggerSwipe { patient_id } => {
+            let patient = selected_patient(app, patient_id)?;
+            let wallet = patient.wallet.clone();
+            run_hotkey(app, move |app, _| {
+                let format = card_format::resolve_format(app, &wallet, None)?;
+                HotkeyManager::send_once(app, &wallet, &format, keystroke::configured_mode(app))
+            })
+            .await?;
+            Ok(json!({ "patient_id": patient.id }))
+        }
+        ControlRequest::StartHotkey { patient_id } => {
+            let patient = selected_patient(app, patient_id)?;
+            let wallet = patient.wallet.clone();
+            run_hotkey(app, move |app, manager| {
+                let format = card_format::resolve_format(app, &wallet, None)?;
+                let mut manager = manager.lock().map_err(|_| "Falha ao obter lock do HotkeyManager".to_string())?;
+                manager.start(app, &wallet, &format, keystroke::configured_mode(app))
+            })
+            .await?;
+            Ok(json!({ "patient_id": patient.id }))
+        }
+        ControlRequest::StopHotkey => {
+            run_hotkey(app, |_, manager| {
+                let mut manager = manager.lock().map_err(|_| "Falha ao obter lock do HotkeyManager".to_string())?;
+                manager.stop()
+            })
+            .await?;
+            Ok(json!(true))
+        }
+        ControlRequest::StartBiometryServer { host, port } => {
+            let patient = selected_patient(app, None)?;
+            let (host, port) = server_address(app, host, port);
+            let state = app.state::<Arc<Mutex<BiometryServerState>>>().inner().clone();
+            biometry_server::spawn_server(app.clone(), &host, port, patient_biometry_data(&patient), state).await?;
+            Ok(json!({ "host": host, "port": port }))
+        }
+        ControlRequest::StopBiometryServer => {
+            let stopped = biometry_server::request_shutdown(app.state::<Arc<Mutex<BiometryServerState>>>().inner());
+            Ok(json!(stopped))
+        }
+        ControlRequest::StartWebcam { source_type, source_data } => {
+            // "facial" streams the selected patient's photo with the label
+            // given as data (default frontal)
+            let source = if source_type == "facial" {
+                let patient = selected_patient(app, None)?;
+                let label = match source_data.as_deref().filter(|l| !l.trim().is_empty()) {
+                    Some(label) => FaceAngle::parse(label).ok_or_else(|| format!("Ângulo de foto desconhecido: {}", label))?,
+                    None => FaceAngle::Frontal,
+                };
+                WebcamSource::facial_photo(&patient, label)?
+            } else {
+                WebcamSource::from_parts(&source_type, source_data.as_deref().unwrap_or_default())?
+            };
+            let webcam = app.state::<Arc<Mutex<WebcamEmulator>>>();
+            let mut emulator = webcam.lock().map_err(|_| "Falha ao obter lock do WebcamEmulator".to_string())?;
+            Ok(json!(emulator.start(source)?))
+        }
+        ControlRequest::StopWebcam => {
+            let webcam = app.state::<Arc<Mutex<WebcamEmulator>>>();
+            let mut emulator = webcam.lock().map_err(|_| "Falha ao obter lock do WebcamEmulator".to_string())?;
+            Ok(json!(emulator.stop()?))
+        }
+    }
+}
+
+async fn handle_connection<S>(stream: S, app: AppHandle)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<ControlRequest>(&line) {
+            Ok(request) => match execute(&app, request).await {
+                Ok(data) => ControlResponse { success: true, message: None, data },
+                Err(message) => ControlResponse { success: false, message: Some(message), data: serde_json::Value::Null },
+            },
+            Err(e) => ControlResponse {
+                success: false,
+                message: Some(format!("Comando inválido: {e}")),
+                data: serde_json::Value::Null,
+            },
+        };
+
+        let mut payload = serde_json::to_string(&response).unwrap_or_default();
+        payload.push('\n');
+        if writer.write_all(payload.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(unix)]
+fn default_endpoint(app: &AppHandle) -> Result<String, String> {
+    let dir = patient::ensure_data_dir(app).map_err(|e| format!("Falha ao obter diretório de dados: {e}"))?;
+    Ok(dir.join("control.sock").to_string_lossy().to_string())
+}
+
+#[cfg(windows)]
+fn default_endpoint(_app: &AppHandle) -> Result<String, String> {
+    Ok(DEFAULT_PIPE_NAME.to_string())
+}
+
+#[cfg(unix)]
+fn listen(app: AppHandle, endpoint: String, mut shutdown_rx: oneshot::Receiver<()>) -> Result<(), String> {
+    use std::os::unix::fs::FileTypeExt;
+
+    // A socket file left behind by a previous run would make bind fail; anything
+    // else at that path is not ours to delete
+    match std::fs::symlink_metadata(&endpoint) {
+        Ok(meta) if meta.file_type().is_socket() => {
+            std::fs::remove_file(&endpoint)
+                .map_err(|e| format!("Falha ao remover socket antigo em {}: {}", endpoint, e))?;
+        }
+        Ok(_) => {
+            return Err(format!("{} já existe e não é um socket; interface de controle não iniciada.", endpoint));
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Falha ao verificar {}: {}", endpoint, e)),
+    }
+    let listener = tokio::net::UnixListener::bind(&endpoint)
+        .map_err(|e| format!("Falha ao criar socket de controle em {}: {}", endpoint, e))?;
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let accepted = tokio::select! {
+                _ = &mut shutdown_rx => break,
+                accepted = listener.accept() => accepted,
+            };
+            match accepted {
+                Ok((stream, _)) => {
+                    tauri::async_runtime::spawn(handle_connection(stream, app.clone()));
+                }
+                Err(e) => tracing::warn!("Erro ao aceitar conexão de controle: {}", e),
+            }
+        }
+        let _ = std::fs::remove_file(&endpoint);
+        tracing::info!("Interface de controle desligada");
+    });
+
+    Ok(())
+}
+
+#[cfg(windows)]
+fn listen(app: AppHandle, endpoint: String, mut shutdown_rx: oneshot::Receiver<()>) -> Result<(), String> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(&endpoint)
+        .map_err(|e| format!("Falha ao criar pipe de controle {}: {}", endpoint, e))?;
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let connected = tokio::select! {
+                _ = &mut shutdown_rx => break,
+                connected = server.connect() => connected,
+            };
+            if let Err(e) = connected {
+                tracing::warn!("Erro ao aceitar conexão de controle: {}", e);
+                continue;
+            }
+
+            // A new pipe instance must exist before handing the connected one off
+            let next = match ServerOptions::new().create(&endpoint) {
+                Ok(next) => next,
+                Err(e) => {
+                    tracing::error!("Falha ao recriar pipe de controle: {}", e);
+                    break;
+                }
+            };
+            let client = std::mem::replace(&mut server, next);
+            tauri::async_runtime::spawn(handle_connection(client, app.clone()));
+        }
+        tracing::info!("Interface de controle desligada");
+    });
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn start_control_interface(
+    app_handle: AppHandle,
+    endpoint: Option<String>,
+    state: tauri::State<'_, ControlState>,
+) -> Result<String, String> {
+    // Held until the listener is registered so two concurrent starts can't both bind
+    let mut state = state.lock().map_err(|_| "Falha ao obter lock da interface de controle".to_string())?;
+    if state.shutdown_tx.is_some() {
+        return Err("Interface de controle já está ativa.".into());
+    }
+
+    let endpoint = match endpoint {
+        Some(e) if !e.trim().is_empty() => e,
+        _ => default_endpoint(&app_handle)?,
+    };
+
+    let (tx, rx) = oneshot::channel::<()>();
+    listen(app_handle, endpoint.clone(), rx)?;
+
+    state.shutdown_tx = Some(tx);
+    state.endpoint = Some(endpoint.clone());
+    tracing::info!("Interface de controle iniciada em {}", endpoint);
+
+    Ok(endpoint)
+}
+
+#[tauri::command]
+pub fn stop_control_interface(state: tauri::State<'_, ControlState>) -> Result<bool, String> {
+    let mut state = state.lock().map_err(|_| "Falha ao obter lock da interface de controle".to_string())?;
+    state.endpoint = None;
+    if let Some(tx) = state.shutdown_tx.take() {
+        let _ = tx.send(());
+    }
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn check_control_interface_status(state: tauri::State<'_, ControlState>) -> Result<Option<String>, String> {
+    let state = state.lock().map_err(|_| "Falha ao obter lock da interface de controle".to_string())?;
+    Ok(state.endpoint.clone())
+}
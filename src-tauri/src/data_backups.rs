@@ -0,0 +1,142 @@
+use rusqlite::Connection;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+use crate::data_lock;
+use crate::patient;
+use crate::workspaces;
+
+// Rotating copies under <data dir>/backups: patients.1.db is the newest
+// snapshot of the database, app_config.1.json the config before the last
+// save, and so on up to MAX_BACKUPS. Other workspaces' databases are
+// patients-<workspace>.<n>.db.
+const BACKUPS_DIR: &str = "backups";
+const MAX_BACKUPS: usize = 5;
+// The database is snapshotted before a write at most this often; it can be
+// large, the config is copied on every save
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Serialize)]
+pub struct RecoverySummary {
+    // False when the database was fine and nothing was touched
+    pub recovered: bool,
+    pub backup: Option<String>,
+    // Where the damaged database was moved
+    pub damaged: Option<String>,
+    pub patients: usize,
+}
+
+fn backups_dir(app_handle: &AppHandle) -> io::Result<PathBuf> {
+    let dir = patient::ensure_data_dir(app_handle)?.join(BACKUPS_DIR);
+    if !dir.exists() {
+        fs::create_dir_all(&dir)?;
+    }
+    Ok(dir)
+}
+
+// <stem>.<n>.<extension>, newest first
+fn backups(dir: &Path, stem: &str, extension: &str) -> Vec<PathBuf> {
+    (1..=MAX_BACKUPS)
+        .map(|n| dir.join(format!("{}.{}.{}", stem, n, extension)))
+        .collect()
+}
+
+// Shifts every copy one place, dropping the oldest, and returns the now free
+// path of the newest
+fn rotate(dir: &Path, stem: &str, extension: &str) -> io::Result<PathBuf> {
+    let paths = backups(dir, stem, extension);
+    for pair in paths.windows(2).rev() {
+        if pair[0].exists() {
+            fs::rename(&pair[0], &pair[1])?;
+        }
+    }
+    Ok(paths[0].clone())
+}
+
+fn is_recent(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age < SNAPSHOT_INTERVAL)
+}
+
+// Called with the data lock held, before a write. A failed snapshot is only
+// logged: it must not block the write.
+pub fn snapshot_patients(app_handle: &AppHandle, conn: &Connection) {
+    let stem = workspaces::backup_stem(app_handle);
+    let result = backups_dir(app_handle).and_then(|dir| {
+        if is_recent(&backups(&dir, &stem, "db")[0]) {
+            return Ok(());
+        }
+        let path = rotate(&dir, &stem, "db")?;
+        conn.execute("VACUUM INTO ?1", [path.to_string_lossy().into_owned()])
+            .map_err(io::Error::other)?;
+        Ok(())
+    });
+    if let Err(e) = result {
+        eprintln!("Falha ao criar cópia de segurança dos pacientes: {}", e);
+    }
+}
+
+// Keeps the config about to be overwritten
+pub fn backup_config(app_handle: &AppHandle, path: &Path) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let target = rotate(&backups_dir(app_handle)?, "app_config", "json")?;
+    fs::copy(path, target)?;
+    Ok(())
+}
+
+// Newest copy of the config that still parses
+pub fn latest_valid_config(app_handle: &AppHandle) -> Option<(PathBuf, serde_json::Value)> {
+    let dir = backups_dir(app_handle).ok()?;
+    backups(&dir, "app_config", "json").into_iter().find_map(|path| {
+        let value = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+        Some((path, value))
+    })
+}
+
+// Puts the newest snapshot that passes `patient::verify_database` in place of
+// the active workspace's patients.db, which is kept as
+// patients.db.damaged-<time> with its WAL files. Does nothing if the current
+// database is valid.
+#[tauri::command]
+pub fn recover_patients(app_handle: AppHandle) -> Result<RecoverySummary, String> {
+    let db_path = patient::database_path(&app_handle).map_err(|e| e.to_string())?;
+    if let Ok(patients) = patient::verify_database(&db_path) {
+        return Ok(RecoverySummary { recovered: false, backup: None, damaged: None, patients });
+    }
+
+    let dir = backups_dir(&app_handle).map_err(|e| format!("Falha ao localizar cópias de segurança: {e}"))?;
+    let (backup, patients) = backups(&dir, &workspaces::backup_stem(&app_handle), "db")
+        .into_iter()
+        .filter(|path| path.exists())
+        .find_map(|path| patient::verify_database(&path).ok().map(|count| (path, count)))
+        .ok_or_else(|| "Nenhuma cópia de segurança válida dos pacientes foi encontrada.".to_string())?;
+
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let damaged = db_path.with_extension(format!("db.damaged-{}", secs));
+    data_lock::with_write_lock(&app_handle, || {
+        for suffix in ["", "-wal", "-shm"] {
+            let file = PathBuf::from(format!("{}{}", db_path.display(), suffix));
+            if file.exists() {
+                fs::rename(&file, format!("{}{}", damaged.display(), suffix))?;
+            }
+        }
+        fs::copy(&backup, &db_path).map(|_| ())
+    })
+    .map_err(|e| format!("Falha ao restaurar cópia de segurança: {e}"))?;
+
+    Ok(RecoverySummary {
+        recovered: true,
+        backup: Some(backup.display().to_string()),
+        damaged: Some(damaged.display().to_string()),
+        patients,
+    })
+}
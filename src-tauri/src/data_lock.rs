@@ -0,0 +1,183 @@
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, ProcessesToUpdate, System};
+use tauri::AppHandle;
+
+use crate::patient;
+
+const LOCK_FILE_NAME: &str = "data.lock";
+// Another instance may be in the middle of a write; wait this long for it
+const LOCK_WAIT: Duration = Duration::from_secs(3);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+// Owner of the data directory, recorded in data.lock. Writes are refused while
+// the owner is another live process, so two app instances can't clobber
+// patients.db; a stale owner (process gone) is taken over silently.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub acquired_at: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DataLockStatus {
+    pub owner: Option<LockOwner>,
+    pub owned_by_us: bool,
+    pub owner_alive: bool,
+}
+
+fn lock_file_path(app_handle: &AppHandle) -> io::Result<PathBuf> {
+    Ok(patient::ensure_data_dir(app_handle)?.join(LOCK_FILE_NAME))
+}
+
+fn process_alive(pid: u32) -> bool {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+    system.process(pid).is_some()
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Opens data.lock holding the OS advisory lock, which serializes writers
+// across processes for the duration of a single write.
+fn open_locked(app_handle: &AppHandle) -> io::Result<File> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_file_path(app_handle)?)?;
+
+    let started = Instant::now();
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(file),
+            Err(TryLockError::WouldBlock) if started.elapsed() < LOCK_WAIT => thread::sleep(LOCK_RETRY_INTERVAL),
+            Err(TryLockError::WouldBlock) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "Diretório de dados bloqueado por outra instância em gravação.",
+                ))
+            }
+            Err(TryLockError::Error(e)) => return Err(e),
+        }
+    }
+}
+
+fn read_owner(file: &mut File) -> Option<LockOwner> {
+    let mut contents = String::new();
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_to_string(&mut contents).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn write_owner(file: &mut File) -> io::Result<()> {
+    let owner = LockOwner {
+        pid: std::process::id(),
+        acquired_at: now_secs(),
+    };
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(serde_json::to_string(&owner)?.as_bytes())?;
+    file.flush()
+}
+
+// Runs a data-dir write while holding the lock, claiming ownership if free
+pub fn with_write_lock<T>(app_handle: &AppHandle, write: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+    let mut file = open_locked(app_handle)?;
+    let own_pid = std::process::id();
+
+    match read_owner(&mut file) {
+        Some(owner) if owner.pid == own_pid => {}
+        Some(owner) if process_alive(owner.pid) => {
+            return Err(io::Error::other(format!(
+                "Diretório de dados em uso por outra instância (PID {}). Feche-a ou assuma o controle nas configurações.",
+                owner.pid
+            )));
+        }
+        _ => write_owner(&mut file)?,
+    }
+
+    let result = write();
+    let _ = file.unlock();
+    result
+}
+
+// Milliseconds since the epoch of this process's last write to each data-dir
+// file, so the file watcher can tell its own changes from external ones
+// without missing an external edit of another file made meanwhile
+static OWN_WRITES: OnceLock<Mutex<HashMap<PathBuf, u64>>> = OnceLock::new();
+
+fn own_writes() -> &'static Mutex<HashMap<PathBuf, u64>> {
+    OWN_WRITES.get_or_init(Default::default)
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub fn mark_own_write(path: &Path) {
+    if let Ok(mut writes) = own_writes().lock() {
+        writes.insert(path.to_path_buf(), now_millis());
+    }
+}
+
+pub fn wrote_recently(path: &Path, window: Duration) -> bool {
+    let Ok(writes) = own_writes().lock() else {
+        return false;
+    };
+    writes
+        .get(path)
+        .is_some_and(|&at| now_millis().saturating_sub(at) < window.as_millis() as u64)
+}
+
+// Writes to a temporary file, flushed to disk, and renames it over `path`, so
+// a crash mid-write leaves either the old or the new JSON, never a truncated one.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    let result = fs::rename(&tmp, path);
+    mark_own_write(path);
+    result
+}
+
+#[tauri::command]
+pub fn get_data_lock_status(app_handle: AppHandle) -> Result<DataLockStatus, String> {
+    let path = lock_file_path(&app_handle).map_err(|e| e.to_string())?;
+    let owner: Option<LockOwner> = fs::read_to_string(path)
+        .ok()
+        .and_then(|c| serde_json::from_str(&c).ok());
+    Ok(DataLockStatus {
+        owned_by_us: owner.as_ref().is_some_and(|o| o.pid == std::process::id()),
+        owner_alive: owner.as_ref().is_some_and(|o| process_alive(o.pid)),
+        owner,
+    })
+}
+
+// Force takeover: the other instance's subsequent writes are refused instead
+#[tauri::command]
+pub fn take_over_data_dir(app_handle: AppHandle) -> Result<LockOwner, String> {
+    let mut file = open_locked(&app_handle).map_err(|e| e.to_string())?;
+    write_owner(&mut file).map_err(|e| format!("Falha ao assumir diretório de dados: {e}"))?;
+    let owner = read_owner(&mut file).ok_or("Falha ao ler arquivo de bloqueio.")?;
+    let _ = file.unlock();
+    Ok(owner)
+}
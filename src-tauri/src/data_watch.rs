@@ -0,0 +1,95 @@
+use notify::RecursiveMode;
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+use crate::data_lock;
+use crate::patient;
+
+// Changes to the same file within this window are reported once
+const DEBOUNCE: Duration = Duration::from_millis(500);
+// Events this close to a write of our own are that write, not an external
+// edit (SQLite also touches the WAL right after a commit)
+const OWN_WRITE_WINDOW: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Changed {
+    Patients,
+    Config,
+}
+
+// Only the active workspace's database counts; the others aren't on screen
+fn classify(app_handle: &AppHandle, path: &Path) -> Option<Changed> {
+    match path.file_name()?.to_str()? {
+        // -shm changes on every read, so it isn't watched
+        "patients.db" | "patients.db-wal" => {
+            let active = patient::database_path(app_handle).ok()?;
+            (path.parent() == active.parent()).then_some(Changed::Patients)
+        }
+        "app_config.json" => Some(Changed::Config),
+        _ => None,
+    }
+}
+
+// The file a write of ours is recorded under: the WAL belongs to its database
+fn written_path(path: &Path) -> PathBuf {
+    match path.to_str().and_then(|p| p.strip_suffix("-wal")) {
+        Some(database) => PathBuf::from(database),
+        None => path.to_path_buf(),
+    }
+}
+
+// Watches the data dir, workspaces included, for patients.db and
+// app_config.json being changed by something other than this process (another
+// tool, a text editor) and emits `patients-changed` / `config-changed` so the
+// UI reloads them. Nothing is cached on this side; every command already
+// reads from disk.
+pub fn watch_data_dir(app_handle: AppHandle) {
+    let dir = match patient::ensure_data_dir(&app_handle) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Falha ao observar diretório de dados: {}", e);
+            return;
+        }
+    };
+    thread::spawn(move || {
+        let (tx, rx) = mpsc::channel::<DebounceEventResult>();
+        let mut debouncer = match new_debouncer(DEBOUNCE, tx) {
+            Ok(debouncer) => debouncer,
+            Err(e) => {
+                eprintln!("Falha ao observar diretório de dados: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = debouncer.watcher().watch(&dir, RecursiveMode::Recursive) {
+            eprintln!("Falha ao observar {}: {}", dir.display(), e);
+            return;
+        }
+
+        for result in rx {
+            let events = match result {
+                Ok(events) => events,
+                Err(e) => {
+                    eprintln!("Erro ao observar diretório de dados: {}", e);
+                    continue;
+                }
+            };
+            let changed: Vec<Changed> = events
+                .iter()
+                .filter(|e| !data_lock::wrote_recently(&written_path(&e.path), OWN_WRITE_WINDOW))
+                .filter_map(|e| classify(&app_handle, &e.path))
+                .collect();
+            if changed.contains(&Changed::Patients) {
+                tracing::debug!("Pacientes alterados fora do aplicativo; recarregando.");
+                let _ = app_handle.emit("patients-changed", ());
+            }
+            if changed.contains(&Changed::Config) {
+                tracing::debug!("Configurações alteradas fora do aplicativo; recarregando.");
+                let _ = app_handle.emit("config-changed", ());
+            }
+        }
+    });
+}
@@ -0,0 +1,151 @@
+use serde::Serialize;
+use serde_json::Value;
+use tauri::AppHandle;
+
+use crate::totvs::error::TotvsError;
+use crate::totvs_endpoints;
+use crate::totvs_http;
+
+// Fields Datasul releases have used for the situation and its explanation
+const STATUS_KEYS: [&str; 5] = ["status", "situation", "eligibilityStatus", "situacao", "eligibility"];
+const REASON_KEYS: [&str; 4] = ["reasons", "impediments", "messages", "details"];
+const MESSAGE_KEYS: [&str; 4] = ["detailedMessage", "message", "description", "reason"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EligibilityStatus {
+    Active,
+    Suspended,
+    Pending,
+    // A situation this emulator doesn't know how to classify; see `raw_status`
+    Unknown,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Eligibility {
+    pub card_number: String,
+    pub status: EligibilityStatus,
+    // Whether the portal would accept a swipe of this card
+    pub accepted: bool,
+    pub reasons: Vec<String>,
+    // Situation as TOTVS sent it
+    pub raw_status: Option<String>,
+}
+
+fn classify(status: &str) -> EligibilityStatus {
+    match status.trim().to_lowercase().as_str() {
+        "a" | "active" | "ativo" | "ativa" | "eligible" | "elegivel" | "elegível" | "ok" | "true" => EligibilityStatus::Active,
+        "s" | "b" | "c" | "i" | "suspended" | "suspenso" | "suspensa" | "blocked" | "bloqueado" | "bloqueada" | "cancelled"
+        | "canceled" | "cancelado" | "cancelada" | "inactive" | "inativo" | "inativa" | "not_eligible" | "inelegivel"
+        | "inelegível" | "false" => EligibilityStatus::Suspended,
+        "p" | "pending" | "pendente" | "em analise" | "em análise" | "awaiting" | "waiting" => EligibilityStatus::Pending,
+        _ => EligibilityStatus::Unknown,
+    }
+}
+
+fn message_of(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Object(_) => MESSAGE_KEYS.iter().find_map(|k| value.get(*k).and_then(|v| v.as_str()).map(String::from)),
+        _ => None,
+    }
+    .map(|m| m.trim().to_string())
+    .filter(|m| !m.is_empty())
+}
+
+// Datasul list endpoints wrap the record in `items`
+fn record(json: &Value) -> &Value {
+    match json.get("items").and_then(|v| v.as_array()).and_then(|items| items.first()) {
+        Some(item) => item,
+        None => json,
+    }
+}
+
+fn normalize(card_number: &str, json: &Value) -> Eligibility {
+    let record = record(json);
+    let raw_status = STATUS_KEYS.iter().find_map(|k| match record.get(*k) {
+        Some(Value::String(s)) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Some(Value::Bool(b)) => Some(b.to_string()),
+        _ => None,
+    });
+    let status = match (&raw_status, record.get("eligible").and_then(|v| v.as_bool())) {
+        (Some(raw), _) => classify(raw),
+        (None, Some(true)) => EligibilityStatus::Active,
+        (None, Some(false)) => EligibilityStatus::Suspended,
+        (None, None) => EligibilityStatus::Unknown,
+    };
+
+    let mut reasons: Vec<String> = REASON_KEYS
+        .iter()
+        .filter_map(|k| record.get(*k))
+        .flat_map(|v| match v {
+            Value::Array(items) => items.iter().filter_map(message_of).collect(),
+            other => message_of(other).into_iter().collect::<Vec<_>>(),
+        })
+        .collect();
+    // A refusal is sometimes only explained in the top-level message
+    if reasons.is_empty() && status != EligibilityStatus::Active {
+        reasons.extend(message_of(record));
+    }
+
+    Eligibility {
+        card_number: card_number.to_string(),
+        status,
+        accepted: status == EligibilityStatus::Active,
+        reasons,
+        raw_status,
+    }
+}
+
+// Asks the Datasul eligibility endpoint whether the beneficiary can be
+// attended, so the emulator can tell if a generated card swipe would be
+// accepted by the real portal
+#[tauri::command]
+pub async fn check_eligibility(app_handle: AppHandle, card_number: String) -> Result<Eligibility, TotvsError> {
+    let card_number = card_number.trim();
+    if card_number.is_empty() {
+        return Err("Número da carteira não informado.".into());
+    }
+
+    let portal = crate::portal_context(&app_handle)?;
+    let client = totvs_http::client(&app_handle)?;
+    let response = totvs_endpoints::send(&app_handle, "eligibility", &portal.credentials, Some(card_number), |url| {
+        tracing::debug!(target: "totvs", "URL Elegibilidade: {}", url);
+        client
+            .get(url)
+            .query(&portal.query())
+            .header("Accept", "application/json")
+            .header("x-totvs-hgp-portal-prestador-clinic", portal.clinic.as_str())
+    })
+    .await?;
+
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| TotvsError::network(format!("Falha ao ler resposta de elegibilidade: {e}")))?;
+    let json: Option<Value> = serde_json::from_str(&text).ok();
+
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(TotvsError::not_found(format!("Beneficiário {} não encontrado no TOTVS.", card_number)));
+    }
+    // Ineligible beneficiaries may come back as a rejected request with the
+    // reason in the error body
+    if matches!(status, reqwest::StatusCode::BAD_REQUEST | reqwest::StatusCode::UNPROCESSABLE_ENTITY) {
+        if let Some(reason) = json.as_ref().and_then(message_of) {
+            return Ok(Eligibility {
+                card_number: card_number.to_string(),
+                status: EligibilityStatus::Suspended,
+                accepted: false,
+                reasons: vec![reason],
+                raw_status: None,
+            });
+        }
+    }
+    if !status.is_success() {
+        return Err(TotvsError::status(status, format!("Falha na requisição: {}", status)));
+    }
+
+    let json = json.ok_or_else(|| TotvsError::parse("Resposta de elegibilidade não é um JSON válido."))?;
+    Ok(normalize(card_number, &json))
+}
@@ -0,0 +1,221 @@
+use base64::{engine::general_purpose as b64, Engine};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+use crate::blob_store;
+use crate::patient::{self, Patient};
+use crate::patient_validation::FieldError;
+
+const PROFILE_FORMAT_VERSION: u32 = 1;
+
+// Keys removed from the exported config unless credentials are explicitly included
+const CREDENTIAL_KEYS: [&str; 4] = ["password", "portal_password", "token_client_secret", "proxy_password"];
+
+// A complete, shareable emulator setup: app config (importer settings, biometry
+// server settings, hotkey profiles, scenarios...) plus the selected patients.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmulatorProfile {
+    pub format_version: u32,
+    pub name: String,
+    pub exported_at: u64,
+    pub config: serde_json::Value,
+    pub patients: Vec<Patient>,
+    // Attachment contents by blob key, base64; blobs live outside the patient
+    // records so they'd be lost on another machine otherwise
+    #[serde(default)]
+    pub attachment_blobs: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfileImportSummary {
+    pub name: String,
+    pub patients_added: usize,
+    pub patients_replaced: usize,
+    // Patients of the profile that failed validation and were left out
+    pub patients_skipped: Vec<FieldError>,
+    pub config_keys_imported: usize,
+}
+
+fn strip_credentials(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for key in CREDENTIAL_KEYS {
+                map.remove(key);
+            }
+            for (_, v) in map.iter_mut() {
+                strip_credentials(v);
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for v in items.iter_mut() {
+                strip_credentials(v);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn merge_config(current: &mut serde_json::Value, incoming: &serde_json::Value) -> usize {
+    let (Some(current_map), Some(incoming_map)) = (current.as_object_mut(), incoming.as_object()) else {
+        *current = incoming.clone();
+        return incoming.as_object().map(|m| m.len()).unwrap_or(0);
+    };
+
+    let mut count = 0;
+    for (key, value) in incoming_map {
+        match current_map.get_mut(key) {
+            // Merge nested sections so local credentials survive a credential-less import
+            Some(existing) if existing.is_object() && value.is_object() => {
+                merge_config(existing, value);
+            }
+            _ => {
+                current_map.insert(key.clone(), value.clone());
+            }
+        }
+        count += 1;
+    }
+    count
+}
+
+#[tauri::command]
+pub fn export_emulator_profile(
+    app_handle: AppHandle,
+    path: String,
+    name: String,
+    patient_ids: Option<Vec<u32>>,
+    include_credentials: bool,
+) -> Result<usize, String> {
+    let mut config = patient::load_config_from_disk(&app_handle)
+        .map_err(|e| format!("Falha ao ler configurações: {e}"))?;
+    if !include_credentials {
+        strip_credentials(&mut config);
+    }
+
+    let mut patients: Vec<Patient> = patient::load_patients_from_disk(&app_handle)
+        .map_err(|e| format!("Falha ao ler pacientes: {e}"))?
+        .into_iter()
+        .filter(|p| patient_ids.as_ref().is_none_or(|ids| ids.contains(&p.id)))
+        .collect();
+
+    let mut attachment_blobs = BTreeMap::new();
+    for patient in patients.iter_mut() {
+        patient.attachments.retain(|attachment| {
+            if attachment_blobs.contains_key(&attachment.blob_key) {
+                return true;
+            }
+            match blob_store::load(&app_handle, &attachment.blob_key) {
+                Ok(data) => {
+                    attachment_blobs.insert(attachment.blob_key.clone(), b64::STANDARD.encode(data));
+                    true
+                }
+                Err(e) => {
+                    tracing::warn!("Anexo {} do paciente {} não exportado: {}", attachment.file_name, patient.id, e);
+                    false
+                }
+            }
+        });
+    }
+
+    let exported_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let profile = EmulatorProfile {
+        format_version: PROFILE_FORMAT_VERSION,
+        name,
+        exported_at,
+        config,
+        patients,
+        attachment_blobs,
+    };
+
+    let json = serde_json::to_string_pretty(&profile)
+        .map_err(|e| format!("Falha ao serializar perfil: {e}"))?;
+    fs::write(PathBuf::from(&path), json)
+        .map_err(|e| format!("Falha ao gravar perfil em {}: {e}", path))?;
+
+    Ok(profile.patients.len())
+}
+
+#[tauri::command]
+pub fn import_emulator_profile(
+    app_handle: AppHandle,
+    path: String,
+    replace_patients: bool,
+) -> Result<ProfileImportSummary, String> {
+    let contents = fs::read_to_string(PathBuf::from(&path))
+        .map_err(|e| format!("Falha ao ler perfil {}: {e}", path))?;
+    let profile: EmulatorProfile = serde_json::from_str(&contents)
+        .map_err(|e| format!("Arquivo de perfil inválido: {e}"))?;
+
+    if profile.format_version > PROFILE_FORMAT_VERSION {
+        return Err(format!(
+            "Versão do perfil ({}) não suportada por esta versão do emulador.",
+            profile.format_version
+        ));
+    }
+
+    let mut config = patient::load_config_from_disk(&app_handle)
+        .map_err(|e| format!("Falha ao ler configurações: {e}"))?;
+    let config_keys_imported = merge_config(&mut config, &profile.config);
+    patient::save_config_to_disk(&app_handle, &config)
+        .map_err(|e| format!("Falha ao salvar configurações: {e}"))?;
+
+    // Blobs are content-addressed, so storing them again yields the key to use here
+    let mut blob_keys = BTreeMap::new();
+    for (key, encoded) in &profile.attachment_blobs {
+        let data = b64::STANDARD
+            .decode(encoded)
+            .map_err(|e| format!("Anexo {} inválido no perfil: {e}", key))?;
+        let stored = blob_store::store(&app_handle, &data).map_err(|e| format!("Falha ao gravar anexo: {e}"))?;
+        blob_keys.insert(key.clone(), stored);
+    }
+
+    let stored = patient::load_patients_from_disk(&app_handle)
+        .map_err(|e| format!("Falha ao ler pacientes: {e}"))?;
+    // Ids continue after the stored ones even when replacing, so a profile patient
+    // never takes over the id (and history) of a patient being removed
+    let mut next_id = stored.iter().map(|p| p.id).max().unwrap_or(0) + 1;
+    let mut patients = if replace_patients { Vec::new() } else { stored };
+
+    let mut patients_added = 0;
+    let mut patients_replaced = 0;
+    for mut incoming in profile.patients {
+        // References to blobs the profile doesn't carry would point at nothing
+        incoming.attachments.retain_mut(|attachment| match blob_keys.get(&attachment.blob_key) {
+            Some(key) => {
+                attachment.blob_key = key.clone();
+                true
+            }
+            None => blob_store::exists(&app_handle, &attachment.blob_key),
+        });
+        // Same wallet means same beneficiary: keep the local id, take the bundled data
+        if let Some(existing) = patients.iter_mut().find(|p| p.wallet == incoming.wallet) {
+            incoming.id = existing.id;
+            *existing = incoming;
+            patients_replaced += 1;
+        } else {
+            incoming.id = next_id;
+            next_id += 1;
+            patients.push(incoming);
+            patients_added += 1;
+        }
+    }
+
+    let patients_skipped =
+        patient::save_patients_from(&app_handle, &patients, "import_emulator_profile", patient::SaveMode::Import)
+            .map_err(|e| format!("Falha ao salvar pacientes: {e}"))?;
+
+    Ok(ProfileImportSummary {
+        name: profile.name,
+        patients_added,
+        patients_replaced,
+        patients_skipped,
+        config_keys_imported,
+    })
+}
@@ -0,0 +1,423 @@
+use base64::{engine::general_purpose as b64, Engine};
+use image::{DynamicImage, GrayImage, ImageOutputFormat};
+use serde::Serialize;
+use std::io::Cursor;
+use tauri::AppHandle;
+
+use crate::blob_store;
+use crate::patient;
+use crate::wsq;
+
+// WSQ files start with the SOI marker
+const WSQ_SOI: [u8; 2] = [0xFF, 0xA0];
+// ISO/IEC 19794-4 finger image record and 19794-2 minutiae record magics
+const ISO_IMAGE_MAGIC: &[u8; 4] = b"FIR\0";
+const ISO_MINUTIAE_MAGIC: &[u8; 4] = b"FMR\0";
+const ISO_IMAGE_V2005: &[u8; 4] = b"010\0";
+const ISO_GENERAL_HEADER_LEN: usize = 32;
+const ISO_FINGER_HEADER_LEN: usize = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateFormat {
+    Wsq,
+    IsoImageRecord,
+    Image,
+    Minutiae,
+    Raw,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RenderedFingerprint {
+    pub format: TemplateFormat,
+    pub width: u32,
+    pub height: u32,
+    pub png_base64: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConversionFailure {
+    pub patient_id: u32,
+    pub finger: String,
+    pub error: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct BatchConversionSummary {
+    pub patients: usize,
+    pub fingerprints: usize,
+    pub converted: usize,
+    pub cached: usize,
+    pub failures: Vec<ConversionFailure>,
+}
+
+fn wsq_to_png(wsq: &[u8]) -> Result<Vec<u8>, String> {
+    encode_png(&DynamicImage::ImageLuma8(decode_wsq(wsq)?))
+}
+
+pub fn wsq_to_png_base64(wsq_b64: &str) -> Result<String, String> {
+    let wsq = b64::STANDARD
+        .decode(wsq_b64.trim())
+        .map_err(|e| format!("Base64 inválido: {e}"))?;
+    Ok(b64::STANDARD.encode(wsq_to_png(&wsq)?))
+}
+
+// Bitrate for WSQ sent to TOTVS, from `wsq_bitrate` in the config
+pub fn wsq_bitrate(app_handle: &AppHandle) -> f32 {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|config| config.get("wsq_bitrate").and_then(|v| v.as_f64()))
+        .map(|rate| rate as f32)
+        .filter(|&rate| rate > 0.0)
+        .unwrap_or(wsq::DEFAULT_BITRATE)
+}
+
+// Encodes a PNG/BMP (any format the image crate reads) as grayscale WSQ
+pub fn image_to_wsq(data: &[u8], bitrate: f32) -> Result<Vec<u8>, String> {
+    let image = image::load_from_memory(data)
+        .map_err(|e| format!("Falha ao decodificar imagem da digital: {e}"))?
+        .to_luma8();
+    wsq::encode(&image, bitrate).map_err(|e| format!("Falha ao gerar WSQ: {e}"))
+}
+
+fn encode_png(image: &DynamicImage) -> Result<Vec<u8>, String> {
+    let mut buf = Cursor::new(Vec::new());
+    image
+        .write_to(&mut buf, ImageOutputFormat::Png)
+        .map_err(|e| format!("Falha ao gerar PNG: {e}"))?;
+    Ok(buf.into_inner())
+}
+
+pub fn detect_format(template: &[u8]) -> TemplateFormat {
+    if template.starts_with(&WSQ_SOI) {
+        TemplateFormat::Wsq
+    } else if template.starts_with(ISO_IMAGE_MAGIC) {
+        TemplateFormat::IsoImageRecord
+    } else if template.starts_with(ISO_MINUTIAE_MAGIC) {
+        TemplateFormat::Minutiae
+    } else if image::guess_format(template).is_ok() {
+        TemplateFormat::Image
+    } else {
+        TemplateFormat::Raw
+    }
+}
+
+fn be_u16(data: &[u8], offset: usize) -> u32 {
+    u32::from(data[offset]) << 8 | u32::from(data[offset + 1])
+}
+
+fn be_u32(data: &[u8], offset: usize) -> usize {
+    (data[offset] as usize) << 24
+        | (data[offset + 1] as usize) << 16
+        | (data[offset + 2] as usize) << 8
+        | data[offset + 3] as usize
+}
+
+fn gray_from_raw(pixels: &[u8], width: u32, height: u32) -> Result<GrayImage, String> {
+    GrayImage::from_raw(width, height, pixels.to_vec())
+        .ok_or_else(|| format!("Dados brutos não correspondem a {}x{} pixels.", width, height))
+}
+
+// Unpacks pixels stored with fewer than 8 bits each (MSB first) to 8-bit gray
+fn unpack_bits(data: &[u8], depth: u32, width: u32, height: u32) -> Result<GrayImage, String> {
+    if depth == 0 || depth > 8 {
+        return Err(format!("Profundidade de pixel {} não suportada.", depth));
+    }
+    // Sizes come from the record header: check them against the data before
+    // allocating, so a corrupted header can't ask for gigabytes
+    let total = u64::from(width) * u64::from(height);
+    if total * u64::from(depth) > data.len() as u64 * 8 {
+        return Err("Imagem ISO truncada.".into());
+    }
+    let total = total as usize;
+    let max = (1u32 << depth) - 1;
+    let mut pixels = Vec::with_capacity(total);
+    let mut bit = 0usize;
+    while pixels.len() < total {
+        let mut value = 0u32;
+        for _ in 0..depth {
+            let byte = *data.get(bit / 8).ok_or("Imagem ISO truncada.")?;
+            value = (value << 1) | u32::from((byte >> (7 - bit % 8)) & 1);
+            bit += 1;
+        }
+        pixels.push((value * 255 / max) as u8);
+    }
+    gray_from_raw(&pixels, width, height)
+}
+
+// ISO/IEC 19794-4:2005 record; only the first finger view is rendered
+fn decode_iso_image_record(record: &[u8]) -> Result<GrayImage, String> {
+    if record.len() < ISO_GENERAL_HEADER_LEN + ISO_FINGER_HEADER_LEN {
+        return Err("Registro ISO 19794-4 truncado.".into());
+    }
+    if &record[4..8] != ISO_IMAGE_V2005 {
+        return Err(format!(
+            "Versão do registro ISO 19794-4 não suportada: {}",
+            String::from_utf8_lossy(&record[4..7])
+        ));
+    }
+
+    let pixel_depth = u32::from(record[28]);
+    let compression = record[29];
+
+    let finger = &record[ISO_GENERAL_HEADER_LEN..];
+    let block_len = be_u32(finger, 0);
+    let width = be_u16(finger, 9);
+    let height = be_u16(finger, 11);
+    let end = block_len.min(finger.len());
+    if end < ISO_FINGER_HEADER_LEN {
+        return Err("Registro ISO 19794-4 com bloco de imagem inválido.".into());
+    }
+    let data = &finger[ISO_FINGER_HEADER_LEN..end];
+
+    match compression {
+        0 if pixel_depth == 8 => gray_from_raw(data, width, height),
+        0 | 1 => unpack_bits(data, pixel_depth, width, height),
+        2 => decode_wsq(data),
+        3 | 5 => image::load_from_memory(data)
+            .map(|img| img.to_luma8())
+            .map_err(|e| format!("Falha ao decodificar imagem do registro ISO: {e}")),
+        4 => Err("Registro ISO com compressão JPEG 2000 não é suportado.".into()),
+        other => Err(format!("Compressão {} do registro ISO desconhecida.", other)),
+    }
+}
+
+fn decode_wsq(wsq: &[u8]) -> Result<GrayImage, String> {
+    wsq::decode(wsq).map_err(|e| format!("Falha ao decodificar WSQ: {e}"))
+}
+
+// Raw 8-bit grayscale carries no dimensions: use the ones given, or assume a
+// square image when the size allows it.
+fn decode_raw(data: &[u8], dimensions: Option<(u32, u32)>) -> Result<GrayImage, String> {
+    let (width, height) = match dimensions {
+        Some(dims) => dims,
+        None => {
+            let side = (data.len() as f64).sqrt() as u32;
+            if side == 0 || (side * side) as usize != data.len() {
+                return Err("Formato da digital não reconhecido. Para dados brutos informe largura e altura.".into());
+            }
+            (side, side)
+        }
+    };
+    gray_from_raw(data, width, height)
+}
+
+pub(crate) fn decode_template(template: &[u8], raw_dimensions: Option<(u32, u32)>) -> Result<(TemplateFormat, GrayImage), String> {
+    let format = detect_format(template);
+    let image = match format {
+        TemplateFormat::Wsq => decode_wsq(template)?,
+        TemplateFormat::IsoImageRecord => decode_iso_image_record(template)?,
+        TemplateFormat::Image => image::load_from_memory(template)
+            .map(|img| img.to_luma8())
+            .map_err(|e| format!("Falha ao decodificar imagem da digital: {e}"))?,
+        TemplateFormat::Minutiae => {
+            return Err("A digital é um template de minúcias (ISO 19794-2) e não contém imagem.".into())
+        }
+        TemplateFormat::Raw => decode_raw(template, raw_dimensions)?,
+    };
+    Ok((format, image))
+}
+
+fn template_to_png(template: &[u8]) -> Result<Vec<u8>, String> {
+    let (_, image) = decode_template(template, None)?;
+    encode_png(&DynamicImage::ImageLuma8(image))
+}
+
+// Previews are cached by the hash of the stored template, so an updated
+// template naturally gets a new preview.
+fn preview_key(template_b64: &str) -> String {
+    format!("fp-preview-{}.png", blob_store::sha256_hex(template_b64.trim().as_bytes()))
+}
+
+fn convert_all(app_handle: &AppHandle, force: bool) -> Result<BatchConversionSummary, String> {
+    let patients = patient::load_patients_from_disk(app_handle)
+        .map_err(|e| format!("Falha ao ler pacientes: {e}"))?;
+
+    let mut summary = BatchConversionSummary::default();
+    for patient in &patients {
+        if patient.digital_biometrics.is_empty() {
+            continue;
+        }
+        summary.patients += 1;
+
+        for digital in &patient.digital_biometrics {
+            summary.fingerprints += 1;
+            let key = preview_key(&digital.data);
+            if !force && blob_store::exists(app_handle, &key) {
+                summary.cached += 1;
+                continue;
+            }
+
+            let result = b64::STANDARD
+                .decode(digital.data.trim())
+                .map_err(|e| format!("Base64 inválido: {e}"))
+                .and_then(|template| template_to_png(&template))
+                .and_then(|png| {
+                    blob_store::store_as(app_handle, &key, &png)
+                        .map_err(|e| format!("Falha ao gravar prévia: {e}"))
+                });
+
+            match result {
+                Ok(()) => summary.converted += 1,
+                Err(error) => summary.failures.push(ConversionFailure {
+                    patient_id: patient.id,
+                    finger: digital.finger.clone(),
+                    error,
+                }),
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+#[tauri::command]
+pub async fn convert_all_fingerprints(app_handle: AppHandle, force: bool) -> Result<BatchConversionSummary, String> {
+    tauri::async_runtime::spawn_blocking(move || convert_all(&app_handle, force))
+        .await
+        .map_err(|e| format!("Falha na conversão em lote: {e}"))?
+}
+
+pub(crate) fn find_template(app_handle: &AppHandle, patient_id: u32, finger: &str) -> Result<String, String> {
+    let patients = patient::load_patients_from_disk(app_handle)
+        .map_err(|e| format!("Falha ao ler pacientes: {e}"))?;
+    let patient = patients
+        .into_iter()
+        .find(|p| p.id == patient_id)
+        .ok_or_else(|| format!("Paciente {} não encontrado.", patient_id))?;
+    patient
+        .digital_biometrics
+        .into_iter()
+        .find(|d| d.finger == finger)
+        .map(|d| d.data)
+        .ok_or_else(|| format!("Digital '{}' não cadastrada para o paciente.", finger))
+}
+
+// Decodes the stored template whatever its format and returns a grayscale PNG
+#[tauri::command]
+pub async fn render_fingerprint(
+    app_handle: AppHandle,
+    patient_id: u32,
+    finger: String,
+    width: Option<u32>,
+    height: Option<u32>,
+) -> Result<RenderedFingerprint, String> {
+    let template_b64 = find_template(&app_handle, patient_id, &finger)?;
+    let template = b64::STANDARD
+        .decode(template_b64.trim())
+        .map_err(|e| format!("Base64 inválido: {e}"))?;
+    let raw_dimensions = width.zip(height);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let (format, image) = decode_template(&template, raw_dimensions)?;
+        let (width, height) = image.dimensions();
+        let png = encode_png(&DynamicImage::ImageLuma8(image))?;
+        // Keep the gallery cache in sync with what was just rendered
+        let _ = blob_store::store_as(&app_handle, &preview_key(&template_b64), &png);
+        Ok(RenderedFingerprint {
+            format,
+            width,
+            height,
+            png_base64: b64::STANDARD.encode(png),
+        })
+    })
+    .await
+    .map_err(|e| format!("Falha ao renderizar digital: {e}"))?
+}
+
+// Returns the cached PNG preview (base64) for a patient's finger, if converted
+#[tauri::command]
+pub fn get_fingerprint_preview(app_handle: AppHandle, patient_id: u32, finger: String) -> Result<Option<String>, String> {
+    let template_b64 = find_template(&app_handle, patient_id, &finger)?;
+
+    let key = preview_key(&template_b64);
+    if !blob_store::exists(&app_handle, &key) {
+        return Ok(None);
+    }
+    let png = blob_store::load(&app_handle, &key).map_err(|e| format!("Falha ao ler prévia: {e}"))?;
+    Ok(Some(b64::STANDARD.encode(png)))
+}
+
+// Converts a WSQ fingerprint (base64), e.g. as downloaded from TOTVS, to a
+// grayscale PNG (base64)
+#[tauri::command]
+pub async fn convert_wsq_to_png(wsq_base64: String) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || wsq_to_png_base64(&wsq_base64))
+        .await
+        .map_err(|e| format!("Falha na conversão WSQ: {e}"))?
+}
+
+// Encodes a fingerprint image (base64 PNG/BMP) as WSQ (base64) for TOTVS.
+// Without `bitrate`, the configured one is used.
+#[tauri::command]
+pub async fn encode_fingerprint_wsq(app_handle: AppHandle, image_base64: String, bitrate: Option<f32>) -> Result<String, String> {
+    let bitrate = bitrate.unwrap_or_else(|| wsq_bitrate(&app_handle));
+    let data = b64::STANDARD
+        .decode(image_base64.trim())
+        .map_err(|e| format!("Base64 inválido: {e}"))?;
+    tauri::async_runtime::spawn_blocking(move || image_to_wsq(&data, bitrate).map(|wsq| b64::STANDARD.encode(wsq)))
+        .await
+        .map_err(|e| format!("Falha na conversão WSQ: {e}"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unpack_bits_scales_to_8_bits() {
+        // 4x1 at 2 bits: 00 01 10 11
+        let image = unpack_bits(&[0b0001_1011], 2, 4, 1).unwrap();
+        assert_eq!(image.as_raw(), &vec![0, 85, 170, 255]);
+        let image = unpack_bits(&[0b1010_0000], 1, 3, 1).unwrap();
+        assert_eq!(image.as_raw(), &vec![255, 0, 255]);
+    }
+
+    #[test]
+    fn unpack_bits_checks_sizes_against_the_data() {
+        assert!(unpack_bits(&[0xFF], 1, 9, 1).is_err());
+        assert!(unpack_bits(&[0xFF; 16], 4, u32::from(u16::MAX), u32::from(u16::MAX)).is_err());
+        assert!(unpack_bits(&[0xFF], 0, 1, 1).is_err());
+        assert!(unpack_bits(&[0xFF], 9, 1, 1).is_err());
+    }
+
+    fn iso_record(depth: u8, compression: u8, width: u16, height: u16, data: &[u8]) -> Vec<u8> {
+        let mut record = vec![0u8; ISO_GENERAL_HEADER_LEN];
+        record[..4].copy_from_slice(ISO_IMAGE_MAGIC);
+        record[4..8].copy_from_slice(ISO_IMAGE_V2005);
+        record[28] = depth;
+        record[29] = compression;
+        let mut finger = vec![0u8; ISO_FINGER_HEADER_LEN];
+        let block_len = (ISO_FINGER_HEADER_LEN + data.len()) as u32;
+        finger[..4].copy_from_slice(&block_len.to_be_bytes());
+        finger[9..11].copy_from_slice(&width.to_be_bytes());
+        finger[11..13].copy_from_slice(&height.to_be_bytes());
+        record.extend(finger);
+        record.extend_from_slice(data);
+        record
+    }
+
+    #[test]
+    fn decodes_iso_image_records() {
+        let image = decode_iso_image_record(&iso_record(8, 0, 2, 2, &[1, 2, 3, 4])).unwrap();
+        assert_eq!((image.dimensions(), image.as_raw().clone()), ((2, 2), vec![1, 2, 3, 4]));
+        let image = decode_iso_image_record(&iso_record(4, 1, 2, 1, &[0xF0])).unwrap();
+        assert_eq!(image.as_raw(), &vec![255, 0]);
+    }
+
+    #[test]
+    fn rejects_iso_records_larger_than_their_data() {
+        assert!(decode_iso_image_record(&iso_record(8, 0, u16::MAX, u16::MAX, &[0; 4])).is_err());
+        assert!(decode_iso_image_record(&iso_record(1, 1, u16::MAX, u16::MAX, &[0; 4])).is_err());
+        assert!(decode_iso_image_record(&iso_record(8, 0, 2, 2, &[])[..40]).is_err());
+    }
+
+    #[test]
+    fn batch_previews_render_wsq_templates() {
+        let wsq = include_bytes!("../tests/fixtures/ridges_256.wsq");
+        let png = template_to_png(wsq).unwrap();
+        let preview = image::load_from_memory(&png).unwrap();
+        assert_eq!((preview.width(), preview.height()), (256, 256));
+        assert_eq!(wsq_to_png(wsq).unwrap(), png);
+    }
+}
@@ -0,0 +1,145 @@
+use base64::{engine::general_purpose as b64, Engine};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::AppHandle;
+
+use crate::beneficiary_import::finger_code;
+use crate::fingerprint::{self, TemplateFormat};
+use crate::patient::{self, Patient};
+use crate::totvs::error::TotvsError;
+use crate::totvs_cache;
+use crate::totvs_endpoints;
+use crate::totvs_http;
+use crate::totvs_log;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnrollPrint {
+    pub finger_code: u32,
+    // WSQ (or any template TOTVS accepts), base64 encoded; PNG/BMP images
+    // are encoded to WSQ before sending
+    pub biometry: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EnrollmentSummary {
+    pub card_number: String,
+    pub enrolled: Vec<u32>,
+    // Stored templates that couldn't be sent, e.g. an unknown finger name
+    pub skipped: Vec<String>,
+}
+
+// Templates of the patient keyed by TOTVS finger code, and the fingers that
+// can't be sent
+pub(crate) fn patient_prints(patient: &Patient) -> (Vec<EnrollPrint>, Vec<String>) {
+    let mut prints = Vec::new();
+    let mut skipped = Vec::new();
+    for digital in &patient.digital_biometrics {
+        match finger_code(&digital.finger) {
+            Some(code) if !digital.data.trim().is_empty() => prints.push(EnrollPrint {
+                finger_code: code,
+                biometry: digital.data.clone(),
+            }),
+            _ => skipped.push(digital.finger.clone()),
+        }
+    }
+    (prints, skipped)
+}
+
+// Templates of the stored patient with this wallet
+fn stored_prints(app_handle: &AppHandle, card_number: &str) -> Result<(Vec<EnrollPrint>, Vec<String>), String> {
+    let patients = patient::load_patients_from_disk(app_handle)
+        .map_err(|e| format!("Falha ao ler pacientes: {e}"))?;
+    let patient = patients
+        .into_iter()
+        .find(|p| p.wallet.trim() == card_number)
+        .ok_or_else(|| format!("Nenhum paciente com a carteira {}.", card_number))?;
+    Ok(patient_prints(&patient))
+}
+
+// Checks the finger codes and encodes PNG/BMP images to WSQ, as TOTVS only
+// takes templates
+pub(crate) fn prepare_prints(prints: &mut [EnrollPrint], bitrate: f32) -> Result<(), String> {
+    for print in prints {
+        if !(1..=10).contains(&print.finger_code) {
+            return Err(format!("Código de dedo inválido: {}", print.finger_code));
+        }
+        let template = b64::STANDARD
+            .decode(print.biometry.trim())
+            .map_err(|e| format!("Base64 inválido na digital {}: {e}", print.finger_code))?;
+        if fingerprint::detect_format(&template) == TemplateFormat::Image {
+            let wsq = fingerprint::image_to_wsq(&template, bitrate)
+                .map_err(|e| format!("Digital {}: {e}", print.finger_code))?;
+            print.biometry = b64::STANDARD.encode(wsq);
+        }
+    }
+    Ok(())
+}
+
+// Body of the fingerprint enrollment POST
+pub(crate) fn enrollment_body(prints: &[EnrollPrint]) -> Value {
+    let items: Vec<Value> = prints
+        .iter()
+        .map(|p| json!({ "fingerCode": p.finger_code, "biometry": p.biometry.trim() }))
+        .collect();
+    json!({ "items": items })
+}
+
+// Registers fingerprint templates for a beneficiary on TOTVS. Without
+// `prints`, the templates of the stored patient with that wallet are sent.
+#[tauri::command]
+pub async fn enroll_fingerprints(
+    app_handle: AppHandle,
+    card_number: String,
+    prints: Option<Vec<EnrollPrint>>,
+) -> Result<EnrollmentSummary, TotvsError> {
+    let card_number = card_number.trim().to_string();
+    if card_number.is_empty() {
+        return Err("Número da carteira não informado.".into());
+    }
+
+    let (mut prints, skipped) = match prints {
+        Some(prints) => (prints, Vec::new()),
+        None => stored_prints(&app_handle, &card_number)?,
+    };
+    if prints.is_empty() {
+        return Err("Nenhuma digital para enviar.".into());
+    }
+    prepare_prints(&mut prints, fingerprint::wsq_bitrate(&app_handle))?;
+
+    let portal = crate::portal_context(&app_handle)?;
+    let body = enrollment_body(&prints);
+    let client = totvs_http::client(&app_handle)?;
+    let response = totvs_endpoints::send(
+        &app_handle,
+        "fingerprint_enrollment",
+        &portal.credentials,
+        Some(&card_number),
+        |url| {
+            tracing::debug!(target: "totvs", "URL Cadastro de digitais: {}", url);
+            client
+                .post(url)
+                .query(&portal.query())
+                .json(&body)
+                .header("Accept", "application/json")
+                .header("x-totvs-hgp-portal-prestador-clinic", portal.clinic.as_str())
+        },
+    )
+    .await?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let txt = response.text().await.unwrap_or_default();
+        tracing::debug!(target: "totvs", "Erro cadastro de digitais status={} body={}", status, totvs_log::redact_body(txt.as_bytes()));
+        return Err(TotvsError::status(status, format!("Falha na requisição: {}", status)));
+    }
+
+    // The cached fingerprint list for this card no longer reflects TOTVS
+    let fingerprints_url = totvs_endpoints::url(&app_handle, &portal.credentials.base_url, "fingerprints", Some(&card_number));
+    totvs_cache::remove(&app_handle, &totvs_cache::key("fingerprints", &fingerprints_url, &portal.query()));
+
+    Ok(EnrollmentSummary {
+        card_number,
+        enrolled: prints.iter().map(|p| p.finger_code).collect(),
+        skipped,
+    })
+}
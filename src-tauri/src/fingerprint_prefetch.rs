@@ -0,0 +1,201 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::beneficiary_import::{new_patient, to_digital_biometrics};
+use crate::import_jobs::ImportJob;
+use crate::patient::{self, DigitalBiometric};
+use crate::webhook::{self, JobFailure, JobReport};
+
+const DEFAULT_CONCURRENCY: usize = 4;
+const MAX_CONCURRENCY: usize = 16;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PrefetchProgress {
+    pub job_id: String,
+    pub done: usize,
+    pub total: usize,
+    pub card_number: String,
+    pub ok: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CardPrefetchStatus {
+    pub ok: bool,
+    pub fingerprints: usize,
+    // Patient the fingerprints were stored on
+    pub patient_id: Option<u32>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PrefetchSummary {
+    pub job_id: String,
+    pub total: usize,
+    // Cards not reached before a cancellation are missing from the map
+    pub cards: BTreeMap<String, CardPrefetchStatus>,
+    pub cancelled: bool,
+}
+
+async fn prefetch(
+    app_handle: &AppHandle,
+    card_numbers: Vec<String>,
+    concurrency: Option<usize>,
+    job: &ImportJob,
+) -> Result<PrefetchSummary, String> {
+    // A card listed twice is fetched once
+    let mut seen = HashSet::new();
+    let cards: Vec<String> = card_numbers
+        .into_iter()
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty() && seen.insert(c.clone()))
+        .collect();
+    let total = cards.len();
+
+    let semaphore = Arc::new(Semaphore::new(
+        concurrency.unwrap_or(DEFAULT_CONCURRENCY).clamp(1, MAX_CONCURRENCY),
+    ));
+    let mut tasks = JoinSet::new();
+    for card_number in cards {
+        let app_handle = app_handle.clone();
+        let semaphore = semaphore.clone();
+        let token = job.token.clone();
+        tasks.spawn(async move {
+            token
+                .run_until_cancelled(async move {
+                    let _permit = semaphore.acquire_owned().await;
+                    let result = crate::fetch_fingerprints(&app_handle, &card_number)
+                        .await
+                        .map_err(String::from)
+                        .map(to_digital_biometrics)
+                        .and_then(|prints| {
+                            if prints.is_empty() {
+                                Err("Nenhuma digital retornada pela API.".to_string())
+                            } else {
+                                Ok(prints)
+                            }
+                        });
+                    (card_number, result)
+                })
+                .await
+        });
+    }
+
+    let mut fetched: HashMap<String, Vec<DigitalBiometric>> = HashMap::new();
+    let mut statuses = BTreeMap::new();
+    while let Some(joined) = tasks.join_next().await {
+        let joined = joined.map_err(|e| format!("Falha na tarefa de download: {e}"))?;
+        let Some((card_number, result)) = joined else {
+            continue;
+        };
+        let status = match result {
+            Ok(prints) => {
+                let status = CardPrefetchStatus {
+                    ok: true,
+                    fingerprints: prints.len(),
+                    patient_id: None,
+                    error: None,
+                };
+                fetched.insert(card_number.clone(), prints);
+                status
+            }
+            Err(error) => CardPrefetchStatus {
+                ok: false,
+                fingerprints: 0,
+                patient_id: None,
+                error: Some(error),
+            },
+        };
+        let _ = app_handle.emit(
+            "fingerprint-prefetch-progress",
+            PrefetchProgress {
+                job_id: job.id.clone(),
+                done: statuses.len() + 1,
+                total,
+                card_number: card_number.clone(),
+                ok: status.ok,
+            },
+        );
+        statuses.insert(card_number, status);
+    }
+
+    // Reload so edits made while the downloads ran are not overwritten. Known
+    // wallets get their fingerprints replaced; other cards become new patients.
+    if !fetched.is_empty() {
+        let mut patients = patient::load_patients_from_disk(app_handle)
+            .map_err(|e| format!("Falha ao ler pacientes: {e}"))?;
+        let mut next_id = patients.iter().map(|p| p.id).max().unwrap_or(0) + 1;
+        for (card_number, prints) in fetched {
+            let patient_id = match patients.iter_mut().find(|p| p.wallet.trim() == card_number) {
+                Some(existing) => {
+                    existing.digital_biometrics = prints;
+                    existing.id
+                }
+                None => {
+                    let id = next_id;
+                    next_id += 1;
+                    let name = format!("Carteira {}", card_number);
+                    patients.push(patient::Patient {
+                        id,
+                        ..new_patient(name, card_number.clone(), prints, String::new())
+                    });
+                    id
+                }
+            };
+            if let Some(status) = statuses.get_mut(&card_number) {
+                status.patient_id = Some(patient_id);
+            }
+        }
+        patient::save_patients_to_disk(app_handle, &patients).map_err(|e| e.to_string())?;
+    }
+
+    Ok(PrefetchSummary {
+        job_id: job.id.clone(),
+        total,
+        cards: statuses,
+        cancelled: job.is_cancelled(),
+    })
+}
+
+// Downloads the fingerprints of every card with bounded concurrency and
+// stores them on the patients, to set up biometric regression suites.
+// Progress is emitted as `fingerprint-prefetch-progress`; cancelling the job
+// keeps what was downloaded so far.
+#[tauri::command]
+pub async fn prefetch_fingerprints(
+    app_handle: AppHandle,
+    card_numbers: Vec<String>,
+    concurrency: Option<usize>,
+    job_id: Option<String>,
+) -> Result<PrefetchSummary, String> {
+    if card_numbers.iter().all(|c| c.trim().is_empty()) {
+        return Err("Nenhum número de carteira informado.".into());
+    }
+    let job = ImportJob::start(&app_handle, "fingerprint-prefetch", job_id)?;
+    let result = prefetch(&app_handle, card_numbers, concurrency, &job).await;
+
+    let report = match &result {
+        Ok(summary) => JobReport {
+            total: summary.total,
+            succeeded: summary.cards.values().filter(|s| s.ok).count(),
+            failures: summary
+                .cards
+                .iter()
+                .filter_map(|(card, status)| {
+                    status.error.as_ref().map(|error| JobFailure {
+                        item: card.clone(),
+                        error: error.clone(),
+                    })
+                })
+                .collect(),
+            cancelled: summary.cancelled,
+            ..JobReport::new(&job.id, &job.kind)
+        },
+        Err(error) => JobReport::failed(&job.id, &job.kind, error),
+    };
+    webhook::send_job_report(&app_handle, report);
+    result
+}
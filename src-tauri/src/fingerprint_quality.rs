@@ -0,0 +1,175 @@
+use base64::{engine::general_purpose as b64, Engine};
+use image::GrayImage;
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::fingerprint::{self, TemplateFormat};
+use crate::patient;
+
+// Blocks the image is analysed in; about two ridges wide at 500 ppi
+const BLOCK: u32 = 16;
+// Blocks flatter than this (gray level std dev) are background
+const FOREGROUND_STD_DEV: f64 = 10.0;
+// Std dev of a well contrasted ridge pattern
+const GOOD_STD_DEV: f64 = 50.0;
+// Share of the image a well placed finger usually covers
+const GOOD_FOREGROUND: f64 = 0.6;
+
+// NFIQ-like estimate: 0 to 100, and the matching level from 1 (excellent)
+// to 5 (poor). Not NFIQ itself, but it separates clear prints from smudged,
+// faint or partial ones.
+#[derive(Debug, Clone, Serialize)]
+pub struct QualityScore {
+    pub score: u8,
+    pub level: u8,
+    // Share of blocks with ridge pattern
+    pub foreground: f64,
+    // Mean ridge orientation certainty over the foreground (0 to 1)
+    pub coherence: f64,
+    // Mean foreground contrast, relative to a good print (0 to 1)
+    pub contrast: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FingerprintQuality {
+    pub finger: String,
+    pub format: Option<TemplateFormat>,
+    pub quality: Option<QualityScore>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PatientQualityReport {
+    pub patient_id: u32,
+    pub fingerprints: Vec<FingerprintQuality>,
+}
+
+fn level(score: u8) -> u8 {
+    match score {
+        80..=100 => 1,
+        60..=79 => 2,
+        40..=59 => 3,
+        20..=39 => 4,
+        _ => 5,
+    }
+}
+
+// Std dev of a block, and how consistently its gradients point the same way
+// (1 for parallel ridges, 0 for noise)
+fn block_stats(image: &GrayImage, bx: u32, by: u32) -> (f64, f64) {
+    let pixel = |x: u32, y: u32| f64::from(image.get_pixel(x, y)[0]);
+    let (mut sum, mut squares) = (0.0, 0.0);
+    let (mut gxx, mut gyy, mut gxy) = (0.0, 0.0, 0.0);
+    for y in by..by + BLOCK {
+        for x in bx..bx + BLOCK {
+            let value = pixel(x, y);
+            sum += value;
+            squares += value * value;
+
+            let gx = pixel((x + 1).min(image.width() - 1), y) - pixel(x.saturating_sub(1), y);
+            let gy = pixel(x, (y + 1).min(image.height() - 1)) - pixel(x, y.saturating_sub(1));
+            gxx += gx * gx;
+            gyy += gy * gy;
+            gxy += gx * gy;
+        }
+    }
+    let count = f64::from(BLOCK * BLOCK);
+    let mean = sum / count;
+    let std_dev = (squares / count - mean * mean).max(0.0).sqrt();
+    let energy = gxx + gyy;
+    let coherence = if energy > 0.0 {
+        ((gxx - gyy).powi(2) + 4.0 * gxy * gxy).sqrt() / energy
+    } else {
+        0.0
+    };
+    (std_dev, coherence)
+}
+
+pub fn score(image: &GrayImage) -> Result<QualityScore, String> {
+    let (blocks_x, blocks_y) = (image.width() / BLOCK, image.height() / BLOCK);
+    if blocks_x == 0 || blocks_y == 0 {
+        return Err(format!("Imagem {}x{} pequena demais para avaliar.", image.width(), image.height()));
+    }
+
+    let mut foreground = 0u32;
+    let (mut coherence, mut contrast) = (0.0, 0.0);
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let (std_dev, block_coherence) = block_stats(image, bx * BLOCK, by * BLOCK);
+            if std_dev < FOREGROUND_STD_DEV {
+                continue;
+            }
+            foreground += 1;
+            coherence += block_coherence;
+            contrast += (std_dev / GOOD_STD_DEV).min(1.0);
+        }
+    }
+
+    let total = f64::from(blocks_x * blocks_y);
+    let (foreground, coherence, contrast) = if foreground == 0 {
+        (0.0, 0.0, 0.0)
+    } else {
+        let count = f64::from(foreground);
+        (count / total, coherence / count, contrast / count)
+    };
+    // Ridge clarity weighs the most; coverage and contrast refine it
+    let combined = 0.5 * coherence + 0.3 * (foreground / GOOD_FOREGROUND).min(1.0) + 0.2 * contrast;
+    let score = (combined * 100.0).round().clamp(0.0, 100.0) as u8;
+    Ok(QualityScore {
+        score,
+        level: level(score),
+        foreground,
+        coherence,
+        contrast,
+    })
+}
+
+fn score_template(template_b64: &str) -> Result<(TemplateFormat, QualityScore), String> {
+    let template = b64::STANDARD
+        .decode(template_b64.trim())
+        .map_err(|e| format!("Base64 inválido: {e}"))?;
+    let (format, image) = fingerprint::decode_template(&template, None)?;
+    Ok((format, score(&image)?))
+}
+
+// Scores every stored fingerprint of the patient; the ones that can't be
+// decoded (e.g. minutiae templates) come back with an error instead
+#[tauri::command]
+pub async fn score_patient_fingerprints(app_handle: AppHandle, patient_id: u32) -> Result<PatientQualityReport, String> {
+    let patients = patient::load_patients_from_disk(&app_handle)
+        .map_err(|e| format!("Falha ao ler pacientes: {e}"))?;
+    let patient = patients
+        .into_iter()
+        .find(|p| p.id == patient_id)
+        .ok_or_else(|| format!("Paciente {} não encontrado.", patient_id))?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let fingerprints = patient
+            .digital_biometrics
+            .into_iter()
+            .map(|digital| match score_template(&digital.data) {
+                Ok((format, quality)) => FingerprintQuality {
+                    finger: digital.finger,
+                    format: Some(format),
+                    quality: Some(quality),
+                    error: None,
+                },
+                Err(error) => FingerprintQuality {
+                    finger: digital.finger,
+                    format: b64::STANDARD
+                        .decode(digital.data.trim())
+                        .ok()
+                        .map(|template| fingerprint::detect_format(&template)),
+                    quality: None,
+                    error: Some(error),
+                },
+            })
+            .collect();
+        PatientQualityReport {
+            patient_id,
+            fingerprints,
+        }
+    })
+    .await
+    .map_err(|e| format!("Falha ao avaliar digitais: {e}"))
+}
@@ -1,41 +1,333 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
-use std::process::{Child, Command};
+use std::process::{Child, Command, ExitStatus};
 use std::io;
 use std::fs;
-use tauri::{AppHandle, Manager};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
 use serde_json;
-use std::env;
 use std::path::Path;
 use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
 
+use crate::ahk_installer;
+use crate::ahk_template;
+use crate::card_format::{self, CardFormat};
+use crate::keystroke::{self, KeystrokeBackend, KeystrokeMode};
+use crate::notifications;
+use crate::patient;
+use crate::target_window;
+
+const PROCESS_WATCH_INTERVAL: Duration = Duration::from_secs(2);
+// Auto-restart gives up on a script that died this many times in the window
+const MAX_RESTARTS: usize = 3;
+const RESTART_WINDOW: Duration = Duration::from_secs(60);
+const DEFAULT_HOTKEY: &str = "^q";
+
+// One hotkey and the card text it types
+#[derive(Debug, Clone, Serialize)]
+pub struct HotkeyBinding {
+    // AutoHotkey syntax, e.g. "^1"
+    pub hotkey: String,
+    pub patient_id: Option<u32>,
+    pub wallet: String,
+    // Text as typed, already rendered with the card format
+    pub text: String,
+    pub mode: KeystrokeMode,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueuedCard {
+    pub patient_id: u32,
+    pub name: String,
+    pub wallet: String,
+    // Text as typed, already rendered with the card format
+    pub text: String,
+}
+
+// Cards one hotkey types in turn, one per press, starting over after the
+// last: rapid sequential check-ins
+struct SendQueue {
+    hotkey: String,
+    cards: Vec<QueuedCard>,
+    mode: KeystrokeMode,
+    // Index of the card the next press types
+    next: Arc<AtomicUsize>,
+    // Where the AutoHotkey script records the card it typed last (1-based)
+    position_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueueStatus {
+    pub hotkey: String,
+    pub cards: Vec<QueuedCard>,
+    pub next: usize,
+}
+
+// What a native hotkey types on each press
+#[derive(Clone)]
+enum NativeAction {
+    Text(String),
+    // The card at the cursor, which then moves to the next one
+    Queue(Arc<Vec<String>>, Arc<AtomicUsize>),
+}
+
+impl NativeAction {
+    fn next_text(&self) -> String {
+        match self {
+            NativeAction::Text(text) => text.clone(),
+            NativeAction::Queue(texts, next) => {
+                let index = next
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |i| Some((i + 1) % texts.len()))
+                    .unwrap_or(0);
+                texts[index % texts.len()].clone()
+            }
+        }
+    }
+}
+
+// Several hotkeys, each typing its own card (Ctrl+1..Ctrl+9 for a demo),
+// served by a single AutoHotkey script or by one native shortcut each
 pub struct HotkeyManager {
     ahk_process: Option<Child>,
     temp_script_path: Option<PathBuf>,
+    // Registered with the global shortcut plugin when the native backend is used
+    native_shortcuts: Option<(AppHandle, Vec<Shortcut>)>,
+    bindings: BTreeMap<String, HotkeyBinding>,
+    queue: Option<SendQueue>,
+    // Used by `start`
+    hotkey: String,
+}
+
+// AutoHotkey hotkey syntax ("^q", "^+1", "#F2") as a global shortcut
+// ("ctrl+q", "ctrl+shift+1", "super+F2")
+fn accelerator(hotkey: &str) -> Result<String, String> {
+    let key = hotkey.trim().trim_start_matches(['^', '+', '!', '#']);
+    if key.is_empty() {
+        return Err(format!("Hotkey '{}' sem tecla.", hotkey));
+    }
+    let modifiers = &hotkey.trim()[..hotkey.trim().len() - key.len()];
+    let mut parts: Vec<&str> = modifiers
+        .chars()
+        .map(|c| match c {
+            '^' => "ctrl",
+            '+' => "shift",
+            '!' => "alt",
+            _ => "super",
+        })
+        .collect();
+    parts.push(key);
+    Ok(parts.join("+"))
+}
+
+fn parse_shortcut(hotkey: &str) -> Result<Shortcut, String> {
+    accelerator(hotkey)?
+        .parse()
+        .map_err(|e| format!("Hotkey '{}' inválida: {}", hotkey, e))
+}
+
+// Hotkeys are written as is into the AutoHotkey script, so whatever the
+// backend only modifiers followed by a key name the native backend also
+// knows are accepted
+fn validate_hotkey(hotkey: &str) -> Result<(), String> {
+    if hotkey.is_empty() || !hotkey.chars().all(|c| c.is_ascii_graphic()) {
+        return Err(format!("Hotkey '{}' inválida: use modificadores (^ + ! #) seguidos de uma tecla, como ^q.", hotkey.escape_debug()));
+    }
+    parse_shortcut(hotkey).map(|_| ())
 }
 
 impl HotkeyManager {
     pub fn new() -> Self {
+        Self::with_hotkey(DEFAULT_HOTKEY)
+    }
+
+    // `hotkey` uses AutoHotkey syntax, e.g. "^q" for Ctrl+Q or "^1" for Ctrl+1
+    pub fn with_hotkey(hotkey: &str) -> Self {
         Self {
             ahk_process: None,
             temp_script_path: None,
+            native_shortcuts: None,
+            bindings: BTreeMap::new(),
+            queue: None,
+            hotkey: hotkey.to_string(),
         }
     }
 
-    pub fn start(&mut self, app_handle: &AppHandle, text_to_send: &str) -> Result<bool, String> {
+    // Binds the manager's own hotkey to `text_to_send`, replacing every other
+    // binding
+    pub fn start(&mut self, app_handle: &AppHandle, text_to_send: &str, card_format: &CardFormat, mode: KeystrokeMode) -> Result<bool, String> {
         if text_to_send.is_empty() {
             return Err("Texto para enviar não pode estar vazio".into());
         }
 
         println!("Starting hotkey with text: {}", text_to_send);
         self.stop()?;
+        let binding = HotkeyBinding {
+            hotkey: self.hotkey.clone(),
+            patient_id: None,
+            wallet: text_to_send.to_string(),
+            text: card_format::render_card(app_handle, card_format, text_to_send),
+            mode,
+        };
+        self.bindings.insert(binding.hotkey.clone(), binding);
+        self.listen(app_handle)
+    }
+
+    // Adds or replaces the binding of `binding.hotkey` and restarts the
+    // listener with every binding
+    pub fn bind(&mut self, app_handle: &AppHandle, binding: HotkeyBinding) -> Result<bool, String> {
+        if self.queue.as_ref().is_some_and(|q| q.hotkey == binding.hotkey) {
+            return Err(format!("A hotkey {} está em uso pela fila de envio.", binding.hotkey));
+        }
+        validate_hotkey(&binding.hotkey)?;
+        let previous = self.bindings.insert(binding.hotkey.clone(), binding.clone());
+        match self.listen(app_handle) {
+            Ok(started) => Ok(started),
+            Err(e) => {
+                // Put the previous bindings back in service
+                match previous {
+                    Some(previous) => self.bindings.insert(previous.hotkey.clone(), previous),
+                    None => self.bindings.remove(&binding.hotkey),
+                };
+                let _ = self.listen(app_handle);
+                Err(e)
+            }
+        }
+    }
+
+    // Returns false if the hotkey wasn't bound
+    pub fn unbind(&mut self, app_handle: &AppHandle, hotkey: &str) -> Result<bool, String> {
+        if self.bindings.remove(hotkey).is_none() {
+            return Ok(false);
+        }
+        self.listen(app_handle)?;
+        Ok(true)
+    }
+
+    pub fn bindings(&self) -> Vec<HotkeyBinding> {
+        self.bindings.values().cloned().collect()
+    }
+
+    // Serves `cards` on `hotkey`, replacing the previous queue and any binding
+    // of that hotkey. Starts at the first card.
+    pub fn load_queue(&mut self, app_handle: &AppHandle, hotkey: &str, cards: Vec<QueuedCard>, mode: KeystrokeMode) -> Result<QueueStatus, String> {
+        if cards.is_empty() {
+            return Err("A fila de envio precisa de ao menos um paciente.".into());
+        }
+        validate_hotkey(hotkey)?;
+        let previous = self.queue.take();
+        let replaced = self.bindings.remove(hotkey);
+        self.queue = Some(SendQueue {
+            hotkey: hotkey.to_string(),
+            cards,
+            mode,
+            next: Arc::new(AtomicUsize::new(0)),
+            position_file: None,
+        });
+        if let Err(e) = self.listen(app_handle) {
+            self.queue = previous;
+            if let Some(binding) = replaced {
+                self.bindings.insert(binding.hotkey.clone(), binding);
+            }
+            let _ = self.listen(app_handle);
+            return Err(e);
+        }
+        self.queue_status().ok_or_else(|| "Fila de envio vazia.".to_string())
+    }
+
+    pub fn queue_status(&mut self) -> Option<QueueStatus> {
+        self.sync_queue_position();
+        let queue = self.queue.as_ref()?;
+        Some(QueueStatus {
+            hotkey: queue.hotkey.clone(),
+            cards: queue.cards.clone(),
+            next: queue.next.load(Ordering::SeqCst),
+        })
+    }
 
+    // Puts the queued patients in the order of `patient_ids`, which must hold
+    // the same patients. The next press types the card at `next`, or the one
+    // it would have typed anyway.
+    pub fn reorder_queue(&mut self, app_handle: &AppHandle, patient_ids: &[u32], next: Option<usize>) -> Result<QueueStatus, String> {
+        self.sync_queue_position();
+        let queue = self.queue.as_mut().ok_or("Nenhuma fila de envio carregada.")?;
+
+        let mut current: Vec<u32> = queue.cards.iter().map(|c| c.patient_id).collect();
+        let mut requested = patient_ids.to_vec();
+        current.sort_unstable();
+        requested.sort_unstable();
+        if current != requested {
+            return Err("A nova ordem deve conter exatamente os pacientes da fila.".into());
+        }
+
+        let upcoming = queue.cards.get(queue.next.load(Ordering::SeqCst)).map(|c| c.patient_id);
+        let mut remaining = std::mem::take(&mut queue.cards);
+        for id in patient_ids {
+            if let Some(index) = remaining.iter().position(|c| c.patient_id == *id) {
+                queue.cards.push(remaining.remove(index));
+            }
+        }
+        let next = match next {
+            Some(index) if index >= queue.cards.len() => {
+                return Err(format!("Posição {} fora da fila de {} cartões.", index, queue.cards.len()));
+            }
+            Some(index) => index,
+            None => upcoming.and_then(|id| patient_ids.iter().position(|p| *p == id)).unwrap_or(0),
+        };
+        queue.next.store(next, Ordering::SeqCst);
+
+        self.listen(app_handle)?;
+        self.queue_status().ok_or_else(|| "Fila de envio vazia.".to_string())
+    }
+
+    // Returns false if no queue was loaded
+    pub fn clear_queue(&mut self, app_handle: &AppHandle) -> Result<bool, String> {
+        if self.queue.take().is_none() {
+            return Ok(false);
+        }
+        self.listen(app_handle)?;
+        Ok(true)
+    }
+
+    // The AutoHotkey script moves through the queue on its own; its position
+    // is read back from the file it writes
+    fn sync_queue_position(&mut self) {
+        let Some(queue) = &self.queue else {
+            return;
+        };
+        let last = queue
+            .position_file
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| text.trim().parse::<usize>().ok());
+        if let Some(last) = last {
+            queue.next.store(last % queue.cards.len(), Ordering::SeqCst);
+        }
+    }
+
+    // (Re)starts the script or shortcuts serving the current bindings; with
+    // none, only stops them
+    fn listen(&mut self, app_handle: &AppHandle) -> Result<bool, String> {
+        self.sync_queue_position();
+        self.stop_listener()?;
+        if self.bindings.is_empty() && self.queue.is_none() {
+            return Ok(false);
+        }
+        for hotkey in self.bindings.keys().chain(self.queue.as_ref().map(|q| &q.hotkey)) {
+            validate_hotkey(hotkey)?;
+        }
+
+        if keystroke::configured_backend(app_handle) == KeystrokeBackend::Native {
+            return self.listen_native(app_handle);
+        }
+
+        let template = ahk_template::load(app_handle)?;
         let ahk_exe_path = Self::find_ahk_path(app_handle)?;
         println!("Using AutoHotkey executable: {}", ahk_exe_path.display());
-        
-        let full_text_to_emulate = format!(";{text_to_send}=011903=004105713104?");
-        let script_content = format!(
-            "#Requires AutoHotkey v2.0\n#SingleInstance force\n\n^q::\n{{\n    SendInput \"{full_text_to_emulate}\"\n    return\n}}\n"
-        );
 
         println!("Creating temporary directory...");
         let temp_dir = tempfile::Builder::new()
@@ -43,6 +335,41 @@ impl HotkeyManager {
             .tempdir()
             .map_err(|e| format!("Falha ao criar diretório temporário: {}", e))?;
 
+        let timing = keystroke::configured_timing(app_handle);
+        let guard = target_window::configured_target(app_handle).ahk_guard("return");
+        let directives = "#Requires AutoHotkey v2.0\n#SingleInstance force\n";
+        let mut script_content = String::new();
+        for binding in self.bindings.values() {
+            let send_statement = format!("{}{}", guard, keystroke::ahk_send_statement(&binding.text, binding.mode, timing)).replace('\n', "\n    ");
+            let hotkey = &binding.hotkey;
+            script_content.push_str(&format!("\n{hotkey}::\n{{\n    {send_statement}\n    return\n}}\n"));
+        }
+        if let Some(queue) = &mut self.queue {
+            let position_file = temp_dir.path().join("queue_position");
+            let mut body = format!(
+                "static next := {}\n{}next := Mod(next, {}) + 1\nswitch next {{\n",
+                queue.next.load(Ordering::SeqCst),
+                guard,
+                queue.cards.len()
+            );
+            for (i, card) in queue.cards.iter().enumerate() {
+                let send_statement = keystroke::ahk_send_statement(&card.text, queue.mode, timing).replace('\n', "\n    ");
+                body.push_str(&format!("case {}:\n    {}\n", i + 1, send_statement));
+            }
+            body.push_str(&format!(
+                "}}\nFileOpen({}, \"w\").Write(next)\nreturn",
+                keystroke::quote_ahk(&position_file.display().to_string())
+            ));
+            let body = body.replace('\n', "\n    ");
+            script_content.push_str(&format!("\n{}::\n{{\n    {}\n}}\n", queue.hotkey, body));
+            queue.position_file = Some(position_file);
+        }
+
+        let script_content = match &template {
+            Some(template) => ahk_template::render(template, directives, &script_content, &temp_dir.path().display().to_string()),
+            None => format!("{}{}", directives, script_content),
+        };
+
         let script_path = temp_dir.path().join("hotkey_script.ahk");
         println!("Writing script to: {}", script_path.display());
         
@@ -65,7 +392,138 @@ impl HotkeyManager {
         Ok(true)
     }
 
+    // Each hotkey is a global shortcut of this process; pressing it types the
+    // text with SendInput on another thread
+    fn listen_native(&mut self, app_handle: &AppHandle) -> Result<bool, String> {
+        #[cfg(target_os = "linux")]
+        crate::linux_input::check_global_hotkeys()?;
+        // Asks for the permission now rather than on the first press
+        #[cfg(target_os = "macos")]
+        crate::macos_input::ensure_access()?;
+        let timing = keystroke::configured_timing(app_handle);
+        let mut actions: Vec<(String, KeystrokeMode, NativeAction)> = self
+            .bindings
+            .values()
+            .map(|b| (b.hotkey.clone(), b.mode, NativeAction::Text(b.text.clone())))
+            .collect();
+        if let Some(queue) = &mut self.queue {
+            // The script's position file no longer applies
+            queue.position_file = None;
+            let texts = queue.cards.iter().map(|c| c.text.clone()).collect();
+            actions.push((queue.hotkey.clone(), queue.mode, NativeAction::Queue(Arc::new(texts), queue.next.clone())));
+        }
+
+        let mut registered = Vec::new();
+        for (hotkey, mode, action) in actions {
+            let shortcut = parse_shortcut(&hotkey)?;
+            let result = app_handle.global_shortcut().on_shortcut(shortcut, move |app, _, event| {
+                if event.state != ShortcutState::Pressed {
+                    return;
+                }
+                let (app, action) = (app.clone(), action.clone());
+                thread::spawn(move || {
+                    if let Err(e) = target_window::check(&app).and_then(|_| keystroke::type_native(&action.next_text(), mode, timing)) {
+                        tracing::error!("{}", e);
+                        notifications::notify(&app, "Falha ao simular cartão", &e);
+                    }
+                });
+            });
+            if let Err(e) = result {
+                let _ = app_handle.global_shortcut().unregister_multiple(registered);
+                return Err(format!("Falha ao registrar hotkey {} (em uso por outro programa?): {}", hotkey, e));
+            }
+            tracing::debug!("Native hotkey {} registered", hotkey);
+            registered.push(shortcut);
+        }
+        self.native_shortcuts = Some((app_handle.clone(), registered));
+        Ok(true)
+    }
+
+    // Types the card text a single time, without registering the Ctrl+Q hotkey.
+    // The generated script exits as soon as the text has been sent.
+    pub fn send_once(app_handle: &AppHandle, text_to_send: &str, card_format: &CardFormat, mode: KeystrokeMode) -> Result<bool, String> {
+        if text_to_send.is_empty() {
+            return Err("Texto para enviar não pode estar vazio".into());
+        }
+        let timing = keystroke::configured_timing(app_handle);
+        if keystroke::configured_backend(app_handle) == KeystrokeBackend::Native {
+            target_window::check(app_handle)?;
+            keystroke::type_native(&card_format::render_card(app_handle, card_format, text_to_send), mode, timing)?;
+            return Ok(true);
+        }
+
+        let ahk_exe_path = Self::find_ahk_path(app_handle)?;
+        let send_statement = format!(
+            "{}{}",
+            target_window::configured_target(app_handle).ahk_guard(&format!("ExitApp {}", target_window::AHK_BLOCKED_EXIT)),
+            keystroke::ahk_send_statement(&card_format::render_card(app_handle, card_format, text_to_send), mode, timing)
+        );
+        let script_content = format!(
+            "#Requires AutoHotkey v2.0\n#NoTrayIcon\n\n{send_statement}\nExitApp\n"
+        );
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("virtual_io_hub_swipe")
+            .tempdir()
+            .map_err(|e| format!("Falha ao criar diretório temporário: {}", e))?;
+        let script_path = temp_dir.path().join("swipe_once.ahk");
+        fs::write(&script_path, script_content)
+            .map_err(|e| format!("Falha ao escrever script temporário: {}", e))?;
+
+        // Wait for the script so the temp dir is only removed after AutoHotkey read it
+        let status = Command::new(&ahk_exe_path)
+            .arg(&script_path)
+            .status()
+            .map_err(|e| format!("Falha ao iniciar AutoHotkey: {}", e))?;
+
+        if status.code() == Some(target_window::AHK_BLOCKED_EXIT) {
+            return Err(target_window::blocked_by_script(app_handle));
+        }
+        if !status.success() {
+            return Err(format!("AutoHotkey terminou com erro: {}", status));
+        }
+        Ok(true)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.ahk_process.is_some() || self.native_shortcuts.is_some()
+    }
+
+    // An AutoHotkey process that exited but wasn't reaped by the watcher yet
+    fn process_exited(&mut self) -> bool {
+        matches!(self.ahk_process.as_mut().map(|p| p.try_wait()), Some(Ok(Some(_))))
+    }
+
+    pub fn process_id(&self) -> Option<u32> {
+        self.ahk_process.as_ref().map(|p| p.id())
+    }
+
+    // Reaps the AutoHotkey process if it exited on its own (crash, killed by
+    // antivirus...). Returns the exit status when that happened.
+    fn reap_exited_process(&mut self) -> Option<ExitStatus> {
+        let status = self.ahk_process.as_mut()?.try_wait().ok()??;
+        self.ahk_process = None;
+        if let Some(path) = self.temp_script_path.take() {
+            let _ = fs::remove_file(path);
+        }
+        Some(status)
+    }
+
+    // Removes every binding and the send queue
     pub fn stop(&mut self) -> Result<bool, String> {
+        self.bindings.clear();
+        self.queue = None;
+        self.stop_listener()
+    }
+
+    fn stop_listener(&mut self) -> Result<bool, String> {
+        if let Some((app_handle, shortcuts)) = self.native_shortcuts.take() {
+            app_handle
+                .global_shortcut()
+                .unregister_multiple(shortcuts)
+                .map_err(|e| format!("Falha ao liberar hotkeys: {}", e))?;
+        }
+
         if let Some(mut process) = self.ahk_process.take() {
             match process.kill() {
                 Ok(_) => {},
@@ -89,17 +547,21 @@ impl HotkeyManager {
         Ok(true)
     }
 
-    fn find_ahk_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    pub fn ahk_candidate_paths(app_handle: &AppHandle) -> Result<[PathBuf; 4], String> {
         // Try to find AutoHotkey in the resources directory
         let resource_dir = app_handle.path().resource_dir()
             .map_err(|e| format!("Falha ao obter diretório de recursos: {}", e))?;
 
-        let ahk_paths = [
+        Ok([
             resource_dir.join("AutoHotkey").join("v2").join("AutoHotkey64.exe"),
             resource_dir.join("resources").join("AutoHotkey").join("v2").join("AutoHotkey64.exe"),
             PathBuf::from(r"C:\Program Files\AutoHotkey\v2\AutoHotkey64.exe"),
             PathBuf::from(r"C:\Program Files (x86)\AutoHotkey\v2\AutoHotkey64.exe"),
-        ];
+        ])
+    }
+
+    fn find_ahk_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+        let ahk_paths = Self::ahk_candidate_paths(app_handle)?;
 
         // Log the paths we're checking for debugging
         println!("Checking for AutoHotkey in the following paths:");
@@ -159,43 +621,35 @@ impl HotkeyManager {
         
         // Try to download and install from official website
         println!("Attempting to download and install AutoHotkey V2...");
-        match Self::download_autohotkey_portable(&ahk_install_dir) {
-            Ok(_) => {
-                if portable_exe.exists() {
-                    println!("Installation completed successfully");
-                    Ok(portable_exe)
-                } else {
-                    // Try alternative method - copy from system installation
-                    println!("Download installation failed, trying to copy from system...");
-                    Self::find_and_copy_autohotkey(&ahk_install_dir)
-                }
-            },
-            Err(e) => {
-                println!("Download and installation failed: {}. Trying alternative method...", e);
-                
-                // Try to find and copy from system PATH or other locations
-                Self::find_and_copy_autohotkey(&ahk_install_dir)
-            }
+        // A failed integrity check is final: no unverified executable is
+        // picked up from elsewhere instead
+        Self::download_autohotkey_portable(app_handle, &ahk_install_dir)?;
+        if !portable_exe.exists() {
+            return Err("Instalação concluída mas executável não encontrado".to_string());
         }
+        println!("Installation completed successfully");
+        Ok(portable_exe)
     }
 
-    fn download_autohotkey_portable(install_dir: &Path) -> Result<(), String> {
+    fn download_autohotkey_portable(app_handle: &AppHandle, install_dir: &Path) -> Result<(), String> {
         println!("Downloading AutoHotkey V2 installer from official website...");
         
-        // Create a temporary file for the installer
+        // Create a temporary file for the installer, keeping only its path
+        // (removed on drop) so the check can open the file locked
         let temp_installer = tempfile::Builder::new()
             .prefix("ahk_v2_installer")
             .suffix(".exe")
             .tempfile()
-            .map_err(|e| format!("Falha ao criar arquivo temporário para download: {}", e))?;
+            .map_err(|e| format!("Falha ao criar arquivo temporário para download: {}", e))?
+            .into_temp_path();
         
-        let temp_installer_path = temp_installer.path();
+        let temp_installer_path: &Path = &temp_installer;
         println!("Downloading to temporary file: {}", temp_installer_path.display());
         
         // Download the installer using reqwest (blocking) - synchronous approach
         let client = reqwest::blocking::Client::new();
         let response = client
-            .get("https://www.autohotkey.com/download/ahk-v2.exe")
+            .get(ahk_installer::INSTALLER_URL)
             .send()
             .map_err(|e| format!("Falha na requisição HTTP: {}", e))?;
         
@@ -211,7 +665,12 @@ impl HotkeyManager {
             .map_err(|e| format!("Falha ao criar arquivo: {}", e))?;
         file.write_all(&data_vec)
             .map_err(|e| format!("Falha ao escrever arquivo: {}", e))?;
-        
+        drop(file);
+
+        // Never run an installer that fails the checksum or signature check;
+        // the file stays locked until the installer has run
+        let _verified = ahk_installer::verify(app_handle, temp_installer_path)?;
+
         println!("Download completed successfully. Installing AutoHotkey V2...");
         
         // Now run the installer silently
@@ -261,34 +720,76 @@ impl HotkeyManager {
         }
     }
 
-    fn find_and_copy_autohotkey(install_dir: &Path) -> Result<PathBuf, String> {
-        // Try to find AutoHotkey in PATH or other locations
-        println!("Searching for AutoHotkey in PATH and other locations...");
-        
-        // Check PATH environment variable
-        if let Ok(path_var) = env::var("PATH") {
-            for path_str in path_var.split(';') {
-                let path = PathBuf::from(path_str);
-                let ahk_path = path.join("AutoHotkey64.exe");
-                if ahk_path.exists() {
-                    println!("Found AutoHotkey in PATH: {}", ahk_path.display());
-                    
-                    let target_path = install_dir.join("AutoHotkey64.exe");
-                    match fs::copy(&ahk_path, &target_path) {
-                        Ok(_) => {
-                            println!("Successfully copied AutoHotkey to: {}", target_path.display());
-                            return Ok(target_path);
-                        },
-                        Err(e) => {
-                            println!("Failed to copy from PATH: {}", e);
-                        }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HotkeyDied {
+    pub status: String,
+    pub restarted: bool,
+    // Why it wasn't brought back, when `hotkey_auto_restart` is on
+    pub restart_error: Option<String>,
+}
+
+// `hotkey_auto_restart` in the app config
+fn auto_restart_enabled(app_handle: &AppHandle) -> bool {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get("hotkey_auto_restart").and_then(|v| v.as_bool()))
+        .unwrap_or(false)
+}
+
+// Background check for an AutoHotkey process that died while the hotkey was
+// supposed to be active. Emits `hotkey-died`; with `hotkey_auto_restart`, the
+// script is started again with the same bindings, unless it keeps dying.
+pub fn watch_process(app_handle: AppHandle) {
+    thread::spawn(move || {
+        let mut restarts: Vec<Instant> = Vec::new();
+        loop {
+            thread::sleep(PROCESS_WATCH_INTERVAL);
+
+            let manager = app_handle.state::<Mutex<HotkeyManager>>();
+            let mut manager = match manager.lock() {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+            let Some(status) = manager.reap_exited_process() else {
+                continue;
+            };
+            tracing::warn!("AutoHotkey process exited unexpectedly: {}", status);
+
+            let mut died = HotkeyDied {
+                status: status.to_string(),
+                restarted: false,
+                restart_error: None,
+            };
+            if auto_restart_enabled(&app_handle) {
+                restarts.retain(|at| at.elapsed() < RESTART_WINDOW);
+                if restarts.len() >= MAX_RESTARTS {
+                    died.restart_error = Some(format!(
+                        "Encerrado {} vezes em {} segundos; reinício automático suspenso.",
+                        MAX_RESTARTS + 1,
+                        RESTART_WINDOW.as_secs()
+                    ));
+                } else {
+                    restarts.push(Instant::now());
+                    match manager.listen(&app_handle) {
+                        Ok(_) => died.restarted = true,
+                        Err(e) => died.restart_error = Some(e),
                     }
                 }
             }
+            drop(manager);
+
+            let _ = app_handle.emit("hotkey-died", &died);
+            let message = if died.restarted {
+                format!("O processo do AutoHotkey foi encerrado inesperadamente ({}) e foi reiniciado.", status)
+            } else {
+                format!("O processo do AutoHotkey foi encerrado inesperadamente ({}).", status)
+            };
+            let title = if died.restarted { "Cartão magnético reiniciado" } else { "Cartão magnético desativado" };
+            notifications::notify(&app_handle, title, &message);
         }
-        
-        Err("AutoHotkey not found in PATH or other locations".to_string())
-    }
+    });
 }
 
 impl Drop for HotkeyManager {
@@ -298,9 +799,11 @@ impl Drop for HotkeyManager {
 }
 
 #[tauri::command]
-pub fn start_hotkey(app_handle: AppHandle, text_to_send: &str, hotkey_manager: tauri::State<'_, std::sync::Mutex<HotkeyManager>>) -> Result<bool, String> {
+pub fn start_hotkey(app_handle: AppHandle, text_to_send: &str, format_id: Option<String>, keystroke_mode: Option<KeystrokeMode>, hotkey_manager: tauri::State<'_, std::sync::Mutex<HotkeyManager>>) -> Result<bool, String> {
+    let card_format = card_format::resolve_format(&app_handle, text_to_send, format_id.as_deref())?;
+    let mode = keystroke::resolve_mode(&app_handle, keystroke_mode);
     let mut manager = hotkey_manager.lock().map_err(|_| "Falha ao obter lock do HotkeyManager".to_string())?;
-    manager.start(&app_handle, text_to_send)
+    manager.start(&app_handle, text_to_send, &card_format, mode)
 }
 
 #[tauri::command]
@@ -311,13 +814,141 @@ pub fn stop_hotkey(hotkey_manager: tauri::State<'_, std::sync::Mutex<HotkeyManag
 
 #[tauri::command]
 pub fn check_hotkey_status(hotkey_manager: tauri::State<'_, std::sync::Mutex<HotkeyManager>>) -> Result<bool, String> {
+    let mut manager = hotkey_manager.lock().map_err(|_| "Falha ao obter lock do HotkeyManager".to_string())?;
+    Ok(manager.is_running() && !manager.process_exited())
+}
+
+// Binds `hotkey` to the wallet of `patient_id`, or to `card_text` when no
+// patient is given. Returns every binding.
+#[tauri::command]
+pub fn register_hotkey_binding(
+    app_handle: AppHandle,
+    hotkey: String,
+    patient_id: Option<u32>,
+    card_text: Option<String>,
+    format_id: Option<String>,
+    keystroke_mode: Option<KeystrokeMode>,
+    hotkey_manager: tauri::State<'_, std::sync::Mutex<HotkeyManager>>,
+) -> Result<Vec<HotkeyBinding>, String> {
+    let hotkey = hotkey.trim().to_string();
+    if hotkey.is_empty() {
+        return Err("Hotkey não informada.".into());
+    }
+    let wallet = match patient_id {
+        Some(id) => patient::get_patient(&app_handle, id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Paciente {} não encontrado.", id))?
+            .wallet,
+        None => card_text.unwrap_or_default(),
+    };
+    let wallet = wallet.trim().to_string();
+    if wallet.is_empty() {
+        return Err("Texto para enviar não pode estar vazio".into());
+    }
+    let card_format = card_format::resolve_format(&app_handle, &wallet, format_id.as_deref())?;
+    let binding = HotkeyBinding {
+        hotkey,
+        patient_id,
+        text: card_format::render_card(&app_handle, &card_format, &wallet),
+        wallet,
+        mode: keystroke::resolve_mode(&app_handle, keystroke_mode),
+    };
+    let mut manager = hotkey_manager.lock().map_err(|_| "Falha ao obter lock do HotkeyManager".to_string())?;
+    manager.bind(&app_handle, binding)?;
+    Ok(manager.bindings())
+}
+
+#[tauri::command]
+pub fn list_hotkey_bindings(hotkey_manager: tauri::State<'_, std::sync::Mutex<HotkeyManager>>) -> Result<Vec<HotkeyBinding>, String> {
     let manager = hotkey_manager.lock().map_err(|_| "Falha ao obter lock do HotkeyManager".to_string())?;
-    Ok(manager.ahk_process.is_some())
+    Ok(manager.bindings())
+}
+
+// Returns false if the hotkey wasn't bound
+#[tauri::command]
+pub fn unregister_hotkey_binding(
+    app_handle: AppHandle,
+    hotkey: String,
+    hotkey_manager: tauri::State<'_, std::sync::Mutex<HotkeyManager>>,
+) -> Result<bool, String> {
+    let mut manager = hotkey_manager.lock().map_err(|_| "Falha ao obter lock do HotkeyManager".to_string())?;
+    manager.unbind(&app_handle, hotkey.trim())
+}
+
+// Loads the patients into the send queue of `hotkey` (by default the
+// manager's own, Ctrl+Q), in this order. Each press types the next card.
+#[tauri::command]
+pub fn load_send_queue(
+    app_handle: AppHandle,
+    patient_ids: Vec<u32>,
+    hotkey: Option<String>,
+    format_id: Option<String>,
+    keystroke_mode: Option<KeystrokeMode>,
+    hotkey_manager: tauri::State<'_, std::sync::Mutex<HotkeyManager>>,
+) -> Result<QueueStatus, String> {
+    let mut cards = Vec::new();
+    for id in patient_ids {
+        let patient = patient::get_patient(&app_handle, id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Paciente {} não encontrado.", id))?;
+        let wallet = patient.wallet.trim().to_string();
+        if wallet.is_empty() {
+            return Err(format!("Paciente {} sem número de carteira.", patient.name));
+        }
+        let card_format = card_format::resolve_format(&app_handle, &wallet, format_id.as_deref())?;
+        cards.push(QueuedCard {
+            patient_id: id,
+            text: card_format.render(&wallet, Some(&patient.name)),
+            name: patient.name,
+            wallet,
+        });
+    }
+    let mode = keystroke::resolve_mode(&app_handle, keystroke_mode);
+    let mut manager = hotkey_manager.lock().map_err(|_| "Falha ao obter lock do HotkeyManager".to_string())?;
+    let hotkey = hotkey.map(|h| h.trim().to_string()).filter(|h| !h.is_empty()).unwrap_or_else(|| manager.hotkey.clone());
+    manager.load_queue(&app_handle, &hotkey, cards, mode)
+}
+
+#[tauri::command]
+pub fn get_send_queue(hotkey_manager: tauri::State<'_, std::sync::Mutex<HotkeyManager>>) -> Result<Option<QueueStatus>, String> {
+    let mut manager = hotkey_manager.lock().map_err(|_| "Falha ao obter lock do HotkeyManager".to_string())?;
+    Ok(manager.queue_status())
+}
+
+#[tauri::command]
+pub fn reorder_send_queue(
+    app_handle: AppHandle,
+    patient_ids: Vec<u32>,
+    next: Option<usize>,
+    hotkey_manager: tauri::State<'_, std::sync::Mutex<HotkeyManager>>,
+) -> Result<QueueStatus, String> {
+    let mut manager = hotkey_manager.lock().map_err(|_| "Falha ao obter lock do HotkeyManager".to_string())?;
+    manager.reorder_queue(&app_handle, &patient_ids, next)
+}
+
+#[tauri::command]
+pub fn clear_send_queue(app_handle: AppHandle, hotkey_manager: tauri::State<'_, std::sync::Mutex<HotkeyManager>>) -> Result<bool, String> {
+    let mut manager = hotkey_manager.lock().map_err(|_| "Falha ao obter lock do HotkeyManager".to_string())?;
+    manager.clear_queue(&app_handle)
 }
 
 #[tauri::command]
 pub fn diagnose_hotkey_system(app_handle: AppHandle) -> Result<serde_json::Value, String> {
     let mut diagnostics = serde_json::Map::new();
+    let backend = keystroke::configured_backend(&app_handle);
+    diagnostics.insert("keystroke_backend".to_string(), serde_json::to_value(backend).unwrap_or_default());
+    diagnostics.insert("keystroke_timing".to_string(), serde_json::to_value(keystroke::configured_timing(&app_handle)).unwrap_or_default());
+    diagnostics.insert(
+        "ahk_script_template".to_string(),
+        match ahk_template::load(&app_handle) {
+            Ok(template) => serde_json::Value::Bool(template.is_some()),
+            Err(e) => serde_json::Value::String(e),
+        },
+    );
+    #[cfg(target_os = "macos")]
+    diagnostics.insert("accessibility_trusted".to_string(), serde_json::Value::Bool(crate::macos_input::accessibility_trusted(false)));
+    #[cfg(target_os = "linux")]
+    diagnostics.insert("linux_input".to_string(), serde_json::to_value(crate::linux_input::detect()).unwrap_or_default());
     
     // Check if we can access the resource directory
     match app_handle.path().resource_dir() {
@@ -388,14 +1019,19 @@ pub fn diagnose_hotkey_system(app_handle: AppHandle) -> Result<serde_json::Value
     
     // Add installation recommendations
     let mut recommendations = serde_json::Map::new();
-    recommendations.insert("auto_install_available".to_string(), serde_json::Value::Bool(true));
+    let auto_install = ahk_installer::configured_trust(&app_handle).can_verify();
+    recommendations.insert("auto_install_available".to_string(), serde_json::Value::Bool(auto_install));
     recommendations.insert("download_url".to_string(), serde_json::Value::String(
-        "https://www.autohotkey.com/download/ahk-v2.exe".to_string()
+        ahk_installer::INSTALLER_URL.to_string()
     ));
-    recommendations.insert("message".to_string(), serde_json::Value::String(
+    let message = if auto_install {
         "O sistema tentará baixar e instalar automaticamente o AutoHotkey V2 do site oficial. \
          Se a instalação automática falhar, você pode baixar manualmente o instalador e executá-lo."
-    .to_string()));
+    } else {
+        "A instalação automática do AutoHotkey V2 está desativada: nenhum checksum ou certificado confiável \
+         foi configurado em \"autohotkey_installer\". Baixe e execute o instalador manualmente."
+    };
+    recommendations.insert("message".to_string(), serde_json::Value::String(message.to_string()));
     
     diagnostics.insert("recommendations".to_string(), serde_json::Value::Object(recommendations));
     
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio_util::sync::CancellationToken;
+
+// Cancellation tokens of the imports currently running, by job id
+pub struct ImportJobs {
+    jobs: HashMap<String, CancellationToken>,
+    next_id: u64,
+}
+
+impl ImportJobs {
+    pub fn new() -> Self {
+        Self {
+            jobs: HashMap::new(),
+            next_id: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ImportJobStarted<'a> {
+    job_id: &'a str,
+    kind: &'a str,
+}
+
+// A registered import. Dropping it removes the job from the registry, so a
+// job is cancellable exactly while its command is running.
+pub struct ImportJob {
+    pub id: String,
+    pub kind: String,
+    pub token: CancellationToken,
+    app_handle: AppHandle,
+}
+
+impl ImportJob {
+    // Registers a job under the id chosen by the caller, or a generated one.
+    // The id is announced through `import-job-started` so the UI can cancel
+    // a job it didn't name.
+    pub fn start(app_handle: &AppHandle, kind: &str, job_id: Option<String>) -> Result<Self, String> {
+        let state = app_handle.state::<Arc<Mutex<ImportJobs>>>();
+        let mut jobs = state
+            .lock()
+            .map_err(|_| "Falha ao obter lock das importações".to_string())?;
+        let id = match job_id.map(|id| id.trim().to_string()).filter(|id| !id.is_empty()) {
+            Some(id) if jobs.jobs.contains_key(&id) => {
+                return Err(format!("Já existe uma importação em andamento com o id {}.", id));
+            }
+            Some(id) => id,
+            None => {
+                jobs.next_id += 1;
+                format!("{}-{}", kind, jobs.next_id - 1)
+            }
+        };
+        let token = CancellationToken::new();
+        jobs.jobs.insert(id.clone(), token.clone());
+        drop(jobs);
+
+        let _ = app_handle.emit("import-job-started", ImportJobStarted { job_id: &id, kind });
+        Ok(Self {
+            id,
+            kind: kind.to_string(),
+            token,
+            app_handle: app_handle.clone(),
+        })
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+}
+
+impl Drop for ImportJob {
+    fn drop(&mut self) {
+        let state = self.app_handle.state::<Arc<Mutex<ImportJobs>>>();
+        if let Ok(mut jobs) = state.lock() {
+            jobs.jobs.remove(&self.id);
+        };
+    }
+}
+
+// Stops a running import. Requests in flight are dropped and the command
+// returns what it finished so far. Returns false if no such job is running.
+#[tauri::command]
+pub fn cancel_import(state: tauri::State<'_, Arc<Mutex<ImportJobs>>>, job_id: String) -> Result<bool, String> {
+    let jobs = state
+        .lock()
+        .map_err(|_| "Falha ao obter lock das importações".to_string())?;
+    match jobs.jobs.get(job_id.trim()) {
+        Some(token) => {
+            token.cancel();
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
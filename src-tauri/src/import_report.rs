@@ -0,0 +1,274 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+use crate::beneficiary_sync::iso_utc;
+use crate::import_jobs::ImportJob;
+use crate::patient;
+
+// A4 in points, Helvetica 10 on 14pt lines
+const PAGE_WIDTH: u32 = 595;
+const PAGE_HEIGHT: u32 = 842;
+const MARGIN: u32 = 50;
+const LEADING: u32 = 14;
+const LINES_PER_PAGE: usize = ((PAGE_HEIGHT - 2 * MARGIN) / LEADING) as usize;
+// About what fits between the margins at 10pt
+const MAX_LINE_CHARS: usize = 95;
+
+// What a bulk import got for one beneficiary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportReportEntry {
+    pub name: String,
+    pub wallet: String,
+    pub photo: bool,
+    pub fingerprints: usize,
+    // Downloads that failed for this beneficiary, with the reason
+    pub failures: Vec<String>,
+}
+
+// Kept under <data dir>/import_reports/<job id>.json once the job finishes,
+// so it can be exported later
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub job_id: String,
+    pub kind: String,
+    pub finished_at: String,
+    pub total: usize,
+    pub cancelled: bool,
+    // Set when the job itself failed, e.g. the search didn't answer
+    pub error: Option<String>,
+    pub entries: Vec<ImportReportEntry>,
+}
+
+impl ImportReport {
+    pub fn new(job: &ImportJob, total: usize, entries: Vec<ImportReportEntry>) -> Self {
+        Self {
+            job_id: job.id.clone(),
+            kind: job.kind.clone(),
+            finished_at: iso_utc(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)),
+            total,
+            cancelled: job.is_cancelled(),
+            error: None,
+            entries,
+        }
+    }
+}
+
+fn report_path(app_handle: &AppHandle, job_id: &str) -> io::Result<PathBuf> {
+    let mut dir = patient::ensure_data_dir(app_handle)?;
+    dir.push("import_reports");
+    if !dir.exists() {
+        fs::create_dir_all(&dir)?;
+    }
+    // Job ids may be chosen by the caller
+    let name: String = job_id
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    Ok(dir.join(format!("{}.json", name)))
+}
+
+pub fn save(app_handle: &AppHandle, report: &ImportReport) {
+    let saved = report_path(app_handle, &report.job_id).and_then(|path| {
+        let json = serde_json::to_string_pretty(report).map_err(io::Error::other)?;
+        fs::write(path, json)
+    });
+    if let Err(e) = saved {
+        tracing::error!("Falha ao gravar relatório da importação {}: {}", report.job_id, e);
+    }
+}
+
+fn load(app_handle: &AppHandle, job_id: &str) -> Result<ImportReport, String> {
+    let path = report_path(app_handle, job_id).map_err(|e| format!("Falha ao localizar relatório: {e}"))?;
+    let json = fs::read_to_string(&path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => format!("Nenhum relatório para a importação {}.", job_id.trim()),
+        _ => format!("Falha ao ler relatório: {e}"),
+    })?;
+    serde_json::from_str(&json).map_err(|e| format!("Relatório inválido: {e}"))
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "sim"
+    } else {
+        "não"
+    }
+}
+
+fn to_csv(report: &ImportReport) -> Vec<u8> {
+    // The BOM makes spreadsheet apps read the accents as UTF-8
+    let mut csv = String::from("\u{FEFF}nome,carteira,foto,digitais,falhas\r\n");
+    for entry in &report.entries {
+        let row = [
+            csv_field(&entry.name),
+            csv_field(&entry.wallet),
+            yes_no(entry.photo).to_string(),
+            entry.fingerprints.to_string(),
+            csv_field(&entry.failures.join("; ")),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+    csv.into_bytes()
+}
+
+fn report_lines(report: &ImportReport) -> Vec<String> {
+    let with_photo = report.entries.iter().filter(|e| e.photo).count();
+    let with_failures = report.entries.iter().filter(|e| !e.failures.is_empty()).count();
+    let mut lines = vec![
+        format!("Relatório de importação {}", report.job_id),
+        format!("Tipo: {} | Concluída em: {}", report.kind, report.finished_at),
+        format!(
+            "Beneficiários: {} de {} processados | com foto: {} | com falhas: {}",
+            report.entries.len(),
+            report.total,
+            with_photo,
+            with_failures
+        ),
+    ];
+    if report.cancelled {
+        lines.push("Importação cancelada antes do fim.".into());
+    }
+    if let Some(error) = &report.error {
+        lines.push(format!("Erro: {}", error));
+    }
+    lines.push(String::new());
+
+    for entry in &report.entries {
+        lines.push(format!(
+            "{} ({}) - foto: {}, digitais: {}",
+            entry.name,
+            entry.wallet,
+            yes_no(entry.photo),
+            entry.fingerprints
+        ));
+        for failure in &entry.failures {
+            lines.push(format!("    Falha: {}", failure));
+        }
+    }
+    lines
+}
+
+// Splits on spaces so no line runs past the right margin
+fn wrap(line: &str) -> Vec<String> {
+    let indent: String = line.chars().take_while(|c| *c == ' ').collect();
+    let mut wrapped = Vec::new();
+    let mut current = String::new();
+    for word in line.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > MAX_LINE_CHARS {
+            wrapped.push(std::mem::take(&mut current));
+        }
+        if current.is_empty() {
+            current.push_str(&indent);
+        } else {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    wrapped.push(current);
+    wrapped
+}
+
+// PDF string literal in WinAnsiEncoding, which matches Latin-1 for accents
+fn pdf_text(line: &str) -> Vec<u8> {
+    let mut out = vec![b'('];
+    for c in line.chars() {
+        match c {
+            '(' | ')' | '\\' => out.extend_from_slice(&[b'\\', c as u8]),
+            ' '..='~' | '\u{A0}'..='\u{FF}' => out.push(c as u32 as u8),
+            '—' => out.push(0x97),
+            '…' => out.push(0x85),
+            _ => out.push(b'?'),
+        }
+    }
+    out.push(b')');
+    out
+}
+
+// Minimal PDF 1.4: one text column, Helvetica, as many pages as needed
+fn to_pdf(report: &ImportReport) -> Vec<u8> {
+    let lines: Vec<String> = report_lines(report).iter().flat_map(|line| wrap(line)).collect();
+    let pages: Vec<&[String]> = lines.chunks(LINES_PER_PAGE).collect();
+
+    // 1 catalog, 2 page tree, 3 and 4 fonts, then a page and its content per page
+    let page_ids: Vec<usize> = (0..pages.len()).map(|i| 5 + 2 * i).collect();
+    let mut objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            page_ids.iter().map(|id| format!("{} 0 R", id)).collect::<Vec<_>>().join(" "),
+            pages.len()
+        )
+        .into_bytes(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_vec(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_vec(),
+    ];
+    for (index, page) in pages.iter().enumerate() {
+        let mut content = format!("BT {} TL {} {} Td\n", LEADING, MARGIN, PAGE_HEIGHT - MARGIN).into_bytes();
+        for (row, line) in page.iter().enumerate() {
+            // The report title, on the first line of the first page, in bold
+            let font = if index == 0 && row == 0 { "/F2 12 Tf " } else { "/F1 10 Tf " };
+            content.extend_from_slice(font.as_bytes());
+            content.extend_from_slice(&pdf_text(line));
+            content.extend_from_slice(b" Tj T*\n");
+        }
+        content.extend_from_slice(b"ET");
+
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH,
+                PAGE_HEIGHT,
+                page_ids[index] + 1
+            )
+            .into_bytes(),
+        );
+        let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+        stream.extend_from_slice(&content);
+        stream.extend_from_slice(b"\nendstream");
+        objects.push(stream);
+    }
+
+    let mut pdf = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        pdf.extend_from_slice(object);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = pdf.len();
+    pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(
+        format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).as_bytes(),
+    );
+    pdf
+}
+
+// Writes the report of a finished bulk import to `path`, as CSV (one row
+// per beneficiary) or PDF (summary and failures)
+#[tauri::command]
+pub fn export_import_report(app_handle: AppHandle, job_id: String, format: String, path: String) -> Result<(), String> {
+    let report = load(&app_handle, &job_id)?;
+    let data = match format.trim().to_lowercase().as_str() {
+        "csv" => to_csv(&report),
+        "pdf" => to_pdf(&report),
+        other => return Err(format!("Formato de relatório não suportado: {}", other)),
+    };
+    fs::write(path.trim(), data).map_err(|e| format!("Falha ao gravar relatório: {e}"))
+}
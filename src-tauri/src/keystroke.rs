@@ -0,0 +1,316 @@
+#[cfg(not(target_os = "linux"))]
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use serde::{Deserialize, Serialize};
+#[cfg(not(target_os = "linux"))]
+use std::thread;
+use std::time::Duration;
+use tauri::AppHandle;
+
+use crate::patient;
+
+// Modifiers of the hotkey may still be down when it fires; they are released
+// and typing waits this long, or the card text would arrive as Ctrl+digits
+pub(crate) const MODIFIER_RELEASE_DELAY: Duration = Duration::from_millis(50);
+
+// What types the card text: SendInput from this process (through enigo) or
+// a generated AutoHotkey script, kept for machines where native input is
+// blocked. `keystroke_backend` in the app config.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeystrokeBackend {
+    #[default]
+    Native,
+    Autohotkey,
+}
+
+pub fn configured_backend(app_handle: &AppHandle) -> KeystrokeBackend {
+    // AutoHotkey only exists on Windows
+    if !cfg!(windows) {
+        return KeystrokeBackend::Native;
+    }
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get("keystroke_backend").cloned())
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+// How the card text is typed. The AutoHotkey statements are below; the
+// native backend follows the same modes in `type_native`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeystrokeMode {
+    // SendText: characters sent as-is, mapped through the active keyboard layout
+    #[default]
+    Text,
+    // Every character as a Unicode packet ({U+XXXX}), independent of the layout
+    // (ABNT2 puts `;`, `=` and `?` on keys that differ from US)
+    Unicode,
+    // Legacy SendInput with Send modifiers (^ + ! # { }) escaped
+    Keys,
+    // Digits as numeric keypad keys with NumLock forced on (legacy Datasul
+    // screens that only take numpad scan codes); other characters as text
+    Numpad,
+}
+
+// Default mode comes from `keystroke_mode` in the app config
+pub fn configured_mode(app_handle: &AppHandle) -> KeystrokeMode {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get("keystroke_mode").cloned())
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+// Pauses for screens that drop characters typed at full speed, under
+// `keystroke_timing` in the app config. Zero means no pause.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeystrokeTiming {
+    // Between two characters
+    pub key_delay_ms: u32,
+    // Before the first one, e.g. to let the focus reach the card field
+    pub initial_delay_ms: u32,
+}
+
+pub fn configured_timing(app_handle: &AppHandle) -> KeystrokeTiming {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get("keystroke_timing").cloned())
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+pub fn resolve_mode(app_handle: &AppHandle, mode: Option<KeystrokeMode>) -> KeystrokeMode {
+    mode.unwrap_or_else(|| configured_mode(app_handle))
+}
+
+// Escapes text for an AutoHotkey v2 quoted string
+pub(crate) fn quote_ahk(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '`' => quoted.push_str("``"),
+            '"' => quoted.push_str("`\""),
+            ';' => quoted.push_str("`;"),
+            '\n' => quoted.push_str("`n"),
+            '\r' => quoted.push_str("`r"),
+            '\t' => quoted.push_str("`t"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn escape_send_keys(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '^' | '+' | '!' | '#' | '{' | '}' => {
+                escaped.push('{');
+                escaped.push(c);
+                escaped.push('}');
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn numpad_key(c: char) -> Option<String> {
+    match c {
+        '0'..='9' => Some(format!("{{Numpad{}}}", c)),
+        '.' => Some("{NumpadDot}".into()),
+        '+' => Some("{NumpadAdd}".into()),
+        '-' => Some("{NumpadSub}".into()),
+        '*' => Some("{NumpadMult}".into()),
+        '/' => Some("{NumpadDiv}".into()),
+        _ => None,
+    }
+}
+
+fn numpad_statements(text: &str) -> String {
+    let mut lines = vec![
+        "numLockWasOn := GetKeyState(\"NumLock\", \"T\")".to_string(),
+        "SetNumLockState \"On\"".to_string(),
+    ];
+
+    // Consecutive numpad keys and consecutive text are sent in chunks
+    let mut keys = String::new();
+    let mut plain = String::new();
+    for c in text.chars() {
+        match numpad_key(c) {
+            Some(key) => {
+                if !plain.is_empty() {
+                    lines.push(format!("SendText {}", quote_ahk(&std::mem::take(&mut plain))));
+                }
+                keys.push_str(&key);
+            }
+            None => {
+                if !keys.is_empty() {
+                    lines.push(format!("SendInput {}", quote_ahk(&std::mem::take(&mut keys))));
+                }
+                plain.push(c);
+            }
+        }
+    }
+    if !keys.is_empty() {
+        lines.push(format!("SendInput {}", quote_ahk(&keys)));
+    }
+    if !plain.is_empty() {
+        lines.push(format!("SendText {}", quote_ahk(&plain)));
+    }
+
+    lines.push("SetNumLockState numLockWasOn ? \"On\" : \"Off\"".to_string());
+    lines.join("\n")
+}
+
+// AutoHotkey v2 statement(s) that type `text` using the given mode; may span
+// several lines
+pub fn ahk_send_statement(text: &str, mode: KeystrokeMode, timing: KeystrokeTiming) -> String {
+    let statement = match mode {
+        KeystrokeMode::Text => format!("SendText {}", quote_ahk(text)),
+        KeystrokeMode::Unicode => {
+            let packets: String = text.chars().map(|c| format!("{{U+{:04X}}}", c as u32)).collect();
+            format!("SendInput {}", quote_ahk(&packets))
+        }
+        KeystrokeMode::Keys => format!("SendInput {}", quote_ahk(&escape_send_keys(text))),
+        KeystrokeMode::Numpad => numpad_statements(text),
+    };
+
+    let mut lines = Vec::new();
+    if timing.initial_delay_ms > 0 {
+        lines.push(format!("Sleep {}", timing.initial_delay_ms));
+    }
+    if timing.key_delay_ms == 0 {
+        lines.push(statement);
+        return lines.join("\n");
+    }
+    // SendInput ignores SetKeyDelay; in Event mode SendText and SendEvent
+    // honor it
+    lines.push("SendMode \"Event\"".to_string());
+    lines.push(format!("SetKeyDelay {}, 0", timing.key_delay_ms));
+    for line in statement.lines() {
+        lines.push(match line.strip_prefix("SendInput ") {
+            Some(keys) => format!("SendEvent {}", keys),
+            None => line.to_string(),
+        });
+    }
+    lines.join("\n")
+}
+
+// Windows virtual-key codes of the numeric keypad digits. Sent as virtual
+// keys they type digits whatever the NumLock state.
+#[cfg(windows)]
+fn numpad_vk(c: char) -> Option<Key> {
+    c.to_digit(10).map(|digit| Key::Other(0x60 + digit))
+}
+
+// macOS key codes of the keypad digits (kVK_ANSI_Keypad0..9); 8 and 9 come
+// after a gap
+#[cfg(target_os = "macos")]
+fn numpad_vk(c: char) -> Option<Key> {
+    c.to_digit(10).map(|digit| Key::Other(if digit < 8 { 0x52 + digit } else { 0x53 + digit }))
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+fn numpad_vk(_c: char) -> Option<Key> {
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn input_error(e: impl std::fmt::Display) -> String {
+    format!("Falha ao digitar texto: {}", e)
+}
+
+// A unit sent to enigo: a run of text, or a single key
+#[cfg(not(target_os = "linux"))]
+enum Stroke {
+    Text(String),
+    Key(Key),
+}
+
+// Strokes typing `text` in this mode. With `per_key`, text runs are split in
+// single characters so a delay can be put between them.
+#[cfg(not(target_os = "linux"))]
+fn strokes(text: &str, mode: KeystrokeMode, per_key: bool) -> Vec<Stroke> {
+    let text_strokes = |text: &str| -> Vec<Stroke> {
+        if text.is_empty() {
+            Vec::new()
+        } else if per_key {
+            text.chars().map(|c| Stroke::Text(c.to_string())).collect()
+        } else {
+            vec![Stroke::Text(text.to_string())]
+        }
+    };
+
+    match mode {
+        // enigo sends text as Unicode packets, which is what both modes ask for
+        KeystrokeMode::Text | KeystrokeMode::Unicode => text_strokes(text),
+        // Each character as a key of the active layout
+        KeystrokeMode::Keys => text.chars().map(|c| Stroke::Key(Key::Unicode(c))).collect(),
+        KeystrokeMode::Numpad => {
+            let mut strokes = Vec::new();
+            let mut plain = String::new();
+            for c in text.chars() {
+                match numpad_vk(c) {
+                    Some(key) => {
+                        strokes.extend(text_strokes(&std::mem::take(&mut plain)));
+                        strokes.push(Stroke::Key(key));
+                    }
+                    None => plain.push(c),
+                }
+            }
+            strokes.extend(text_strokes(&plain));
+            strokes
+        }
+    }
+}
+
+// Types `text` into the focused window with SendInput, the native
+// counterpart of `ahk_send_statement`
+#[cfg(not(target_os = "linux"))]
+pub fn type_native(text: &str, mode: KeystrokeMode, timing: KeystrokeTiming) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    crate::macos_input::ensure_access()?;
+    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| format!("Falha ao iniciar entrada de teclado: {}", e))?;
+    for modifier in [Key::Control, Key::Shift, Key::Alt, Key::Meta] {
+        enigo.key(modifier, Direction::Release).map_err(input_error)?;
+    }
+    thread::sleep(MODIFIER_RELEASE_DELAY.max(Duration::from_millis(timing.initial_delay_ms.into())));
+
+    let key_delay = Duration::from_millis(timing.key_delay_ms.into());
+    for (i, stroke) in strokes(text, mode, !key_delay.is_zero()).into_iter().enumerate() {
+        if i > 0 && !key_delay.is_zero() {
+            thread::sleep(key_delay);
+        }
+        match stroke {
+            Stroke::Text(text) => enigo.text(&text),
+            Stroke::Key(key) => enigo.key(key, Direction::Click),
+        }
+        .map_err(input_error)?;
+    }
+    Ok(())
+}
+
+// Without SendInput on Linux, xdotool or a uinput keyboard types instead
+#[cfg(target_os = "linux")]
+pub fn type_native(text: &str, mode: KeystrokeMode, timing: KeystrokeTiming) -> Result<(), String> {
+    crate::linux_input::type_text(text, mode, timing)
+}
+
+// Typing needs the Accessibility permission on macOS; elsewhere it's always
+// granted. With `prompt`, macOS shows its dialog leading to the settings.
+#[cfg(target_os = "macos")]
+#[tauri::command]
+pub fn check_input_permission(prompt: Option<bool>) -> bool {
+    crate::macos_input::accessibility_trusted(prompt.unwrap_or(false))
+}
+
+#[cfg(not(target_os = "macos"))]
+#[tauri::command]
+pub fn check_input_permission(_prompt: Option<bool>) -> bool {
+    true
+}
@@ -1,16 +1,94 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
-use tauri::AppHandle;
+use tauri::{AppHandle, Emitter};
 use serde_json;
 use std::sync::{Mutex, Arc};
-use serde::{Deserialize};
-use reqwest;
+use serde::{Deserialize, Serialize};
 use base64::{engine::general_purpose as b64, Engine};
-use image::{DynamicImage, ImageEncoder, ColorType};
 
 mod patient;
+mod config_migrations;
+mod patient_tags;
+mod patient_audit;
+mod patient_history;
+mod patient_cleanup;
+mod patient_duplicates;
+mod patient_validation;
+mod data_lock;
+mod data_backups;
+mod data_watch;
+mod portable;
+mod workspaces;
+mod biometric_crypto;
 mod hotkey;
+mod ahk_installer;
+mod ahk_template;
+mod keystroke;
+mod target_window;
+#[cfg(target_os = "linux")]
+mod linux_input;
+#[cfg(target_os = "macos")]
+mod macos_input;
 mod biometry_server;
+mod biometry_approval;
+mod openapi;
 mod webcam_emulator;
+mod emulator_profile;
+mod control_interface;
+mod notifications;
+mod card_format;
+mod magstripe;
+mod barcode;
+mod multi_identity;
+mod resource_monitor;
+mod blob_store;
+mod attachments;
+mod fingerprint;
+mod wsq;
+mod minutiae;
+mod fingerprint_quality;
+mod ansi_nist;
+mod photo_pipeline;
+mod streamed_download;
+mod circuit_breaker;
+mod totvs_http;
+mod totvs_auth;
+mod config_assistant;
+mod smartcard;
+mod photo_refresh;
+mod photo_thumbnails;
+mod fingerprint_prefetch;
+mod verification_audit;
+mod setup_wizard;
+mod totvs;
+mod beneficiary_import;
+mod connection_test;
+mod totvs_cache;
+mod checkin;
+mod eligibility;
+mod authorizations;
+mod schema_drift;
+mod fingerprint_enrollment;
+mod totvs_profiles;
+mod totvs_log;
+mod import_jobs;
+mod import_report;
+mod spreadsheet_import;
+mod backup;
+mod totvs_endpoints;
+mod mock_totvs;
+mod patient_api;
+mod auto_send;
+mod totvs_export;
+mod totvs_replay;
+mod totvs_mirror;
+mod patient_diff;
+mod beneficiary_sync;
+mod webhook;
+
+use config_assistant::TotvsCredentials;
+use patient_validation::PatientSaveError;
+use totvs::error::TotvsError;
+use totvs::models::{self, Beneficiary, FacialPhoto, Fingerprint};
 
 // Remove greet command as we don't need it
 
@@ -20,126 +98,281 @@ struct BeneficiarySearchParams {
     modality: Option<String>,
     proposal: Option<String>,
     contract: Option<String>,
+    page: Option<u32>,
+    page_size: Option<u32>,
+    // Fetch every page from `page` on and merge the items
+    #[serde(default)]
+    all_pages: bool,
+    // Only records modified since this ISO 8601 instant, on servers that
+    // support it (query parameter `sync_changed_after_param`, default `changedAfter`)
+    #[serde(default)]
+    changed_after: Option<String>,
 }
 
-// helper to obtain config section
-fn get_cfg<'a>(root: &'a serde_json::Value) -> Result<&'a serde_json::Value, String> {
-    if let Some(imp) = root.get("importer_config") {
-        Ok(imp)
-    } else {
-        Ok(root)
+// Safety net against an endpoint that keeps reporting hasNext
+const MAX_SEARCH_PAGES: u32 = 200;
+
+#[derive(Debug, Clone, Serialize)]
+struct BeneficiarySearchProgress {
+    page: u32,
+    fetched: usize,
+    has_next: bool,
+}
+
+// helper to obtain config section, with the active connection profile applied
+fn get_cfg(root: &serde_json::Value) -> Result<serde_json::Value, TotvsError> {
+    Ok(totvs_profiles::importer_settings(root))
+}
+
+fn totvs_credentials(importer_cfg: &serde_json::Value) -> Result<TotvsCredentials, TotvsError> {
+    let field = |key: &str| importer_cfg.get(key).and_then(|v| v.as_str()).map(String::from);
+    Ok(TotvsCredentials {
+        base_url: field("base_url").ok_or_else(|| TotvsError::config("Base URL não definida nas configurações."))?,
+        user: field("user").ok_or_else(|| TotvsError::config("Usuário não definido nas configurações."))?,
+        password: field("password").ok_or_else(|| TotvsError::config("Senha não definida nas configurações."))?,
+        auth_mode: None,
+    })
+}
+
+// Clinic/provider identification the Portal do Prestador endpoints expect
+// with every request, both as query parameters and the clinic header
+pub(crate) struct PortalContext {
+    pub credentials: TotvsCredentials,
+    pub clinic: String,
+    pub provider_code: String,
+    pub health_insurer_code: String,
+}
+
+impl PortalContext {
+    pub fn query(&self) -> [(&str, &str); 3] {
+        [
+            ("provider", self.provider_code.as_str()),
+            ("providerHealthInsurer", self.health_insurer_code.as_str()),
+            ("clinic", self.clinic.as_str()),
+        ]
     }
 }
 
-// WSQ decoding temporarily disabled due to crate API mismatch
-fn wsq_to_png_base64(wsq_b64: &str) -> Result<String,String> {
-    Err("WSQ conversion not supported in current build".into())
+pub(crate) fn portal_context(app_handle: &AppHandle) -> Result<PortalContext, TotvsError> {
+    let config_value = patient::load_config_from_disk(app_handle)
+        .map_err(|e| TotvsError::config(format!("Falha ao ler configurações: {e}")))?;
+    let importer_cfg = get_cfg(&config_value)?;
+    let field = |key: &str| importer_cfg.get(key).and_then(|v| v.as_str()).map(String::from);
+
+    Ok(PortalContext {
+        credentials: totvs_credentials(&importer_cfg)?,
+        clinic: field("clinic").ok_or_else(|| TotvsError::config("Clínica não definida nas configurações."))?,
+        provider_code: field("provider_code").ok_or_else(|| TotvsError::config("Código do prestador não definido nas configurações."))?,
+        health_insurer_code: field("health_insurer_code").ok_or_else(|| TotvsError::config("Código da operadora não definido nas configurações."))?,
+    })
 }
 
 #[tauri::command]
 fn load_patients(app_handle: AppHandle) -> Result<Vec<patient::Patient>, String> {
-    patient::load_patients_from_disk(&app_handle).map_err(|e| e.to_string())
+    let patients = patient::load_patients_from_disk(&app_handle).map_err(|e| e.to_string())?;
+    Ok(patients.into_iter().map(photo_thumbnails::listed).collect())
 }
 
 #[tauri::command]
-fn save_patients(app_handle: AppHandle, patients: Vec<patient::Patient>) -> Result<(), String> {
-    patient::save_patients_to_disk(&app_handle, &patients).map_err(|e| e.to_string())
+fn get_patient(app_handle: AppHandle, id: u32) -> Result<patient::Patient, String> {
+    patient::get_patient(&app_handle, id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Paciente {} não encontrado.", id))
 }
 
 #[tauri::command]
-fn load_config(app_handle: AppHandle) -> Result<serde_json::Value, String> {
-    patient::load_config_from_disk(&app_handle).map_err(|e| e.to_string())
+fn add_patient(app_handle: AppHandle, patient: patient::Patient) -> Result<patient::Patient, PatientSaveError> {
+    Ok(patient::add_patient(&app_handle, patient)?)
 }
 
 #[tauri::command]
-fn save_config(app_handle: AppHandle, value: serde_json::Value) -> Result<(), String> {
-    patient::save_config_to_disk(&app_handle, &value).map_err(|e| e.to_string())
+fn update_patient(app_handle: AppHandle, patient: patient::Patient) -> Result<patient::Patient, PatientSaveError> {
+    Ok(patient::update_patient(&app_handle, patient)?)
 }
 
 #[tauri::command]
-async fn search_beneficiaries(app_handle: AppHandle, params: BeneficiarySearchParams) -> Result<serde_json::Value, String> {
-    // Carrega configurações salvas (contendo base_url, user, password)
-    let config_value = patient::load_config_from_disk(&app_handle)
-        .map_err(|e| format!("Falha ao ler configurações: {e}"))?;
+fn delete_patient(app_handle: AppHandle, id: u32, version: Option<u32>) -> Result<(), String> {
+    patient::delete_patient(&app_handle, id, version).map_err(|e| e.to_string())
+}
 
-    let importer_cfg = get_cfg(&config_value)?;
+#[tauri::command]
+fn find_patient_by_wallet(app_handle: AppHandle, wallet: String) -> Result<Option<patient::Patient>, String> {
+    patient::find_patient_by_wallet(&app_handle, &wallet).map_err(|e| e.to_string())
+}
 
-    let base_url = importer_cfg.get("base_url").and_then(|v| v.as_str())
-        .ok_or("Base URL não definida nas configurações.")?;
-    let user = importer_cfg.get("user").and_then(|v| v.as_str())
-        .ok_or("Usuário não definido nas configurações.")?;
-    let password = importer_cfg.get("password").and_then(|v| v.as_str())
-        .ok_or("Senha não definida nas configurações.")?;
+#[tauri::command]
+fn search_patients_by_name(app_handle: AppHandle, prefix: String) -> Result<Vec<patient::Patient>, String> {
+    let patients = patient::search_patients_by_name(&app_handle, &prefix).map_err(|e| e.to_string())?;
+    Ok(patients.into_iter().map(photo_thumbnails::listed).collect())
+}
 
-    let search_endpoint = "/dts/datasul-rest/resources/prg/hvp/v2/beneficiaries/subscriber";
-    let url = format!("{}{}", base_url.trim_end_matches('/'), search_endpoint);
+// One page of the patients matching `filter`, for lists too long to load at
+// once. Like the other lists, photos come as thumbnails only.
+#[tauri::command]
+fn query_patients(
+    app_handle: AppHandle,
+    filter: Option<patient::PatientFilter>,
+    sort: Option<patient::PatientSort>,
+    page: Option<u32>,
+    page_size: Option<u32>,
+) -> Result<patient::PatientPage, String> {
+    let page = patient::query_patient_page(
+        &app_handle,
+        &filter.unwrap_or_default(),
+        &sort.unwrap_or_default(),
+        page.unwrap_or(1),
+        page_size.unwrap_or(patient::DEFAULT_PAGE_SIZE),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(patient::PatientPage {
+        items: page.items.into_iter().map(photo_thumbnails::listed).collect(),
+        ..page
+    })
+}
 
-    // Monta parâmetros da query conforme implementação Python
-    let mut query_params = vec![
-        ("includeActive", "true"),
-        ("includeInactive", "false"),
-        ("includePending", "true"),
-        ("guarantor", params.guarantor.as_str()),
-        ("page", "1"),
-        ("expand", "person,dependents,dependents.person,cancellationReason,dependents.cancellationReason"),
-    ];
+#[tauri::command]
+fn save_patients(app_handle: AppHandle, patients: Vec<patient::Patient>) -> Result<(), PatientSaveError> {
+    Ok(patient::save_patients_to_disk(&app_handle, &patients)?)
+}
 
-    if let Some(modality) = params.modality.as_deref() {
-        query_params.push(("modality", modality));
-    }
-    if let Some(proposal) = params.proposal.as_deref() {
-        query_params.push(("proposal", proposal));
-    }
-    if let Some(contract) = params.contract.as_deref() {
-        query_params.push(("contract", contract));
-    }
+#[tauri::command]
+fn bulk_update_patients(
+    app_handle: AppHandle,
+    filter: patient::PatientFilter,
+    patch: patient::PatientPatch,
+) -> Result<patient::BulkUpdateSummary, String> {
+    patient::bulk_update(&app_handle, &filter, &patch).map_err(|e| e.to_string())
+}
 
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&url)
-        .basic_auth(user, Some(password))
-        .query(&query_params)
-        .header("Accept", "application/json")
-        .send()
-        .await
-        .map_err(|e| format!("Erro na requisição: {e}"))?;
+#[tauri::command]
+fn load_config(app_handle: AppHandle) -> Result<serde_json::Value, String> {
+    patient::load_config_from_disk(&app_handle).map_err(|e| e.to_string())
+}
 
-    if !response.status().is_success() {
-        return Err(format!("Falha na requisição: {}", response.status()));
-    }
+#[tauri::command]
+fn save_config(app_handle: AppHandle, value: serde_json::Value) -> Result<(), String> {
+    patient::save_config_to_disk(&app_handle, &value).map_err(|e| e.to_string())
+}
 
-    let json: serde_json::Value = response
-        .json()
-        .await
-        .map_err(|e| format!("Falha ao decodificar JSON: {e}"))?;
+// Shared by the search command and the family import
+pub(crate) async fn fetch_beneficiaries(app_handle: &AppHandle, params: &BeneficiarySearchParams) -> Result<Vec<Beneficiary>, TotvsError> {
+    // Carrega configurações salvas (contendo base_url, user, password)
+    let config_value = patient::load_config_from_disk(app_handle)
+        .map_err(|e| TotvsError::config(format!("Falha ao ler configurações: {e}")))?;
 
-    // Retorna o array "items" ou lista vazia se não existir
-    Ok(json.get("items").cloned().unwrap_or_else(|| serde_json::Value::Array(vec![])))
+    let importer_cfg = get_cfg(&config_value)?;
+
+    let credentials = totvs_credentials(&importer_cfg)?;
+
+    let url = totvs_endpoints::url(app_handle, &credentials.base_url, "beneficiary_search", None);
+    let changed_after_param = importer_cfg
+        .get("sync_changed_after_param")
+        .and_then(|v| v.as_str())
+        .unwrap_or("changedAfter");
+
+    let first_page = params.page.unwrap_or(1).max(1);
+    let page_size = params.page_size.map(|n| n.to_string());
+    let client = totvs_http::client(app_handle)?;
+    let mut items = Vec::new();
+
+    for page in first_page..first_page.saturating_add(MAX_SEARCH_PAGES) {
+        let page_str = page.to_string();
+        // Monta parâmetros da query conforme implementação Python
+        let mut query_params = vec![
+            ("includeActive", "true"),
+            ("includeInactive", "false"),
+            ("includePending", "true"),
+            ("guarantor", params.guarantor.as_str()),
+            ("page", page_str.as_str()),
+            ("expand", "person,dependents,dependents.person,cancellationReason,dependents.cancellationReason"),
+        ];
+
+        if let Some(page_size) = page_size.as_deref() {
+            query_params.push(("pageSize", page_size));
+        }
+        if let Some(modality) = params.modality.as_deref() {
+            query_params.push(("modality", modality));
+        }
+        if let Some(proposal) = params.proposal.as_deref() {
+            query_params.push(("proposal", proposal));
+        }
+        if let Some(contract) = params.contract.as_deref() {
+            query_params.push(("contract", contract));
+        }
+        if let Some(changed_after) = params.changed_after.as_deref() {
+            query_params.push((changed_after_param, changed_after));
+        }
+
+        let cache_key = totvs_cache::key("beneficiary_search", &url, &query_params);
+        let (json, from_cache) = match totvs_cache::get(app_handle, &cache_key) {
+            Some(json) => (json, true),
+            None => {
+                let response = totvs_endpoints::send(app_handle, "beneficiary_search", &credentials, None, |url| {
+                    client
+                        .get(url)
+                        .query(&query_params)
+                        .header("Accept", "application/json")
+                })
+                .await?;
+
+                if !response.status().is_success() {
+                    return Err(TotvsError::status(response.status(), format!("Falha na requisição (página {}): {}", page, response.status())));
+                }
+
+                let json: serde_json::Value = response
+                    .json()
+                    .await
+                    .map_err(|e| TotvsError::parse(format!("Falha ao decodificar JSON: {e}")))?;
+                totvs_cache::put(app_handle, &cache_key, &json);
+                totvs_mirror::save_search_page(app_handle, &json);
+                (json, false)
+            }
+        };
+
+        if let Some(page_items) = json.get("items").and_then(|v| v.as_array()) {
+            let path = format!("página {}: items", page);
+            let parsed = models::parse_list(page_items.clone(), &path, Beneficiary::from_value)?;
+            items.extend(parsed.into_iter().map(|b| Beneficiary { from_cache, ..b }));
+        }
+        let has_next = json.get("hasNext").and_then(|v| v.as_bool()).unwrap_or(false);
+
+        if !params.all_pages {
+            break;
+        }
+        let _ = app_handle.emit(
+            "beneficiary-search-progress",
+            BeneficiarySearchProgress { page, fetched: items.len(), has_next },
+        );
+        if !has_next {
+            break;
+        }
+    }
+
+    Ok(items)
 }
 
 #[tauri::command]
-async fn get_beneficiary_details(app_handle: AppHandle, card_number: String) -> Result<serde_json::Value, String> {
+async fn search_beneficiaries(app_handle: AppHandle, params: BeneficiarySearchParams) -> Result<Vec<Beneficiary>, TotvsError> {
+    fetch_beneficiaries(&app_handle, &params).await
+}
+
+// Shared by the details command and the one-shot import
+pub(crate) async fn fetch_beneficiary_details(app_handle: &AppHandle, card_number: &str) -> Result<Beneficiary, TotvsError> {
     // Carrega configurações salvas (contendo base_url, user, password)
-    let config_value = patient::load_config_from_disk(&app_handle)
-        .map_err(|e| format!("Falha ao ler configurações: {e}"))?;
+    let config_value = patient::load_config_from_disk(app_handle)
+        .map_err(|e| TotvsError::config(format!("Falha ao ler configurações: {e}")))?;
 
     let importer_cfg = get_cfg(&config_value)?;
 
-    let base_url = importer_cfg.get("base_url").and_then(|v| v.as_str())
-        .ok_or("Base URL não definida nas configurações.")?;
-    let user = importer_cfg.get("user").and_then(|v| v.as_str())
-        .ok_or("Usuário não definido nas configurações.")?;
-    let password = importer_cfg.get("password").and_then(|v| v.as_str())
-        .ok_or("Senha não definida nas configurações.")?;
+    let credentials = totvs_credentials(&importer_cfg)?;
     let clinic = importer_cfg.get("clinic").and_then(|v| v.as_str())
-        .ok_or("Clínica não definida nas configurações.")?;
+        .ok_or_else(|| TotvsError::config("Clínica não definida nas configurações."))?;
     let provider_code = importer_cfg.get("provider_code").and_then(|v| v.as_str())
-        .ok_or("Código do prestador não definido nas configurações.")?;
+        .ok_or_else(|| TotvsError::config("Código do prestador não definido nas configurações."))?;
     let health_insurer_code = importer_cfg.get("health_insurer_code").and_then(|v| v.as_str())
-        .ok_or("Código da operadora não definido nas configurações.")?;
+        .ok_or_else(|| TotvsError::config("Código da operadora não definido nas configurações."))?;
 
-    let details_endpoint = format!("/dts/datasul-rest/resources/prg/portprest/v1/checkin/beneficiaries/{}", card_number);
-    let url = format!("{}{}", base_url.trim_end_matches('/'), details_endpoint);
+    let url = totvs_endpoints::url(app_handle, &credentials.base_url, "beneficiary_details", Some(card_number));
 
     // Monta parâmetros da query conforme implementação Python
     let query_params = vec![
@@ -148,61 +381,66 @@ async fn get_beneficiary_details(app_handle: AppHandle, card_number: String) ->
         ("clinic", clinic),
     ];
 
-    println!("URL Detalhes: {}", url);
-    println!("Query params: {:?}", query_params);
-    println!("Header clinic: {}", clinic);
-
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&url)
-        .basic_auth(user, Some(password))
-        .query(&query_params)
-        .header("Accept", "application/json")
-        .header("x-totvs-hgp-portal-prestador-clinic", clinic)
-        .send()
-        .await
-        .map_err(|e| format!("Erro na requisição: {e}"))?;
+    tracing::debug!(target: "totvs", "URL Detalhes: {} params={:?}", url, query_params);
+
+    let cache_key = totvs_cache::key("beneficiary_details", &url, &query_params);
+    if let Some(json) = totvs_cache::get(app_handle, &cache_key) {
+        let beneficiary = Beneficiary::from_value(json, "beneficiário")?;
+        return Ok(Beneficiary { from_cache: true, ..beneficiary });
+    }
+
+    let client = totvs_http::client(app_handle)?;
+    let response = totvs_endpoints::send(app_handle, "beneficiary_details", &credentials, Some(card_number), |url| {
+        client
+            .get(url)
+            .query(&query_params)
+            .header("Accept", "application/json")
+            .header("x-totvs-hgp-portal-prestador-clinic", clinic)
+    })
+    .await?;
 
     if !response.status().is_success() {
         let status_code = response.status();
         let txt = response.text().await.unwrap_or_default();
-        println!("Erro detalhes status={} body={}", status_code, txt);
-        return Err(format!("Falha na requisição: {}", status_code));
+        tracing::warn!(target: "totvs", "Erro detalhes status={} body={}", status_code, totvs_log::redact_body(txt.as_bytes()));
+        return Err(TotvsError::status(status_code, format!("Falha na requisição: {}", status_code)));
     }
 
     let json: serde_json::Value = response
         .json()
         .await
-        .map_err(|e| format!("Falha ao decodificar JSON: {e}"))?;
+        .map_err(|e| TotvsError::parse(format!("Falha ao decodificar JSON: {e}")))?;
 
-    Ok(json)
+    let beneficiary = Beneficiary::from_value(json.clone(), "beneficiário")?;
+    totvs_cache::put(app_handle, &cache_key, &json);
+    totvs_mirror::save(app_handle, card_number, "details", &json);
+    Ok(beneficiary)
 }
 
 #[tauri::command]
-async fn get_fingerprints(app_handle: AppHandle, card_number: String) -> Result<serde_json::Value, String> {
+async fn get_beneficiary_details(app_handle: AppHandle, card_number: String) -> Result<Beneficiary, TotvsError> {
+    fetch_beneficiary_details(&app_handle, &card_number).await
+}
+
+// Shared by the fingerprints command and the one-shot import
+pub(crate) async fn fetch_fingerprints(app_handle: &AppHandle, card_number: &str) -> Result<Vec<Fingerprint>, TotvsError> {
     // Carrega configurações salvas (contendo base_url, user, password)
-    let config_value = patient::load_config_from_disk(&app_handle)
-        .map_err(|e| format!("Falha ao ler configurações: {e}"))?;
+    let config_value = patient::load_config_from_disk(app_handle)
+        .map_err(|e| TotvsError::config(format!("Falha ao ler configurações: {e}")))?;
 
     let importer_cfg = get_cfg(&config_value)?;
 
-    let base_url = importer_cfg.get("base_url").and_then(|v| v.as_str())
-        .ok_or("Base URL não definida nas configurações.")?;
-    let user = importer_cfg.get("user").and_then(|v| v.as_str())
-        .ok_or("Usuário não definido nas configurações.")?;
-    let password = importer_cfg.get("password").and_then(|v| v.as_str())
-        .ok_or("Senha não definida nas configurações.")?;
+    let credentials = totvs_credentials(&importer_cfg)?;
     let clinic = importer_cfg.get("clinic").and_then(|v| v.as_str())
-        .ok_or("Clínica não definida nas configurações.")?;
+        .ok_or_else(|| TotvsError::config("Clínica não definida nas configurações."))?;
 
-    let fingerprint_endpoint = format!("/dts/datasul-rest/resources/prg/portprest/v1/checkin/beneficiaries/{}/fingerPrints", card_number);
-    let url = format!("{}{}", base_url.trim_end_matches('/'), fingerprint_endpoint);
+    let url = totvs_endpoints::url(app_handle, &credentials.base_url, "fingerprints", Some(card_number));
     
     // Obter query params necessários
     let provider_code = importer_cfg.get("provider_code").and_then(|v| v.as_str())
-        .ok_or("Código do prestador não definido nas configurações.")?;
+        .ok_or_else(|| TotvsError::config("Código do prestador não definido nas configurações."))?;
     let health_insurer_code = importer_cfg.get("health_insurer_code").and_then(|v| v.as_str())
-        .ok_or("Código da operadora não definido nas configurações.")?;
+        .ok_or_else(|| TotvsError::config("Código da operadora não definido nas configurações."))?;
     
     // Monta parâmetros da query conforme implementação Python
     let query_params = vec![
@@ -211,56 +449,56 @@ async fn get_fingerprints(app_handle: AppHandle, card_number: String) -> Result<
         ("clinic", clinic),
     ];
     
-    println!("URL Digitais: {}", url);
-    println!("Query params digitais: {:?}", query_params);
-
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&url)
-        .basic_auth(user, Some(password))
-        .query(&query_params)
-        .header("Accept", "application/json")
-        .header("x-totvs-hgp-portal-prestador-clinic", clinic)
-        .send()
-        .await
-        .map_err(|e| format!("Erro na requisição: {e}"))?;
+    tracing::debug!(target: "totvs", "URL Digitais: {} params={:?}", url, query_params);
+
+    let cache_key = totvs_cache::key("fingerprints", &url, &query_params);
+    if let Some(json) = totvs_cache::get(app_handle, &cache_key) {
+        let items_arr = json.as_array().cloned().unwrap_or_default();
+        let fingerprints = models::parse_list(items_arr, "items", Fingerprint::from_value)?;
+        return Ok(fingerprints.into_iter().map(|f| Fingerprint { from_cache: true, ..f }).collect());
+    }
+
+    let client = totvs_http::client(app_handle)?;
+    let response = totvs_endpoints::send(app_handle, "fingerprints", &credentials, Some(card_number), |url| {
+        client
+            .get(url)
+            .query(&query_params)
+            .header("Accept", "application/json")
+            .header("x-totvs-hgp-portal-prestador-clinic", clinic)
+    })
+    .await?;
 
     if !response.status().is_success() {
-        return Err(format!("Falha na requisição: {}", response.status()));
+        return Err(TotvsError::status(response.status(), format!("Falha na requisição: {}", response.status())));
     }
 
-    let json: serde_json::Value = response
-        .json()
-        .await
-        .map_err(|e| format!("Falha ao decodificar JSON: {e}"))?;
+    let json = streamed_download::download_json(app_handle, response, &format!("fingerprints-{}.json", card_number)).await?;
+    totvs_mirror::save(app_handle, card_number, "fingerprints", &json);
 
-    println!("Resposta JSON de digitais: {:?}", json);
+    tracing::debug!(target: "totvs", "Digitais recebidas: {} item(ns)", json.get("items").and_then(|v| v.as_array()).map_or(0, |a| a.len()));
     
     // Retorna o array "items" ou lista vazia se não existir
     let items_arr = json.get("items").and_then(|v| v.as_array()).cloned().unwrap_or_default();
-    Ok(serde_json::Value::Array(items_arr))
+    let fingerprints = models::parse_list(items_arr.clone(), "items", Fingerprint::from_value)?;
+    totvs_cache::put(app_handle, &cache_key, &serde_json::Value::Array(items_arr));
+    Ok(fingerprints)
 }
 
-#[tauri::command]
-async fn get_facial_biometry(app_handle: AppHandle, card_number: String) -> Result<String, String> {
+// Shared by the photo command, the imports and the batch photo refresh
+pub(crate) async fn fetch_facial_photo(app_handle: &AppHandle, card_number: &str) -> Result<FacialPhoto, TotvsError> {
     // Carrega configurações salvas (contendo base_url, user, password)
-    let config_value = patient::load_config_from_disk(&app_handle)
-        .map_err(|e| format!("Falha ao ler configurações: {e}"))?;
+    let config_value = patient::load_config_from_disk(app_handle)
+        .map_err(|e| TotvsError::config(format!("Falha ao ler configurações: {e}")))?;
 
     let importer_cfg = get_cfg(&config_value)?;
 
-    let base_url = importer_cfg.get("base_url").and_then(|v| v.as_str())
-        .ok_or("Base URL não definida nas configurações.")?;
-    let user = importer_cfg.get("user").and_then(|v| v.as_str())
-        .ok_or("Usuário não definido nas configurações.")?;
-    let password = importer_cfg.get("password").and_then(|v| v.as_str())
-        .ok_or("Senha não definida nas configurações.")?;
+    let credentials = totvs_credentials(&importer_cfg)?;
     let clinic = importer_cfg.get("clinic").and_then(|v| v.as_str())
-        .ok_or("Clínica não definida nas configurações.")?;
+        .ok_or_else(|| TotvsError::config("Clínica não definida nas configurações."))?;
     let provider_code = importer_cfg.get("provider_code").and_then(|v| v.as_str())
-        .ok_or("Código do prestador não definido nas configurações.")?;
+        .ok_or_else(|| TotvsError::config("Código do prestador não definido nas configurações."))?;
     let health_insurer_code = importer_cfg.get("health_insurer_code").and_then(|v| v.as_str())
-        .ok_or("Código da operadora não definido nas configurações.")?;
+        .ok_or_else(|| TotvsError::config("Código da operadora não definido nas configurações."))?;
 
     // Monta parâmetros da query conforme implementação Python
     let query_params = vec![
@@ -269,34 +507,68 @@ async fn get_facial_biometry(app_handle: AppHandle, card_number: String) -> Resu
         ("clinic", clinic),
     ];
     
-    let photo_endpoint = format!("/dts/datasul-rest/resources/prg/portprest/v1/checkin/beneficiaries/{}/photo", card_number);
-    let url = format!("{}{}", base_url.trim_end_matches('/'), photo_endpoint);
+    let url = totvs_endpoints::url(app_handle, &credentials.base_url, "facial_photo", Some(card_number));
     
-    println!("URL Foto: {}", url);
-    println!("Query params foto: {:?}", query_params);
-
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&url)
-        .basic_auth(user, Some(password))
-        .query(&query_params)
-        .header("Accept", "application/json")
-        .header("x-totvs-hgp-portal-prestador-clinic", clinic)
-        .send()
-        .await
-        .map_err(|e| format!("Erro na requisição: {e}"))?;
+    tracing::debug!(target: "totvs", "URL Foto: {} params={:?}", url, query_params);
+
+    let cache_key = totvs_cache::key("facial_photo", &url, &query_params);
+    if let Some(photo) = totvs_cache::get(app_handle, &cache_key).and_then(|v| v.as_str().map(String::from)) {
+        return Ok(FacialPhoto { photo, from_cache: true, file: None });
+    }
+
+    let client = totvs_http::client(app_handle)?;
+    let response = totvs_endpoints::send(app_handle, "facial_photo", &credentials, Some(card_number), |url| {
+        client
+            .get(url)
+            .query(&query_params)
+            .header("Accept", "application/json")
+            .header("x-totvs-hgp-portal-prestador-clinic", clinic)
+    })
+    .await?;
 
     if !response.status().is_success() {
-        return Err(format!("Falha na requisição: {}", response.status()));
+        return Err(TotvsError::status(response.status(), format!("Falha na requisição: {}", response.status())));
     }
 
-    // A resposta deve ser um base64 da imagem
-    let photo_base64: String = response
-        .text()
-        .await
-        .map_err(|e| format!("Falha ao obter dados da foto: {e}"))?;
+    // A resposta deve ser um base64 da imagem; fotos grandes vão para disco
+    let name = format!("facial_photo-{}", card_number);
+    let photo = match streamed_download::download_base64(app_handle, response, &name).await? {
+        streamed_download::Downloaded::Memory(data) => {
+            let photo_base64 = b64::STANDARD.encode(data);
+            totvs_cache::put(app_handle, &cache_key, &serde_json::Value::String(photo_base64.clone()));
+            FacialPhoto { photo: photo_base64, from_cache: false, file: None }
+        }
+        streamed_download::Downloaded::File(path) => FacialPhoto {
+            photo: String::new(),
+            from_cache: false,
+            file: Some(path.to_string_lossy().into_owned()),
+        },
+    };
+    // The photo isn't buffered as received, so the mirror gets it re-encoded.
+    // The file is left for the caller, which removes it once read.
+    if totvs_mirror::enabled(app_handle) {
+        let base64 = match &photo.file {
+            Some(path) => streamed_download::file_base64(std::path::Path::new(path)),
+            None => Ok(photo.photo.clone()),
+        };
+        match base64 {
+            Ok(base64) => totvs_mirror::save(app_handle, card_number, "photo", &serde_json::json!({ "photo": base64 })),
+            Err(e) => tracing::warn!("Foto da carteira {} não espelhada: {}", card_number, e),
+        }
+    }
+    Ok(photo)
+}
 
-    Ok(photo_base64)
+#[tauri::command]
+async fn get_fingerprints(app_handle: AppHandle, card_number: String) -> Result<Vec<Fingerprint>, TotvsError> {
+    let fingerprints = fetch_fingerprints(&app_handle, &card_number).await?;
+    Ok(streamed_download::spill_fingerprints(&app_handle, &card_number, fingerprints))
+}
+
+#[tauri::command]
+async fn get_facial_biometry(app_handle: AppHandle, card_number: String) -> Result<FacialPhoto, TotvsError> {
+    let photo = fetch_facial_photo(&app_handle, &card_number).await?;
+    Ok(FacialPhoto { photo: photo_refresh::normalize_photo(&photo.photo), ..photo })
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -305,31 +577,197 @@ pub fn run() {
     let hotkey_manager = Mutex::new(hotkey::HotkeyManager::new());
     let biometry_server_state = Arc::new(Mutex::new(biometry_server::BiometryServerState::new()));
     let webcam_emulator = Arc::new(Mutex::new(webcam_emulator::WebcamEmulator::new()));
+    let control_interface_state = Arc::new(Mutex::new(control_interface::ControlInterfaceState::new()));
+    let multi_identity_state = Arc::new(Mutex::new(multi_identity::MultiIdentityState::new()));
+    let biometry_approval_queue = Arc::new(Mutex::new(biometry_approval::ApprovalQueue::new()));
+    let smartcard_state = Arc::new(Mutex::new(smartcard::SmartCardState::new()));
+    let circuit_breaker_state = Arc::new(Mutex::new(circuit_breaker::CircuitBreakerState::new()));
+    let totvs_token_cache = Arc::new(tokio::sync::Mutex::new(totvs_auth::TokenCache::new()));
+    let totvs_response_cache = Arc::new(Mutex::new(totvs_cache::TotvsCache::new()));
+    let totvs_log_buffer = Arc::new(Mutex::new(totvs_log::TotvsLogBuffer::new()));
+    let import_jobs = Arc::new(Mutex::new(import_jobs::ImportJobs::new()));
+    let totvs_rate_limiter = Arc::new(Mutex::new(totvs_http::RateLimiter::new()));
+    let totvs_endpoint_versions = Arc::new(Mutex::new(totvs_endpoints::EndpointVersions::new()));
+    let mock_totvs_state = Arc::new(Mutex::new(mock_totvs::MockTotvsState::new()));
+    let patient_api_state = Arc::new(Mutex::new(patient_api::PatientApiState::new()));
+    let totvs_replay_state = Arc::new(Mutex::new(totvs_replay::ReplayState::new()));
+    let patient_history = Arc::new(Mutex::new(patient_history::PatientHistory::new()));
+    let auto_send_state = Arc::new(Mutex::new(auto_send::AutoSendState::new()));
     
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_global_shortcut::Builder::new().build())
         .manage(hotkey_manager)
         .manage(biometry_server_state)
         .manage(webcam_emulator)
+        .manage(control_interface_state)
+        .manage(multi_identity_state)
+        .manage(circuit_breaker_state)
+        .manage(totvs_token_cache)
+        .manage(totvs_response_cache)
+        .manage(totvs_log_buffer)
+        .manage(import_jobs)
+        .manage(totvs_rate_limiter)
+        .manage(totvs_endpoint_versions)
+        .manage(mock_totvs_state)
+        .manage(patient_api_state)
+        .manage(totvs_replay_state)
+        .manage(biometry_approval_queue)
+        .manage(smartcard_state)
+        .manage(patient_history)
+        .manage(auto_send_state)
+        .setup(|app| {
+            totvs_log::init(app.handle());
+            hotkey::watch_process(app.handle().clone());
+            data_watch::watch_data_dir(app.handle().clone());
+            patient_cleanup::start_scheduler(app.handle().clone());
+            streamed_download::clear_downloads(app.handle());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             load_patients,
+            get_patient,
+            add_patient,
+            update_patient,
+            delete_patient,
+            find_patient_by_wallet,
+            search_patients_by_name,
+            query_patients,
             save_patients,
+            bulk_update_patients,
+            patient_tags::add_patient_tags,
+            patient_tags::remove_patient_tags,
+            patient_tags::list_tags,
+            patient_tags::rename_tag,
+            patient_tags::filter_patients,
+            patient_audit::get_audit_log,
+            patient_history::undo_patient_change,
+            patient_history::redo_patient_change,
+            patient_history::get_patient_history,
+            patient_cleanup::run_cleanup,
+            patient_duplicates::find_duplicate_patients,
+            patient_duplicates::merge_patients,
+            patient_validation::validate_patient,
+            streamed_download::read_downloaded_file,
+            photo_thumbnails::get_patient_photo,
+            workspaces::list_workspaces,
+            workspaces::create_workspace,
+            workspaces::switch_workspace,
+            workspaces::clone_workspace,
+            workspaces::delete_workspace,
             load_config,
             save_config,
             hotkey::start_hotkey,
             hotkey::stop_hotkey,
             hotkey::check_hotkey_status,
+            hotkey::register_hotkey_binding,
+            hotkey::list_hotkey_bindings,
+            hotkey::unregister_hotkey_binding,
+            hotkey::load_send_queue,
+            hotkey::get_send_queue,
+            hotkey::reorder_send_queue,
+            hotkey::clear_send_queue,
+            auto_send::start_auto_send,
+            auto_send::stop_auto_send,
+            auto_send::get_auto_send_status,
             hotkey::diagnose_hotkey_system,
+            ahk_template::check_ahk_script_template,
+            keystroke::check_input_permission,
             biometry_server::start_biometry_server,
             biometry_server::stop_biometry_server,
             biometry_server::check_biometry_server_status,
+            biometry_server::set_biometry_rate_limit,
+            biometry_server::get_biometry_openapi,
+            biometry_approval::set_biometry_manual_approval,
+            biometry_approval::list_pending_biometry_requests,
+            biometry_approval::resolve_biometry_request,
             webcam_emulator::start_webcam_emulator,
             webcam_emulator::stop_webcam_emulator,
             webcam_emulator::check_webcam_emulator_status,
+            webcam_emulator::start_webcam_patient_photo,
+            webcam_emulator::push_webcam_frame,
             search_beneficiaries,
             get_beneficiary_details,
             get_fingerprints,
-            get_facial_biometry
+            get_facial_biometry,
+            emulator_profile::export_emulator_profile,
+            emulator_profile::import_emulator_profile,
+            control_interface::start_control_interface,
+            control_interface::stop_control_interface,
+            control_interface::check_control_interface_status,
+            notifications::notify_emulator_event,
+            card_format::list_card_formats,
+            card_format::validate_card,
+            card_format::preview_card_text,
+            multi_identity::start_multi_identity,
+            multi_identity::stop_multi_identity,
+            multi_identity::check_multi_identity_status,
+            resource_monitor::get_resource_usage,
+            fingerprint::convert_all_fingerprints,
+            fingerprint::get_fingerprint_preview,
+            fingerprint::render_fingerprint,
+            fingerprint::convert_wsq_to_png,
+            fingerprint::encode_fingerprint_wsq,
+            minutiae::describe_fingerprint_template,
+            fingerprint_quality::score_patient_fingerprints,
+            circuit_breaker::get_totvs_circuit_status,
+            circuit_breaker::reset_totvs_circuits,
+            data_lock::get_data_lock_status,
+            data_lock::take_over_data_dir,
+            data_backups::recover_patients,
+            portable::get_portable_status,
+            portable::set_portable_mode,
+            config_assistant::fetch_totvs_options,
+            smartcard::start_smartcard_emulator,
+            smartcard::stop_smartcard_emulator,
+            smartcard::check_smartcard_emulator_status,
+            attachments::add_patient_attachment,
+            attachments::list_patient_attachments,
+            attachments::remove_patient_attachment,
+            attachments::open_patient_attachment,
+            photo_refresh::refresh_imported_patient_photos,
+            fingerprint_prefetch::prefetch_fingerprints,
+            verification_audit::get_patient_verification_history,
+            verification_audit::clear_patient_verification_history,
+            setup_wizard::check_setup_totvs,
+            setup_wizard::check_setup_autohotkey,
+            setup_wizard::check_setup_python,
+            setup_wizard::check_setup_virtual_camera,
+            setup_wizard::check_setup_ports,
+            setup_wizard::run_setup_checks,
+            beneficiary_import::import_beneficiary,
+            beneficiary_import::import_guarantor,
+            connection_test::test_totvs_connection,
+            totvs_cache::clear_totvs_cache,
+            checkin::perform_checkin,
+            eligibility::check_eligibility,
+            authorizations::list_authorizations,
+            schema_drift::check_schema_drift,
+            fingerprint_enrollment::enroll_fingerprints,
+            totvs_profiles::list_totvs_profiles,
+            totvs_profiles::create_totvs_profile,
+            totvs_profiles::switch_totvs_profile,
+            totvs_log::get_recent_totvs_logs,
+            import_jobs::cancel_import,
+            import_report::export_import_report,
+            spreadsheet_import::import_patients_from_file,
+            backup::create_backup,
+            backup::restore_backup,
+            ansi_nist::export_ansi_nist,
+            totvs_export::export_patients_totvs,
+            totvs_mirror::reimport_from_mirror,
+            patient_diff::compare_patient_with_totvs,
+            config_assistant::list_health_insurers,
+            config_assistant::list_providers,
+            config_assistant::list_clinics,
+            mock_totvs::start_mock_totvs_server,
+            mock_totvs::stop_mock_totvs_server,
+            patient_api::start_patient_api,
+            patient_api::stop_patient_api,
+            totvs_replay::set_totvs_traffic_mode,
+            totvs_replay::get_totvs_traffic_mode,
+            beneficiary_sync::sync_beneficiaries
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
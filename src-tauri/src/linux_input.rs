@@ -0,0 +1,301 @@
+use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use evdev::{AttributeSet, EventType, InputEvent, Key};
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::keystroke::{KeystrokeMode, KeystrokeTiming, MODIFIER_RELEASE_DELAY};
+
+// Typing on Linux, where the automation runs on VMs: xdotool in an X11
+// session, otherwise a virtual keyboard on /dev/uinput (Wayland, or no
+// display at all). uinput sends key codes, so the text goes through a US
+// layout. VIRTUAL_IO_HUB_LINUX_INPUT=xdotool|uinput forces a method.
+const ENV_VAR: &str = "VIRTUAL_IO_HUB_LINUX_INPUT";
+const UINPUT_PATH: &str = "/dev/uinput";
+// Time the session takes to pick up a new input device; keys sent before are lost
+const DEVICE_SETTLE_DELAY: Duration = Duration::from_millis(300);
+// Between press and release, and between keys without a configured delay
+const UINPUT_KEY_DELAY: Duration = Duration::from_millis(2);
+
+// Created on first use and kept, so only the first card waits for it
+static DEVICE: Mutex<Option<VirtualDevice>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputMethod {
+    Xdotool,
+    Uinput,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UinputAccess {
+    Available,
+    Missing,
+    PermissionDenied,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Capabilities {
+    // x11, wayland or none
+    pub session: &'static str,
+    pub xdotool: Option<String>,
+    pub uinput: UinputAccess,
+    // What typing will use; `error` says why nothing can
+    pub method: Option<InputMethod>,
+    pub error: Option<String>,
+    pub global_hotkeys: bool,
+}
+
+fn session() -> &'static str {
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some()
+        || std::env::var("XDG_SESSION_TYPE").is_ok_and(|t| t.eq_ignore_ascii_case("wayland"));
+    if wayland {
+        "wayland"
+    } else if std::env::var_os("DISPLAY").is_some() {
+        "x11"
+    } else {
+        "none"
+    }
+}
+
+fn find_xdotool() -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths).map(|dir| dir.join("xdotool")).find(|p| p.is_file())
+}
+
+fn uinput_access() -> UinputAccess {
+    if DEVICE.lock().map(|device| device.is_some()).unwrap_or(false) {
+        return UinputAccess::Available;
+    }
+    match OpenOptions::new().write(true).open(UINPUT_PATH) {
+        Ok(_) => UinputAccess::Available,
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => UinputAccess::PermissionDenied,
+        Err(_) => UinputAccess::Missing,
+    }
+}
+
+fn uinput_error(access: UinputAccess) -> String {
+    match access {
+        UinputAccess::PermissionDenied => format!(
+            "Sem permissão de escrita em {}. Adicione o usuário ao grupo 'input', crie /etc/udev/rules.d/99-uinput.rules com \
+             KERNEL==\"uinput\", GROUP=\"input\", MODE=\"0660\" e entre novamente na sessão.",
+            UINPUT_PATH
+        ),
+        _ => format!("{} não existe. Carregue o módulo com 'sudo modprobe uinput'.", UINPUT_PATH),
+    }
+}
+
+fn choose(session: &str, xdotool: Option<&Path>, uinput: UinputAccess) -> Result<InputMethod, String> {
+    let uinput_method = || match uinput {
+        UinputAccess::Available => Ok(InputMethod::Uinput),
+        access => Err(uinput_error(access)),
+    };
+    let forced = std::env::var(ENV_VAR).map(|v| v.trim().to_ascii_lowercase()).unwrap_or_default();
+    match forced.as_str() {
+        "xdotool" => xdotool
+            .map(|_| InputMethod::Xdotool)
+            .ok_or_else(|| "xdotool não encontrado no PATH. Instale com 'sudo apt install xdotool'.".to_string()),
+        "uinput" => uinput_method(),
+        _ if session == "x11" && xdotool.is_some() => Ok(InputMethod::Xdotool),
+        _ => uinput_method().map_err(|e| {
+            if session == "x11" {
+                format!("xdotool não encontrado no PATH e {}", e)
+            } else {
+                e
+            }
+        }),
+    }
+}
+
+// Global shortcuts grab keys from the X server; Wayland doesn't let an app do it
+pub fn check_global_hotkeys() -> Result<(), String> {
+    match session() {
+        "x11" => Ok(()),
+        "wayland" => Err("Hotkeys globais não funcionam em sessões Wayland. Use uma sessão X11 ou dispare a digitação pela interface de controle.".into()),
+        _ => Err("Nenhuma sessão gráfica (DISPLAY não definido); hotkeys globais precisam de um servidor X. Dispare a digitação pela interface de controle.".into()),
+    }
+}
+
+pub fn detect() -> Capabilities {
+    let session = session();
+    let xdotool = find_xdotool();
+    let uinput = uinput_access();
+    let method = choose(session, xdotool.as_deref(), uinput);
+    Capabilities {
+        session,
+        xdotool: xdotool.map(|p| p.display().to_string()),
+        uinput,
+        method: method.as_ref().ok().copied(),
+        error: method.err(),
+        global_hotkeys: check_global_hotkeys().is_ok(),
+    }
+}
+
+pub fn type_text(text: &str, mode: KeystrokeMode, timing: KeystrokeTiming) -> Result<(), String> {
+    let session = session();
+    let xdotool = find_xdotool();
+    match choose(session, xdotool.as_deref(), uinput_access())? {
+        InputMethod::Xdotool => type_xdotool(xdotool.as_deref().unwrap_or(Path::new("xdotool")), text, mode, timing),
+        InputMethod::Uinput => type_uinput(text, mode, timing),
+    }
+}
+
+fn initial_delay(timing: KeystrokeTiming) -> Duration {
+    MODIFIER_RELEASE_DELAY.max(Duration::from_millis(timing.initial_delay_ms.into()))
+}
+
+// Text runs are typed; in numpad mode digits go as keypad keys
+fn type_xdotool(xdotool: &Path, text: &str, mode: KeystrokeMode, timing: KeystrokeTiming) -> Result<(), String> {
+    let mut runs: Vec<(bool, String)> = Vec::new();
+    for c in text.chars() {
+        let keypad = mode == KeystrokeMode::Numpad && c.is_ascii_digit();
+        match runs.last_mut() {
+            Some((run_keypad, run)) if *run_keypad == keypad => run.push(c),
+            _ => runs.push((keypad, c.to_string())),
+        }
+    }
+
+    thread::sleep(initial_delay(timing));
+    let delay = timing.key_delay_ms.to_string();
+    for (keypad, run) in runs {
+        let mut command = Command::new(xdotool);
+        if keypad {
+            command
+                .args(["key", "--clearmodifiers", "--delay", delay.as_str()])
+                .args(run.chars().map(|d| format!("KP_{}", d)));
+        } else {
+            command.args(["type", "--clearmodifiers", "--delay", delay.as_str(), "--", run.as_str()]);
+        }
+        let output = command.output().map_err(|e| format!("Falha ao executar xdotool: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("xdotool falhou: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+    }
+    Ok(())
+}
+
+const LETTERS: [Key; 26] = [
+    Key::KEY_A, Key::KEY_B, Key::KEY_C, Key::KEY_D, Key::KEY_E, Key::KEY_F, Key::KEY_G,
+    Key::KEY_H, Key::KEY_I, Key::KEY_J, Key::KEY_K, Key::KEY_L, Key::KEY_M, Key::KEY_N,
+    Key::KEY_O, Key::KEY_P, Key::KEY_Q, Key::KEY_R, Key::KEY_S, Key::KEY_T, Key::KEY_U,
+    Key::KEY_V, Key::KEY_W, Key::KEY_X, Key::KEY_Y, Key::KEY_Z,
+];
+const DIGITS: [Key; 10] = [
+    Key::KEY_0, Key::KEY_1, Key::KEY_2, Key::KEY_3, Key::KEY_4,
+    Key::KEY_5, Key::KEY_6, Key::KEY_7, Key::KEY_8, Key::KEY_9,
+];
+const KEYPAD_DIGITS: [Key; 10] = [
+    Key::KEY_KP0, Key::KEY_KP1, Key::KEY_KP2, Key::KEY_KP3, Key::KEY_KP4,
+    Key::KEY_KP5, Key::KEY_KP6, Key::KEY_KP7, Key::KEY_KP8, Key::KEY_KP9,
+];
+// Shifted digits of the US layout, from 0
+const DIGIT_SYMBOLS: &str = ")!@#$%^&*(";
+
+// Key and whether Shift is held, on a US layout
+fn us_key(c: char, keypad: bool) -> Option<(Key, bool)> {
+    if let Some(digit) = c.to_digit(10) {
+        let keys = if keypad { &KEYPAD_DIGITS } else { &DIGITS };
+        return Some((keys[digit as usize], false));
+    }
+    if c.is_ascii_lowercase() {
+        return Some((LETTERS[(c as u8 - b'a') as usize], false));
+    }
+    if c.is_ascii_uppercase() {
+        return Some((LETTERS[(c as u8 - b'A') as usize], true));
+    }
+    if let Some(digit) = DIGIT_SYMBOLS.find(c) {
+        return Some((DIGITS[digit], true));
+    }
+    let key = match c {
+        ' ' => (Key::KEY_SPACE, false),
+        '\n' => (Key::KEY_ENTER, false),
+        '\t' => (Key::KEY_TAB, false),
+        '-' => (Key::KEY_MINUS, false),
+        '_' => (Key::KEY_MINUS, true),
+        '=' => (Key::KEY_EQUAL, false),
+        '+' => (Key::KEY_EQUAL, true),
+        '[' => (Key::KEY_LEFTBRACE, false),
+        '{' => (Key::KEY_LEFTBRACE, true),
+        ']' => (Key::KEY_RIGHTBRACE, false),
+        '}' => (Key::KEY_RIGHTBRACE, true),
+        ';' => (Key::KEY_SEMICOLON, false),
+        ':' => (Key::KEY_SEMICOLON, true),
+        '\'' => (Key::KEY_APOSTROPHE, false),
+        '"' => (Key::KEY_APOSTROPHE, true),
+        '`' => (Key::KEY_GRAVE, false),
+        '~' => (Key::KEY_GRAVE, true),
+        '\\' => (Key::KEY_BACKSLASH, false),
+        '|' => (Key::KEY_BACKSLASH, true),
+        ',' => (Key::KEY_COMMA, false),
+        '<' => (Key::KEY_COMMA, true),
+        '.' => (Key::KEY_DOT, false),
+        '>' => (Key::KEY_DOT, true),
+        '/' => (Key::KEY_SLASH, false),
+        '?' => (Key::KEY_SLASH, true),
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn create_device() -> Result<VirtualDevice, String> {
+    // Every key of a standard keyboard
+    let mut keys = AttributeSet::<Key>::new();
+    for code in 1..=248 {
+        keys.insert(Key::new(code));
+    }
+    VirtualDeviceBuilder::new()
+        .and_then(|builder| builder.name("Virtual IO Hub keyboard").with_keys(&keys))
+        .and_then(|builder| builder.build())
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::PermissionDenied => uinput_error(UinputAccess::PermissionDenied),
+            io::ErrorKind::NotFound => uinput_error(UinputAccess::Missing),
+            _ => format!("Falha ao criar teclado virtual em {}: {}", UINPUT_PATH, e),
+        })
+}
+
+fn click(device: &mut VirtualDevice, key: Key, shift: bool) -> io::Result<()> {
+    let event = |key: Key, value: i32| InputEvent::new(EventType::KEY, key.code(), value);
+    if shift {
+        device.emit(&[event(Key::KEY_LEFTSHIFT, 1)])?;
+    }
+    device.emit(&[event(key, 1)])?;
+    thread::sleep(UINPUT_KEY_DELAY);
+    device.emit(&[event(key, 0)])?;
+    if shift {
+        device.emit(&[event(Key::KEY_LEFTSHIFT, 0)])?;
+    }
+    Ok(())
+}
+
+// Keypad digits need NumLock on in the session
+fn type_uinput(text: &str, mode: KeystrokeMode, timing: KeystrokeTiming) -> Result<(), String> {
+    let keypad = mode == KeystrokeMode::Numpad;
+    let keys: Vec<(Key, bool)> = text.chars().filter_map(|c| us_key(c, keypad)).collect();
+    let unmapped: String = text.chars().filter(|&c| us_key(c, keypad).is_none()).collect();
+    if !unmapped.is_empty() {
+        return Err(format!("Caracteres sem tecla no layout US, nada foi digitado: {}", unmapped));
+    }
+
+    let mut device = DEVICE.lock().map_err(|_| "Falha ao obter lock do teclado virtual".to_string())?;
+    if device.is_none() {
+        *device = Some(create_device()?);
+        thread::sleep(DEVICE_SETTLE_DELAY);
+    }
+    let Some(device) = device.as_mut() else {
+        return Err("Teclado virtual indisponível".into());
+    };
+
+    thread::sleep(initial_delay(timing));
+    let key_delay = Duration::from_millis(timing.key_delay_ms.into()).max(UINPUT_KEY_DELAY);
+    for (key, shift) in keys {
+        click(device, key, shift).map_err(|e| format!("Falha ao digitar texto: {}", e))?;
+        thread::sleep(key_delay);
+    }
+    Ok(())
+}
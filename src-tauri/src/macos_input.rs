@@ -0,0 +1,32 @@
+use core_foundation::base::TCFType;
+use core_foundation::boolean::CFBoolean;
+use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+use core_foundation::string::{CFString, CFStringRef};
+
+// On macOS the text is posted as CGEvents (through enigo), which the system
+// silently drops until the app is allowed under Privacy & Security >
+// Accessibility. The global shortcuts themselves need no permission.
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    fn AXIsProcessTrustedWithOptions(options: CFDictionaryRef) -> bool;
+    static kAXTrustedCheckOptionPrompt: CFStringRef;
+}
+
+// With `prompt`, an untrusted app also gets the system dialog that opens the
+// Accessibility settings; it's shown once per launch at most
+pub fn accessibility_trusted(prompt: bool) -> bool {
+    let key = unsafe { CFString::wrap_under_get_rule(kAXTrustedCheckOptionPrompt) };
+    let options = CFDictionary::from_CFType_pairs(&[(key, CFBoolean::from(prompt))]);
+    unsafe { AXIsProcessTrustedWithOptions(options.as_concrete_TypeRef()) }
+}
+
+// Asks for the permission when missing. macOS only applies it to processes
+// started after it's granted for some versions, hence the restart hint.
+pub fn ensure_access() -> Result<(), String> {
+    if accessibility_trusted(true) {
+        return Ok(());
+    }
+    Err("O macOS bloqueou a digitação simulada. Permita o aplicativo em Ajustes do Sistema > Privacidade e Segurança > \
+         Acessibilidade e ative a hotkey novamente (se continuar bloqueado, reinicie o aplicativo)."
+        .into())
+}
@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+
+use crate::card_format::CheckDigitRule;
+use crate::patient_duplicates::name_key;
+
+// Magnetic stripe as a USB reader in keyboard mode types it (ISO 7811/7813):
+// each selected track between its start and end sentinels, optionally with
+// the LRC character after the end sentinel. A card format with `magstripe`
+// types this instead of its template.
+
+// Track 1 name field
+const NAME_MAX: usize = 26;
+// PAN of a financial card; longer wallets don't fit the stripe
+const PAN_MAX: usize = 19;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Track {
+    // Alphanumeric (IATA): PAN, holder name and expiry
+    Track1,
+    // Numeric (ABA): PAN and expiry, what the Datasul screens read
+    Track2,
+    // Numeric (THRIFT): free data
+    Track3,
+}
+
+impl Track {
+    fn number(self) -> u8 {
+        match self {
+            Track::Track1 => 1,
+            Track::Track2 => 2,
+            Track::Track3 => 3,
+        }
+    }
+
+    // Characters the track holds, sentinels and LRC included
+    fn max_len(self) -> usize {
+        match self {
+            Track::Track1 => 79,
+            Track::Track2 => 40,
+            Track::Track3 => 107,
+        }
+    }
+
+    // Track 1 encodes 6 data bits per character from 0x20, the numeric
+    // tracks 4 bits from 0x30
+    fn encoding(self) -> (u8, u8) {
+        match self {
+            Track::Track1 => (0x20, 0x3F),
+            Track::Track2 | Track::Track3 => (0x30, 0x0F),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sentinels {
+    pub track1_start: String,
+    pub track2_start: String,
+    // Some readers type `+` for track 3
+    pub track3_start: String,
+    pub end: String,
+}
+
+impl Default for Sentinels {
+    fn default() -> Self {
+        Self {
+            track1_start: "%".into(),
+            track2_start: ";".into(),
+            track3_start: ";".into(),
+            end: "?".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MagstripeLayout {
+    // Typed in this order
+    pub tracks: Vec<Track>,
+    pub sentinels: Sentinels,
+    // Types each track's LRC after its end sentinel; most readers drop it
+    pub lrc: bool,
+    // Check digit computed and appended to the wallet to make the PAN
+    pub pan_check_digit: CheckDigitRule,
+    pub service_code: String,
+    // After the service code on tracks 1 and 2
+    pub discretionary: String,
+    // Track 3 content; {pan} is replaced by the PAN
+    pub track3_data: String,
+    // Typed between tracks and after the last one (e.g. "\n" for Enter)
+    pub track_separator: String,
+    pub suffix: String,
+    // Track 1 name when the wallet isn't of a registered patient
+    pub default_name: String,
+}
+
+impl Default for MagstripeLayout {
+    fn default() -> Self {
+        Self {
+            tracks: vec![Track::Track1, Track::Track2],
+            sentinels: Sentinels::default(),
+            lrc: false,
+            pan_check_digit: CheckDigitRule::None,
+            service_code: "000".into(),
+            discretionary: String::new(),
+            track3_data: "01{pan}=".into(),
+            track_separator: String::new(),
+            suffix: String::new(),
+            default_name: "BENEFICIARIO/TESTE".into(),
+        }
+    }
+}
+
+// XOR of the data bits of every character from the start through the end
+// sentinel, encoded as a character of the track
+fn lrc(track: Track, text: &str) -> char {
+    let (base, mask) = track.encoding();
+    let lrc = text.bytes().fold(0u8, |lrc, b| lrc ^ (b.wrapping_sub(base) & mask));
+    char::from(lrc + base)
+}
+
+// Uppercase ASCII without the track 1 sentinels and separator
+fn track1_text(text: &str) -> String {
+    text.to_ascii_uppercase()
+        .chars()
+        .filter(|&c| matches!(c, ' '..='_') && !matches!(c, '%' | '^' | '?'))
+        .collect()
+}
+
+// Digits and the field separator
+fn numeric_text(text: &str) -> String {
+    text.chars().filter(|&c| c.is_ascii_digit() || c == '=').collect()
+}
+
+// SURNAME/GIVEN NAMES, without accents, cut to the field size
+fn track1_name(name: &str) -> String {
+    let key = name_key(name).to_ascii_uppercase();
+    let mut words: Vec<&str> = key.split_whitespace().collect();
+    let name = match words.pop() {
+        Some(surname) if !words.is_empty() => format!("{}/{}", surname, words.join(" ")),
+        Some(surname) => surname.to_string(),
+        None => String::new(),
+    };
+    track1_text(&name).chars().take(NAME_MAX).collect()
+}
+
+impl MagstripeLayout {
+    fn track(&self, track: Track, pan: &str, expiry: &str, holder: Option<&str>) -> String {
+        let (start, data) = match track {
+            Track::Track1 => {
+                let name = holder.filter(|h| !h.trim().is_empty()).map_or_else(|| track1_text(&self.default_name), track1_name);
+                let fields = track1_text(&format!("{}{}{}", expiry, self.service_code, self.discretionary));
+                (&self.sentinels.track1_start, format!("B{}^{}^{}", track1_text(pan), name, fields))
+            }
+            Track::Track2 => {
+                let data = format!("{}={}{}{}", pan, expiry, self.service_code, self.discretionary);
+                (&self.sentinels.track2_start, numeric_text(&data))
+            }
+            Track::Track3 => (&self.sentinels.track3_start, numeric_text(&self.track3_data.replace("{pan}", pan))),
+        };
+        let mut text = format!("{}{}{}", start, data, self.sentinels.end);
+        if self.lrc {
+            text.push(lrc(track, &text));
+        }
+        text
+    }
+
+    // `pan` already carries its check digit; `expiry` is YYMM
+    pub fn render(&self, pan: &str, expiry: &str, holder: Option<&str>) -> String {
+        let tracks: Vec<String> = self.tracks.iter().map(|&t| self.track(t, pan, expiry, holder)).collect();
+        let mut text = tracks.join(&self.track_separator);
+        text.push_str(&self.suffix);
+        text
+    }
+
+    pub fn uses_name(&self) -> bool {
+        self.tracks.contains(&Track::Track1)
+    }
+
+    pub fn validate(&self, pan: &str, expiry: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.tracks.is_empty() {
+            errors.push("Nenhuma trilha selecionada no formato magnético.".to_string());
+        }
+        if pan.len() > PAN_MAX {
+            errors.push(format!("O PAN da trilha tem {} dígitos; o limite é {}.", pan.len(), PAN_MAX));
+        }
+        // The longest name, as it can be any patient's
+        let longest_name = "X".repeat(NAME_MAX);
+        for &track in &self.tracks {
+            let len = self.track(track, pan, expiry, Some(&longest_name)).chars().count();
+            if len > track.max_len() {
+                errors.push(format!(
+                    "A trilha {} teria {} caracteres; o limite é {}.",
+                    track.number(),
+                    len,
+                    track.max_len()
+                ));
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lrc_xors_the_data_bits_of_the_track() {
+        assert_eq!(lrc(Track::Track2, ";1234=5?"), '8');
+        assert_eq!(lrc(Track::Track1, "%B123^SILVA/JOAO^2512000?"), '9');
+    }
+
+    #[test]
+    fn render_appends_each_track_lrc() {
+        let layout = MagstripeLayout { lrc: true, ..MagstripeLayout::default() };
+        assert_eq!(
+            layout.render("123", "2512", Some("João Silva")),
+            "%B123^SILVA/JOAO^2512000?9;123=2512000?="
+        );
+        let layout = MagstripeLayout::default();
+        assert_eq!(layout.render("123", "2512", None), "%B123^BENEFICIARIO/TESTE^2512000?;123=2512000?");
+    }
+}
@@ -0,0 +1,241 @@
+use base64::{engine::general_purpose as b64, Engine};
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::fingerprint;
+
+// Finger minutiae records: ISO/IEC 19794-2:2005 and ANSI INCITS 378-2004.
+// Both start with "FMR\0" and version " 20\0" and share the finger view and
+// minutia layouts; the general header differs.
+const MAGIC: &[u8; 4] = b"FMR\0";
+const VERSION_2005: &[u8; 4] = b" 20\0";
+const ISO_HEADER_LEN: usize = 24;
+const ANSI_HEADER_LEN: usize = 26;
+// ANSI records over 64 KiB store 0 in the short length and a 4-byte one after it
+const ANSI_LONG_HEADER_LEN: usize = 30;
+const VIEW_HEADER_LEN: usize = 4;
+const MINUTIA_LEN: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateStandard {
+    Iso19794_2,
+    Ansi378,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MinutiaKind {
+    RidgeEnding,
+    Bifurcation,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Minutia {
+    pub kind: MinutiaKind,
+    pub x: u16,
+    pub y: u16,
+    // Degrees, counter-clockwise from the horizontal
+    pub angle: f32,
+    pub quality: u8,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FingerView {
+    pub finger_position: u8,
+    pub view_number: u8,
+    pub impression_type: u8,
+    pub quality: u8,
+    pub minutiae: Vec<Minutia>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MinutiaeTemplate {
+    pub standard: TemplateStandard,
+    pub width: u16,
+    pub height: u16,
+    // Pixels per centimetre
+    pub resolution_x: u16,
+    pub resolution_y: u16,
+    pub views: Vec<FingerView>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TemplateSummary {
+    pub template: MinutiaeTemplate,
+    pub summary: String,
+}
+
+fn be_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from(data[offset]) << 8 | u16::from(data[offset + 1])
+}
+
+fn be_u32(data: &[u8], offset: usize) -> usize {
+    (data[offset] as usize) << 24
+        | (data[offset + 1] as usize) << 16
+        | (data[offset + 2] as usize) << 8
+        | data[offset + 3] as usize
+}
+
+// The record length field tells the two apart: 4 bytes in ISO, 2 (or 2 + 4)
+// in ANSI. Returns the standard and where the image size fields start.
+fn detect_standard(record: &[u8]) -> Result<(TemplateStandard, usize), String> {
+    if record.len() < ISO_HEADER_LEN || &record[..4] != MAGIC {
+        return Err("Template de minúcias não reconhecido.".into());
+    }
+    if &record[4..8] != VERSION_2005 {
+        return Err(format!(
+            "Versão do template de minúcias não suportada: {}",
+            String::from_utf8_lossy(&record[4..7]).trim()
+        ));
+    }
+
+    let len = record.len();
+    if be_u32(record, 8) == len {
+        // Length, then the capture equipment
+        return Ok((TemplateStandard::Iso19794_2, 14));
+    }
+    if record.len() >= ANSI_HEADER_LEN && usize::from(be_u16(record, 8)) == len {
+        // Length, CBEFF product id and capture equipment
+        return Ok((TemplateStandard::Ansi378, 16));
+    }
+    if record.len() >= ANSI_LONG_HEADER_LEN && be_u16(record, 8) == 0 && be_u32(record, 10) == len {
+        return Ok((TemplateStandard::Ansi378, 20));
+    }
+    Err("Tamanho declarado no template de minúcias não confere com os dados.".into())
+}
+
+fn parse_minutia(data: &[u8], standard: TemplateStandard) -> Minutia {
+    let kind = match data[0] >> 6 {
+        1 => MinutiaKind::RidgeEnding,
+        2 => MinutiaKind::Bifurcation,
+        _ => MinutiaKind::Other,
+    };
+    // ISO counts the angle in 360/256 degree steps, ANSI in 2 degree steps
+    let angle = match standard {
+        TemplateStandard::Iso19794_2 => f32::from(data[4]) * 360.0 / 256.0,
+        TemplateStandard::Ansi378 => f32::from(data[4]) * 2.0,
+    };
+    Minutia {
+        kind,
+        x: be_u16(data, 0) & 0x3FFF,
+        y: be_u16(data, 2) & 0x3FFF,
+        angle,
+        quality: data[5],
+    }
+}
+
+pub fn parse(record: &[u8]) -> Result<MinutiaeTemplate, String> {
+    let (standard, size_offset) = detect_standard(record)?;
+    let views_offset = size_offset + 10;
+    let mut template = MinutiaeTemplate {
+        standard,
+        width: be_u16(record, size_offset),
+        height: be_u16(record, size_offset + 2),
+        resolution_x: be_u16(record, size_offset + 4),
+        resolution_y: be_u16(record, size_offset + 6),
+        views: Vec::new(),
+    };
+    let view_count = record[size_offset + 8];
+
+    let mut offset = views_offset;
+    for view in 0..view_count {
+        let header = record
+            .get(offset..offset + VIEW_HEADER_LEN)
+            .ok_or_else(|| format!("Template de minúcias truncado na vista {}.", view + 1))?;
+        let minutiae_count = usize::from(header[3]);
+        offset += VIEW_HEADER_LEN;
+
+        let minutiae_len = minutiae_count * MINUTIA_LEN;
+        let minutiae = record
+            .get(offset..offset + minutiae_len)
+            .ok_or_else(|| format!("Template de minúcias truncado na vista {}.", view + 1))?
+            .chunks_exact(MINUTIA_LEN)
+            .map(|data| parse_minutia(data, standard))
+            .collect();
+        offset += minutiae_len;
+
+        // Extended data (ridge counts, cores, deltas) is skipped
+        let extended_len = record
+            .get(offset..offset + 2)
+            .map(|len| usize::from(be_u16(len, 0)))
+            .ok_or_else(|| format!("Template de minúcias truncado na vista {}.", view + 1))?;
+        offset += 2 + extended_len;
+
+        template.views.push(FingerView {
+            finger_position: header[0],
+            view_number: header[1] >> 4,
+            impression_type: header[1] & 0x0F,
+            quality: header[2],
+            minutiae,
+        });
+    }
+    Ok(template)
+}
+
+// Finger position codes shared by both standards
+fn position_name(position: u8) -> String {
+    match position {
+        0 => "Dedo desconhecido".into(),
+        1 => "Polegar Direito".into(),
+        2 => "Indicador Direito".into(),
+        3 => "Médio Direito".into(),
+        4 => "Anelar Direito".into(),
+        5 => "Mínimo Direito".into(),
+        6 => "Polegar Esquerdo".into(),
+        7 => "Indicador Esquerdo".into(),
+        8 => "Médio Esquerdo".into(),
+        9 => "Anelar Esquerdo".into(),
+        10 => "Mínimo Esquerdo".into(),
+        other => format!("Posição {}", other),
+    }
+}
+
+pub fn summarize(template: &MinutiaeTemplate) -> String {
+    let standard = match template.standard {
+        TemplateStandard::Iso19794_2 => "ISO 19794-2",
+        TemplateStandard::Ansi378 => "ANSI 378",
+    };
+    let mut lines = vec![format!(
+        "{} — imagem {}x{} px, {} dpi, {} vista(s)",
+        standard,
+        template.width,
+        template.height,
+        // Resolutions are stored per centimetre
+        (f32::from(template.resolution_x) * 2.54).round(),
+        template.views.len()
+    )];
+
+    for view in &template.views {
+        let count = |kind: MinutiaKind| view.minutiae.iter().filter(|m| m.kind == kind).count();
+        let average_quality = if view.minutiae.is_empty() {
+            0
+        } else {
+            view.minutiae.iter().map(|m| u32::from(m.quality)).sum::<u32>() / view.minutiae.len() as u32
+        };
+        lines.push(format!(
+            "{}: {} minúcias ({} terminações, {} bifurcações, {} outras), qualidade da vista {}, qualidade média das minúcias {}",
+            position_name(view.finger_position),
+            view.minutiae.len(),
+            count(MinutiaKind::RidgeEnding),
+            count(MinutiaKind::Bifurcation),
+            count(MinutiaKind::Other),
+            view.quality,
+            average_quality
+        ));
+    }
+    lines.join("\n")
+}
+
+// Parses a patient's stored minutiae template (ISO/ANSI) for display
+#[tauri::command]
+pub fn describe_fingerprint_template(app_handle: AppHandle, patient_id: u32, finger: String) -> Result<TemplateSummary, String> {
+    let template_b64 = fingerprint::find_template(&app_handle, patient_id, &finger)?;
+    let record = b64::STANDARD
+        .decode(template_b64.trim())
+        .map_err(|e| format!("Base64 inválido: {e}"))?;
+    let template = parse(&record)?;
+    let summary = summarize(&template);
+    Ok(TemplateSummary { template, summary })
+}
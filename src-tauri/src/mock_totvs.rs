@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde_json::{json, Value};
+use tauri::AppHandle;
+use tokio::sync::oneshot;
+
+use crate::beneficiary_import::{details_key, finger_code};
+use crate::patient::{self, Patient};
+
+const HOST: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 21080;
+const DEFAULT_PAGE_SIZE: usize = 20;
+const HVP: &str = "/dts/datasul-rest/resources/prg/hvp/v2";
+const PORTPREST: &str = "/dts/datasul-rest/resources/prg/portprest/v1";
+const CLINIC_HEADER: &str = "x-totvs-hgp-portal-prestador-clinic";
+const TOKEN_PATH: &str = "/api/oauth2/v1/token";
+
+// Offline stand-in for the Datasul endpoints the app uses, answering from
+// the local patient store. Point base_url at the returned address; any
+// user/password is accepted. Check-ins and fingerprint enrollments are
+// acknowledged without changing the store.
+pub struct MockTotvsState {
+    shutdown_tx: Option<oneshot::Sender<()>>,
+    base_url: Option<String>,
+}
+
+impl MockTotvsState {
+    pub fn new() -> Self {
+        Self {
+            shutdown_tx: None,
+            base_url: None,
+        }
+    }
+}
+
+fn load_patients(app_handle: &AppHandle) -> Result<Vec<Patient>, String> {
+    patient::load_patients_from_disk(app_handle).map_err(|e| e.to_string())
+}
+
+fn server_error(message: String) -> Response {
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "message": message }))).into_response()
+}
+
+// The details endpoint gets the short card number, the others the full wallet
+fn find_patient(patients: Vec<Patient>, card: &str) -> Option<Patient> {
+    let key = details_key(card);
+    patients
+        .into_iter()
+        .find(|p| p.wallet.trim() == card.trim() || details_key(&p.wallet) == key)
+}
+
+fn not_found(card: &str) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "message": format!("Beneficiário {} não encontrado", card) })),
+    )
+        .into_response()
+}
+
+// Beneficiary as the Datasul details endpoint returns it; also the seed
+// record written by `export_patients_totvs`
+pub(crate) fn beneficiary_json(patient: &Patient) -> Value {
+    let wallet = patient.wallet.trim();
+    let chars: Vec<char> = wallet.chars().collect();
+    let split = chars.len().saturating_sub(13);
+    json!({
+        "healthInsurer": chars[..split].iter().collect::<String>(),
+        "cardNumber": chars[split..].iter().collect::<String>(),
+        "completeCardNumber": wallet,
+        "name": patient.name,
+        "person": { "name": patient.name },
+        "dependents": [],
+    })
+}
+
+async fn search(State(app_handle): State<AppHandle>, Query(query): Query<HashMap<String, String>>) -> Response {
+    let patients = match load_patients(&app_handle) {
+        Ok(patients) => patients,
+        Err(e) => return server_error(e),
+    };
+    // The guarantor is matched against wallet and name; empty or `*` lists everyone
+    let filter = query.get("guarantor").map(|g| g.trim().to_lowercase()).unwrap_or_default();
+    let matches: Vec<Value> = patients
+        .iter()
+        .filter(|p| filter.is_empty() || filter == "*" || p.wallet.contains(&filter) || p.name.to_lowercase().contains(&filter))
+        .map(beneficiary_json)
+        .collect();
+
+    let page: usize = query.get("page").and_then(|p| p.parse().ok()).unwrap_or(1).max(1);
+    let page_size: usize = query.get("pageSize").and_then(|p| p.parse().ok()).unwrap_or(DEFAULT_PAGE_SIZE).max(1);
+    let start = (page - 1).saturating_mul(page_size);
+    let items: Vec<&Value> = matches.iter().skip(start).take(page_size).collect();
+    let has_next = start.saturating_add(page_size) < matches.len();
+    Json(json!({ "items": items, "hasNext": has_next })).into_response()
+}
+
+async fn details(State(app_handle): State<AppHandle>, Path(card): Path<String>) -> Response {
+    match load_patients(&app_handle).map(|patients| find_patient(patients, &card)) {
+        Ok(Some(patient)) => Json(beneficiary_json(&patient)).into_response(),
+        Ok(None) => not_found(&card),
+        Err(e) => server_error(e),
+    }
+}
+
+async fn fingerprints(State(app_handle): State<AppHandle>, Path(card): Path<String>) -> Response {
+    let patient = match load_patients(&app_handle).map(|patients| find_patient(patients, &card)) {
+        Ok(Some(patient)) => patient,
+        Ok(None) => return not_found(&card),
+        Err(e) => return server_error(e),
+    };
+    let items: Vec<Value> = patient
+        .digital_biometrics
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| !d.data.trim().is_empty())
+        .map(|(i, d)| {
+            let code = finger_code(&d.finger).unwrap_or(i as u32 + 1);
+            json!({ "fingerCode": code, "biometry": d.data })
+        })
+        .collect();
+    Json(json!({ "items": items, "hasNext": false })).into_response()
+}
+
+async fn photo(State(app_handle): State<AppHandle>, Path(card): Path<String>) -> Response {
+    match load_patients(&app_handle).map(|patients| find_patient(patients, &card)) {
+        Ok(Some(patient)) => Json(json!({ "photo": patient.main_photo() })).into_response(),
+        Ok(None) => not_found(&card),
+        Err(e) => server_error(e),
+    }
+}
+
+async fn checkin(State(app_handle): State<AppHandle>, Path(card): Path<String>) -> Response {
+    match load_patients(&app_handle).map(|patients| find_patient(patients, &card)) {
+        Ok(Some(_)) => {
+            let protocol = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_millis())
+                .unwrap_or_default()
+                .to_string();
+            Json(json!({ "protocol": protocol, "authorizationCode": format!("DEMO{}", protocol) })).into_response()
+        }
+        Ok(None) => not_found(&card),
+        Err(e) => server_error(e),
+    }
+}
+
+async fn enroll_fingerprints(
+    State(app_handle): State<AppHandle>,
+    Path(card): Path<String>,
+    Json(body): Json<Value>,
+) -> Response {
+    match load_patients(&app_handle).map(|patients| find_patient(patients, &card)) {
+        Ok(Some(_)) => {}
+        Ok(None) => return not_found(&card),
+        Err(e) => return server_error(e),
+    }
+    let count = body.get("items").and_then(|i| i.as_array()).map_or(0, Vec::len);
+    if count == 0 {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "message": "Nenhuma digital informada" }))).into_response();
+    }
+    (StatusCode::CREATED, Json(json!({ "enrolled": count }))).into_response()
+}
+
+// For connections set to OAuth; the token is never checked
+async fn token() -> Json<Value> {
+    Json(json!({ "access_token": "mock-totvs", "token_type": "Bearer", "expires_in": 3600 }))
+}
+
+// Fixed lookup lists so the settings screen and the connection test work;
+// the requested clinic is always listed
+async fn health_insurers() -> Json<Value> {
+    Json(json!({ "items": [{ "code": "0001", "name": "Operadora Demonstração" }], "hasNext": false }))
+}
+
+async fn providers() -> Json<Value> {
+    Json(json!({ "items": [{ "code": "1", "name": "Prestador Demonstração" }], "hasNext": false }))
+}
+
+async fn clinics(headers: HeaderMap) -> Json<Value> {
+    let mut items = vec![json!({ "code": "1", "name": "Clínica Demonstração" })];
+    if let Some(clinic) = headers.get(CLINIC_HEADER).and_then(|v| v.to_str().ok()).filter(|c| *c != "1") {
+        items.push(json!({ "code": clinic, "name": format!("Clínica {}", clinic) }));
+    }
+    Json(json!({ "items": items, "hasNext": false }))
+}
+
+fn build_router(app_handle: AppHandle) -> Router {
+    Router::new()
+        .route(&format!("{}/beneficiaries/subscriber", HVP), get(search))
+        .route(&format!("{}/checkin/beneficiaries/:card", PORTPREST), get(details).post(checkin))
+        .route(
+            &format!("{}/checkin/beneficiaries/:card/fingerPrints", PORTPREST),
+            get(fingerprints).post(enroll_fingerprints),
+        )
+        .route(&format!("{}/checkin/beneficiaries/:card/photo", PORTPREST), get(photo))
+        .route(&format!("{}/healthInsurers", PORTPREST), get(health_insurers))
+        .route(&format!("{}/providers", PORTPREST), get(providers))
+        .route(&format!("{}/providers/:provider/clinics", PORTPREST), get(clinics))
+        .route(TOKEN_PATH, post(token))
+        .with_state(app_handle)
+}
+
+// Without a port the default one is tried, then any free port
+async fn bind(port: Option<u16>) -> Result<tokio::net::TcpListener, String> {
+    let addr: SocketAddr = format!("{}:{}", HOST, port.unwrap_or(DEFAULT_PORT))
+        .parse()
+        .map_err(|e| format!("Endereço inválido: {}", e))?;
+    match tokio::net::TcpListener::bind(&addr).await {
+        Ok(listener) => Ok(listener),
+        Err(e) if port.is_none() && e.kind() == std::io::ErrorKind::AddrInUse => {
+            tokio::net::TcpListener::bind((HOST, 0))
+                .await
+                .map_err(|e| format!("Falha ao vincular servidor TOTVS simulado: {}", e))
+        }
+        Err(e) => Err(format!("Falha ao vincular servidor TOTVS simulado em {}: {}", addr, e)),
+    }
+}
+
+// Returns the base_url to configure for the mock
+#[tauri::command]
+pub async fn start_mock_totvs_server(
+    app_handle: AppHandle,
+    port: Option<u16>,
+    state: tauri::State<'_, Arc<Mutex<MockTotvsState>>>,
+) -> Result<String, String> {
+    if let Some(base_url) = state.lock().unwrap().base_url.clone() {
+        return Ok(base_url);
+    }
+
+    let listener = bind(port).await?;
+    let addr = listener
+        .local_addr()
+        .map_err(|e| format!("Falha ao obter endereço do servidor TOTVS simulado: {}", e))?;
+    let base_url = format!("http://{}", addr);
+
+    let (tx, rx) = oneshot::channel::<()>();
+    {
+        let mut s = state.lock().unwrap();
+        // Another call started it while this one was binding
+        if let Some(running) = s.base_url.clone() {
+            return Ok(running);
+        }
+        s.shutdown_tx = Some(tx);
+        s.base_url = Some(base_url.clone());
+    }
+
+    let server_state = state.inner().clone();
+    let app = build_router(app_handle);
+    tracing::info!("Servidor TOTVS simulado iniciado em {}", base_url);
+    tokio::spawn(async move {
+        let server = axum::serve(listener, app).with_graceful_shutdown(async {
+            rx.await.ok();
+            tracing::info!("Servidor TOTVS simulado desligado");
+        });
+        if let Err(e) = server.await {
+            tracing::error!("Erro no servidor TOTVS simulado: {}", e);
+            let mut s = server_state.lock().unwrap();
+            s.shutdown_tx = None;
+            s.base_url = None;
+        }
+    });
+
+    Ok(base_url)
+}
+
+// Returns false if the mock wasn't running
+#[tauri::command]
+pub fn stop_mock_totvs_server(state: tauri::State<'_, Arc<Mutex<MockTotvsState>>>) -> Result<bool, String> {
+    let mut state = state
+        .lock()
+        .map_err(|_| "Falha ao obter lock do servidor TOTVS simulado".to_string())?;
+    state.base_url = None;
+    Ok(match state.shutdown_tx.take() {
+        Some(tx) => tx.send(()).is_ok(),
+        None => false,
+    })
+}
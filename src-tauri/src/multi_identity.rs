@@ -0,0 +1,247 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::biometry_server::{self, BiometryServerState};
+use crate::card_format;
+use crate::hotkey::HotkeyManager;
+use crate::keystroke::{self, KeystrokeMode};
+use crate::patient::{self, FaceAngle, Patient};
+use crate::webcam_emulator::{WebcamEmulator, WebcamSource};
+
+const DEFAULT_SERVER_HOST: &str = "127.0.0.1";
+
+// One complete simulated identity. Each emulator is optional so an identity can,
+// for instance, only expose a biometry server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentityConfig {
+    pub patient_id: u32,
+    pub biometry_host: Option<String>,
+    pub biometry_port: Option<u16>,
+    pub webcam_device: Option<String>,
+    // Photo streamed to the webcam; frontal when absent
+    #[serde(default)]
+    pub webcam_photo: Option<FaceAngle>,
+    pub hotkey: Option<String>,
+    pub card_format_id: Option<String>,
+    pub keystroke_mode: Option<KeystrokeMode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdentityStatus {
+    pub patient_id: u32,
+    pub patient_name: String,
+    pub biometry_port: Option<u16>,
+    pub biometry_running: bool,
+    pub webcam_device: Option<String>,
+    pub webcam_running: bool,
+    pub hotkey: Option<String>,
+    pub hotkey_running: bool,
+}
+
+struct IdentityRuntime {
+    config: IdentityConfig,
+    patient_name: String,
+    biometry: Option<Arc<Mutex<BiometryServerState>>>,
+    webcam: Option<WebcamEmulator>,
+    hotkey: Option<HotkeyManager>,
+}
+
+impl IdentityRuntime {
+    fn stop(&mut self) {
+        if let Some(state) = self.biometry.take() {
+            biometry_server::request_shutdown(&state);
+        }
+        if let Some(mut webcam) = self.webcam.take() {
+            let _ = webcam.stop();
+        }
+        if let Some(mut hotkey) = self.hotkey.take() {
+            let _ = hotkey.stop();
+        }
+    }
+
+    fn status(&self) -> IdentityStatus {
+        IdentityStatus {
+            patient_id: self.config.patient_id,
+            patient_name: self.patient_name.clone(),
+            biometry_port: self.config.biometry_port,
+            biometry_running: self.biometry.as_ref().is_some_and(biometry_server::is_running),
+            webcam_device: self.config.webcam_device.clone(),
+            webcam_running: self.webcam.as_ref().is_some_and(|w| w.is_running()),
+            hotkey: self.config.hotkey.clone(),
+            hotkey_running: self.hotkey.as_ref().is_some_and(|h| h.is_running()),
+        }
+    }
+}
+
+// Parallel identities for multi-guichê testing; independent from the single
+// emulators managed by the other screens.
+pub struct MultiIdentityState {
+    identities: Vec<IdentityRuntime>,
+}
+
+impl MultiIdentityState {
+    pub fn new() -> Self {
+        Self {
+            identities: Vec::new(),
+        }
+    }
+
+    // (label, pid) of the child processes owned by the running identities
+    pub fn process_ids(&self) -> Vec<(String, u32)> {
+        let mut pids = Vec::new();
+        for identity in &self.identities {
+            let id = identity.config.patient_id;
+            if let Some(pid) = identity.hotkey.as_ref().and_then(|h| h.process_id()) {
+                pids.push((format!("autohotkey[paciente {}]", id), pid));
+            }
+            if let Some(pid) = identity.webcam.as_ref().and_then(|w| w.process_id()) {
+                pids.push((format!("python_webcam[paciente {}]", id), pid));
+            }
+        }
+        pids
+    }
+
+    fn stop_all(&mut self) {
+        for identity in self.identities.iter_mut() {
+            identity.stop();
+        }
+        self.identities.clear();
+    }
+}
+
+fn check_conflicts(identities: &[IdentityConfig]) -> Result<(), String> {
+    let mut ports = HashSet::new();
+    let mut devices = HashSet::new();
+    let mut hotkeys = HashSet::new();
+
+    for identity in identities {
+        if let Some(port) = identity.biometry_port {
+            if !ports.insert(port) {
+                return Err(format!("Porta {} usada por mais de uma identidade.", port));
+            }
+        }
+        if let Some(device) = &identity.webcam_device {
+            if !devices.insert(device.clone()) {
+                return Err(format!("Webcam '{}' usada por mais de uma identidade.", device));
+            }
+        }
+        if let Some(hotkey) = &identity.hotkey {
+            if !hotkeys.insert(hotkey.to_lowercase()) {
+                return Err(format!("Atalho '{}' usado por mais de uma identidade.", hotkey));
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn start_identity(app_handle: &AppHandle, config: IdentityConfig, patient: Patient) -> Result<IdentityRuntime, String> {
+    let mut runtime = IdentityRuntime {
+        config: config.clone(),
+        patient_name: patient.name.clone(),
+        biometry: None,
+        webcam: None,
+        hotkey: None,
+    };
+
+    if let Some(port) = config.biometry_port {
+        let host = config.biometry_host.clone().unwrap_or_else(|| DEFAULT_SERVER_HOST.to_string());
+        let state = Arc::new(Mutex::new(BiometryServerState::new()));
+        let data = patient.digital_biometrics.iter().map(|d| d.data.clone()).collect();
+        biometry_server::spawn_server(app_handle.clone(), &host, port, data, state.clone()).await?;
+        runtime.biometry = Some(state);
+    }
+
+    if let Some(device) = &config.webcam_device {
+        let mut webcam = WebcamEmulator::with_device(device);
+        let started = WebcamSource::facial_photo(&patient, config.webcam_photo.unwrap_or_default())
+            .and_then(|source| webcam.start(source));
+        if let Err(e) = started {
+            runtime.stop();
+            return Err(e);
+        }
+        runtime.webcam = Some(webcam);
+    }
+
+    if let Some(hotkey) = config.hotkey.clone() {
+        let app = app_handle.clone();
+        let format_id = config.card_format_id.clone();
+        let keystroke_mode = config.keystroke_mode;
+        let wallet = patient.wallet.clone();
+        // AutoHotkey may need to be downloaded, which blocks
+        let started = tauri::async_runtime::spawn_blocking(move || {
+            let format = card_format::resolve_format(&app, &wallet, format_id.as_deref())?;
+            let mut manager = HotkeyManager::with_hotkey(&hotkey);
+            manager.start(&app, &wallet, &format, keystroke::resolve_mode(&app, keystroke_mode))?;
+            Ok::<_, String>(manager)
+        })
+        .await
+        .map_err(|e| format!("Falha ao iniciar hotkey: {e}"))
+        .and_then(|r| r);
+
+        match started {
+            Ok(manager) => runtime.hotkey = Some(manager),
+            Err(e) => {
+                runtime.stop();
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(runtime)
+}
+
+#[tauri::command]
+pub async fn start_multi_identity(
+    app_handle: AppHandle,
+    identities: Vec<IdentityConfig>,
+    state: tauri::State<'_, Arc<Mutex<MultiIdentityState>>>,
+) -> Result<Vec<IdentityStatus>, String> {
+    if identities.is_empty() {
+        return Err("Nenhuma identidade informada.".into());
+    }
+    check_conflicts(&identities)?;
+
+    let patients = patient::load_patients_from_disk(&app_handle)
+        .map_err(|e| format!("Falha ao ler pacientes: {e}"))?;
+
+    state.lock().unwrap().stop_all();
+
+    let mut started: Vec<IdentityRuntime> = Vec::new();
+    for config in identities {
+        let patient = match patients.iter().find(|p| p.id == config.patient_id) {
+            Some(p) => p.clone(),
+            None => {
+                started.iter_mut().for_each(|r| r.stop());
+                return Err(format!("Paciente {} não encontrado.", config.patient_id));
+            }
+        };
+
+        match start_identity(&app_handle, config, patient).await {
+            Ok(runtime) => started.push(runtime),
+            Err(e) => {
+                // All or nothing: tear down identities already running
+                started.iter_mut().for_each(|r| r.stop());
+                return Err(e);
+            }
+        }
+    }
+
+    let mut state = state.lock().unwrap();
+    state.identities = started;
+    Ok(state.identities.iter().map(|r| r.status()).collect())
+}
+
+#[tauri::command]
+pub fn stop_multi_identity(state: tauri::State<'_, Arc<Mutex<MultiIdentityState>>>) -> Result<bool, String> {
+    let mut state = state.lock().map_err(|_| "Falha ao obter lock do modo multi-identidade".to_string())?;
+    state.stop_all();
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn check_multi_identity_status(state: tauri::State<'_, Arc<Mutex<MultiIdentityState>>>) -> Result<Vec<IdentityStatus>, String> {
+    let state = state.lock().map_err(|_| "Falha ao obter lock do modo multi-identidade".to_string())?;
+    Ok(state.identities.iter().map(|r| r.status()).collect())
+}
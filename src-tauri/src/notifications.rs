@@ -0,0 +1,38 @@
+use tauri::AppHandle;
+use tauri_plugin_notification::NotificationExt;
+
+use crate::patient;
+
+// Operators usually keep the emulator minimized behind the portal, so problems
+// that need attention are raised as OS notifications. Can be turned off with
+// `notifications_enabled: false` in the app config.
+fn notifications_enabled(app_handle: &AppHandle) -> bool {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get("notifications_enabled").and_then(|v| v.as_bool()))
+        .unwrap_or(true)
+}
+
+pub fn notify(app_handle: &AppHandle, title: &str, body: &str) {
+    if !notifications_enabled(app_handle) {
+        return;
+    }
+
+    if let Err(e) = app_handle
+        .notification()
+        .builder()
+        .title(title)
+        .body(body)
+        .show()
+    {
+        tracing::warn!("Falha ao exibir notificação \"{}\": {}", title, e);
+    }
+}
+
+// Lets the frontend raise the same notifications for flows it still drives
+// (e.g. the patient importer finishing with errors).
+#[tauri::command]
+pub fn notify_emulator_event(app_handle: AppHandle, title: String, body: String) -> Result<(), String> {
+    notify(&app_handle, &title, &body);
+    Ok(())
+}
@@ -0,0 +1,155 @@
+use serde_json::{json, Value};
+
+use crate::biometry_server::BiometryRoutes;
+
+pub const OPENAPI_PATH: &str = "/openapi.json";
+
+fn message_response(description: &str) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": { "$ref": "#/components/schemas/MessageResponse" } } }
+    })
+}
+
+fn capture_operation(summary: &str, throttled: bool) -> Value {
+    let mut responses = json!({
+        "200": {
+            "description": "Biometria capturada",
+            "content": { "application/json": { "schema": { "$ref": "#/components/schemas/CaptureResponse" } } }
+        },
+        "403": message_response("Captura recusada pelo operador (modo de aprovação manual)"),
+        "404": message_response("Nenhuma biometria registrada no emulador")
+    });
+    if throttled {
+        responses["429"] = throttled_response();
+    }
+    json!({ "summary": summary, "responses": responses })
+}
+
+fn verify_operation(throttled: bool) -> Value {
+    let mut responses = json!({
+        "200": {
+            "description": "Resultado da verificação",
+            "content": { "application/json": { "schema": { "$ref": "#/components/schemas/VerifyResponse" } } }
+        },
+        "400": message_response("Código de biometria não fornecido")
+    });
+    if throttled {
+        responses["429"] = throttled_response();
+    }
+    json!({
+        "summary": "Verifica uma biometria contra as carregadas no emulador",
+        "requestBody": {
+            "required": true,
+            "content": { "application/json": { "schema": { "$ref": "#/components/schemas/VerifyRequest" } } }
+        },
+        "responses": responses
+    })
+}
+
+fn throttled_response() -> Value {
+    json!({
+        "description": "Limite de requisições excedido",
+        "headers": { "Retry-After": { "schema": { "type": "integer" }, "description": "Segundos até nova tentativa" } },
+        "content": { "application/json": { "schema": { "$ref": "#/components/schemas/MessageResponse" } } }
+    })
+}
+
+// OpenAPI 3 document for the emulated agent as currently configured (route
+// remapping and throttling included), for integration partners.
+pub fn biometry_server_spec(routes: &BiometryRoutes, server_url: &str, throttled: bool) -> Value {
+    let mut paths = serde_json::Map::new();
+
+    let mut root = verify_operation(throttled);
+    root["summary"] = json!("Captura por padrão; com {\"command\": \"verify\"} executa a verificação");
+    root["requestBody"]["required"] = json!(false);
+    root["requestBody"]["content"]["application/json"]["schema"] = json!({ "$ref": "#/components/schemas/RootRequest" });
+    root["responses"]["200"]["content"]["application/json"]["schema"] = json!({
+        "oneOf": [
+            { "$ref": "#/components/schemas/CaptureResponse" },
+            { "$ref": "#/components/schemas/VerifyResponse" }
+        ]
+    });
+    root["responses"]["404"] = message_response("Nenhuma biometria registrada no emulador");
+    paths.insert(routes.root.clone(), json!({ "post": root }));
+
+    paths.insert(
+        routes.capture.clone(),
+        json!({ "post": capture_operation("Retorna a primeira biometria carregada", throttled) }),
+    );
+    paths.insert(routes.verify.clone(), json!({ "post": verify_operation(throttled) }));
+    paths.insert(
+        routes.shutdown.clone(),
+        json!({
+            "post": {
+                "summary": "Desliga o servidor",
+                "responses": {
+                    "200": message_response("Servidor desligando"),
+                    "500": message_response("Servidor não pôde ser desligado")
+                }
+            }
+        }),
+    );
+    paths.insert(
+        OPENAPI_PATH.to_string(),
+        json!({
+            "get": {
+                "summary": "Este documento",
+                "responses": { "200": { "description": "Documento OpenAPI", "content": { "application/json": {} } } }
+            }
+        }),
+    );
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "VirtualIOHub - Agente de biometria emulado",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "servers": [{ "url": server_url }],
+        "paths": paths,
+        "components": {
+            "schemas": {
+                "RootRequest": {
+                    "type": "object",
+                    "properties": {
+                        "device": { "type": "string" },
+                        "command": { "type": "string", "example": "verify" },
+                        "code": { "type": "array", "items": { "type": "string" } }
+                    }
+                },
+                "VerifyRequest": {
+                    "type": "object",
+                    "required": ["command", "code"],
+                    "properties": {
+                        "command": { "type": "string" },
+                        "code": { "type": "array", "items": { "type": "string" }, "description": "Templates em base64" }
+                    }
+                },
+                "CaptureResponse": {
+                    "type": "object",
+                    "properties": {
+                        "success": { "type": "boolean" },
+                        "code": { "type": "string", "nullable": true, "description": "Template em base64" },
+                        "message": { "type": "string", "nullable": true }
+                    }
+                },
+                "VerifyResponse": {
+                    "type": "object",
+                    "properties": {
+                        "success": { "type": "boolean" },
+                        "match": { "type": "boolean" },
+                        "message": { "type": "string", "nullable": true }
+                    }
+                },
+                "MessageResponse": {
+                    "type": "object",
+                    "properties": {
+                        "success": { "type": "boolean" },
+                        "message": { "type": "string", "nullable": true }
+                    }
+                }
+            }
+        }
+    })
+}
@@ -1,85 +1,1111 @@
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::{params, Connection, OptionalExtension, Params, TransactionBehavior};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::fs;
 use std::io::{self, Read};
 use dirs;
 
+use crate::biometric_crypto;
+use crate::config_migrations;
+use crate::data_backups;
+use crate::data_lock;
+use crate::patient_audit;
+use crate::patient_history;
+use crate::patient_validation::{FieldError, PatientValidator, ValidationFailed};
+use crate::photo_thumbnails;
+use crate::portable;
+use crate::workspaces;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DigitalBiometric {
     pub finger: String,
     pub data: String,
 }
 
+// Angle a facial photo was taken from; liveness checks ask for several
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FaceAngle {
+    #[default]
+    Frontal,
+    Left,
+    Right,
+    WithMask,
+}
+
+impl FaceAngle {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FaceAngle::Frontal => "frontal",
+            FaceAngle::Left => "left",
+            FaceAngle::Right => "right",
+            FaceAngle::WithMask => "with-mask",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        [FaceAngle::Frontal, FaceAngle::Left, FaceAngle::Right, FaceAngle::WithMask]
+            .into_iter()
+            .find(|angle| angle.as_str() == text.trim())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FacialImage {
+    #[serde(default)]
+    pub label: FaceAngle,
+    pub data: String,
+    // JPEG made from `data` when the patient is saved; see `photo_thumbnails`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub thumbnail: String,
+}
+
+// Records from before several photos were kept have a single base64 string,
+// which becomes the frontal photo
+fn deserialize_facial_images<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<FacialImage>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Single(String),
+        Many(Vec<FacialImage>),
+    }
+    Ok(match Option::<Stored>::deserialize(deserializer)? {
+        Some(Stored::Many(images)) => images,
+        Some(Stored::Single(data)) if !data.is_empty() => vec![FacialImage {
+            label: FaceAngle::Frontal,
+            data,
+            thumbnail: String::new(),
+        }],
+        _ => Vec::new(),
+    })
+}
+
+// Document stored in the blob store (order PDF, consent form...)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: u32,
+    pub file_name: String,
+    pub mime_type: String,
+    pub size: u64,
+    pub blob_key: String,
+    pub added_at: u64,
+}
+
+// One biometry server verification attributed to this patient
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationEntry {
+    pub timestamp: u64,
+    pub endpoint: String,
+    pub matched: bool,
+    pub score: u8,
+}
+
+// What a check-in of the patient should end in
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpectedCheckin {
+    Approved,
+    Denied,
+    // Needs an authorization before the attendance
+    PendingAuthorization,
+}
+
+// Plan situation the test case assumes in TOTVS
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanStatus {
+    Active,
+    Suspended,
+    Cancelled,
+    // Inadimplente
+    Defaulting,
+}
+
+// What the patient's test case is meant to prove. Documentation only: the
+// emulator doesn't act on it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    pub expected_checkin: Option<ExpectedCheckin>,
+    pub plan_status: Option<PlanStatus>,
+    // Em carência
+    pub grace_period: bool,
+    // Cobertura parcial temporária (CPT)
+    pub partial_coverage: bool,
+    // Procedures still under carência, e.g. "consultas", "internação"
+    pub grace_procedures: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Patient {
+    // Assigned by the store; ignored by `add_patient`
+    #[serde(default)]
     pub id: u32,
     pub name: String,
     pub wallet: String,
-    pub facial_biometric: String,
+    // At most one photo per label
+    #[serde(default, alias = "facial_biometric", deserialize_with = "deserialize_facial_images")]
+    pub facial_biometrics: Vec<FacialImage>,
     pub digital_biometrics: Vec<DigitalBiometric>,
     pub imported: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub verification_history: Vec<VerificationEntry>,
+    // Free text about the test case
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub scenario: Scenario,
+    // Incremented on every change; `update_patient` refuses a stale copy
+    #[serde(default)]
+    pub version: u32,
+}
+
+impl Patient {
+    pub fn facial_photo(&self, label: FaceAngle) -> Option<&str> {
+        self.facial_biometrics
+            .iter()
+            .find(|image| image.label == label && !image.data.is_empty())
+            .map(|image| image.data.as_str())
+    }
+
+    // The photo sent where a single one is expected (TOTVS, ANSI/NIST export,
+    // the webcam by default): the frontal one, or else the first
+    pub fn main_photo(&self) -> &str {
+        self.facial_photo(FaceAngle::Frontal)
+            .or_else(|| self.facial_biometrics.iter().map(|image| image.data.as_str()).find(|data| !data.is_empty()))
+            .unwrap_or_default()
+    }
+
+    pub fn has_facial_photo(&self) -> bool {
+        !self.main_photo().is_empty()
+    }
+
+    // Replaces the photo with this label; empty `data` removes it
+    pub fn set_facial_photo(&mut self, label: FaceAngle, data: String) {
+        self.facial_biometrics.retain(|image| image.label != label);
+        if !data.is_empty() {
+            self.facial_biometrics.push(FacialImage { label, data, thumbnail: String::new() });
+        }
+    }
+}
+
+// Selects patients for bulk edits; every criterion given must match
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PatientFilter {
+    pub ids: Option<Vec<u32>>,
+    pub imported: Option<bool>,
+    pub tag: Option<String>,
+    pub name_contains: Option<String>,
+    pub wallet_prefix: Option<String>,
+    pub notes_contains: Option<String>,
+    pub expected_checkin: Option<ExpectedCheckin>,
+    pub plan_status: Option<PlanStatus>,
+    pub grace_period: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PatientPatch {
+    pub imported: Option<bool>,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+    pub clear_facial_biometric: bool,
+    pub clear_digital_biometrics: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct BulkUpdateSummary {
+    pub matched: usize,
+    pub updated: usize,
+    pub updated_ids: Vec<u32>,
+}
+
+// Case folding shared by `PatientFilter::matches` and FILTER_SQL (as `fold_case`),
+// so both agree on accented names
+fn fold_case(text: &str) -> String {
+    text.to_lowercase()
 }
 
+impl PatientFilter {
+    pub fn matches(&self, patient: &Patient) -> bool {
+        self.ids.as_ref().is_none_or(|ids| ids.contains(&patient.id))
+            && self.imported.is_none_or(|imported| patient.imported == imported)
+            && self.tag.as_ref().is_none_or(|tag| patient.tags.iter().any(|t| t.trim() == tag.trim()))
+            && self
+                .name_contains
+                .as_ref()
+                .is_none_or(|name| fold_case(&patient.name).contains(&fold_case(name.trim())))
+            && self
+                .wallet_prefix
+                .as_ref()
+                .is_none_or(|prefix| patient.wallet.starts_with(prefix.trim()))
+            && self
+                .notes_contains
+                .as_ref()
+                .is_none_or(|notes| fold_case(&patient.notes).contains(&fold_case(notes.trim())))
+            && self.expected_checkin.is_none_or(|expected| patient.scenario.expected_checkin == Some(expected))
+            && self.plan_status.is_none_or(|status| patient.scenario.plan_status == Some(status))
+            && self.grace_period.is_none_or(|grace| patient.scenario.grace_period == grace)
+    }
+}
+
+impl PatientPatch {
+    // Returns whether the patient actually changed
+    pub fn apply(&self, patient: &mut Patient) -> bool {
+        let mut changed = false;
+
+        if let Some(imported) = self.imported {
+            changed |= patient.imported != imported;
+            patient.imported = imported;
+        }
+        for tag in &self.add_tags {
+            let tag = tag.trim();
+            if !tag.is_empty() && !patient.tags.iter().any(|t| t == tag) {
+                patient.tags.push(tag.to_string());
+                changed = true;
+            }
+        }
+        let before = patient.tags.len();
+        patient.tags.retain(|t| !self.remove_tags.contains(t));
+        changed |= patient.tags.len() != before;
+
+        if self.clear_facial_biometric && !patient.facial_biometrics.is_empty() {
+            patient.facial_biometrics.clear();
+            changed = true;
+        }
+        if self.clear_digital_biometrics && !patient.digital_biometrics.is_empty() {
+            patient.digital_biometrics.clear();
+            changed = true;
+        }
+
+        changed
+    }
+}
+
+// Applies the patch to every matching patient and saves once, so either all
+// edits land on disk or none do.
+pub fn bulk_update(
+    app_handle: &tauri::AppHandle,
+    filter: &PatientFilter,
+    patch: &PatientPatch,
+) -> io::Result<BulkUpdateSummary> {
+    let mut patients = load_patients_from_disk(app_handle)?;
+    let mut summary = BulkUpdateSummary::default();
+
+    for patient in patients.iter_mut().filter(|p| filter.matches(p)) {
+        summary.matched += 1;
+        if patch.apply(patient) {
+            summary.updated += 1;
+            summary.updated_ids.push(patient.id);
+        }
+    }
+
+    if summary.updated > 0 {
+        save_patients_to_disk(app_handle, &patients)?;
+    }
+    Ok(summary)
+}
+
+// <exe dir>/data in portable mode, VirtualIOHub in the user's data dir otherwise
 pub fn ensure_data_dir(_app_handle: &tauri::AppHandle) -> io::Result<PathBuf> {
-    let mut dir = dirs::data_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "data dir not found"))?;
-    dir.push("VirtualIOHub");
+    let dir = match portable::data_dir() {
+        Some(dir) => dir,
+        None => dirs::data_dir()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "data dir not found"))?
+            .join("VirtualIOHub"),
+    };
     if !dir.exists() {
         fs::create_dir_all(&dir)?;
     }
     Ok(dir)
 }
 
+// Where patients were kept before the SQLite database; read once to migrate
 pub fn patients_file_path(app_handle: &tauri::AppHandle) -> io::Result<PathBuf> {
     let mut dir = ensure_data_dir(app_handle)?;
     dir.push("patients.json");
     Ok(dir)
 }
 
+// The active workspace's database
+pub fn database_path(app_handle: &tauri::AppHandle) -> io::Result<PathBuf> {
+    workspaces::database_path(app_handle, &workspaces::active(app_handle))
+}
+
 pub fn config_file_path(app_handle: &tauri::AppHandle) -> io::Result<PathBuf> {
     let mut dir = ensure_data_dir(app_handle)?;
     dir.push("app_config.json");
     Ok(dir)
 }
 
-pub fn load_patients_from_disk(app_handle: &tauri::AppHandle) -> io::Result<Vec<Patient>> {
-    let path = patients_file_path(app_handle)?;
+fn db_error(error: rusqlite::Error) -> io::Error {
+    io::Error::other(error)
+}
+
+// A save or delete based on a copy older than the stored patient
+#[derive(Debug)]
+pub struct VersionConflict(pub String);
+
+impl std::fmt::Display for VersionConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for VersionConflict {}
+
+fn version_conflict(message: String) -> io::Error {
+    io::Error::other(VersionConflict(message))
+}
+
+// One step of the database format. `PRAGMA user_version` holds how many
+// steps were applied, so an older database runs only the ones it is missing,
+// in order, each in its own transaction.
+struct Migration {
+    description: &'static str,
+    // Keep a copy of the database as it was before the step
+    backup: bool,
+    run: fn(&tauri::AppHandle, &Connection) -> io::Result<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "importa patients.json ou cria os pacientes de exemplo",
+        backup: false,
+        run: import_legacy_patients,
+    },
+    // No backup: the copy would keep the plaintext this step exists to remove
+    Migration {
+        description: "criptografa as biometrias",
+        backup: false,
+        run: encrypt_biometrics,
+    },
+    Migration {
+        description: "gera miniaturas das fotos",
+        backup: true,
+        run: generate_thumbnails,
+    },
+];
+// Index of "criptografa as biometrias"
+const ENCRYPT_STEP: usize = 1;
+
+pub(crate) fn open_database(app_handle: &tauri::AppHandle) -> io::Result<Connection> {
+    open_database_at(app_handle, &database_path(app_handle)?)
+}
+
+// Each patient is one row: the whole record as JSON in `data`, with name and
+// wallet copied to indexed columns for lookups.
+fn create_schema(conn: &Connection) -> io::Result<()> {
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA busy_timeout = 3000;
+         PRAGMA secure_delete = ON;
+         CREATE TABLE IF NOT EXISTS patients (
+             id INTEGER PRIMARY KEY,
+             name TEXT NOT NULL COLLATE NOCASE,
+             wallet TEXT NOT NULL,
+             data TEXT NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS patients_wallet ON patients (wallet);
+         CREATE INDEX IF NOT EXISTS patients_name ON patients (name);",
+    )
+    .map_err(db_error)?;
+    conn.create_scalar_function(
+        "fold_case",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| Ok(ctx.get::<Option<String>>(0)?.map(|text| fold_case(&text))),
+    )
+    .map_err(db_error)?;
+    patient_audit::create_table(conn)
+}
+
+pub(crate) fn open_database_at(app_handle: &tauri::AppHandle, path: &Path) -> io::Result<Connection> {
+    let mut conn = Connection::open(path).map_err(db_error)?;
+    create_schema(&conn)?;
+    if user_version(&conn)? < MIGRATIONS.len() {
+        migrate(app_handle, &mut conn, path)?;
+    }
+    Ok(conn)
+}
+
+fn user_version(conn: &Connection) -> io::Result<usize> {
+    let version: i64 = conn.query_row("PRAGMA user_version", params![], |row| row.get(0)).map_err(db_error)?;
+    Ok(version.max(0) as usize)
+}
+
+// A database with no patients, already at the latest version so the demo
+// patients aren't created
+pub(crate) fn create_empty_database(path: &Path) -> io::Result<()> {
+    let conn = Connection::open(path).map_err(db_error)?;
+    create_schema(&conn)?;
+    conn.pragma_update(None, "user_version", MIGRATIONS.len() as i64).map_err(db_error)
+}
+
+fn migrate(app_handle: &tauri::AppHandle, conn: &mut Connection, path: &Path) -> io::Result<()> {
+    data_lock::with_write_lock(app_handle, || {
+        // Another window may have migrated since the version was first read
+        let from = user_version(conn)?;
+        if from == 0 {
+            backup_legacy_patients(app_handle)?;
+        }
+        for (index, step) in MIGRATIONS.iter().enumerate().skip(from) {
+            tracing::info!("Migrando banco de pacientes para a versão {}: {}", index + 1, step.description);
+            if step.backup && index > 0 {
+                backup_database(conn, path, index)?;
+            }
+            // IMMEDIATE takes SQLite's write lock up front, so a process
+            // that bypasses the data lock can't run the same step alongside
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(db_error)?;
+            if user_version(&tx)? > index {
+                continue;
+            }
+            (step.run)(app_handle, &tx)?;
+            tx.pragma_update(None, "user_version", (index + 1) as i64).map_err(db_error)?;
+            tx.commit().map_err(db_error)?;
+            data_lock::mark_own_write(path);
+        }
+        // The plaintext the encryption replaced lingers in free pages and the
+        // WAL otherwise; secure_delete only covers what's deleted from now on
+        if from <= ENCRYPT_STEP {
+            conn.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);").map_err(db_error)?;
+            data_lock::mark_own_write(path);
+        }
+
+        // Plaintext left by earlier versions, now in the database
+        let json_path = patients_file_path(app_handle)?;
+        for path in [json_path.clone(), json_path.with_extension("json.migrated")] {
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+// patients.json as it was before the database, kept as patients.json.v0.bak.
+// The whole file is encrypted like the biometrics it holds; decrypting it
+// with the same key gives back the original contents.
+fn backup_legacy_patients(app_handle: &tauri::AppHandle) -> io::Result<()> {
+    let json_path = patients_file_path(app_handle)?;
+    let source = [json_path.clone(), json_path.with_extension("json.migrated")]
+        .into_iter()
+        .find(|path| path.exists());
+    let Some(source) = source else {
+        return Ok(());
+    };
+    let backup = json_path.with_extension("json.v0.bak");
+    if backup.exists() {
+        return Ok(());
+    }
+    let contents = fs::read_to_string(&source)?;
+    data_lock::write_atomic(&backup, biometric_crypto::encrypt(&contents)?.as_bytes())
+}
+
+// patients.db.v<version>.bak, overwritten if a previous attempt left one
+fn backup_database(conn: &Connection, db_path: &Path, version: usize) -> io::Result<()> {
+    let path = db_path.with_extension(format!("db.v{}.bak", version));
+    if path.exists() {
+        fs::remove_file(&path)?;
+    }
+    conn.execute("VACUUM INTO ?1", [path.to_string_lossy().into_owned()]).map_err(db_error)?;
+    Ok(())
+}
+
+// Early patients.json files named the name field differently and lacked the
+// fields added since; both are fixed before deserializing
+fn upgrade_legacy_patient(value: &mut serde_json::Value) {
+    let Some(patient) = value.as_object_mut() else {
+        return;
+    };
+    if !patient.contains_key("name") {
+        if let Some(name) = ["full_name", "fullName", "nome"].iter().find_map(|key| patient.remove(*key)) {
+            patient.insert("name".into(), name);
+        }
+    }
+    for (key, default) in [
+        ("name", serde_json::json!("")),
+        ("wallet", serde_json::json!("")),
+        ("digital_biometrics", serde_json::json!([])),
+        ("imported", serde_json::json!(false)),
+    ] {
+        if patient.get(key).is_none_or(|v| v.is_null()) {
+            patient.insert(key.into(), default);
+        }
+    }
+}
+
+// 0 -> 1: patients.json is copied in, or the demo patients are created if
+// there's none
+fn import_legacy_patients(app_handle: &tauri::AppHandle, conn: &Connection) -> io::Result<()> {
+    let json_path = patients_file_path(app_handle)?;
+    let patients: Vec<Patient> = if json_path.exists() {
+        let mut contents = String::new();
+        fs::File::open(&json_path)?.read_to_string(&mut contents)?;
+        let mut values: Vec<serde_json::Value> = serde_json::from_str(&contents)?;
+        values.iter_mut().for_each(upgrade_legacy_patient);
+        values.into_iter().map(serde_json::from_value).collect::<Result<_, _>>()?
+    } else {
+        // Never over patients already stored
+        let stored: i64 = conn.query_row("SELECT COUNT(*) FROM patients", params![], |row| row.get(0)).map_err(db_error)?;
+        if stored > 0 {
+            return Ok(());
+        }
+        default_patients()
+    };
+    for patient in &patients {
+        upsert(conn, patient)?;
+    }
+    Ok(())
+}
+
+// 1 -> 2: every row is rewritten, which encrypts it
+fn encrypt_biometrics(_app_handle: &tauri::AppHandle, conn: &Connection) -> io::Result<()> {
+    for patient in query_patients(conn, "SELECT data FROM patients", params![])? {
+        upsert(conn, &patient)?;
+    }
+    Ok(())
+}
+
+// 2 -> 3: photos stored before thumbnails existed get one
+fn generate_thumbnails(_app_handle: &tauri::AppHandle, conn: &Connection) -> io::Result<()> {
+    for mut patient in query_patients(conn, "SELECT data FROM patients", params![])? {
+        photo_thumbnails::refresh(&mut patient, None);
+        upsert(conn, &patient)?;
+    }
+    Ok(())
+}
+
+// Unchanged rows aren't rewritten. Biometrics are encrypted here and
+// decrypted when read, so the rest of the app only sees plaintext.
+fn upsert(conn: &Connection, patient: &Patient) -> io::Result<()> {
+    let data = serde_json::to_string(&biometric_crypto::encrypt_patient(patient)?)?;
+    conn.execute(
+        "INSERT INTO patients (id, name, wallet, data) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (id) DO UPDATE SET name = excluded.name, wallet = excluded.wallet, data = excluded.data
+         WHERE patients.data != excluded.data",
+        params![patient.id, patient.name, patient.wallet, data],
+    )
+    .map_err(db_error)?;
+    Ok(())
+}
+
+fn query_patients(conn: &Connection, sql: &str, params: impl Params) -> io::Result<Vec<Patient>> {
+    let mut stmt = conn.prepare(sql).map_err(db_error)?;
+    let rows: Vec<String> = stmt
+        .query_map(params, |row| row.get(0))
+        .map_err(db_error)?
+        .collect::<Result<_, _>>()
+        .map_err(db_error)?;
+    rows.iter().map(|data| from_row(data)).collect()
+}
+
+fn from_row(data: &str) -> io::Result<Patient> {
+    biometric_crypto::decrypt_patient(serde_json::from_str(data)?)
+}
+
+// Opens a database file as it is, without setup or migrations, and checks
+// that SQLite finds it intact and that every patient in it can be read.
+// Returns the number of patients.
+pub fn verify_database(path: &Path) -> io::Result<usize> {
     if !path.exists() {
-        // create with defaults
-        let default = default_patients();
-        save_patients_to_disk(app_handle, &default)?;
-        return Ok(default);
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} não existe.", path.display())));
     }
-    let mut file = fs::File::open(path)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-    let patients: Vec<Patient> = serde_json::from_str(&contents)?;
-    Ok(patients)
+    let conn = Connection::open(path).map_err(db_error)?;
+    let status: String = conn.query_row("PRAGMA integrity_check", params![], |row| row.get(0)).map_err(db_error)?;
+    if status != "ok" {
+        return Err(io::Error::other(format!("Banco de pacientes corrompido: {}", status)));
+    }
+    Ok(query_patients(&conn, "SELECT data FROM patients", params![])?.len())
+}
+
+pub fn load_patients_from_disk(app_handle: &tauri::AppHandle) -> io::Result<Vec<Patient>> {
+    load_patients_at(app_handle, &database_path(app_handle)?)
+}
+
+// Patients of a database other than the active workspace's
+pub fn load_patients_at(app_handle: &tauri::AppHandle, path: &Path) -> io::Result<Vec<Patient>> {
+    let conn = open_database_at(app_handle, path)?;
+    query_patients(&conn, "SELECT data FROM patients ORDER BY id", params![])
+}
+
+fn stored_patient(conn: &Connection, id: u32) -> io::Result<Option<Patient>> {
+    let data: Option<String> = conn
+        .query_row("SELECT data FROM patients WHERE id = ?1", [id], |row| row.get(0))
+        .optional()
+        .map_err(db_error)?;
+    data.map(|data| from_row(&data)).transpose()
+}
+
+// A patient as it was before and after a committed mutation; None when it
+// didn't exist (created) or no longer exists (deleted). Kept by
+// patient_history for undo.
+pub struct PatientChange {
+    pub before: Option<Patient>,
+    pub after: Option<Patient>,
+}
+
+// Runs `write` in one transaction while holding the data dir lock. A
+// snapshot of the database is taken first when the last one is old enough.
+fn write_transaction<T>(app_handle: &tauri::AppHandle, write: impl FnOnce(&Connection) -> io::Result<T>) -> io::Result<T> {
+    let path = database_path(app_handle)?;
+    let mut conn = open_database_at(app_handle, &path)?;
+    data_lock::with_write_lock(app_handle, || {
+        data_backups::snapshot_patients(app_handle, &conn);
+        let tx = conn.transaction().map_err(db_error)?;
+        let result = write(&tx)?;
+        tx.commit().map_err(db_error)?;
+        data_lock::mark_own_write(&path);
+        Ok(result)
+    })
 }
 
 pub fn save_patients_to_disk(app_handle: &tauri::AppHandle, patients: &Vec<Patient>) -> io::Result<()> {
-    let path = patients_file_path(app_handle)?;
-    let json = serde_json::to_string_pretty(patients)?;
-    fs::write(path, json)
+    save_patients_from(app_handle, patients, "save", SaveMode::Edit).map(|_| ())
+}
+
+// What a save of the whole list means
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveMode {
+    // Edits of copies loaded earlier: a patient whose version isn't the
+    // stored one is refused, and stored patients missing from the list are
+    // kept (they may have been added elsewhere since)
+    Edit,
+    // Imports: the list replaces the store, whatever versions it carries.
+    // Invalid patients are left out (a stored one stays as it was) and
+    // returned instead of failing the import.
+    Import,
+    // Backups: the list replaces the store as it is, unvalidated, so data
+    // saved under older rules can still be brought back
+    Restore,
+}
+
+// Inserts or updates the patients of the list in one transaction, deleting
+// the ones missing from it outside `SaveMode::Edit`. A patient that changed
+// gets the stored version plus one. `source` tells the audit log what made
+// the change. Patients that changed are validated as `mode` says: in an
+// edit nothing is saved if any of them fails. Returns the errors of the
+// patients an import left out.
+pub fn save_patients_from(
+    app_handle: &tauri::AppHandle,
+    patients: &Vec<Patient>,
+    source: &str,
+    mode: SaveMode,
+) -> io::Result<Vec<FieldError>> {
+    let validator = PatientValidator::new(app_handle);
+    let (changes, skipped) = write_transaction(app_handle, |tx| {
+        let mut stored: HashMap<u32, Patient> = query_patients(tx, "SELECT data FROM patients", params![])?
+            .into_iter()
+            .map(|p| (p.id, p))
+            .collect();
+        let mut invalid: Vec<FieldError> = Vec::new();
+        let mut skipped: Vec<FieldError> = Vec::new();
+        let mut changes = Vec::new();
+        for patient in patients {
+            let mut patient = patient.clone();
+            let old = stored.remove(&patient.id);
+            photo_thumbnails::restore_omitted(&mut patient, old.as_ref());
+            photo_thumbnails::refresh(&mut patient, old.as_ref());
+            let changed = match &old {
+                Some(old) => {
+                    if mode == SaveMode::Edit && patient.version != old.version {
+                        return Err(version_conflict(format!(
+                            "Paciente {} foi alterado em outra janela (versão {}, esta cópia é a {}). Recarregue antes de salvar.",
+                            patient.id, old.version, patient.version
+                        )));
+                    }
+                    patient.version = old.version;
+                    serde_json::to_string(&patient)? != serde_json::to_string(old)?
+                }
+                None => {
+                    patient.version = patient.version.max(1);
+                    true
+                }
+            };
+            if changed {
+                if old.is_some() {
+                    patient.version += 1;
+                }
+                match mode {
+                    SaveMode::Edit => invalid.extend(validator.validate(&patient)),
+                    SaveMode::Import => {
+                        let errors = validator.validate(&patient);
+                        if !errors.is_empty() {
+                            skipped.extend(errors);
+                            continue;
+                        }
+                    }
+                    SaveMode::Restore => {}
+                }
+            }
+            patient_audit::record(tx, source, old.as_ref(), Some(&patient))?;
+            upsert(tx, &patient)?;
+            if changed {
+                changes.push(PatientChange { before: old, after: Some(patient) });
+            }
+        }
+        // Dropping the transaction rolls back what was written above
+        if !invalid.is_empty() {
+            return Err(ValidationFailed(invalid).into());
+        }
+        if mode != SaveMode::Edit {
+            for (id, old) in stored {
+                patient_audit::record(tx, source, Some(&old), None)?;
+                tx.execute("DELETE FROM patients WHERE id = ?1", [id]).map_err(db_error)?;
+                changes.push(PatientChange { before: Some(old), after: None });
+            }
+        }
+        Ok((changes, skipped))
+    })?;
+    patient_history::record(app_handle, source, changes);
+    Ok(skipped)
+}
+
+pub fn get_patient(app_handle: &tauri::AppHandle, id: u32) -> io::Result<Option<Patient>> {
+    stored_patient(&open_database(app_handle)?, id)
+}
+
+// Inserts with the next free id and version 1
+pub fn add_patient(app_handle: &tauri::AppHandle, mut patient: Patient) -> io::Result<Patient> {
+    photo_thumbnails::restore_omitted(&mut patient, None);
+    PatientValidator::new(app_handle).check(&patient)?;
+    photo_thumbnails::refresh(&mut patient, None);
+    let patient = write_transaction(app_handle, |tx| {
+        let max_id: Option<u32> = tx
+            .query_row("SELECT MAX(id) FROM patients", params![], |row| row.get(0))
+            .map_err(db_error)?;
+        let patient = Patient {
+            id: max_id.unwrap_or(0) + 1,
+            version: 1,
+            ..patient
+        };
+        patient_audit::record(tx, "add_patient", None, Some(&patient))?;
+        upsert(tx, &patient)?;
+        Ok(patient)
+    })?;
+    patient_history::record(app_handle, "add_patient", vec![PatientChange { before: None, after: Some(patient.clone()) }]);
+    Ok(patient)
+}
+
+// Replaces the stored patient only if `patient.version` is the stored one,
+// so an edit based on an outdated copy doesn't overwrite someone else's
+pub fn update_patient(app_handle: &tauri::AppHandle, mut patient: Patient) -> io::Result<Patient> {
+    let validator = PatientValidator::new(app_handle);
+    let (stored, patient) = write_transaction(app_handle, |tx| {
+        let stored = stored_patient(tx, patient.id)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Paciente {} não encontrado.", patient.id)))?;
+        if stored.version != patient.version {
+            return Err(version_conflict(format!(
+                "Paciente {} foi alterado em outra janela (versão {}, esta cópia é a {}). Recarregue antes de salvar.",
+                patient.id, stored.version, patient.version
+            )));
+        }
+        photo_thumbnails::restore_omitted(&mut patient, Some(&stored));
+        validator.check(&patient)?;
+        photo_thumbnails::refresh(&mut patient, Some(&stored));
+        let patient = Patient {
+            version: stored.version + 1,
+            ..patient
+        };
+        patient_audit::record(tx, "update_patient", Some(&stored), Some(&patient))?;
+        upsert(tx, &patient)?;
+        Ok((stored, patient))
+    })?;
+    patient_history::record(app_handle, "update_patient", vec![PatientChange { before: Some(stored), after: Some(patient.clone()) }]);
+    Ok(patient)
+}
+
+// Applies `edit` to the stored patient inside one write transaction, for
+// changes made by the app itself (e.g. appending to the verification history)
+// that must not race a save of the whole list. Unvalidated, and left out of
+// the undo history. Returns what `edit` returned.
+pub fn modify_patient<T>(
+    app_handle: &tauri::AppHandle,
+    id: u32,
+    source: &str,
+    edit: impl FnOnce(&mut Patient) -> T,
+) -> io::Result<T> {
+    write_transaction(app_handle, |tx| {
+        let stored = stored_patient(tx, id)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Paciente {} não encontrado.", id)))?;
+        let mut patient = stored.clone();
+        let result = edit(&mut patient);
+        patient.version = stored.version + 1;
+        patient_audit::record(tx, source, Some(&stored), Some(&patient))?;
+        upsert(tx, &patient)?;
+        Ok(result)
+    })
+}
+
+// With `version`, the delete is refused if the patient changed since then
+pub fn delete_patient(app_handle: &tauri::AppHandle, id: u32, version: Option<u32>) -> io::Result<()> {
+    let stored = write_transaction(app_handle, |tx| {
+        let stored = stored_patient(tx, id)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Paciente {} não encontrado.", id)))?;
+        if let Some(version) = version.filter(|v| *v != stored.version) {
+            return Err(version_conflict(format!(
+                "Paciente {} foi alterado em outra janela (versão {}, esta cópia é a {}). Recarregue antes de excluir.",
+                id, stored.version, version
+            )));
+        }
+        patient_audit::record(tx, "delete_patient", Some(&stored), None)?;
+        tx.execute("DELETE FROM patients WHERE id = ?1", [id]).map_err(db_error)?;
+        Ok(stored)
+    })?;
+    patient_history::record(app_handle, "delete_patient", vec![PatientChange { before: Some(stored), after: None }]);
+    Ok(())
+}
+
+// Deletes the patients with these ids in one transaction, as one undoable
+// change; ids not stored are ignored. Returns the deleted patients.
+pub fn delete_patients(app_handle: &tauri::AppHandle, ids: &[u32], source: &str) -> io::Result<Vec<Patient>> {
+    let deleted = write_transaction(app_handle, |tx| {
+        let mut deleted = Vec::new();
+        for id in ids {
+            if let Some(stored) = stored_patient(tx, *id)? {
+                patient_audit::record(tx, source, Some(&stored), None)?;
+                tx.execute("DELETE FROM patients WHERE id = ?1", [id]).map_err(db_error)?;
+                deleted.push(stored);
+            }
+        }
+        Ok(deleted)
+    })?;
+    let changes = deleted.iter().map(|p| PatientChange { before: Some(p.clone()), after: None }).collect();
+    patient_history::record(app_handle, source, changes);
+    Ok(deleted)
+}
+
+// Replaces the kept patient with `merged` and deletes `removed`, provided
+// neither changed since they were read
+pub fn merge_patient_records(app_handle: &tauri::AppHandle, merged: Patient, removed: &Patient) -> io::Result<Patient> {
+    PatientValidator::new(app_handle).check(&merged)?;
+    let (changes, merged) = write_transaction(app_handle, |tx| {
+        let mut current = Vec::new();
+        for (id, version) in [(merged.id, merged.version), (removed.id, removed.version)] {
+            let stored = stored_patient(tx, id)?
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Paciente {} não encontrado.", id)))?;
+            if stored.version != version {
+                return Err(version_conflict(format!(
+                    "Paciente {} foi alterado durante a mesclagem. Tente novamente.",
+                    id
+                )));
+            }
+            current.push(stored);
+        }
+        let mut merged = Patient {
+            version: merged.version + 1,
+            ..merged
+        };
+        photo_thumbnails::refresh(&mut merged, Some(&current[0]));
+        patient_audit::record_merge(tx, &current[0], &merged, &current[1])?;
+        upsert(tx, &merged)?;
+        tx.execute("DELETE FROM patients WHERE id = ?1", [removed.id]).map_err(db_error)?;
+        let mut current = current.into_iter();
+        let changes = vec![
+            PatientChange { before: current.next(), after: Some(merged.clone()) },
+            PatientChange { before: current.next(), after: None },
+        ];
+        Ok((changes, merged))
+    })?;
+    patient_history::record(app_handle, "merge_patients", changes);
+    Ok(merged)
+}
+
+// Puts every patient of `changes` back as it was before, newest change
+// first, and returns the changes that would redo it. Refused when a patient
+// is no longer as the change left it. Restored patients get a new version,
+// so copies open elsewhere still see the conflict.
+pub fn revert_changes(app_handle: &tauri::AppHandle, changes: &[PatientChange], source: &str) -> io::Result<Vec<PatientChange>> {
+    write_transaction(app_handle, |tx| {
+        let mut reverted = Vec::new();
+        for change in changes.iter().rev() {
+            let id = change.after.as_ref().or(change.before.as_ref()).map_or(0, |p| p.id);
+            let current = stored_patient(tx, id)?;
+            let unchanged = match (&current, &change.after) {
+                (Some(current), Some(after)) => current.version == after.version,
+                (None, None) => true,
+                _ => false,
+            };
+            if !unchanged {
+                return Err(version_conflict(format!("Paciente {} foi alterado depois disso.", id)));
+            }
+            let restored = change.before.clone().map(|before| Patient {
+                version: current.as_ref().map_or(before.version, |c| c.version) + 1,
+                ..before
+            });
+            patient_audit::record(tx, source, current.as_ref(), restored.as_ref())?;
+            match &restored {
+                Some(patient) => upsert(tx, patient)?,
+                None => {
+                    tx.execute("DELETE FROM patients WHERE id = ?1", [id]).map_err(db_error)?;
+                }
+            }
+            reverted.push(PatientChange { before: current, after: restored });
+        }
+        Ok(reverted)
+    })
+}
+
+pub fn find_patient_by_wallet(app_handle: &tauri::AppHandle, wallet: &str) -> io::Result<Option<Patient>> {
+    let conn = open_database(app_handle)?;
+    let data: Option<String> = conn
+        .query_row("SELECT data FROM patients WHERE wallet = ?1 ORDER BY id LIMIT 1", [wallet.trim()], |row| row.get(0))
+        .optional()
+        .map_err(db_error)?;
+    data.map(|data| from_row(&data)).transpose()
+}
+
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+// Names starting with `prefix`, ignoring case; served by the name index
+pub fn search_patients_by_name(app_handle: &tauri::AppHandle, prefix: &str) -> io::Result<Vec<Patient>> {
+    let conn = open_database(app_handle)?;
+    let pattern = format!("{}%", escape_like(prefix.trim()));
+    query_patients(&conn, "SELECT data FROM patients WHERE name LIKE ?1 ESCAPE '\\' ORDER BY name", [pattern])
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    Id,
+    Name,
+    Wallet,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PatientSort {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PatientPage {
+    pub items: Vec<Patient>,
+    // Patients matching the filter, over all pages
+    pub total: u64,
+    pub page: u32,
+    pub page_size: u32,
+}
+
+pub const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+
+// Same criteria as `PatientFilter::matches`, evaluated by SQLite so only the
+// requested page is read and decrypted. Tags, the imported flag, notes and
+// scenario are read from the JSON in `data`; rows saved before the scenario
+// existed have none, which `PatientFilter` reads as not in carência.
+const FILTER_SQL: &str = "(?1 IS NULL OR id IN (SELECT value FROM json_each(?1)))
+     AND (?2 IS NULL OR json_extract(data, '$.imported') = ?2)
+     AND (?3 IS NULL OR EXISTS (SELECT 1 FROM json_each(data, '$.tags') WHERE trim(value) = ?3))
+     AND (?4 IS NULL OR instr(fold_case(name), ?4) > 0)
+     AND (?5 IS NULL OR substr(wallet, 1, length(?5)) = ?5)
+     AND (?6 IS NULL OR instr(fold_case(COALESCE(json_extract(data, '$.notes'), '')), ?6) > 0)
+     AND (?7 IS NULL OR json_extract(data, '$.scenario.expected_checkin') = ?7)
+     AND (?8 IS NULL OR json_extract(data, '$.scenario.plan_status') = ?8)
+     AND (?9 IS NULL OR COALESCE(json_extract(data, '$.scenario.grace_period'), 0) = ?9)";
+
+// `page` starts at 1
+pub fn query_patient_page(
+    app_handle: &tauri::AppHandle,
+    filter: &PatientFilter,
+    sort: &PatientSort,
+    page: u32,
+    page_size: u32,
+) -> io::Result<PatientPage> {
+    let conn = open_database(app_handle)?;
+    let page = page.max(1);
+    let page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+
+    let ids = filter.ids.as_ref().map(serde_json::to_string).transpose()?;
+    let imported = filter.imported;
+    let tag = filter.tag.as_deref().map(str::trim);
+    let name = filter.name_contains.as_deref().map(|n| fold_case(n.trim()));
+    let wallet = filter.wallet_prefix.as_deref().map(str::trim);
+    let notes = filter.notes_contains.as_deref().map(|n| fold_case(n.trim()));
+    let expected_checkin = filter.expected_checkin.map(serde_json::to_value).transpose()?;
+    let expected_checkin = expected_checkin.as_ref().and_then(|v| v.as_str());
+    let plan_status = filter.plan_status.map(serde_json::to_value).transpose()?;
+    let plan_status = plan_status.as_ref().and_then(|v| v.as_str());
+    let grace_period = filter.grace_period;
+
+    let total: u64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM patients WHERE {}", FILTER_SQL),
+            params![ids, imported, tag, name, wallet, notes, expected_checkin, plan_status, grace_period],
+            |row| row.get(0),
+        )
+        .map_err(db_error)?;
+    let column = match sort.field {
+        SortField::Id => "id",
+        SortField::Name => "name",
+        SortField::Wallet => "wallet",
+    };
+    let direction = if sort.descending { "DESC" } else { "ASC" };
+    let sql = format!(
+        "SELECT data FROM patients WHERE {} ORDER BY {} {}, id {} LIMIT ?10 OFFSET ?11",
+        FILTER_SQL, column, direction, direction
+    );
+    let offset = (page as u64 - 1) * page_size as u64;
+    let items = query_patients(&conn, &sql, params![ids, imported, tag, name, wallet, notes, expected_checkin, plan_status, grace_period, page_size, offset])?;
+    Ok(PatientPage { items, total, page, page_size })
 }
 
 pub fn load_config_from_disk(app_handle: &tauri::AppHandle) -> io::Result<serde_json::Value> {
     let path = config_file_path(app_handle)?;
     if !path.exists() {
-        let empty = serde_json::json!({});
+        let mut empty = serde_json::json!({});
+        config_migrations::stamp(&mut empty);
         save_config_to_disk(app_handle, &empty)?;
         return Ok(empty);
     }
-    let mut file = fs::File::open(path)?;
+    let mut file = fs::File::open(&path)?;
     let mut contents = String::new();
     file.read_to_string(&mut contents)?;
-    let value: serde_json::Value = serde_json::from_str(&contents)?;
+    let mut value: serde_json::Value = match serde_json::from_str(&contents) {
+        Ok(value) => value,
+        // A damaged config is replaced by the newest copy that still parses
+        Err(e) => {
+            let (backup, value) = data_backups::latest_valid_config(app_handle).ok_or(e)?;
+            tracing::warn!("app_config.json inválido; restaurado de {}", backup.display());
+            fs::copy(&path, path.with_extension("json.damaged"))?;
+            data_lock::with_write_lock(app_handle, || data_lock::write_atomic(&path, contents_of(&value)?.as_bytes()))?;
+            value
+        }
+    };
+    let from = config_migrations::version(&value);
+    if from < config_migrations::SCHEMA_VERSION {
+        fs::copy(&path, path.with_extension(format!("json.v{}.bak", from)))?;
+        if config_migrations::migrate(&mut value) {
+            save_config_to_disk(app_handle, &value)?;
+        }
+    }
     Ok(value)
 }
 
+fn contents_of(config: &serde_json::Value) -> io::Result<String> {
+    Ok(serde_json::to_string_pretty(config)?)
+}
+
 pub fn save_config_to_disk(app_handle: &tauri::AppHandle, value: &serde_json::Value) -> io::Result<()> {
     let path = config_file_path(app_handle)?;
-    let json = serde_json::to_string_pretty(value)?;
-    fs::write(path, json)
+    let mut value = value.clone();
+    config_migrations::stamp(&mut value);
+    let json = contents_of(&value)?;
+    data_lock::with_write_lock(app_handle, || {
+        data_backups::backup_config(app_handle, &path)?;
+        data_lock::write_atomic(&path, json.as_bytes())
+    })
 }
 
 fn default_patients() -> Vec<Patient> {
@@ -88,17 +1114,98 @@ fn default_patients() -> Vec<Patient> {
             id: 1,
             name: "Ana Silva".into(),
             wallet: "9876543210123456".into(),
-            facial_biometric: String::new(),
+            facial_biometrics: Vec::new(),
             digital_biometrics: Vec::new(),
             imported: false,
+            tags: Vec::new(),
+            attachments: Vec::new(),
+            verification_history: Vec::new(),
+            notes: String::new(),
+            scenario: Scenario::default(),
+            version: 1,
         },
         Patient {
             id: 2,
             name: "Bruno Costa".into(),
             wallet: "1234567890654321".into(),
-            facial_biometric: String::new(),
+            facial_biometrics: Vec::new(),
             digital_biometrics: Vec::new(),
             imported: false,
+            tags: Vec::new(),
+            attachments: Vec::new(),
+            verification_history: Vec::new(),
+            notes: String::new(),
+            scenario: Scenario::default(),
+            version: 1,
         },
     ]
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filtered_ids(conn: &Connection, grace_period: Option<bool>, imported: Option<bool>) -> Vec<u32> {
+        let none: Option<&str> = None;
+        let mut stmt = conn
+            .prepare(&format!("SELECT id FROM patients WHERE {} ORDER BY id", FILTER_SQL))
+            .unwrap();
+        stmt.query_map(params![none, imported, none, none, none, none, none, none, grace_period], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn filter_sql_reads_a_missing_scenario_as_defaults() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        for (id, data) in [
+            (1, r#"{"imported":false}"#),
+            (2, r#"{"imported":true,"scenario":{"grace_period":true}}"#),
+            (3, r#"{"imported":false,"scenario":{"grace_period":false}}"#),
+        ] {
+            conn.execute(
+                "INSERT INTO patients (id, name, wallet, data) VALUES (?1, 'Paciente', '123', ?2)",
+                params![id, data],
+            )
+            .unwrap();
+        }
+
+        assert_eq!(filtered_ids(&conn, None, None), vec![1, 2, 3]);
+        assert_eq!(filtered_ids(&conn, Some(false), None), vec![1, 3]);
+        assert_eq!(filtered_ids(&conn, Some(true), None), vec![2]);
+        assert_eq!(filtered_ids(&conn, None, Some(true)), vec![2]);
+    }
+
+    #[test]
+    fn filter_sql_and_matches_agree_on_accents_and_tags() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        let mut patients = default_patients();
+        patients[0].name = "JOSÉ DA SILVA".into();
+        patients[0].tags = vec![" retorno ".into()];
+        for patient in &patients {
+            upsert(&conn, patient).unwrap();
+        }
+
+        for filter in [
+            PatientFilter { name_contains: Some(" josé ".into()), ..Default::default() },
+            PatientFilter { tag: Some("retorno".into()), ..Default::default() },
+        ] {
+            let name = filter.name_contains.as_deref().map(|n| fold_case(n.trim()));
+            let tag = filter.tag.as_deref().map(str::trim);
+            let none: Option<&str> = None;
+            let mut stmt = conn
+                .prepare(&format!("SELECT id FROM patients WHERE {} ORDER BY id", FILTER_SQL))
+                .unwrap();
+            let from_sql: Vec<u32> = stmt
+                .query_map(params![none, none, tag, name, none, none, none, none, none], |row| row.get(0))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+            let from_matches: Vec<u32> = patients.iter().filter(|p| filter.matches(p)).map(|p| p.id).collect();
+            assert_eq!(from_sql, vec![1]);
+            assert_eq!(from_matches, from_sql);
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::oneshot;
+
+use crate::patient::{self, Patient, PatientFilter, PatientSort, VersionConflict};
+use crate::patient_validation::{PatientSaveError, ValidationFailed};
+use crate::photo_thumbnails;
+
+const HOST: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 21090;
+
+// Optional REST API over the patient store, for test suites that provision
+// patients before driving the TOTVS UI. Only listens on localhost, and every
+// request needs `Authorization: Bearer <token>` with the token generated at
+// start (also written to patient_api.token in the data dir) and a localhost
+// Host header, so neither other users' processes nor web pages (through DNS
+// rebinding) can read the biometrics. Lists carry thumbnails, not photos.
+//   GET    /patients?name=&wallet=&tag=&imported=&page=&page_size=
+//   GET    /patients/{id}
+//   POST   /patients
+//   PUT    /patients/{id}   (without `version`, overwrites the stored one)
+//   DELETE /patients/{id}
+// Errors are { "kind", "message" }, plus `errors` for refused patients.
+pub struct PatientApiState {
+    shutdown_tx: Option<oneshot::Sender<()>>,
+    info: Option<PatientApiInfo>,
+}
+
+impl PatientApiState {
+    pub fn new() -> Self {
+        Self {
+            shutdown_tx: None,
+            info: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PatientApiInfo {
+    pub base_url: String,
+    pub token: String,
+}
+
+// What every request is checked against
+struct Access {
+    token: String,
+    port: u16,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ListQuery {
+    name: Option<String>,
+    wallet: Option<String>,
+    tag: Option<String>,
+    imported: Option<bool>,
+    page: Option<u32>,
+    page_size: Option<u32>,
+}
+
+fn error(error: io::Error) -> Response {
+    let inner = error.get_ref();
+    let status = match error.kind() {
+        io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        _ if inner.is_some_and(|e| e.is::<ValidationFailed>()) => StatusCode::UNPROCESSABLE_ENTITY,
+        _ if inner.is_some_and(|e| e.is::<VersionConflict>()) => StatusCode::CONFLICT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(PatientSaveError::from(error))).into_response()
+}
+
+fn refused(status: StatusCode, kind: &str, message: &str) -> Response {
+    (status, Json(json!({ "kind": kind, "message": message }))).into_response()
+}
+
+// The store is SQLite plus decryption and thumbnails, kept off the runtime
+async fn blocking<T: Send + 'static>(work: impl FnOnce() -> io::Result<T> + Send + 'static) -> io::Result<T> {
+    tokio::task::spawn_blocking(work).await.map_err(io::Error::other)?
+}
+
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Compares every byte, so the time taken doesn't tell how much matched
+fn same_token(given: &str, expected: &str) -> bool {
+    given.len() == expected.len() && given.bytes().zip(expected.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+fn allowed_host(host: &str, port: u16) -> bool {
+    ["127.0.0.1", "localhost"].iter().any(|name| host.eq_ignore_ascii_case(&format!("{}:{}", name, port)))
+}
+
+async fn check_access(State(access): State<Arc<Access>>, request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    let host = headers.get(header::HOST).and_then(|v| v.to_str().ok());
+    if !host.is_some_and(|host| allowed_host(host, access.port)) {
+        return refused(StatusCode::FORBIDDEN, "forbidden_host", "Host não permitido.");
+    }
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if !token.is_some_and(|t| same_token(t.trim(), &access.token)) {
+        return refused(StatusCode::UNAUTHORIZED, "unauthorized", "Token da API de pacientes ausente ou inválido.");
+    }
+    next.run(request).await
+}
+
+fn not_found(id: u32) -> Response {
+    let message = format!("Paciente {} não encontrado.", id);
+    (StatusCode::NOT_FOUND, Json(json!({ "kind": "not_found", "message": message }))).into_response()
+}
+
+fn changed(app_handle: &AppHandle) {
+    let _ = app_handle.emit("patients-changed", ());
+}
+
+async fn list(State(app_handle): State<AppHandle>, Query(query): Query<ListQuery>) -> Response {
+    let filter = PatientFilter {
+        name_contains: query.name,
+        wallet_prefix: query.wallet,
+        tag: query.tag,
+        imported: query.imported,
+        ..Default::default()
+    };
+    let page = query.page.unwrap_or(1);
+    let page_size = query.page_size.unwrap_or(patient::DEFAULT_PAGE_SIZE);
+    let result = blocking(move || {
+        let mut page = patient::query_patient_page(&app_handle, &filter, &PatientSort::default(), page, page_size)?;
+        page.items = page.items.into_iter().map(photo_thumbnails::listed).collect();
+        Ok(page)
+    })
+    .await;
+    match result {
+        Ok(page) => Json(page).into_response(),
+        Err(e) => error(e),
+    }
+}
+
+async fn get_one(State(app_handle): State<AppHandle>, Path(id): Path<u32>) -> Response {
+    match blocking(move || patient::get_patient(&app_handle, id)).await {
+        Ok(Some(patient)) => Json(patient).into_response(),
+        Ok(None) => not_found(id),
+        Err(e) => error(e),
+    }
+}
+
+async fn create(State(app_handle): State<AppHandle>, Json(patient): Json<Patient>) -> Response {
+    let store = app_handle.clone();
+    match blocking(move || patient::add_patient(&store, patient)).await {
+        Ok(patient) => {
+            changed(&app_handle);
+            (StatusCode::CREATED, Json(patient)).into_response()
+        }
+        Err(e) => error(e),
+    }
+}
+
+async fn update(State(app_handle): State<AppHandle>, Path(id): Path<u32>, Json(mut patient): Json<Patient>) -> Response {
+    patient.id = id;
+    let store = app_handle.clone();
+    let result = blocking(move || {
+        if patient.version == 0 {
+            let stored = patient::get_patient(&store, id)?
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Paciente {} não encontrado.", id)))?;
+            patient.version = stored.version;
+        }
+        patient::update_patient(&store, patient)
+    })
+    .await;
+    match result {
+        Ok(patient) => {
+            changed(&app_handle);
+            Json(patient).into_response()
+        }
+        Err(e) => error(e),
+    }
+}
+
+async fn delete(State(app_handle): State<AppHandle>, Path(id): Path<u32>) -> Response {
+    let store = app_handle.clone();
+    match blocking(move || patient::delete_patient(&store, id, None)).await {
+        Ok(()) => {
+            changed(&app_handle);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => error(e),
+    }
+}
+
+fn build_router(app_handle: AppHandle, access: Arc<Access>) -> Router {
+    Router::new()
+        .route("/patients", get(list).post(create))
+        .route("/patients/:id", get(get_one).put(update).delete(delete))
+        .layer(middleware::from_fn_with_state(access, check_access))
+        .with_state(app_handle)
+}
+
+fn token_file(app_handle: &AppHandle) -> io::Result<std::path::PathBuf> {
+    Ok(patient::ensure_data_dir(app_handle)?.join("patient_api.token"))
+}
+
+// Returns the base URL and the token requests must carry
+#[tauri::command]
+pub async fn start_patient_api(
+    app_handle: AppHandle,
+    port: Option<u16>,
+    state: tauri::State<'_, Arc<Mutex<PatientApiState>>>,
+) -> Result<PatientApiInfo, String> {
+    if let Some(info) = state.lock().unwrap().info.clone() {
+        return Ok(info);
+    }
+
+    let addr: SocketAddr = format!("{}:{}", HOST, port.unwrap_or(DEFAULT_PORT))
+        .parse()
+        .map_err(|e| format!("Endereço inválido: {}", e))?;
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .map_err(|e| format!("Falha ao vincular API de pacientes em {}: {}", addr, e))?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let info = PatientApiInfo {
+        base_url: format!("http://{}:{}", HOST, port),
+        token: generate_token(),
+    };
+    let token_path = token_file(&app_handle).map_err(|e| e.to_string())?;
+    fs::write(&token_path, &info.token)
+        .map_err(|e| format!("Falha ao gravar {}: {}", token_path.display(), e))?;
+
+    let (tx, rx) = oneshot::channel::<()>();
+    {
+        let mut s = state.lock().unwrap();
+        s.shutdown_tx = Some(tx);
+        s.info = Some(info.clone());
+    }
+
+    let server_state = state.inner().clone();
+    let access = Arc::new(Access { token: info.token.clone(), port });
+    let app = build_router(app_handle, access);
+    tokio::spawn(async move {
+        let server = axum::serve(listener, app).with_graceful_shutdown(async {
+            rx.await.ok();
+        });
+        let result = server.await;
+        let _ = fs::remove_file(&token_path);
+        if let Err(e) = result {
+            tracing::error!("Erro na API de pacientes: {}", e);
+            let mut s = server_state.lock().unwrap();
+            s.shutdown_tx = None;
+            s.info = None;
+        }
+    });
+
+    Ok(info)
+}
+
+// Returns false if the API wasn't running
+#[tauri::command]
+pub fn stop_patient_api(state: tauri::State<'_, Arc<Mutex<PatientApiState>>>) -> Result<bool, String> {
+    let mut state = state
+        .lock()
+        .map_err(|_| "Falha ao obter lock da API de pacientes".to_string())?;
+    state.info = None;
+    Ok(match state.shutdown_tx.take() {
+        Some(tx) => tx.send(()).is_ok(),
+        None => false,
+    })
+}
@@ -0,0 +1,224 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+use sysinfo::System;
+use tauri::AppHandle;
+
+use crate::blob_store::sha256_hex;
+use crate::patient::{self, Patient};
+
+// Every create, update and delete of a patient is appended to `audit_log` in
+// patients.db, in the transaction that makes the change. Triggers reject
+// UPDATE and DELETE on the table, so entries can't be rewritten.
+const DEFAULT_LIMIT: u32 = 200;
+const MAX_LIMIT: u32 = 5000;
+// Bookkeeping, not patient data
+const IGNORED_FIELDS: [&str; 2] = ["version", "verification_history"];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    // null when the patient was created / deleted
+    pub old: Value,
+    pub new: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub timestamp: u64,
+    // OS user and machine that made the change
+    pub actor: String,
+    // "create", "update" or "delete"
+    pub action: String,
+    // What triggered it: "save", "import", "add_patient"...
+    pub source: String,
+    pub patient_id: u32,
+    pub wallet: String,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AuditFilter {
+    pub patient_id: Option<u32>,
+    pub wallet: Option<String>,
+    pub action: Option<String>,
+    pub source: Option<String>,
+    // Unix seconds, inclusive
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    pub limit: Option<u32>,
+}
+
+pub fn create_table(conn: &Connection) -> io::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+             id INTEGER PRIMARY KEY AUTOINCREMENT,
+             timestamp INTEGER NOT NULL,
+             actor TEXT NOT NULL,
+             action TEXT NOT NULL,
+             source TEXT NOT NULL,
+             patient_id INTEGER NOT NULL,
+             wallet TEXT NOT NULL,
+             changes TEXT NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS audit_log_patient ON audit_log (patient_id);
+         CREATE TRIGGER IF NOT EXISTS audit_log_no_update BEFORE UPDATE ON audit_log
+         BEGIN SELECT RAISE(ABORT, 'audit_log is append-only'); END;
+         CREATE TRIGGER IF NOT EXISTS audit_log_no_delete BEFORE DELETE ON audit_log
+         BEGIN SELECT RAISE(ABORT, 'audit_log is append-only'); END;",
+    )
+    .map_err(io::Error::other)
+}
+
+fn actor() -> String {
+    let user = std::env::var("USERNAME")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_else(|_| "desconhecido".into());
+    match System::host_name() {
+        Some(host) => format!("{}@{}", user, host),
+        None => user,
+    }
+}
+
+// Biometrics are logged as hashes: enough to see that they changed without
+// copying photos and templates, which are stored encrypted, into the log
+fn hash(data: &str) -> Value {
+    if data.is_empty() {
+        Value::String(String::new())
+    } else {
+        Value::String(sha256_hex(data.as_bytes()))
+    }
+}
+
+fn auditable(patient: &Patient) -> io::Result<Map<String, Value>> {
+    let Value::Object(mut fields) = serde_json::to_value(patient)? else {
+        return Ok(Map::new());
+    };
+    for field in IGNORED_FIELDS {
+        fields.remove(field);
+    }
+    let photos = patient.facial_biometrics.iter().map(|image| (image.label.as_str().to_string(), hash(&image.data))).collect();
+    fields.insert("facial_biometrics".into(), Value::Object(photos));
+    let fingers = patient.digital_biometrics.iter().map(|d| (d.finger.clone(), hash(&d.data))).collect();
+    fields.insert("digital_biometrics".into(), Value::Object(fingers));
+    Ok(fields)
+}
+
+fn diff(old: Option<&Patient>, new: Option<&Patient>) -> io::Result<Vec<FieldChange>> {
+    let old = old.map(auditable).transpose()?.unwrap_or_default();
+    let new = new.map(auditable).transpose()?.unwrap_or_default();
+    let mut fields: Vec<&String> = old.keys().chain(new.keys()).collect();
+    fields.sort();
+    fields.dedup();
+    Ok(fields
+        .into_iter()
+        .filter_map(|field| {
+            let (old, new) = (old.get(field).cloned().unwrap_or_default(), new.get(field).cloned().unwrap_or_default());
+            (old != new).then(|| FieldChange { field: field.clone(), old, new })
+        })
+        .collect())
+}
+
+// Appends the change from `old` to `new` (None for created / deleted). An
+// update that only touched ignored fields isn't logged.
+pub fn record(conn: &Connection, source: &str, old: Option<&Patient>, new: Option<&Patient>) -> io::Result<()> {
+    let (action, patient) = match (old, new) {
+        (None, Some(new)) => ("create", new),
+        (Some(old), None) => ("delete", old),
+        (Some(_), Some(new)) => ("update", new),
+        (None, None) => return Ok(()),
+    };
+    let changes = diff(old, new)?;
+    if changes.is_empty() {
+        return Ok(());
+    }
+    append(conn, source, action, patient, &changes)
+}
+
+// `removed` merged into `kept` (now `merged`). Both entries name the other
+// patient, so each history leads to the other.
+pub fn record_merge(conn: &Connection, kept: &Patient, merged: &Patient, removed: &Patient) -> io::Result<()> {
+    let link = |field: &str, id: u32| FieldChange { field: field.into(), old: Value::Null, new: Value::from(id) };
+    let mut changes = diff(Some(kept), Some(merged))?;
+    changes.push(link("merged_from", removed.id));
+    append(conn, "merge_patients", "update", merged, &changes)?;
+    let mut changes = diff(Some(removed), None)?;
+    changes.push(link("merged_into", kept.id));
+    append(conn, "merge_patients", "delete", removed, &changes)
+}
+
+fn append(conn: &Connection, source: &str, action: &str, patient: &Patient, changes: &[FieldChange]) -> io::Result<()> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    conn.execute(
+        "INSERT INTO audit_log (timestamp, actor, action, source, patient_id, wallet, changes)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![timestamp, actor(), action, source, patient.id, patient.wallet, serde_json::to_string(changes)?],
+    )
+    .map_err(io::Error::other)?;
+    Ok(())
+}
+
+// Time of the newest logged change of each patient id
+pub fn last_change_times(app_handle: &AppHandle) -> io::Result<HashMap<u32, u64>> {
+    let conn = patient::open_database(app_handle)?;
+    let mut stmt = conn
+        .prepare("SELECT patient_id, MAX(timestamp) FROM audit_log GROUP BY patient_id")
+        .map_err(io::Error::other)?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).map_err(io::Error::other)?;
+    rows.collect::<Result<_, _>>().map_err(io::Error::other)
+}
+
+fn read_log(app_handle: &AppHandle, filter: &AuditFilter) -> io::Result<Vec<AuditEntry>> {
+    let conn = patient::open_database(app_handle)?;
+    let limit = filter.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, timestamp, actor, action, source, patient_id, wallet, changes FROM audit_log
+             WHERE (?1 IS NULL OR patient_id = ?1)
+               AND (?2 IS NULL OR wallet = ?2)
+               AND (?3 IS NULL OR action = ?3)
+               AND (?4 IS NULL OR source = ?4)
+               AND (?5 IS NULL OR timestamp >= ?5)
+               AND (?6 IS NULL OR timestamp <= ?6)
+             ORDER BY id DESC LIMIT ?7",
+        )
+        .map_err(io::Error::other)?;
+    let rows = stmt
+        .query_map(
+            params![
+                filter.patient_id,
+                filter.wallet.as_deref().map(str::trim),
+                filter.action,
+                filter.source,
+                filter.since,
+                filter.until,
+                limit
+            ],
+            |row| {
+                let changes: String = row.get(7)?;
+                Ok(AuditEntry {
+                    id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    actor: row.get(2)?,
+                    action: row.get(3)?,
+                    source: row.get(4)?,
+                    patient_id: row.get(5)?,
+                    wallet: row.get(6)?,
+                    changes: serde_json::from_str(&changes).unwrap_or_default(),
+                })
+            },
+        )
+        .map_err(io::Error::other)?;
+    rows.collect::<Result<_, _>>().map_err(io::Error::other)
+}
+
+// Newest first
+#[tauri::command]
+pub fn get_audit_log(app_handle: AppHandle, filter: Option<AuditFilter>) -> Result<Vec<AuditEntry>, String> {
+    read_log(&app_handle, &filter.unwrap_or_default()).map_err(|e| format!("Falha ao ler auditoria: {e}"))
+}
@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+
+use crate::patient::{self, Patient};
+use crate::patient_audit;
+
+// Retention of test patients, under `patient_retention` in app_config.json.
+// A patient is stale when neither the audit log nor its verification history
+// shows it touched in `max_age_days`. Patients older than the audit log, with
+// neither, are kept: their age isn't known.
+const CONFIG_KEY: &str = "patient_retention";
+const DAY_SECS: u64 = 86_400;
+// How often the scheduled job looks at the policy
+const CHECK_INTERVAL: Duration = Duration::from_secs(3_600);
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    pub max_age_days: u32,
+    // Patients brought from TOTVS are only removed when this is set
+    pub include_imported: bool,
+    // Patients with any of these tags are never removed
+    pub keep_tags: Vec<String>,
+    // Runs the cleanup every `interval_hours` while the app is open
+    pub scheduled: bool,
+    pub interval_hours: u32,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_age_days: 90,
+            include_imported: false,
+            keep_tags: Vec::new(),
+            scheduled: false,
+            interval_hours: 24,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StalePatient {
+    pub id: u32,
+    pub name: String,
+    pub wallet: String,
+    pub last_touched: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanupReport {
+    pub dry_run: bool,
+    pub max_age_days: u32,
+    // Removed, or that would be with `dry_run`
+    pub patients: Vec<StalePatient>,
+    // Kept because their age isn't known
+    pub unknown_age: usize,
+}
+
+fn load_policy(app_handle: &AppHandle) -> RetentionPolicy {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get(CONFIG_KEY).cloned())
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn protected(policy: &RetentionPolicy, patient: &Patient) -> bool {
+    (patient.imported && !policy.include_imported) || patient.tags.iter().any(|tag| policy.keep_tags.contains(tag))
+}
+
+fn cleanup(app_handle: &AppHandle, policy: &RetentionPolicy, dry_run: bool) -> Result<CleanupReport, String> {
+    let patients = patient::load_patients_from_disk(app_handle).map_err(|e| format!("Falha ao carregar pacientes: {e}"))?;
+    let changed = patient_audit::last_change_times(app_handle).map_err(|e| format!("Falha ao ler auditoria: {e}"))?;
+    let cutoff = now().saturating_sub(u64::from(policy.max_age_days) * DAY_SECS);
+
+    let mut stale = Vec::new();
+    let mut unknown_age = 0;
+    for patient in patients.iter().filter(|p| !protected(policy, p)) {
+        let verified = patient.verification_history.iter().map(|v| v.timestamp).max();
+        let Some(last_touched) = changed.get(&patient.id).copied().max(verified) else {
+            unknown_age += 1;
+            continue;
+        };
+        if last_touched < cutoff {
+            stale.push(StalePatient {
+                id: patient.id,
+                name: patient.name.clone(),
+                wallet: patient.wallet.clone(),
+                last_touched,
+            });
+        }
+    }
+
+    if !dry_run && !stale.is_empty() {
+        let ids: Vec<u32> = stale.iter().map(|p| p.id).collect();
+        patient::delete_patients(app_handle, &ids, "cleanup").map_err(|e| format!("Falha ao remover pacientes: {e}"))?;
+        let _ = app_handle.emit("patients-changed", ());
+    }
+    Ok(CleanupReport {
+        dry_run,
+        max_age_days: policy.max_age_days,
+        patients: stale,
+        unknown_age,
+    })
+}
+
+// Started once at startup; does nothing until the policy is `scheduled`.
+// Every run is reported with a `patient-cleanup` event.
+pub fn start_scheduler(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_run: Option<SystemTime> = None;
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let policy = load_policy(&app_handle);
+            let interval = Duration::from_secs(u64::from(policy.interval_hours.max(1)) * 3_600);
+            let due = last_run.is_none_or(|at| at.elapsed().map_or(true, |elapsed| elapsed >= interval));
+            if !policy.scheduled || !due {
+                continue;
+            }
+            last_run = Some(SystemTime::now());
+            let app = app_handle.clone();
+            let report = tauri::async_runtime::spawn_blocking(move || cleanup(&app, &policy, false)).await;
+            match report {
+                Ok(Ok(report)) => {
+                    let _ = app_handle.emit("patient-cleanup", &report);
+                }
+                Ok(Err(e)) => tracing::error!("Limpeza agendada de pacientes falhou: {}", e),
+                Err(e) => tracing::error!("Limpeza agendada de pacientes falhou: {}", e),
+            }
+        }
+    });
+}
+
+// With `dry_run` only lists what the retention policy would remove. The
+// removal is one undoable change.
+#[tauri::command]
+pub async fn run_cleanup(app_handle: AppHandle, dry_run: bool) -> Result<CleanupReport, String> {
+    let policy = load_policy(&app_handle);
+    tauri::async_runtime::spawn_blocking(move || cleanup(&app_handle, &policy, dry_run))
+        .await
+        .map_err(|e| e.to_string())?
+}
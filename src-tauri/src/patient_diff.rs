@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::beneficiary_import::{details_key, resolve_name, to_digital_biometrics, wallet_of};
+use crate::blob_store::sha256_hex;
+use crate::patient::{self, DigitalBiometric, FaceAngle};
+use crate::photo_pipeline;
+use crate::streamed_download;
+use crate::totvs::error::TotvsError;
+use crate::totvs_cache;
+use crate::totvs_endpoints;
+
+#[derive(Debug, Serialize)]
+pub struct FieldDiff {
+    pub local: String,
+    pub totvs: String,
+    pub changed: bool,
+}
+
+impl FieldDiff {
+    fn new(local: String, totvs: String) -> Self {
+        let changed = local.trim() != totvs.trim();
+        Self { local, totvs, changed }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FingerprintDiff {
+    // Finger names as stored on the patient
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+    pub unchanged: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PatientDiff {
+    pub patient_id: u32,
+    pub name: FieldDiff,
+    pub wallet: FieldDiff,
+    // SHA-256 of the base64 photo; empty when there is none
+    pub photo_hash: FieldDiff,
+    pub fingerprints: FingerprintDiff,
+    // Whether anything differs from what TOTVS returns now
+    pub stale: bool,
+}
+
+// Ignores line breaks and a data URL header, which don't change the image
+fn content_hash(base64: &str) -> String {
+    let data = base64.split_once("base64,").map_or(base64, |(_, data)| data);
+    let data: String = data.split_whitespace().collect();
+    if data.is_empty() {
+        String::new()
+    } else {
+        sha256_hex(data.as_bytes())
+    }
+}
+
+fn by_finger(prints: &[DigitalBiometric]) -> BTreeMap<&str, String> {
+    prints.iter().map(|p| (p.finger.as_str(), content_hash(&p.data))).collect()
+}
+
+fn diff_fingerprints(local: &[DigitalBiometric], totvs: &[DigitalBiometric]) -> FingerprintDiff {
+    let (local, totvs) = (by_finger(local), by_finger(totvs));
+    let mut diff = FingerprintDiff {
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+        unchanged: Vec::new(),
+    };
+    for (finger, hash) in &totvs {
+        match local.get(finger) {
+            None => diff.added.push(finger.to_string()),
+            Some(local_hash) if local_hash != hash => diff.changed.push(finger.to_string()),
+            Some(_) => diff.unchanged.push(finger.to_string()),
+        }
+    }
+    diff.removed = local.keys().filter(|f| !totvs.contains_key(*f)).map(|f| f.to_string()).collect();
+    diff
+}
+
+// Fetches the patient's beneficiary from TOTVS again and tells which fields
+// the local copy no longer matches. The photo goes through the same pipeline
+// as on import, so a change of photo settings since then also shows up as a
+// different hash.
+#[tauri::command]
+pub async fn compare_patient_with_totvs(app_handle: AppHandle, patient_id: u32) -> Result<PatientDiff, TotvsError> {
+    let patients = patient::load_patients_from_disk(&app_handle).map_err(|e| format!("Falha ao ler pacientes: {e}"))?;
+    let local = patients
+        .into_iter()
+        .find(|p| p.id == patient_id)
+        .ok_or_else(|| format!("Paciente {} não encontrado.", patient_id))?;
+    let wallet = local.wallet.trim().to_string();
+    if wallet.is_empty() {
+        return Err(format!("Paciente {} não tem carteira.", patient_id).into());
+    }
+
+    // Cached answers would hide exactly the changes being looked for
+    let details_key = details_key(&wallet);
+    let portal = crate::portal_context(&app_handle)?;
+    for (endpoint, card) in [("beneficiary_details", &details_key), ("fingerprints", &wallet), ("facial_photo", &wallet)] {
+        let url = totvs_endpoints::url(&app_handle, &portal.credentials.base_url, endpoint, Some(card));
+        totvs_cache::remove(&app_handle, &totvs_cache::key(endpoint, &url, &portal.query()));
+    }
+    let (details, fingerprints, photo) = tokio::join!(
+        crate::fetch_beneficiary_details(&app_handle, &details_key),
+        crate::fetch_fingerprints(&app_handle, &wallet),
+        crate::fetch_facial_photo(&app_handle, &wallet),
+    );
+    let details = details?;
+    let totvs_prints = to_digital_biometrics(fingerprints?);
+    let totvs_photo = streamed_download::photo_base64(&photo?)?;
+    let totvs_photo = if totvs_photo.trim().is_empty() {
+        String::new()
+    } else {
+        photo_pipeline::prepare(&photo_pipeline::settings(&app_handle), totvs_photo).await
+    };
+
+    let totvs_wallet = wallet_of(&details.health_insurer, &details.card_number, &details.complete_card_number).unwrap_or_default();
+    let name = FieldDiff::new(local.name.clone(), resolve_name(&details.name, &details.person));
+    let wallet = FieldDiff::new(local.wallet.clone(), totvs_wallet);
+    let photo_hash = FieldDiff::new(content_hash(local.facial_photo(FaceAngle::Frontal).unwrap_or_default()), content_hash(&totvs_photo));
+    let fingerprints = diff_fingerprints(&local.digital_biometrics, &totvs_prints);
+
+    let stale = name.changed
+        || wallet.changed
+        || photo_hash.changed
+        || !fingerprints.added.is_empty()
+        || !fingerprints.removed.is_empty()
+        || !fingerprints.changed.is_empty();
+    Ok(PatientDiff {
+        patient_id,
+        name,
+        wallet,
+        photo_hash,
+        fingerprints,
+        stale,
+    })
+}
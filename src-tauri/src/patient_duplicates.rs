@@ -0,0 +1,194 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use tauri::AppHandle;
+
+use crate::patient::{self, Patient, Scenario};
+use crate::schema_drift::edit_distance;
+
+// Names at least this similar (1 - edit distance / length) are reported
+const NAME_SIMILARITY: f32 = 0.85;
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateCandidate {
+    pub id: u32,
+    pub name: String,
+    pub wallet: String,
+    pub imported: bool,
+    pub fingerprints: usize,
+    pub has_photo: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    // "wallet" (same card number) or "name" (similar names, different cards)
+    pub reason: String,
+    // 1.0 for wallet matches
+    pub similarity: f32,
+    pub patients: Vec<DuplicateCandidate>,
+}
+
+fn candidate(patient: &Patient) -> DuplicateCandidate {
+    DuplicateCandidate {
+        id: patient.id,
+        name: patient.name.clone(),
+        wallet: patient.wallet.clone(),
+        imported: patient.imported,
+        fingerprints: patient.digital_biometrics.len(),
+        has_photo: patient.has_facial_photo(),
+    }
+}
+
+fn wallet_key(wallet: &str) -> String {
+    wallet.chars().filter(|c| c.is_ascii_alphanumeric()).collect()
+}
+
+// Lowercase, without accents or punctuation, single spaces
+pub(crate) fn name_key(name: &str) -> String {
+    let folded: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'á' | 'à' | 'â' | 'ã' | 'ä' => 'a',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'í' | 'ì' | 'î' | 'ï' => 'i',
+            'ó' | 'ò' | 'ô' | 'õ' | 'ö' => 'o',
+            'ú' | 'ù' | 'û' | 'ü' => 'u',
+            'ç' => 'c',
+            'ñ' => 'n',
+            c if c.is_alphanumeric() => c,
+            _ => ' ',
+        })
+        .collect();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn name_similarity(a: &str, b: &str) -> f32 {
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 0.0;
+    }
+    1.0 - edit_distance(a, b) as f32 / longest as f32
+}
+
+// Patients sharing a wallet, then pairs with similar names. To keep
+// thousands of patients fast, names are only compared with those sharing
+// their first or their last word, so a typo is caught unless it hits both.
+// A missing surname only counts when the rest of the name keeps the pair
+// above NAME_SIMILARITY, i.e. in long names.
+#[tauri::command]
+pub fn find_duplicate_patients(app_handle: AppHandle) -> Result<Vec<DuplicateGroup>, String> {
+    let patients = patient::load_patients_from_disk(&app_handle).map_err(|e| format!("Falha ao ler pacientes: {e}"))?;
+    let mut groups = Vec::new();
+
+    let mut by_wallet: BTreeMap<String, Vec<&Patient>> = BTreeMap::new();
+    for p in &patients {
+        let key = wallet_key(&p.wallet);
+        if !key.is_empty() {
+            by_wallet.entry(key).or_default().push(p);
+        }
+    }
+    let mut same_wallet: HashSet<(u32, u32)> = HashSet::new();
+    for group in by_wallet.values().filter(|g| g.len() > 1) {
+        for a in group {
+            for b in group {
+                same_wallet.insert((a.id, b.id));
+            }
+        }
+        groups.push(DuplicateGroup {
+            reason: "wallet".into(),
+            similarity: 1.0,
+            patients: group.iter().map(|p| candidate(p)).collect(),
+        });
+    }
+
+    // Keyed by ("first"/"last", word) so both blockings share one map
+    let mut by_word: BTreeMap<(&str, String), Vec<(&Patient, String)>> = BTreeMap::new();
+    for p in &patients {
+        let key = name_key(&p.name);
+        let words: Vec<&str> = key.split(' ').filter(|w| !w.is_empty()).collect();
+        if let (Some(first), Some(last)) = (words.first(), words.last()) {
+            by_word.entry(("first", first.to_string())).or_default().push((p, key.clone()));
+            by_word.entry(("last", last.to_string())).or_default().push((p, key.clone()));
+        }
+    }
+    let mut compared: HashSet<(u32, u32)> = HashSet::new();
+    for block in by_word.values() {
+        for (i, (a, a_name)) in block.iter().enumerate() {
+            for (b, b_name) in &block[i + 1..] {
+                if same_wallet.contains(&(a.id, b.id)) || !compared.insert((a.id.min(b.id), a.id.max(b.id))) {
+                    continue;
+                }
+                let similarity = name_similarity(a_name, b_name);
+                if similarity >= NAME_SIMILARITY {
+                    groups.push(DuplicateGroup {
+                        reason: "name".into(),
+                        similarity,
+                        patients: vec![candidate(a), candidate(b)],
+                    });
+                }
+            }
+        }
+    }
+    Ok(groups)
+}
+
+fn merged(mut keep: Patient, remove: &Patient) -> Patient {
+    if keep.name.trim().is_empty() {
+        keep.name = remove.name.clone();
+    }
+    if keep.wallet.trim().is_empty() {
+        keep.wallet = remove.wallet.clone();
+    }
+    // Likewise for the photo of each angle
+    for image in &remove.facial_biometrics {
+        if keep.facial_photo(image.label).is_none() {
+            keep.set_facial_photo(image.label, image.data.clone());
+        }
+    }
+    // The kept patient's finger wins when both have it
+    for digital in &remove.digital_biometrics {
+        if !keep.digital_biometrics.iter().any(|d| d.finger == digital.finger) {
+            keep.digital_biometrics.push(digital.clone());
+        }
+    }
+    keep.imported |= remove.imported;
+    for tag in &remove.tags {
+        if !keep.tags.contains(tag) {
+            keep.tags.push(tag.clone());
+        }
+    }
+    // Attachment ids are per patient, so the removed one's are renumbered
+    let mut next_id = keep.attachments.iter().map(|a| a.id).max().unwrap_or(0) + 1;
+    for attachment in &remove.attachments {
+        if !keep.attachments.iter().any(|a| a.blob_key == attachment.blob_key) {
+            keep.attachments.push(patient::Attachment { id: next_id, ..attachment.clone() });
+            next_id += 1;
+        }
+    }
+    if keep.notes.trim().is_empty() {
+        keep.notes = remove.notes.clone();
+    }
+    if keep.scenario == Scenario::default() {
+        keep.scenario = remove.scenario.clone();
+    }
+    keep.verification_history.extend(remove.verification_history.iter().cloned());
+    keep.verification_history.sort_by_key(|v| v.timestamp);
+    keep
+}
+
+// Folds `remove_id` into `keep_id`: biometrics, tags, attachments and
+// verifications are combined, the kept patient's data winning where both
+// have a value, and `remove_id` is deleted. The audit log links the two.
+#[tauri::command]
+pub fn merge_patients(app_handle: AppHandle, keep_id: u32, remove_id: u32) -> Result<Patient, String> {
+    if keep_id == remove_id {
+        return Err("Escolha dois pacientes diferentes para mesclar.".into());
+    }
+    let find = |id: u32| -> Result<Patient, String> {
+        patient::get_patient(&app_handle, id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Paciente {} não encontrado.", id))
+    };
+    let (keep, remove) = (find(keep_id)?, find(remove_id)?);
+    patient::merge_patient_records(&app_handle, merged(keep, &remove), &remove).map_err(|e| e.to_string())
+}
@@ -0,0 +1,169 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::biometric_crypto;
+use crate::patient::{self, Patient, PatientChange};
+
+// Changes kept for undo; the oldest is forgotten past this
+const MAX_ENTRIES: usize = 50;
+
+// One patient mutation (a save, an edit, a merge...) with every patient it
+// touched, so it's undone as a whole
+struct HistoryEntry {
+    label: String,
+    timestamp: u64,
+    changes: Vec<SealedChange>,
+}
+
+// A change with photos and fingerprints encrypted as in the database, so
+// the history doesn't keep the biometrics of the last patients in the clear
+struct SealedChange {
+    before: Option<Patient>,
+    after: Option<Patient>,
+}
+
+fn seal(changes: Vec<PatientChange>) -> io::Result<Vec<SealedChange>> {
+    let encrypt = |patient: Option<Patient>| patient.as_ref().map(biometric_crypto::encrypt_patient).transpose();
+    changes
+        .into_iter()
+        .map(|change| Ok(SealedChange { before: encrypt(change.before)?, after: encrypt(change.after)? }))
+        .collect()
+}
+
+fn unseal(changes: &[SealedChange]) -> io::Result<Vec<PatientChange>> {
+    let decrypt = |patient: &Option<Patient>| patient.clone().map(biometric_crypto::decrypt_patient).transpose();
+    changes
+        .iter()
+        .map(|change| Ok(PatientChange { before: decrypt(&change.before)?, after: decrypt(&change.after)? }))
+        .collect()
+}
+
+// In memory only: restarting the app or switching workspace starts a new
+// history
+pub struct PatientHistory {
+    undo: VecDeque<HistoryEntry>,
+    redo: Vec<HistoryEntry>,
+}
+
+impl PatientHistory {
+    pub fn new() -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    fn status(&self) -> HistoryStatus {
+        HistoryStatus {
+            undo_label: self.undo.back().map(|e| e.label.clone()),
+            redo_label: self.redo.last().map(|e| e.label.clone()),
+            undo_depth: self.undo.len(),
+            redo_depth: self.redo.len(),
+            last_change: self.undo.back().map(|e| e.timestamp),
+        }
+    }
+
+    fn push_undo(&mut self, entry: HistoryEntry) {
+        if self.undo.len() == MAX_ENTRIES {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(entry);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryStatus {
+    // What the next undo / redo would revert
+    pub undo_label: Option<String>,
+    pub redo_label: Option<String>,
+    pub undo_depth: usize,
+    pub redo_depth: usize,
+    pub last_change: Option<u64>,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// Called after a mutation was committed. A new change makes the undone ones
+// impossible to redo.
+pub fn record(app_handle: &AppHandle, label: &str, changes: Vec<PatientChange>) {
+    if changes.is_empty() {
+        return;
+    }
+    let Some(state) = app_handle.try_state::<Arc<Mutex<PatientHistory>>>() else {
+        return;
+    };
+    let changes = match seal(changes) {
+        Ok(changes) => changes,
+        Err(e) => {
+            tracing::warn!("Alteração '{}' não poderá ser desfeita: {}", label, e);
+            return;
+        }
+    };
+    let mut history = state.lock().unwrap();
+    history.redo.clear();
+    history.push_undo(HistoryEntry {
+        label: label.to_string(),
+        timestamp: now(),
+        changes,
+    });
+}
+
+pub fn clear(app_handle: &AppHandle) {
+    if let Some(state) = app_handle.try_state::<Arc<Mutex<PatientHistory>>>() {
+        let mut history = state.lock().unwrap();
+        history.undo.clear();
+        history.redo.clear();
+    }
+}
+
+// Reverts the entry on top of `from` and moves it to the other stack. The
+// history lock is released while the database is written; if the revert
+// fails (the patients changed since), the entry is dropped, as it can no
+// longer be applied.
+fn step(app_handle: &AppHandle, undo: bool) -> Result<HistoryStatus, String> {
+    let state = app_handle.state::<Arc<Mutex<PatientHistory>>>();
+    let entry = {
+        let mut history = state.lock().unwrap();
+        if undo { history.undo.pop_back() } else { history.redo.pop() }
+    };
+    let Some(entry) = entry else {
+        return Err(if undo { "Nada para desfazer." } else { "Nada para refazer." }.into());
+    };
+
+    let source = if undo { "undo" } else { "redo" };
+    let changes = unseal(&entry.changes)
+        .and_then(|changes| patient::revert_changes(app_handle, &changes, source))
+        .and_then(seal)
+        .map_err(|e| format!("Não foi possível {} '{}': {}", if undo { "desfazer" } else { "refazer" }, entry.label, e))?;
+    let _ = app_handle.emit("patients-changed", ());
+
+    let mut history = state.lock().unwrap();
+    let entry = HistoryEntry { changes, ..entry };
+    if undo {
+        history.redo.push(entry);
+    } else {
+        history.push_undo(entry);
+    }
+    Ok(history.status())
+}
+
+#[tauri::command]
+pub fn undo_patient_change(app_handle: AppHandle) -> Result<HistoryStatus, String> {
+    step(&app_handle, true)
+}
+
+#[tauri::command]
+pub fn redo_patient_change(app_handle: AppHandle) -> Result<HistoryStatus, String> {
+    step(&app_handle, false)
+}
+
+#[tauri::command]
+pub fn get_patient_history(state: tauri::State<'_, Arc<Mutex<PatientHistory>>>) -> HistoryStatus {
+    state.lock().unwrap().status()
+}
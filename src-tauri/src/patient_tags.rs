@@ -0,0 +1,91 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tauri::AppHandle;
+
+use crate::patient::{self, BulkUpdateSummary, Patient, PatientFilter, PatientPatch};
+use crate::photo_thumbnails;
+
+// Tags group patients by test scenario ("pediatria", "carência", "inativo"...)
+#[derive(Debug, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub patients: usize,
+}
+
+fn clean(tags: Vec<String>) -> Result<Vec<String>, String> {
+    let tags: Vec<String> = tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()).map(String::from).collect();
+    if tags.is_empty() {
+        return Err("Nenhuma tag informada.".into());
+    }
+    Ok(tags)
+}
+
+fn patch_ids(app_handle: &AppHandle, ids: Vec<u32>, patch: PatientPatch) -> Result<BulkUpdateSummary, String> {
+    if ids.is_empty() {
+        return Err("Nenhum paciente informado.".into());
+    }
+    let filter = PatientFilter {
+        ids: Some(ids),
+        ..Default::default()
+    };
+    patient::bulk_update(app_handle, &filter, &patch).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn add_patient_tags(app_handle: AppHandle, ids: Vec<u32>, tags: Vec<String>) -> Result<BulkUpdateSummary, String> {
+    let patch = PatientPatch {
+        add_tags: clean(tags)?,
+        ..Default::default()
+    };
+    patch_ids(&app_handle, ids, patch)
+}
+
+#[tauri::command]
+pub fn remove_patient_tags(app_handle: AppHandle, ids: Vec<u32>, tags: Vec<String>) -> Result<BulkUpdateSummary, String> {
+    let patch = PatientPatch {
+        remove_tags: clean(tags)?,
+        ..Default::default()
+    };
+    patch_ids(&app_handle, ids, patch)
+}
+
+// Every tag in use, alphabetically, with how many patients have it
+#[tauri::command]
+pub fn list_tags(app_handle: AppHandle) -> Result<Vec<TagCount>, String> {
+    let patients = patient::load_patients_from_disk(&app_handle).map_err(|e| e.to_string())?;
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for tag in patients.iter().flat_map(|p| &p.tags) {
+        *counts.entry(tag.as_str()).or_default() += 1;
+    }
+    Ok(counts.into_iter().map(|(tag, patients)| TagCount { tag: tag.to_string(), patients }).collect())
+}
+
+// Renames the tag on every patient; if a patient already has `to`, the two
+// are merged. Returns how many patients changed.
+#[tauri::command]
+pub fn rename_tag(app_handle: AppHandle, from: String, to: String) -> Result<usize, String> {
+    let (from, to) = (from.trim(), to.trim());
+    if from.is_empty() || to.is_empty() {
+        return Err("Informe a tag atual e o novo nome.".into());
+    }
+    let mut patients = patient::load_patients_from_disk(&app_handle).map_err(|e| e.to_string())?;
+    let mut changed = 0;
+    for patient in patients.iter_mut().filter(|p| p.tags.iter().any(|t| t == from)) {
+        patient.tags.retain(|t| t != from);
+        if !patient.tags.iter().any(|t| t == to) {
+            patient.tags.push(to.to_string());
+        }
+        changed += 1;
+    }
+    if changed > 0 {
+        patient::save_patients_to_disk(&app_handle, &patients).map_err(|e| e.to_string())?;
+    }
+    Ok(changed)
+}
+
+// Patients matching the filter (by tag, name, wallet...), in id order
+#[tauri::command]
+pub fn filter_patients(app_handle: AppHandle, filter: PatientFilter) -> Result<Vec<Patient>, String> {
+    let patients = patient::load_patients_from_disk(&app_handle).map_err(|e| e.to_string())?;
+    Ok(patients.into_iter().filter(|p| filter.matches(p)).map(photo_thumbnails::listed).collect())
+}
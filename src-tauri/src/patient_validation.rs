@@ -0,0 +1,164 @@
+use base64::Engine;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use tauri::AppHandle;
+
+use crate::card_format::{self, CardFormat};
+use crate::patient::{FaceAngle, Patient, VersionConflict};
+use crate::streamed_download::LENIENT;
+
+// Problem with one field of a patient. `field` is the path inside the record,
+// e.g. `wallet` or `digital_biometrics[1].data`.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    // 0 for a patient not stored yet
+    pub patient_id: u32,
+    pub field: String,
+    pub message: String,
+}
+
+// Carried inside the io::Error of a refused save, so commands can send the
+// field errors to the frontend instead of a single message
+#[derive(Debug)]
+pub struct ValidationFailed(pub Vec<FieldError>);
+
+impl fmt::Display for ValidationFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(first) = self.0.first() else {
+            return f.write_str("Paciente inválido.");
+        };
+        write!(f, "Paciente inválido ({}): {}", first.field, first.message)?;
+        if self.0.len() > 1 {
+            write!(f, " (e mais {} erro(s))", self.0.len() - 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationFailed {}
+
+impl From<ValidationFailed> for io::Error {
+    fn from(error: ValidationFailed) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+// Failure of a command that saves patients, serialized as
+// { "kind": ..., "message": ... } like `TotvsError`, with the field errors
+// when the patient was refused
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PatientSaveError {
+    Validation { message: String, errors: Vec<FieldError> },
+    // The copy saved is older than the stored patient
+    Conflict { message: String },
+    Other { message: String },
+}
+
+impl From<io::Error> for PatientSaveError {
+    fn from(error: io::Error) -> Self {
+        let message = error.to_string();
+        let inner = error.get_ref();
+        if let Some(ValidationFailed(errors)) = inner.and_then(|e| e.downcast_ref::<ValidationFailed>()) {
+            return Self::Validation { message, errors: errors.clone() };
+        }
+        if inner.is_some_and(|e| e.is::<VersionConflict>()) {
+            return Self::Conflict { message };
+        }
+        Self::Other { message }
+    }
+}
+
+// The wallet is checked against its card format (length, operator code,
+// check digit), chosen as `card_format::resolve_format` does. Formats are
+// read once, so a validator can be used while the data lock is held.
+pub struct PatientValidator {
+    formats: Vec<CardFormat>,
+    default_format: String,
+}
+
+impl PatientValidator {
+    pub fn new(app_handle: &AppHandle) -> Self {
+        Self {
+            formats: card_format::load_formats(app_handle),
+            default_format: card_format::default_format_id(app_handle),
+        }
+    }
+
+    pub fn validate(&self, patient: &Patient) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let mut error = |field: String, message: String| {
+            errors.push(FieldError { patient_id: patient.id, field, message });
+        };
+
+        if patient.name.trim().is_empty() {
+            error("name".into(), "Nome não informado.".into());
+        }
+
+        match card_format::pick_format(&self.formats, &self.default_format, &patient.wallet, None) {
+            Ok(format) => format.validate(&patient.wallet).into_iter().for_each(|m| error("wallet".into(), m)),
+            Err(_) if patient.wallet.trim().is_empty() => error("wallet".into(), "Número da carteira vazio.".into()),
+            Err(_) => {}
+        }
+
+        let mut labels: HashMap<FaceAngle, usize> = HashMap::new();
+        for (index, image) in patient.facial_biometrics.iter().enumerate() {
+            if let Some(first) = labels.insert(image.label, index) {
+                error(
+                    format!("facial_biometrics[{}].label", index),
+                    format!("Foto '{}' repetida (já em facial_biometrics[{}]).", image.label.as_str(), first),
+                );
+            }
+            if let Err(e) = decode_base64(&image.data) {
+                error(format!("facial_biometrics[{}].data", index), format!("Foto não é base64 válido: {}", e));
+            }
+        }
+
+        let mut fingers: HashMap<String, usize> = HashMap::new();
+        for (index, digital) in patient.digital_biometrics.iter().enumerate() {
+            let finger = digital.finger.trim().to_lowercase();
+            if finger.is_empty() {
+                error(format!("digital_biometrics[{}].finger", index), "Dedo não informado.".into());
+            } else if let Some(first) = fingers.get(&finger) {
+                error(
+                    format!("digital_biometrics[{}].finger", index),
+                    format!("Dedo '{}' repetido (já em digital_biometrics[{}]).", digital.finger, first),
+                );
+            } else {
+                fingers.insert(finger, index);
+            }
+            if digital.data.trim().is_empty() {
+                error(format!("digital_biometrics[{}].data", index), "Digital vazia.".into());
+            } else if let Err(e) = decode_base64(&digital.data) {
+                error(format!("digital_biometrics[{}].data", index), format!("Digital não é base64 válido: {}", e));
+            }
+        }
+
+        errors
+    }
+
+    // Refuses the save with every problem found
+    pub fn check(&self, patient: &Patient) -> io::Result<()> {
+        let errors = self.validate(patient);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationFailed(errors).into())
+        }
+    }
+}
+
+// Accepts a data URL prefix, line breaks and missing padding, as the photos
+// and templates coming from TOTVS have
+fn decode_base64(text: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    let data = text.rsplit(',').next().unwrap_or_default();
+    LENIENT.decode(data.split_whitespace().collect::<String>())
+}
+
+// Lets the UI show the errors before saving
+#[tauri::command]
+pub fn validate_patient(app_handle: AppHandle, patient: Patient) -> Vec<FieldError> {
+    PatientValidator::new(&app_handle).validate(&patient)
+}
@@ -0,0 +1,202 @@
+use base64::{engine::general_purpose as b64, Engine};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageOutputFormat};
+use serde::Deserialize;
+use std::io::Cursor;
+use tauri::AppHandle;
+
+use crate::patient;
+use crate::photo_refresh::normalize_photo;
+
+// EXIF tag holding the camera orientation
+const ORIENTATION_TAG: u16 = 0x0112;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PhotoFormat {
+    Jpeg,
+    Png,
+}
+
+// `photo_normalization` in app_config.json. The default 3:4 portrait suits
+// face matching; photos are cropped to the target's aspect ratio.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PhotoSettings {
+    pub enabled: bool,
+    pub width: u32,
+    pub height: u32,
+    pub format: PhotoFormat,
+    // JPEG quality, 1 to 100
+    pub quality: u8,
+}
+
+impl Default for PhotoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            width: 480,
+            height: 640,
+            format: PhotoFormat::Jpeg,
+            quality: 90,
+        }
+    }
+}
+
+pub fn settings(app_handle: &AppHandle) -> PhotoSettings {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|config| config.get("photo_normalization").cloned())
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+// Orientation (1 to 8) from the EXIF block of a JPEG, if any
+fn exif_orientation(jpeg: &[u8]) -> Option<u16> {
+    if !jpeg.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut pos = 2;
+    while pos + 4 <= jpeg.len() {
+        if jpeg[pos] != 0xFF {
+            return None;
+        }
+        let marker = jpeg[pos + 1];
+        match marker {
+            // Start of scan or end of image: no more metadata
+            0xDA | 0xD9 => return None,
+            // Markers without a length
+            0x01 | 0xD0..=0xD7 | 0xFF => {
+                pos += if marker == 0xFF { 1 } else { 2 };
+                continue;
+            }
+            _ => {}
+        }
+        let len = usize::from(u16::from_be_bytes([jpeg[pos + 2], jpeg[pos + 3]]));
+        let body = jpeg.get(pos + 4..pos + 2 + len)?;
+        if marker == 0xE1 {
+            if let Some(tiff) = body.strip_prefix(b"Exif\0\0") {
+                return tiff_orientation(tiff);
+            }
+        }
+        pos += 2 + len;
+    }
+    None
+}
+
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let little_endian = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| {
+        let bytes = [*tiff.get(offset)?, *tiff.get(offset + 1)?];
+        Some(if little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    };
+    let u32_at = |offset: usize| {
+        let bytes: [u8; 4] = tiff.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    };
+
+    // First IFD: 2-byte entry count, then 12-byte entries
+    let ifd = u32_at(4)? as usize;
+    let entries = u16_at(ifd)?;
+    (0..usize::from(entries))
+        .map(|i| ifd + 2 + i * 12)
+        .find(|&entry| u16_at(entry) == Some(ORIENTATION_TAG))
+        .and_then(|entry| u16_at(entry + 8))
+}
+
+fn apply_orientation(image: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+// Largest centred region with the target's aspect ratio
+fn center_crop(image: DynamicImage, width: u32, height: u32) -> DynamicImage {
+    let (w, h) = (u64::from(image.width()), u64::from(image.height()));
+    let (target_w, target_h) = (u64::from(width), u64::from(height));
+    let (crop_w, crop_h) = if w * target_h > h * target_w {
+        ((h * target_w / target_h).max(1), h)
+    } else {
+        (w, (w * target_h / target_w).max(1))
+    };
+    image.crop_imm(((w - crop_w) / 2) as u32, ((h - crop_h) / 2) as u32, crop_w as u32, crop_h as u32)
+}
+
+fn encode(image: &DynamicImage, settings: &PhotoSettings) -> Result<Vec<u8>, String> {
+    let mut buf = Cursor::new(Vec::new());
+    match settings.format {
+        PhotoFormat::Jpeg => {
+            let rgb = image.to_rgb8();
+            JpegEncoder::new_with_quality(&mut buf, settings.quality.clamp(1, 100))
+                .encode(rgb.as_raw(), rgb.width(), rgb.height(), image::ColorType::Rgb8)
+                .map_err(|e| format!("Falha ao gerar JPEG: {e}"))?;
+        }
+        PhotoFormat::Png => image
+            .write_to(&mut buf, ImageOutputFormat::Png)
+            .map_err(|e| format!("Falha ao gerar PNG: {e}"))?,
+    }
+    Ok(buf.into_inner())
+}
+
+// Decodes the photo whatever its format, fixes its orientation, crops and
+// resizes it to the target and re-encodes it. Input and output are base64.
+pub fn process(photo_b64: &str, settings: &PhotoSettings) -> Result<String, String> {
+    if settings.width == 0 || settings.height == 0 {
+        return Err("Dimensões de foto configuradas inválidas.".into());
+    }
+    let data = b64::STANDARD
+        .decode(photo_b64.trim())
+        .map_err(|e| format!("Base64 inválido: {e}"))?;
+    let format = image::guess_format(&data).map_err(|_| "Formato da foto não reconhecido.".to_string())?;
+    let image = image::load_from_memory_with_format(&data, format)
+        .map_err(|e| format!("Falha ao decodificar foto: {e}"))?;
+
+    let image = match format {
+        ImageFormat::Jpeg => apply_orientation(image, exif_orientation(&data).unwrap_or(1)),
+        _ => image,
+    };
+    let image = center_crop(image, settings.width, settings.height).resize_exact(
+        settings.width,
+        settings.height,
+        FilterType::Lanczos3,
+    );
+    Ok(b64::STANDARD.encode(encode(&image, settings)?))
+}
+
+// Photo as stored in the patient record: unwrapped from the API response and
+// normalized. A photo the pipeline can't read is kept as received.
+pub async fn prepare(settings: &PhotoSettings, raw: String) -> String {
+    // Decoding and resizing take long enough to stall the async runtime
+    let settings = settings.clone();
+    let fallback = raw.clone();
+    tauri::async_runtime::spawn_blocking(move || prepare_blocking(&settings, &raw))
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("Foto mantida sem normalização: {}", e);
+            normalize_photo(&fallback)
+        })
+}
+
+// `prepare` for code already off the async runtime
+pub fn prepare_blocking(settings: &PhotoSettings, raw: &str) -> String {
+    let photo = normalize_photo(raw);
+    if !settings.enabled || photo.is_empty() {
+        return photo;
+    }
+    process(&photo, settings).unwrap_or_else(|e| {
+        eprintln!("Foto mantida sem normalização: {}", e);
+        photo
+    })
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::import_jobs::ImportJob;
+use crate::patient::{self, FaceAngle};
+use crate::photo_pipeline;
+use crate::streamed_download;
+use crate::webhook::{self, JobFailure, JobReport};
+
+const DEFAULT_CONCURRENCY: usize = 4;
+const MAX_CONCURRENCY: usize = 16;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PhotoRefreshProgress {
+    pub job_id: String,
+    pub done: usize,
+    pub total: usize,
+    pub patient_id: u32,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PhotoRefreshFailure {
+    pub patient_id: u32,
+    pub wallet: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PhotoRefreshSummary {
+    pub job_id: String,
+    pub total: usize,
+    pub updated: usize,
+    pub failures: Vec<PhotoRefreshFailure>,
+    pub cancelled: bool,
+}
+
+// Same normalization the frontend applies: the API may answer with a raw
+// base64 string, a JSON string, a data URL or an object with `photo`.
+pub(crate) fn normalize_photo(raw: &str) -> String {
+    let photo = match serde_json::from_str::<serde_json::Value>(raw) {
+        Ok(serde_json::Value::String(s)) => s,
+        Ok(value) => value.get("photo").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+        Err(_) => raw.to_string(),
+    };
+    let photo = photo.rsplit(',').next().unwrap_or_default();
+    photo.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+async fn refresh_photos(app_handle: &AppHandle, concurrency: Option<usize>, job: &ImportJob) -> Result<PhotoRefreshSummary, String> {
+    let targets: Vec<(u32, String)> = patient::load_patients_from_disk(app_handle)
+        .map_err(|e| format!("Falha ao ler pacientes: {e}"))?
+        .into_iter()
+        .filter(|p| p.imported && !p.wallet.trim().is_empty())
+        .map(|p| (p.id, p.wallet))
+        .collect();
+    let total = targets.len();
+
+    let semaphore = Arc::new(Semaphore::new(
+        concurrency.unwrap_or(DEFAULT_CONCURRENCY).clamp(1, MAX_CONCURRENCY),
+    ));
+    let photo_settings = photo_pipeline::settings(app_handle);
+    let mut tasks = JoinSet::new();
+    for (patient_id, wallet) in targets {
+        let app_handle = app_handle.clone();
+        let photo_settings = photo_settings.clone();
+        let semaphore = semaphore.clone();
+        let token = job.token.clone();
+        tasks.spawn(async move {
+            token
+                .run_until_cancelled(async move {
+                    let _permit = semaphore.acquire_owned().await;
+                    let raw = crate::fetch_facial_photo(&app_handle, &wallet)
+                        .await
+                        .map_err(String::from)
+                        .and_then(|raw| streamed_download::photo_base64(&raw));
+                    let result = match raw {
+                        Ok(raw) => Ok(photo_pipeline::prepare(&photo_settings, raw).await),
+                        Err(e) => Err(e),
+                    }
+                    .and_then(|photo| {
+                        if photo.is_empty() {
+                            Err("Foto vazia retornada pela API.".to_string())
+                        } else {
+                            Ok(photo)
+                        }
+                    });
+                    (patient_id, wallet, result)
+                })
+                .await
+        });
+    }
+
+    let mut photos: HashMap<u32, String> = HashMap::new();
+    let mut failures = Vec::new();
+    let mut done = 0;
+    while let Some(joined) = tasks.join_next().await {
+        let joined = joined.map_err(|e| format!("Falha na tarefa de download: {e}"))?;
+        let Some((patient_id, wallet, result)) = joined else {
+            continue;
+        };
+        done += 1;
+        let error = match result {
+            Ok(photo) => {
+                photos.insert(patient_id, photo);
+                None
+            }
+            Err(error) => {
+                failures.push(PhotoRefreshFailure {
+                    patient_id,
+                    wallet,
+                    error: error.clone(),
+                });
+                Some(error)
+            }
+        };
+        let _ = app_handle.emit(
+            "photo-refresh-progress",
+            PhotoRefreshProgress {
+                job_id: job.id.clone(),
+                done,
+                total,
+                patient_id,
+                ok: error.is_none(),
+                error,
+            },
+        );
+    }
+
+    // Reload so edits made while the downloads ran are not overwritten
+    let mut updated = 0;
+    if !photos.is_empty() {
+        let mut patients = patient::load_patients_from_disk(app_handle)
+            .map_err(|e| format!("Falha ao ler pacientes: {e}"))?;
+        for p in patients.iter_mut() {
+            if let Some(photo) = photos.remove(&p.id) {
+                p.set_facial_photo(FaceAngle::Frontal, photo);
+                updated += 1;
+            }
+        }
+        patient::save_patients_to_disk(app_handle, &patients).map_err(|e| e.to_string())?;
+    }
+
+    Ok(PhotoRefreshSummary {
+        job_id: job.id.clone(),
+        total,
+        updated,
+        failures,
+        cancelled: job.is_cancelled(),
+    })
+}
+
+// Re-fetches the facial photo of every imported patient. Progress is emitted
+// as `photo-refresh-progress`; results are written back in a single save.
+// Cancelling the job keeps the photos downloaded so far.
+#[tauri::command]
+pub async fn refresh_imported_patient_photos(
+    app_handle: AppHandle,
+    concurrency: Option<usize>,
+    job_id: Option<String>,
+) -> Result<PhotoRefreshSummary, String> {
+    let job = ImportJob::start(&app_handle, "photo-refresh", job_id)?;
+    let result = refresh_photos(&app_handle, concurrency, &job).await;
+
+    let report = match &result {
+        Ok(summary) => JobReport {
+            total: summary.total,
+            succeeded: summary.updated,
+            failures: summary
+                .failures
+                .iter()
+                .map(|f| JobFailure {
+                    item: f.wallet.clone(),
+                    error: f.error.clone(),
+                })
+                .collect(),
+            cancelled: summary.cancelled,
+            ..JobReport::new(&job.id, &job.kind)
+        },
+        Err(error) => JobReport::failed(&job.id, &job.kind, error),
+    };
+    webhook::send_job_report(&app_handle, report);
+    result
+}
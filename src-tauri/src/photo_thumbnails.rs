@@ -0,0 +1,91 @@
+use base64::{engine::general_purpose as b64, Engine};
+use image::codecs::jpeg::JpegEncoder;
+use std::io::Cursor;
+use tauri::AppHandle;
+
+use crate::patient::{self, FaceAngle, Patient};
+use crate::streamed_download::LENIENT;
+
+// Thumbnails are stored next to each photo in the patient record (encrypted
+// like it) and are all the patient lists send: a page of full photos made the
+// webview sluggish. The full photo comes from `get_patient_photo`.
+const THUMBNAIL_SIZE: u32 = 96;
+const THUMBNAIL_QUALITY: u8 = 75;
+
+// Small JPEG of the photo, keeping its aspect ratio. Input and output are
+// base64.
+pub fn thumbnail(photo_b64: &str) -> Result<String, String> {
+    let data = photo_b64.rsplit(',').next().unwrap_or_default();
+    let data = LENIENT
+        .decode(data.split_whitespace().collect::<String>())
+        .map_err(|e| format!("Base64 inválido: {e}"))?;
+    let image = image::load_from_memory(&data).map_err(|e| format!("Falha ao decodificar foto: {e}"))?;
+    let rgb = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgb8();
+
+    let mut buf = Cursor::new(Vec::new());
+    JpegEncoder::new_with_quality(&mut buf, THUMBNAIL_QUALITY)
+        .encode(rgb.as_raw(), rgb.width(), rgb.height(), image::ColorType::Rgb8)
+        .map_err(|e| format!("Falha ao gerar miniatura: {e}"))?;
+    Ok(b64::STANDARD.encode(buf.into_inner()))
+}
+
+// A patient sent back from a list has its photos without data. Each one gets
+// the stored data back; one with nothing stored is dropped.
+pub fn restore_omitted(patient: &mut Patient, stored: Option<&Patient>) {
+    patient.facial_biometrics.retain_mut(|image| {
+        if !image.data.is_empty() || image.thumbnail.is_empty() {
+            return true;
+        }
+        match stored.and_then(|s| s.facial_photo(image.label)) {
+            Some(data) => {
+                image.data = data.to_string();
+                true
+            }
+            None => false,
+        }
+    });
+}
+
+// Before a save: a photo unchanged since `stored` keeps its thumbnail, the
+// others get a new one. A photo that can't be decoded is kept without one.
+pub fn refresh(patient: &mut Patient, stored: Option<&Patient>) {
+    for image in &mut patient.facial_biometrics {
+        let previous = stored.and_then(|s| s.facial_biometrics.iter().find(|i| i.label == image.label));
+        match previous {
+            Some(previous) if previous.data == image.data && !previous.thumbnail.is_empty() => {
+                image.thumbnail = previous.thumbnail.clone();
+            }
+            _ if image.data.is_empty() => image.thumbnail.clear(),
+            _ => {
+                image.thumbnail = thumbnail(&image.data).unwrap_or_else(|e| {
+                    eprintln!("Miniatura da foto de {} não gerada: {}", patient.name, e);
+                    String::new()
+                });
+            }
+        }
+    }
+}
+
+// The patient as the lists send it: thumbnails instead of photos. A photo
+// without a thumbnail is sent whole.
+pub fn listed(mut patient: Patient) -> Patient {
+    for image in &mut patient.facial_biometrics {
+        if !image.thumbnail.is_empty() {
+            image.data.clear();
+        }
+    }
+    patient
+}
+
+// Full photo with this label (frontal by default)
+#[tauri::command]
+pub fn get_patient_photo(app_handle: AppHandle, patient_id: u32, label: Option<FaceAngle>) -> Result<String, String> {
+    let patient = patient::get_patient(&app_handle, patient_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Paciente {} não encontrado.", patient_id))?;
+    let label = label.unwrap_or_default();
+    patient
+        .facial_photo(label)
+        .map(String::from)
+        .ok_or_else(|| format!("{} não tem foto '{}'.", patient.name, label.as_str()))
+}
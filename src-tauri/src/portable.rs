@@ -0,0 +1,122 @@
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tauri::AppHandle;
+
+use crate::biometric_crypto;
+use crate::patient;
+
+// Portable mode keeps every file of the app (patients, config, logs, the
+// encryption key) in a `data` folder next to the executable, so it can run
+// from a USB stick without touching the user profile. It's on when
+// `portable.flag` sits next to the executable or VIRTUAL_IO_HUB_PORTABLE is
+// set to 1/true. Decided once per run: switching takes effect on restart.
+const FLAG_FILE: &str = "portable.flag";
+const ENV_VAR: &str = "VIRTUAL_IO_HUB_PORTABLE";
+const DATA_DIR: &str = "data";
+// The keyring belongs to the machine, so the key travels with the data
+const KEY_FILE: &str = "biometric.key";
+
+static PORTABLE_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+#[derive(Debug, Serialize)]
+pub struct PortableStatus {
+    // Mode of this run
+    pub active: bool,
+    // Mode of the next run
+    pub flag_present: bool,
+    pub data_dir: String,
+}
+
+fn exe_dir() -> Option<PathBuf> {
+    std::env::current_exe().ok()?.parent().map(Path::to_path_buf)
+}
+
+fn env_enabled() -> bool {
+    std::env::var(ENV_VAR).is_ok_and(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true"))
+}
+
+fn flag_path() -> Option<PathBuf> {
+    Some(exe_dir()?.join(FLAG_FILE))
+}
+
+// <exe dir>/data in portable mode
+pub fn data_dir() -> Option<PathBuf> {
+    PORTABLE_DIR
+        .get_or_init(|| {
+            if env_enabled() || flag_path().is_some_and(|flag| flag.exists()) {
+                exe_dir().map(|dir| dir.join(DATA_DIR))
+            } else {
+                None
+            }
+        })
+        .clone()
+}
+
+// Files only needed for a moment (an attachment being opened) stay on the
+// stick too in portable mode
+pub fn temp_dir() -> PathBuf {
+    data_dir().map_or_else(std::env::temp_dir, |dir| dir.join("tmp"))
+}
+
+pub fn key_file() -> Option<PathBuf> {
+    Some(data_dir()?.join(KEY_FILE))
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else if entry.file_name() != "data.lock" {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+fn status(app_handle: &AppHandle) -> Result<PortableStatus, String> {
+    Ok(PortableStatus {
+        active: data_dir().is_some(),
+        flag_present: flag_path().is_some_and(|flag| flag.exists()),
+        data_dir: patient::ensure_data_dir(app_handle).map_err(|e| e.to_string())?.display().to_string(),
+    })
+}
+
+#[tauri::command]
+pub fn get_portable_status(app_handle: AppHandle) -> Result<PortableStatus, String> {
+    status(&app_handle)
+}
+
+// Creates or removes portable.flag; the app must be restarted. With
+// `copy_data`, turning it on also copies the current data and encryption key
+// to the portable folder, if that folder is still empty.
+#[tauri::command]
+pub fn set_portable_mode(app_handle: AppHandle, enabled: bool, copy_data: Option<bool>) -> Result<PortableStatus, String> {
+    let exe_dir = exe_dir().ok_or("Pasta do executável não encontrada.")?;
+    let flag = exe_dir.join(FLAG_FILE);
+
+    if !enabled {
+        if flag.exists() {
+            fs::remove_file(&flag).map_err(|e| format!("Falha ao remover {}: {e}", FLAG_FILE))?;
+        }
+        return status(&app_handle);
+    }
+
+    if copy_data.unwrap_or(false) && data_dir().is_none() {
+        let target = exe_dir.join(DATA_DIR);
+        let empty = fs::read_dir(&target).map_or(true, |mut entries| entries.next().is_none());
+        if !empty {
+            return Err(format!("A pasta {} já contém dados; nada foi copiado.", target.display()));
+        }
+        let current = patient::ensure_data_dir(&app_handle).map_err(|e| e.to_string())?;
+        copy_dir(&current, &target).map_err(|e| format!("Falha ao copiar dados para {}: {e}", target.display()))?;
+        biometric_crypto::export_key(&target.join(KEY_FILE)).map_err(|e| format!("Falha ao copiar chave de criptografia: {e}"))?;
+    }
+    fs::write(&flag, b"").map_err(|e| format!("Falha ao criar {} (pasta somente leitura?): {e}", FLAG_FILE))?;
+    status(&app_handle)
+}
@@ -0,0 +1,161 @@
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, ProcessesToUpdate, System, MINIMUM_CPU_UPDATE_INTERVAL};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::biometry_server::BiometryServerState;
+use crate::hotkey::HotkeyManager;
+use crate::multi_identity::MultiIdentityState;
+use crate::patient;
+use crate::webcam_emulator::WebcamEmulator;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResourceThresholds {
+    pub max_cpu_percent: Option<f32>,
+    pub max_memory_mb: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessUsage {
+    pub name: String,
+    pub pid: Option<u32>,
+    pub running: bool,
+    // The biometry server is a task inside the app, so it reports the app process
+    pub in_process: bool,
+    pub cpu_percent: f32,
+    pub memory_bytes: u64,
+    pub uptime_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ResourceWarning {
+    name: String,
+    pid: Option<u32>,
+    message: String,
+}
+
+fn config_thresholds(app_handle: &AppHandle) -> ResourceThresholds {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get("resource_thresholds").cloned())
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn monitored_processes(app_handle: &AppHandle) -> Vec<(String, Option<u32>)> {
+    let mut processes = Vec::new();
+
+    let hotkey_pid = app_handle
+        .state::<Mutex<HotkeyManager>>()
+        .lock()
+        .ok()
+        .and_then(|m| m.process_id());
+    processes.push(("autohotkey".to_string(), hotkey_pid));
+
+    let webcam_pid = app_handle
+        .state::<Arc<Mutex<WebcamEmulator>>>()
+        .lock()
+        .ok()
+        .and_then(|w| w.process_id());
+    processes.push(("python_webcam".to_string(), webcam_pid));
+
+    if let Ok(multi) = app_handle.state::<Arc<Mutex<MultiIdentityState>>>().lock() {
+        processes.extend(multi.process_ids().into_iter().map(|(name, pid)| (name, Some(pid))));
+    }
+
+    processes
+}
+
+fn sample(processes: &[(String, Option<u32>)], biometry_uptime: Option<u64>) -> Vec<ProcessUsage> {
+    let own_pid = std::process::id();
+    let mut pids: Vec<Pid> = processes
+        .iter()
+        .filter_map(|(_, pid)| pid.map(Pid::from_u32))
+        .collect();
+    pids.push(Pid::from_u32(own_pid));
+
+    // CPU usage is computed between two refreshes
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::Some(&pids), true);
+    std::thread::sleep(MINIMUM_CPU_UPDATE_INTERVAL);
+    system.refresh_processes(ProcessesToUpdate::Some(&pids), true);
+
+    let mut usage: Vec<ProcessUsage> = processes
+        .iter()
+        .map(|(name, pid)| {
+            let process = pid.and_then(|p| system.process(Pid::from_u32(p)));
+            ProcessUsage {
+                name: name.clone(),
+                pid: *pid,
+                running: process.is_some(),
+                in_process: false,
+                cpu_percent: process.map(|p| p.cpu_usage()).unwrap_or(0.0),
+                memory_bytes: process.map(|p| p.memory()).unwrap_or(0),
+                uptime_secs: process.map(|p| p.run_time()),
+            }
+        })
+        .collect();
+
+    let own = system.process(Pid::from_u32(own_pid));
+    usage.push(ProcessUsage {
+        name: "biometry_server".to_string(),
+        pid: Some(own_pid),
+        running: biometry_uptime.is_some(),
+        in_process: true,
+        cpu_percent: own.map(|p| p.cpu_usage()).unwrap_or(0.0),
+        memory_bytes: own.map(|p| p.memory()).unwrap_or(0),
+        uptime_secs: biometry_uptime,
+    });
+
+    usage
+}
+
+fn check_thresholds(app_handle: &AppHandle, usage: &[ProcessUsage], thresholds: &ResourceThresholds) {
+    for process in usage.iter().filter(|p| p.running && !p.in_process) {
+        let mut problems = Vec::new();
+        if let Some(max_cpu) = thresholds.max_cpu_percent {
+            if process.cpu_percent > max_cpu {
+                problems.push(format!("CPU em {:.1}% (limite {:.1}%)", process.cpu_percent, max_cpu));
+            }
+        }
+        if let Some(max_mb) = thresholds.max_memory_mb {
+            let used_mb = process.memory_bytes / (1024 * 1024);
+            if used_mb > max_mb {
+                problems.push(format!("memória em {} MB (limite {} MB)", used_mb, max_mb));
+            }
+        }
+
+        if !problems.is_empty() {
+            let _ = app_handle.emit(
+                "resource-warning",
+                ResourceWarning {
+                    name: process.name.clone(),
+                    pid: process.pid,
+                    message: problems.join(", "),
+                },
+            );
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn get_resource_usage(
+    app_handle: AppHandle,
+    thresholds: Option<ResourceThresholds>,
+) -> Result<Vec<ProcessUsage>, String> {
+    let processes = monitored_processes(&app_handle);
+    let biometry_uptime = app_handle
+        .state::<Arc<Mutex<BiometryServerState>>>()
+        .lock()
+        .ok()
+        .and_then(|s| s.uptime_secs());
+
+    let usage = tauri::async_runtime::spawn_blocking(move || sample(&processes, biometry_uptime))
+        .await
+        .map_err(|e| format!("Falha ao coletar uso de recursos: {e}"))?;
+
+    let thresholds = thresholds.unwrap_or_else(|| config_thresholds(&app_handle));
+    check_thresholds(&app_handle, &usage, &thresholds);
+
+    Ok(usage)
+}
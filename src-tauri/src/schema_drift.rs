@@ -0,0 +1,371 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use serde_json::Value;
+use tauri::AppHandle;
+
+use crate::beneficiary_import::details_key;
+use crate::beneficiary_sync::iso_utc;
+use crate::totvs::error::TotvsError;
+use crate::totvs_endpoints;
+use crate::totvs_http;
+use crate::PortalContext;
+
+// Structure the importer expects from each resource, as path -> type.
+// `items[]` is an element of `items`; alternatives are separated by `|`
+// and `text` is a body that isn't JSON. Only fields every Datasul release
+// sends are listed, so anything else shows up as added.
+const BENEFICIARY_SEARCH: &[(&str, &str)] = &[
+    ("", "object"),
+    ("items", "array"),
+    ("items[]", "object"),
+    ("items[].healthInsurer", "string|number"),
+    ("items[].cardNumber", "string|number"),
+    ("items[].completeCardNumber", "string|number"),
+    ("items[].name", "string"),
+    ("items[].person", "object"),
+    ("items[].person.name", "string"),
+    ("items[].dependents", "array"),
+    ("items[].dependents[]", "object"),
+    ("items[].dependents[].healthInsurer", "string|number"),
+    ("items[].dependents[].cardNumber", "string|number"),
+    ("items[].dependents[].completeCardNumber", "string|number"),
+    ("items[].dependents[].name", "string"),
+    ("items[].dependents[].person", "object"),
+    ("items[].dependents[].person.name", "string"),
+    ("hasNext", "boolean"),
+];
+const BENEFICIARY_DETAILS: &[(&str, &str)] = &[
+    ("", "object"),
+    ("healthInsurer", "string|number"),
+    ("cardNumber", "string|number"),
+    ("completeCardNumber", "string|number"),
+    ("name", "string"),
+    ("person", "object"),
+    ("person.name", "string"),
+];
+const FINGERPRINTS: &[(&str, &str)] = &[
+    ("", "object"),
+    ("items", "array"),
+    ("items[]", "object"),
+    ("items[].fingerCode", "number|string"),
+    ("items[].biometry", "string"),
+    ("hasNext", "boolean"),
+];
+const FACIAL_PHOTO: &[(&str, &str)] = &[("", "object|text"), ("photo", "string")];
+
+// Renames already seen across releases; the models accept both names
+const KNOWN_RENAMES: [(&str, &str); 6] = [
+    ("name", "cardName"),
+    ("name", "fullName"),
+    ("name", "nome"),
+    ("fingerCode", "code"),
+    ("biometry", "data"),
+    ("photo", "image"),
+];
+// Array elements looked at per array; fingerprint lists are short anyway
+const SAMPLE_ITEMS: usize = 20;
+
+#[derive(Debug, Serialize)]
+pub struct FieldType {
+    pub path: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RenamedField {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangedField {
+    pub path: String,
+    pub expected: String,
+    pub found: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResourceDrift {
+    pub resource: String,
+    // Set when the resource couldn't be fetched; the lists are then empty
+    pub error: Option<String>,
+    pub added: Vec<FieldType>,
+    pub removed: Vec<FieldType>,
+    pub renamed: Vec<RenamedField>,
+    pub changed: Vec<ChangedField>,
+}
+
+impl ResourceDrift {
+    fn failed(resource: &str, error: TotvsError) -> Self {
+        Self {
+            resource: resource.to_string(),
+            error: Some(error.to_string()),
+            added: Vec::new(),
+            removed: Vec::new(),
+            renamed: Vec::new(),
+            changed: Vec::new(),
+        }
+    }
+
+    fn drifted(&self) -> bool {
+        !self.removed.is_empty() || !self.renamed.is_empty() || !self.changed.is_empty()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SchemaDriftReport {
+    pub card_number: String,
+    pub checked_at: String,
+    // A field the importer relies on was removed, renamed or changed type.
+    // Added fields alone don't count.
+    pub drifted: bool,
+    pub resources: Vec<ResourceDrift>,
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn collect(value: &Value, path: String, out: &mut BTreeMap<String, BTreeSet<&'static str>>) {
+    out.entry(path.clone()).or_default().insert(type_name(value));
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                let child_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                collect(child, child_path, out);
+            }
+        }
+        Value::Array(items) => {
+            for item in items.iter().take(SAMPLE_ITEMS) {
+                collect(item, format!("{}[]", path), out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn parent(path: &str) -> Option<&str> {
+    if path.is_empty() {
+        None
+    } else if let Some(array) = path.strip_suffix("[]") {
+        Some(array)
+    } else {
+        Some(path.rsplit_once('.').map_or("", |(parent, _)| parent))
+    }
+}
+
+fn leaf(path: &str) -> &str {
+    path.rsplit_once('.').map_or(path, |(_, leaf)| leaf)
+}
+
+fn normalized(name: &str) -> String {
+    name.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase()
+}
+
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = (previous + usize::from(ca != *cb)).min(row[j] + 1).min(current + 1);
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+// Same key modulo case and separators, a known rename, a prefix/suffix
+// added (cardNumber -> beneficiaryCardNumber) or a small typo-like change
+fn similar(from: &str, to: &str) -> bool {
+    if KNOWN_RENAMES.iter().any(|(a, b)| (*a == from && *b == to) || (*a == to && *b == from)) {
+        return true;
+    }
+    let (a, b) = (normalized(from), normalized(to));
+    let shorter = a.len().min(b.len());
+    a == b || (shorter >= 4 && (a.contains(&b) || b.contains(&a))) || (shorter >= 4 && edit_distance(&a, &b) <= 2)
+}
+
+fn joined(types: &BTreeSet<&str>) -> String {
+    types.iter().copied().collect::<Vec<_>>().join("|")
+}
+
+fn compare(resource: &str, expected: &[(&str, &str)], actual: &BTreeMap<String, BTreeSet<&'static str>>) -> ResourceDrift {
+    let expected: BTreeMap<&str, Vec<&str>> = expected.iter().map(|(path, types)| (*path, types.split('|').collect())).collect();
+    let found = |path: &str| actual.get(path).map(|types| types.iter().filter(|t| **t != "null").copied().collect::<Vec<_>>());
+
+    // A path is only checked when every ancestor came with the expected
+    // type and a value: nothing can be said about the fields of a null
+    // person or the elements of an empty list
+    let checkable = |path: &str| {
+        let mut current = parent(path);
+        while let Some(ancestor) = current {
+            let Some(types) = found(ancestor) else { return false };
+            let wanted = expected.get(ancestor).cloned().unwrap_or_default();
+            if types.is_empty() || !types.iter().all(|t| wanted.contains(t)) || !types.iter().any(|t| *t == "object" || *t == "array") {
+                return false;
+            }
+            current = parent(ancestor);
+        }
+        true
+    };
+
+    let mut removed = Vec::new();
+    let mut changed = Vec::new();
+    for (path, wanted) in &expected {
+        if !checkable(path) {
+            continue;
+        }
+        match found(path) {
+            // An element missing means the list was empty
+            None if path.ends_with("[]") => {}
+            None => removed.push(FieldType { path: path.to_string(), kind: wanted.join("|") }),
+            Some(types) if !types.iter().all(|t| wanted.contains(t)) => changed.push(ChangedField {
+                path: path.to_string(),
+                expected: wanted.join("|"),
+                found: types.join("|"),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    // Only the outermost new key is listed, not the fields inside it
+    let mut added: Vec<FieldType> = actual
+        .iter()
+        .filter(|(path, _)| !expected.contains_key(path.as_str()))
+        .filter(|(path, _)| parent(path).is_some_and(|p| expected.contains_key(p) && checkable(path)))
+        .map(|(path, types)| FieldType { path: path.clone(), kind: joined(types) })
+        .collect();
+
+    let mut renamed = Vec::new();
+    removed.retain(|gone: &FieldType| {
+        let wanted: Vec<&str> = gone.kind.split('|').collect();
+        let candidate = added.iter().position(|new| {
+            parent(&new.path) == parent(&gone.path)
+                && new.kind.split('|').filter(|t| *t != "null").all(|t| wanted.contains(&t))
+                && similar(leaf(&gone.path), leaf(&new.path))
+        });
+        match candidate {
+            Some(index) => {
+                let new = added.remove(index);
+                renamed.push(RenamedField { from: gone.path.clone(), to: new.path });
+                false
+            }
+            None => true,
+        }
+    });
+
+    ResourceDrift {
+        resource: resource.to_string(),
+        error: None,
+        added,
+        removed,
+        renamed,
+        changed,
+    }
+}
+
+// Raw body of one resource, bypassing the cache and the typed models
+async fn fetch_raw(
+    app_handle: &AppHandle,
+    portal: &PortalContext,
+    endpoint: &str,
+    card_number: Option<&str>,
+    query: &[(&str, &str)],
+) -> Result<Value, TotvsError> {
+    let client = totvs_http::client(app_handle)?;
+    let response = totvs_endpoints::send(app_handle, endpoint, &portal.credentials, card_number, |url| {
+        client
+            .get(url)
+            .query(query)
+            .header("Accept", "application/json")
+            .header("x-totvs-hgp-portal-prestador-clinic", portal.clinic.as_str())
+    })
+    .await?;
+    if !response.status().is_success() {
+        return Err(TotvsError::status(response.status(), format!("Falha na requisição: {}", response.status())));
+    }
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| TotvsError::network(format!("Falha ao ler resposta: {e}")))?;
+    Ok(serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+async fn check_resource(
+    app_handle: &AppHandle,
+    portal: &PortalContext,
+    endpoint: &str,
+    card_number: Option<&str>,
+    query: &[(&str, &str)],
+    expected: &[(&str, &str)],
+) -> ResourceDrift {
+    match fetch_raw(app_handle, portal, endpoint, card_number, query).await {
+        Ok(json) => {
+            let mut actual = BTreeMap::new();
+            match json {
+                // Not JSON: only the root can be compared
+                Value::Null => {
+                    actual.insert(String::new(), BTreeSet::from(["text"]));
+                }
+                json => collect(&json, String::new(), &mut actual),
+            }
+            compare(endpoint, expected, &actual)
+        }
+        Err(e) => ResourceDrift::failed(endpoint, e),
+    }
+}
+
+// Fetches one of each TOTVS resource for the card (and the guarantor's
+// search page, when given) and compares the JSON structure with the one
+// the importer was written for, to catch changes after a Datasul upgrade
+#[tauri::command]
+pub async fn check_schema_drift(
+    app_handle: AppHandle,
+    card_number: String,
+    guarantor: Option<String>,
+) -> Result<SchemaDriftReport, TotvsError> {
+    let card_number = card_number.trim().to_string();
+    if card_number.is_empty() {
+        return Err("Número da carteira não informado.".into());
+    }
+    let portal = crate::portal_context(&app_handle)?;
+    let query = portal.query();
+
+    let mut resources = Vec::new();
+    if let Some(guarantor) = guarantor.as_deref().map(str::trim).filter(|g| !g.is_empty()) {
+        let search_query = [
+            ("guarantor", guarantor),
+            ("page", "1"),
+            ("pageSize", "1"),
+            ("expand", "person,dependents,dependents.person"),
+        ];
+        resources.push(check_resource(&app_handle, &portal, "beneficiary_search", None, &search_query, BENEFICIARY_SEARCH).await);
+    }
+    let details_key = details_key(&card_number);
+    for (endpoint, card, expected) in [
+        ("beneficiary_details", &details_key, BENEFICIARY_DETAILS),
+        ("fingerprints", &card_number, FINGERPRINTS),
+        ("facial_photo", &card_number, FACIAL_PHOTO),
+    ] {
+        resources.push(check_resource(&app_handle, &portal, endpoint, Some(card), &query, expected).await);
+    }
+
+    Ok(SchemaDriftReport {
+        card_number,
+        checked_at: iso_utc(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)),
+        drifted: resources.iter().any(ResourceDrift::drifted),
+        resources,
+    })
+}
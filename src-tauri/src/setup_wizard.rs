@@ -0,0 +1,230 @@
+use std::net::TcpListener;
+use std::time::Duration;
+use serde::Serialize;
+use tauri::AppHandle;
+use tokio::process::Command;
+
+use crate::config_assistant::{TotvsCredentials, PORTPREST_BASE};
+use crate::hotkey::HotkeyManager;
+use crate::keystroke::{self, KeystrokeBackend};
+use crate::patient;
+use crate::totvs::error::TotvsError;
+use crate::totvs_auth;
+use crate::totvs_http;
+
+const PYTHON_TIMEOUT: Duration = Duration::from_secs(20);
+const DEFAULT_BIOMETRY_PORT: u16 = 21004;
+
+// Result of one first-run wizard step; `remediation` tells the user what to do
+// when the step fails.
+#[derive(Debug, Serialize)]
+pub struct SetupCheck {
+    pub step: String,
+    pub passed: bool,
+    pub detail: String,
+    pub remediation: Option<String>,
+}
+
+impl SetupCheck {
+    fn pass(step: &str, detail: impl Into<String>) -> Self {
+        Self {
+            step: step.to_string(),
+            passed: true,
+            detail: detail.into(),
+            remediation: None,
+        }
+    }
+
+    fn fail(step: &str, detail: impl Into<String>, remediation: impl Into<String>) -> Self {
+        Self {
+            step: step.to_string(),
+            passed: false,
+            detail: detail.into(),
+            remediation: Some(remediation.into()),
+        }
+    }
+}
+
+// Runs a short Python snippet; Ok carries stdout, Err the reason it failed
+async fn run_python(code: &str) -> Result<String, String> {
+    let output = Command::new("python").args(["-c", code]).kill_on_drop(true).output();
+    let output = tokio::time::timeout(PYTHON_TIMEOUT, output)
+        .await
+        .map_err(|_| "Tempo esgotado ao executar o Python.".to_string())?
+        .map_err(|e| format!("Python não encontrado: {}", e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(stderr.trim().lines().last().unwrap_or("erro desconhecido").to_string())
+    }
+}
+
+// Probes the TOTVS API with the credentials typed in the wizard. Uses its own
+// circuit so a wrong URL doesn't suspend the calls made by the importer.
+#[tauri::command]
+pub async fn check_setup_totvs(app_handle: AppHandle, credentials: TotvsCredentials) -> Result<SetupCheck, TotvsError> {
+    const STEP: &str = "totvs";
+    let base_url = credentials.base_url.trim().trim_end_matches('/');
+    if base_url.is_empty() {
+        return Ok(SetupCheck::fail(STEP, "Base URL não informada.", "Informe a URL do servidor TOTVS, ex.: https://servidor:8080."));
+    }
+
+    let request = totvs_http::client(&app_handle)?
+        .get(format!("{}{}/healthInsurers", base_url, PORTPREST_BASE))
+        .query(&[("page", "1"), ("pageSize", "1")])
+        .header("Accept", "application/json");
+    let response = totvs_auth::send(&app_handle, "setup_check", &credentials, request).await;
+
+    let check = match response {
+        Err(e) => SetupCheck::fail(
+            STEP,
+            e,
+            "Confira a Base URL (protocolo, host e porta) e se o servidor está acessível desta máquina (VPN, firewall).",
+        ),
+        Ok(r) if r.status().is_success() => SetupCheck::pass(STEP, "Servidor TOTVS acessível e credenciais aceitas."),
+        Ok(r) if r.status() == reqwest::StatusCode::UNAUTHORIZED || r.status() == reqwest::StatusCode::FORBIDDEN => {
+            SetupCheck::fail(
+                STEP,
+                format!("Credenciais recusadas ({}).", r.status()),
+                "Confira usuário, senha e o modo de autenticação, e se o usuário tem acesso ao Portal do Prestador.",
+            )
+        }
+        Ok(r) if r.status() == reqwest::StatusCode::NOT_FOUND => SetupCheck::fail(
+            STEP,
+            "Serviço do Portal do Prestador não encontrado (404).",
+            "A Base URL deve apontar para a raiz do Datasul, sem /dts/datasul-rest no final.",
+        ),
+        Ok(r) => SetupCheck::fail(
+            STEP,
+            format!("Resposta inesperada do servidor: {}", r.status()),
+            "Tente novamente; se persistir, verifique os logs do servidor TOTVS.",
+        ),
+    };
+    Ok(check)
+}
+
+// Only looks for an existing install; unlike the hotkey itself, it never
+// downloads AutoHotkey. Only needed with the `autohotkey` keystroke backend.
+#[tauri::command]
+pub fn check_setup_autohotkey(app_handle: AppHandle) -> Result<SetupCheck, String> {
+    const STEP: &str = "autohotkey";
+    if keystroke::configured_backend(&app_handle) == KeystrokeBackend::Native {
+        return Ok(SetupCheck::pass(STEP, "Digitação nativa em uso; o AutoHotkey não é necessário."));
+    }
+    let paths = HotkeyManager::ahk_candidate_paths(&app_handle)?;
+    let check = match paths.iter().find(|p| p.exists()) {
+        Some(path) => SetupCheck::pass(STEP, format!("AutoHotkey v2 encontrado em {}", path.display())),
+        None => SetupCheck::fail(
+            STEP,
+            "AutoHotkey v2 não encontrado.",
+            "Instale o AutoHotkey v2 (autohotkey.com) ou deixe o emulador instalá-lo ao iniciar a primeira hotkey (requer internet).",
+        ),
+    };
+    Ok(check)
+}
+
+#[tauri::command]
+pub async fn check_setup_python() -> Result<Vec<SetupCheck>, String> {
+    let version = match run_python("import sys; print(sys.version.split()[0])").await {
+        Ok(version) => version,
+        Err(e) => {
+            return Ok(vec![SetupCheck::fail(
+                "python",
+                e,
+                "Instale o Python 3 (python.org) marcando \"Add python.exe to PATH\".",
+            )])
+        }
+    };
+
+    let modules = run_python("import pyvirtualcam, cv2, numpy; print(pyvirtualcam.__version__)").await;
+    let modules_check = match modules {
+        Ok(v) => SetupCheck::pass("python_modules", format!("pyvirtualcam {} com OpenCV e NumPy.", v)),
+        Err(e) => SetupCheck::fail(
+            "python_modules",
+            e,
+            "Execute: python -m pip install pyvirtualcam opencv-python numpy",
+        ),
+    };
+    Ok(vec![SetupCheck::pass("python", format!("Python {}", version)), modules_check])
+}
+
+// Opening a tiny camera is the only reliable way to know pyvirtualcam has a
+// backend (OBS Virtual Camera, Unity Capture or v4l2loopback) to talk to.
+#[tauri::command]
+pub async fn check_setup_virtual_camera() -> Result<SetupCheck, String> {
+    const STEP: &str = "virtual_camera";
+    let probe = "import pyvirtualcam\n\
+                 with pyvirtualcam.Camera(width=64, height=48, fps=5) as cam:\n    print(cam.device)";
+    let check = match run_python(probe).await {
+        Ok(device) => SetupCheck::pass(STEP, format!("Câmera virtual disponível: {}", device)),
+        Err(e) => SetupCheck::fail(
+            STEP,
+            e,
+            "Instale o OBS Studio e inicie a \"Câmera Virtual\" uma vez (ou o Unity Capture). No Linux, carregue o módulo v4l2loopback.",
+        ),
+    };
+    Ok(check)
+}
+
+// Checks the ports the emulator listens on. Defaults to the configured
+// biometry server port; a port held by the emulator's own running server is
+// reported as in use as well.
+#[tauri::command]
+pub fn check_setup_ports(app_handle: AppHandle, ports: Option<Vec<u16>>) -> Result<Vec<SetupCheck>, String> {
+    let ports = ports.unwrap_or_else(|| {
+        let configured = patient::load_config_from_disk(&app_handle)
+            .ok()
+            .and_then(|cfg| {
+                let v = cfg.get("server_port")?;
+                v.as_u64()
+                    .and_then(|n| u16::try_from(n).ok())
+                    .or_else(|| v.as_str().and_then(|s| s.parse().ok()))
+            });
+        vec![configured.unwrap_or(DEFAULT_BIOMETRY_PORT)]
+    });
+
+    let checks = ports
+        .into_iter()
+        .map(|port| {
+            let step = format!("port_{}", port);
+            match TcpListener::bind(("127.0.0.1", port)) {
+                Ok(_) => SetupCheck::pass(&step, format!("Porta {} livre.", port)),
+                Err(e) => SetupCheck::fail(
+                    &step,
+                    format!("Porta {} indisponível: {}", port, e),
+                    "Pare o servidor de biometria do emulador se estiver ativo, encerre o programa que usa a porta ou escolha outra nas configurações.",
+                ),
+            }
+        })
+        .collect();
+    Ok(checks)
+}
+
+// Every step in wizard order; TOTVS is skipped when no credentials are given
+#[tauri::command]
+pub async fn run_setup_checks(
+    app_handle: AppHandle,
+    credentials: Option<TotvsCredentials>,
+) -> Result<Vec<SetupCheck>, String> {
+    let mut checks = Vec::new();
+    if let Some(credentials) = credentials {
+        checks.push(check_setup_totvs(app_handle.clone(), credentials).await?);
+    }
+    checks.push(check_setup_autohotkey(app_handle.clone())?);
+    let python = check_setup_python().await?;
+    let python_ready = python.iter().all(|c| c.passed);
+    checks.extend(python);
+    if python_ready {
+        checks.push(check_setup_virtual_camera().await?);
+    } else {
+        checks.push(SetupCheck::fail(
+            "virtual_camera",
+            "Não verificado: Python ou módulos ausentes.",
+            "Corrija os passos do Python e verifique novamente.",
+        ));
+    }
+    checks.extend(check_setup_ports(app_handle, None)?);
+    Ok(checks)
+}
@@ -0,0 +1,320 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::oneshot;
+
+use crate::patient::{self, Patient};
+
+// The virtual reader itself is provided by the vsmartcard `vpcd` driver, which
+// shows up as a regular PC/SC reader and forwards everything to a virtual card
+// connected on this port. We play the card side.
+const DEFAULT_VPCD_HOST: &str = "127.0.0.1";
+const DEFAULT_VPCD_PORT: u16 = 35963;
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+// vpcd control messages (single byte payloads)
+const VPCD_POWER_OFF: u8 = 0x00;
+const VPCD_POWER_ON: u8 = 0x01;
+const VPCD_RESET: u8 = 0x02;
+const VPCD_GET_ATR: u8 = 0x04;
+
+// INS not supported
+const DEFAULT_FALLBACK_SW: &str = "6D00";
+
+// One scripted exchange. `apdu` is hex and may end in `*` to match by prefix;
+// `response` is hex and accepts the placeholders {wallet_ascii}, {wallet_bcd}
+// and {name_ascii}.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApduRule {
+    pub apdu: String,
+    pub response: String,
+}
+
+// Configurable via `smartcard_script` in the app config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApduScript {
+    pub atr: String,
+    pub rules: Vec<ApduRule>,
+    pub fallback: String,
+}
+
+impl Default for ApduScript {
+    fn default() -> Self {
+        let rule = |apdu: &str, response: &str| ApduRule {
+            apdu: apdu.into(),
+            response: response.into(),
+        };
+        Self {
+            atr: "3B021450".into(),
+            rules: vec![
+                // SELECT by AID
+                rule("00A40400*", "9000"),
+                // READ RECORD / READ BINARY return the wallet number
+                rule("00B2*", "{wallet_ascii}9000"),
+                rule("00B0*", "{wallet_ascii}9000"),
+            ],
+            fallback: DEFAULT_FALLBACK_SW.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SmartCardStatus {
+    pub running: bool,
+    pub connected: bool,
+    pub patient_id: Option<u32>,
+    pub endpoint: Option<String>,
+}
+
+pub struct SmartCardState {
+    shutdown_tx: Option<oneshot::Sender<()>>,
+    patient_id: Option<u32>,
+    endpoint: Option<String>,
+    connected: Arc<Mutex<bool>>,
+}
+
+impl SmartCardState {
+    pub fn new() -> Self {
+        Self {
+            shutdown_tx: None,
+            patient_id: None,
+            endpoint: None,
+            connected: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(tx) = self.shutdown_tx.take() {
+            let _ = tx.send(());
+        }
+        self.patient_id = None;
+        self.endpoint = None;
+        *self.connected.lock().unwrap() = false;
+    }
+}
+
+fn normalize_hex(hex: &str) -> String {
+    hex.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_uppercase()
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, String> {
+    let hex = normalize_hex(hex);
+    if !hex.len().is_multiple_of(2) {
+        return Err(format!("Hexadecimal com tamanho ímpar: {}", hex));
+    }
+    // By bytes, so a multi-byte character is an error instead of a slice
+    // that isn't on a char boundary
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let digit = |b: u8| (b as char).to_digit(16).ok_or_else(|| format!("Hexadecimal inválido: {}", hex));
+            Ok((digit(pair[0])? * 16 + digit(pair[1])?) as u8)
+        })
+        .collect()
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+// Digits packed two per byte, padded with F
+fn wallet_bcd(wallet: &str) -> String {
+    let mut digits: String = wallet.chars().filter(|c| c.is_ascii_digit()).collect();
+    if !digits.len().is_multiple_of(2) {
+        digits.push('F');
+    }
+    digits
+}
+
+fn render_response(template: &str, patient: &Patient) -> Result<Vec<u8>, String> {
+    let name: String = patient.name.chars().filter(|c| c.is_ascii()).collect::<String>().to_uppercase();
+    let hex = template
+        .replace("{wallet_ascii}", &encode_hex(patient.wallet.as_bytes()))
+        .replace("{wallet_bcd}", &wallet_bcd(&patient.wallet))
+        .replace("{name_ascii}", &encode_hex(name.as_bytes()));
+    decode_hex(&hex)
+}
+
+impl ApduScript {
+    fn validate(&self) -> Result<(), String> {
+        decode_hex(&self.atr)?;
+        decode_hex(&self.fallback)?;
+        for rule in &self.rules {
+            decode_hex(rule.apdu.trim_end_matches('*'))?;
+        }
+        Ok(())
+    }
+
+    fn respond(&self, apdu: &[u8], patient: &Patient) -> Vec<u8> {
+        let apdu_hex = encode_hex(apdu);
+        let rule = self.rules.iter().find(|rule| {
+            let pattern = normalize_hex(&rule.apdu);
+            match pattern.strip_suffix('*') {
+                Some(prefix) => apdu_hex.starts_with(prefix),
+                None => apdu_hex == pattern,
+            }
+        });
+
+        match rule.map(|r| render_response(&r.response, patient)) {
+            Some(Ok(response)) => response,
+            Some(Err(e)) => {
+                tracing::warn!("Resposta APDU inválida para {}: {}", apdu_hex, e);
+                decode_hex(DEFAULT_FALLBACK_SW).unwrap_or_default()
+            }
+            None => decode_hex(&self.fallback).unwrap_or_default(),
+        }
+    }
+}
+
+fn load_script(app_handle: &AppHandle) -> Result<ApduScript, String> {
+    let script = patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get("smartcard_script").cloned())
+        .map(serde_json::from_value::<ApduScript>)
+        .transpose()
+        .map_err(|e| format!("Configuração 'smartcard_script' inválida: {}", e))?
+        .unwrap_or_default();
+    script.validate()?;
+    Ok(script)
+}
+
+async fn send_message(stream: &mut TcpStream, payload: &[u8]) -> std::io::Result<()> {
+    let len = u16::try_from(payload.len()).unwrap_or(u16::MAX);
+    stream.write_all(&len.to_be_bytes()).await?;
+    stream.write_all(&payload[..len as usize]).await
+}
+
+// Serves one vpcd connection until it drops. Messages in both directions are
+// a 2-byte big-endian length followed by the payload.
+async fn serve_connection(mut stream: TcpStream, script: &ApduScript, patient: &Patient) -> std::io::Result<()> {
+    let atr = decode_hex(&script.atr).unwrap_or_default();
+    loop {
+        let mut len = [0u8; 2];
+        stream.read_exact(&mut len).await?;
+        let mut payload = vec![0u8; u16::from_be_bytes(len) as usize];
+        stream.read_exact(&mut payload).await?;
+
+        match payload.as_slice() {
+            [VPCD_GET_ATR] => send_message(&mut stream, &atr).await?,
+            [VPCD_POWER_OFF] | [VPCD_POWER_ON] | [VPCD_RESET] => {}
+            apdu => {
+                let response = script.respond(apdu, patient);
+                tracing::debug!("APDU {} -> {}", encode_hex(apdu), encode_hex(&response));
+                send_message(&mut stream, &response).await?;
+            }
+        }
+    }
+}
+
+async fn run_card(
+    endpoint: String,
+    script: ApduScript,
+    patient: Patient,
+    connected: Arc<Mutex<bool>>,
+    mut shutdown_rx: oneshot::Receiver<()>,
+) {
+    loop {
+        let session = async {
+            // vpcd may not be up yet (driver service starting); keep retrying
+            let stream = TcpStream::connect(&endpoint).await?;
+            tracing::info!("Cartão virtual conectado ao leitor em {}", endpoint);
+            *connected.lock().unwrap() = true;
+            serve_connection(stream, &script, &patient).await
+        };
+
+        tokio::select! {
+            _ = &mut shutdown_rx => break,
+            result = session => {
+                *connected.lock().unwrap() = false;
+                if let Err(e) = result {
+                    tracing::warn!("Conexão com o leitor virtual encerrada: {}", e);
+                }
+            }
+        }
+
+        tokio::select! {
+            _ = &mut shutdown_rx => break,
+            _ = tokio::time::sleep(RECONNECT_INTERVAL) => {}
+        }
+    }
+    *connected.lock().unwrap() = false;
+    tracing::info!("Cartão virtual desligado");
+}
+
+#[tauri::command]
+pub fn start_smartcard_emulator(
+    app_handle: AppHandle,
+    patient_id: u32,
+    host: Option<String>,
+    port: Option<u16>,
+    state: tauri::State<'_, Arc<Mutex<SmartCardState>>>,
+) -> Result<bool, String> {
+    let patient = patient::load_patients_from_disk(&app_handle)
+        .map_err(|e| format!("Falha ao ler pacientes: {e}"))?
+        .into_iter()
+        .find(|p| p.id == patient_id)
+        .ok_or_else(|| format!("Paciente {} não encontrado.", patient_id))?;
+    let script = load_script(&app_handle)?;
+    let endpoint = format!(
+        "{}:{}",
+        host.unwrap_or_else(|| DEFAULT_VPCD_HOST.to_string()),
+        port.unwrap_or(DEFAULT_VPCD_PORT)
+    );
+
+    let mut state = state.lock().map_err(|_| "Falha ao obter lock do cartão virtual".to_string())?;
+    state.stop();
+
+    let (tx, rx) = oneshot::channel();
+    // Fresh flag so the previous task winding down can't overwrite it
+    let connected = Arc::new(Mutex::new(false));
+    state.connected = connected.clone();
+    tauri::async_runtime::spawn(run_card(endpoint.clone(), script, patient, connected, rx));
+
+    state.shutdown_tx = Some(tx);
+    state.patient_id = Some(patient_id);
+    state.endpoint = Some(endpoint);
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn stop_smartcard_emulator(state: tauri::State<'_, Arc<Mutex<SmartCardState>>>) -> Result<bool, String> {
+    let mut state = state.lock().map_err(|_| "Falha ao obter lock do cartão virtual".to_string())?;
+    state.stop();
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn check_smartcard_emulator_status(state: tauri::State<'_, Arc<Mutex<SmartCardState>>>) -> Result<SmartCardStatus, String> {
+    let state = state.lock().map_err(|_| "Falha ao obter lock do cartão virtual".to_string())?;
+    let connected = *state.connected.lock().unwrap();
+    Ok(SmartCardStatus {
+        running: state.shutdown_tx.is_some(),
+        connected,
+        patient_id: state.patient_id,
+        endpoint: state.endpoint.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_hex_round_trip() {
+        assert_eq!(decode_hex("00 a4 04 0C").unwrap(), vec![0x00, 0xA4, 0x04, 0x0C]);
+        assert_eq!(encode_hex(&decode_hex("9000").unwrap()), "9000");
+    }
+
+    #[test]
+    fn decode_hex_rejects_invalid_input() {
+        assert!(decode_hex("0").is_err());
+        assert!(decode_hex("0G").is_err());
+        // Two bytes in UTF-8: even length, but not hex
+        assert!(decode_hex("é").is_err());
+        assert!(decode_hex("aé0").is_err());
+    }
+}
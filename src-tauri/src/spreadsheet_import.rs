@@ -0,0 +1,335 @@
+use calamine::{open_workbook_auto, Data, Reader};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use tauri::AppHandle;
+
+use crate::beneficiary_import::{finger_code, finger_name, new_patient};
+use crate::card_format;
+use crate::patient::{self, DigitalBiometric, Patient};
+
+// Digits a spreadsheet number holds exactly; a longer wallet typed as a
+// number already had its last digits rounded by Excel
+const MAX_EXACT_DIGITS: usize = 15;
+
+// Which spreadsheet column (by header, ignoring case) holds each field.
+// Only name and wallet are required.
+#[derive(Debug, Deserialize)]
+pub struct ColumnMapping {
+    pub name: String,
+    pub wallet: String,
+    #[serde(default)]
+    pub tags: Option<String>,
+    // Base64 photo
+    #[serde(default)]
+    pub facial_biometric: Option<String>,
+    // Finger name (e.g. "Polegar Direito") -> column with the base64 template
+    #[serde(default)]
+    pub fingerprints: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlannedPatient {
+    // 1-based, counting the header
+    pub row: usize,
+    // Assigned only when not a dry run
+    pub id: Option<u32>,
+    pub name: String,
+    pub wallet: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateRow {
+    pub row: usize,
+    pub wallet: String,
+    // Stored patient with this wallet, or earlier row of the file
+    pub existing_id: Option<u32>,
+    pub duplicate_of_row: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowError {
+    pub row: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpreadsheetImportReport {
+    pub dry_run: bool,
+    pub rows: usize,
+    pub created: Vec<PlannedPatient>,
+    pub duplicates: Vec<DuplicateRow>,
+    pub errors: Vec<RowError>,
+}
+
+// Spreadsheets tend to format card numbers with dots, dashes or spaces
+fn wallet_key(wallet: &str) -> String {
+    wallet.chars().filter(|c| c.is_ascii_alphanumeric()).collect()
+}
+
+// Every cell is read as text. `numeric` marks a spreadsheet cell stored as a
+// number, which lost the leading zeros its column format may display.
+struct Cell {
+    text: String,
+    numeric: bool,
+}
+
+impl Cell {
+    fn text(text: impl Into<String>) -> Self {
+        Self { text: text.into(), numeric: false }
+    }
+}
+
+// Card numbers typed in Excel come back as floats; whole ones are written
+// without the decimal part
+fn cell_text(cell: &Data) -> Cell {
+    match cell {
+        Data::Float(f) if f.fract() == 0.0 => Cell { text: format!("{:.0}", f), numeric: true },
+        Data::Int(i) => Cell { text: i.to_string(), numeric: true },
+        Data::Empty => Cell::text(""),
+        other => Cell::text(other.to_string()),
+    }
+}
+
+fn read_xlsx(path: &Path) -> Result<Vec<Vec<Cell>>, String> {
+    let mut workbook = open_workbook_auto(path).map_err(|e| format!("Falha ao abrir planilha: {e}"))?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| "A planilha não tem abas.".to_string())?
+        .map_err(|e| format!("Falha ao ler planilha: {e}"))?;
+    Ok(range.rows().map(|row| row.iter().map(cell_text).collect()).collect())
+}
+
+// A wallet from a number cell, zero-padded back to the card length. None
+// when Excel already rounded it.
+fn numeric_wallet(wallet: &str, wallet_length: Option<usize>) -> Option<String> {
+    if wallet.len() > MAX_EXACT_DIGITS {
+        return None;
+    }
+    Some(match wallet_length {
+        Some(length) if wallet.len() < length => format!("{:0>length$}", wallet),
+        _ => wallet.to_string(),
+    })
+}
+
+// Excel in pt-BR saves CSV with ';', so the separator is whichever of ';'
+// and ',' the header line has more of
+fn read_csv(path: &Path) -> Result<Vec<Vec<Cell>>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Falha ao ler CSV: {e}"))?;
+    let text = text.trim_start_matches('\u{feff}');
+    let header = text.lines().next().unwrap_or_default();
+    let delimiter = if header.matches(';').count() > header.matches(',').count() { b';' } else { b',' };
+    csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes())
+        .records()
+        .map(|record| {
+            record
+                .map(|r| r.iter().map(Cell::text).collect())
+                .map_err(|e| format!("CSV inválido: {e}"))
+        })
+        .collect()
+}
+
+fn read_rows(path: &Path) -> Result<Vec<Vec<Cell>>, String> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    match extension.as_str() {
+        "csv" | "txt" => read_csv(path),
+        "xlsx" | "xlsm" | "xls" | "ods" => read_xlsx(path),
+        _ => Err(format!("Formato de arquivo não suportado: .{}. Use CSV ou XLSX.", extension)),
+    }
+}
+
+struct Columns {
+    name: usize,
+    wallet: usize,
+    tags: Option<usize>,
+    facial_biometric: Option<usize>,
+    fingerprints: Vec<(String, usize)>,
+}
+
+impl Columns {
+    fn resolve(header: &[Cell], mapping: &ColumnMapping) -> Result<Self, String> {
+        let find = |column: &str| {
+            header
+                .iter()
+                .position(|h| h.text.trim().eq_ignore_ascii_case(column.trim()))
+                .ok_or_else(|| format!("Coluna \"{}\" não encontrada no cabeçalho.", column))
+        };
+        let fingerprints = mapping
+            .fingerprints
+            .iter()
+            .map(|(finger, column)| {
+                let code = finger_code(finger).ok_or_else(|| format!("Dedo desconhecido no mapeamento: {}", finger))?;
+                Ok((finger_name(code), find(column)?))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self {
+            name: find(&mapping.name)?,
+            wallet: find(&mapping.wallet)?,
+            tags: mapping.tags.as_deref().map(find).transpose()?,
+            facial_biometric: mapping.facial_biometric.as_deref().map(find).transpose()?,
+            fingerprints,
+        })
+    }
+
+    fn patient(&self, row: &[Cell]) -> Patient {
+        let cell = |index: usize| row.get(index).map(|v| v.text.trim().to_string()).unwrap_or_default();
+        let digital_biometrics = self
+            .fingerprints
+            .iter()
+            .map(|(finger, index)| DigitalBiometric { finger: finger.clone(), data: cell(*index) })
+            .filter(|d| !d.data.is_empty())
+            .collect();
+        let mut patient = new_patient(
+            cell(self.name),
+            wallet_key(&cell(self.wallet)),
+            digital_biometrics,
+            self.facial_biometric.map(cell).unwrap_or_default(),
+        );
+        patient.imported = false;
+        patient.tags = self
+            .tags
+            .map(|index| {
+                cell(index)
+                    .split([';', ','])
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        patient
+    }
+}
+
+// Creates patients from a CSV or XLSX (first sheet) whose first row is the
+// header. Rows whose wallet is already stored, or repeats an earlier row,
+// are reported and skipped. With `dry_run` nothing is saved.
+#[tauri::command]
+pub async fn import_patients_from_file(
+    app_handle: AppHandle,
+    path: String,
+    mapping: ColumnMapping,
+    dry_run: Option<bool>,
+) -> Result<SpreadsheetImportReport, String> {
+    tauri::async_runtime::spawn_blocking(move || import_file(&app_handle, &path, &mapping, dry_run.unwrap_or(false)))
+        .await
+        .map_err(|e| format!("Falha ao importar planilha: {e}"))?
+}
+
+fn import_file(
+    app_handle: &AppHandle,
+    path: &str,
+    mapping: &ColumnMapping,
+    dry_run: bool,
+) -> Result<SpreadsheetImportReport, String> {
+    let rows = read_rows(Path::new(path.trim()))?;
+    let (header, rows) = rows.split_first().ok_or_else(|| "O arquivo está vazio.".to_string())?;
+    let columns = Columns::resolve(header, mapping)?;
+    let wallet_length = card_format::resolve_format(app_handle, "", None).ok().and_then(|f| f.wallet_length);
+
+    let mut patients = patient::load_patients_from_disk(app_handle).map_err(|e| format!("Falha ao ler pacientes: {e}"))?;
+    let stored: HashMap<String, u32> = patients.iter().map(|p| (wallet_key(&p.wallet), p.id)).collect();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut next_id = patients.iter().map(|p| p.id).max().unwrap_or(0) + 1;
+    let mut report = SpreadsheetImportReport {
+        dry_run,
+        rows: 0,
+        created: Vec::new(),
+        duplicates: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    for (index, row) in rows.iter().enumerate() {
+        let row_number = index + 2;
+        if row.iter().all(|c| c.text.trim().is_empty()) {
+            continue;
+        }
+        report.rows += 1;
+
+        let mut patient = columns.patient(row);
+        if row.get(columns.wallet).is_some_and(|c| c.numeric) {
+            match numeric_wallet(&patient.wallet, wallet_length) {
+                Some(wallet) => patient.wallet = wallet,
+                None => {
+                    report.errors.push(RowError {
+                        row: row_number,
+                        message: "Carteira gravada como número na planilha perdeu dígitos; formate a coluna como texto.".into(),
+                    });
+                    continue;
+                }
+            }
+        }
+        if patient.wallet.is_empty() {
+            report.errors.push(RowError { row: row_number, message: "Carteira em branco.".into() });
+            continue;
+        }
+        if patient.name.is_empty() {
+            report.errors.push(RowError { row: row_number, message: "Nome em branco.".into() });
+            continue;
+        }
+        let existing_id = stored.get(&patient.wallet).copied();
+        let duplicate_of_row = seen.get(&patient.wallet).copied();
+        if existing_id.is_some() || duplicate_of_row.is_some() {
+            report.duplicates.push(DuplicateRow {
+                row: row_number,
+                wallet: patient.wallet,
+                existing_id,
+                duplicate_of_row,
+            });
+            continue;
+        }
+        seen.insert(patient.wallet.clone(), row_number);
+
+        let id = (!dry_run).then_some(next_id);
+        report.created.push(PlannedPatient {
+            row: row_number,
+            id,
+            name: patient.name.clone(),
+            wallet: patient.wallet.clone(),
+        });
+        if !dry_run {
+            patients.push(Patient { id: next_id, ..patient });
+            next_id += 1;
+        }
+    }
+
+    if !dry_run && !report.created.is_empty() {
+        let skipped = patient::save_patients_from(app_handle, &patients, "spreadsheet_import", patient::SaveMode::Import)
+            .map_err(|e| e.to_string())?;
+        // Rows whose patient failed validation move from created to errors
+        for error in skipped {
+            let Some(index) = report.created.iter().position(|c| c.id == Some(error.patient_id)) else {
+                continue;
+            };
+            let row = report.created.remove(index).row;
+            report.errors.push(RowError { row, message: error.message });
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_wallets_get_their_leading_zeros_back() {
+        assert_eq!(numeric_wallet("12345", Some(8)).as_deref(), Some("00012345"));
+        assert_eq!(numeric_wallet("12345678", Some(8)).as_deref(), Some("12345678"));
+        assert_eq!(numeric_wallet("12345", None).as_deref(), Some("12345"));
+        assert_eq!(numeric_wallet("1234567890123456", Some(17)), None);
+    }
+
+    #[test]
+    fn cell_text_marks_number_cells() {
+        let cell = cell_text(&Data::Float(1234.0));
+        assert_eq!((cell.text.as_str(), cell.numeric), ("1234", true));
+        let cell = cell_text(&Data::String("0001234".into()));
+        assert_eq!((cell.text.as_str(), cell.numeric), ("0001234", false));
+    }
+}
@@ -0,0 +1,303 @@
+use base64::alphabet;
+use base64::engine::{general_purpose as b64, DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+use crate::biometric_crypto::{self, EncryptedReader, EncryptedWriter};
+use crate::patient;
+use crate::totvs::error::TotvsError;
+use crate::totvs::models::{FacialPhoto, Fingerprint};
+
+// Responses of these endpoints carry whole images in base64. The shared
+// client doesn't buffer them; they're read chunk by chunk here.
+pub const STREAMED_ENDPOINTS: [&str; 2] = ["facial_photo", "fingerprints"];
+// Size (bytes) past which a download is written, encrypted, to
+// <data dir>/downloads instead of kept in memory; `stream_to_disk_threshold`
+// in app_config.json
+const DEFAULT_THRESHOLD: u64 = 2 * 1024 * 1024;
+// Shorter base64-looking runs may still be JSON keys or a data URL header
+const MIN_PAYLOAD_RUN: usize = 64;
+// Bytes kept from the start of a download to guess the file type
+const HEAD_LEN: usize = 16;
+
+// Chunks end anywhere, so the last one may lack its padding
+pub(crate) const LENIENT: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+pub enum Downloaded {
+    Memory(Vec<u8>),
+    File(PathBuf),
+}
+
+pub fn threshold(app_handle: &AppHandle) -> u64 {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|config| config.get("stream_to_disk_threshold").and_then(|v| v.as_u64()))
+        .unwrap_or(DEFAULT_THRESHOLD)
+}
+
+fn downloads_dir(app_handle: &AppHandle) -> io::Result<PathBuf> {
+    let mut dir = patient::ensure_data_dir(app_handle)?;
+    dir.push("downloads");
+    if !dir.exists() {
+        fs::create_dir_all(&dir)?;
+    }
+    Ok(dir)
+}
+
+// One file per card and kind, replaced by the next download of the same item
+fn file_name(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect()
+}
+
+fn extension(head: &[u8]) -> &'static str {
+    match image::guess_format(head) {
+        Ok(image::ImageFormat::Jpeg) => "jpg",
+        Ok(image::ImageFormat::Png) => "png",
+        Ok(image::ImageFormat::Bmp) => "bmp",
+        _ if head.starts_with(&[0xFF, 0xA0]) => "wsq",
+        _ => "bin",
+    }
+}
+
+enum Sink {
+    Memory(Vec<u8>),
+    // Boxed: the cipher state is large next to an empty Vec
+    Disk(Box<EncryptedWriter<File>>),
+}
+
+// Keeps data in memory until it passes the threshold, then moves it to a file
+struct Spill {
+    threshold: u64,
+    path: PathBuf,
+    sink: Sink,
+    head: Vec<u8>,
+}
+
+impl Spill {
+    fn new(app_handle: &AppHandle, name: &str) -> Result<Self, String> {
+        let dir = downloads_dir(app_handle).map_err(|e| format!("Falha ao preparar pasta de downloads: {e}"))?;
+        Ok(Self {
+            threshold: threshold(app_handle),
+            path: dir.join(file_name(name)),
+            sink: Sink::Memory(Vec::new()),
+            head: Vec::new(),
+        })
+    }
+
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        if self.head.len() < HEAD_LEN {
+            let take = (HEAD_LEN - self.head.len()).min(data.len());
+            self.head.extend_from_slice(&data[..take]);
+        }
+        if let Sink::Memory(buf) = &self.sink {
+            if (buf.len() + data.len()) as u64 > self.threshold {
+                let mut file = Box::new(EncryptedWriter::new(File::create(&self.path)?)?);
+                file.write_all(buf)?;
+                self.sink = Sink::Disk(file);
+            }
+        }
+        match &mut self.sink {
+            Sink::Memory(buf) => buf.extend_from_slice(data),
+            Sink::Disk(file) => file.write_all(data)?,
+        }
+        Ok(())
+    }
+
+    // Files get an extension matching their content, for the frontend
+    fn finish(self, with_extension: bool) -> io::Result<Downloaded> {
+        match self.sink {
+            Sink::Memory(buf) => Ok(Downloaded::Memory(buf)),
+            Sink::Disk(file) => {
+                file.finish()?.sync_all()?;
+                if !with_extension {
+                    return Ok(Downloaded::File(self.path));
+                }
+                let path = self.path.with_extension(extension(&self.head));
+                fs::rename(&self.path, &path)?;
+                Ok(Downloaded::File(path))
+            }
+        }
+    }
+}
+
+// Picks the base64 payload out of whatever wraps it (a JSON string or
+// object, a data URL) as the bytes arrive, and decodes it in 4-char groups
+#[derive(Default)]
+struct Base64Decoder {
+    run: Vec<u8>,
+    longest: Vec<u8>,
+    committed: bool,
+    finished: bool,
+    escaped: bool,
+}
+
+impl Base64Decoder {
+    fn push(&mut self, chunk: &[u8], out: &mut Spill) -> Result<(), String> {
+        for &byte in chunk {
+            if self.finished {
+                break;
+            }
+            if std::mem::take(&mut self.escaped) {
+                // JSON escapes: "\/" is a slash, "\n" and friends are line breaks
+                if byte == b'/' {
+                    self.run.push(byte);
+                }
+                continue;
+            }
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'+' | b'/' | b'=' => self.run.push(byte),
+                b'\\' => self.escaped = true,
+                b' ' | b'\t' | b'\r' | b'\n' => {}
+                _ if self.committed => self.finished = true,
+                _ => {
+                    if self.run.len() > self.longest.len() {
+                        self.longest = std::mem::take(&mut self.run);
+                    }
+                    self.run.clear();
+                }
+            }
+            if !self.committed && self.run.len() >= MIN_PAYLOAD_RUN {
+                self.committed = true;
+            }
+        }
+        if self.committed {
+            let complete = self.run.len() - self.run.len() % 4;
+            let rest = self.run.split_off(complete);
+            let data = LENIENT.decode(&self.run).map_err(|e| format!("Base64 inválido: {e}"))?;
+            out.write(&data).map_err(|e| format!("Falha ao gravar download: {e}"))?;
+            self.run = rest;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self, out: &mut Spill) -> Result<(), String> {
+        // A payload shorter than MIN_PAYLOAD_RUN was never committed
+        if !self.committed && self.longest.len() > self.run.len() {
+            self.run = std::mem::take(&mut self.longest);
+        }
+        let data = LENIENT.decode(&self.run).map_err(|e| format!("Base64 inválido: {e}"))?;
+        out.write(&data).map_err(|e| format!("Falha ao gravar download: {e}"))
+    }
+}
+
+// Reads a base64 response as it arrives and returns the decoded bytes, in
+// memory or, past the threshold, in <data dir>/downloads/<name>.<ext>
+pub async fn download_base64(app_handle: &AppHandle, mut response: reqwest::Response, name: &str) -> Result<Downloaded, TotvsError> {
+    let mut out = Spill::new(app_handle, name)?;
+    let mut decoder = Base64Decoder::default();
+    while let Some(chunk) = response.chunk().await.map_err(|e| TotvsError::network(format!("Falha ao receber dados: {e}")))? {
+        decoder.push(&chunk, &mut out).map_err(TotvsError::parse)?;
+    }
+    decoder.finish(&mut out).map_err(TotvsError::parse)?;
+    Ok(out.finish(true).map_err(|e| format!("Falha ao gravar download: {e}"))?)
+}
+
+// Reads a JSON response as it arrives, spilling large bodies to a temporary
+// file so the raw text and the parsed value aren't both held in memory
+pub async fn download_json(app_handle: &AppHandle, mut response: reqwest::Response, name: &str) -> Result<serde_json::Value, TotvsError> {
+    let mut out = Spill::new(app_handle, name)?;
+    while let Some(chunk) = response.chunk().await.map_err(|e| TotvsError::network(format!("Falha ao receber dados: {e}")))? {
+        out.write(&chunk).map_err(|e| format!("Falha ao gravar download: {e}"))?;
+    }
+    match out.finish(false).map_err(|e| format!("Falha ao gravar download: {e}"))? {
+        Downloaded::Memory(body) => serde_json::from_slice(&body).map_err(|e| TotvsError::parse(format!("Falha ao decodificar JSON: {e}"))),
+        Downloaded::File(path) => {
+            let file = File::open(&path)
+                .and_then(|file| EncryptedReader::new(BufReader::new(file)))
+                .map_err(|e| format!("Falha ao ler download: {e}"))?;
+            let json = serde_json::from_reader(file).map_err(|e| TotvsError::parse(format!("Falha ao decodificar JSON: {e}")));
+            let _ = fs::remove_file(&path);
+            json
+        }
+    }
+}
+
+pub(crate) fn file_base64(path: &Path) -> Result<String, String> {
+    let data = biometric_crypto::read_encrypted_file(path).map_err(|e| format!("Falha ao ler download: {e}"))?;
+    Ok(b64::STANDARD.encode(data))
+}
+
+// Spilled files are only needed once, so they're removed once read
+fn take_file(path: &Path) -> Result<String, String> {
+    let base64 = file_base64(path)?;
+    let _ = fs::remove_file(path);
+    Ok(base64)
+}
+
+// The photo as base64 whether it came inline or was written to disk, for
+// callers that store it in the patient record
+pub fn photo_base64(photo: &FacialPhoto) -> Result<String, String> {
+    match &photo.file {
+        None => Ok(photo.photo.clone()),
+        Some(path) => take_file(Path::new(path)),
+    }
+}
+
+// Files left by a session that ended before reading them
+pub fn clear_downloads(app_handle: &AppHandle) {
+    let Ok(entries) = downloads_dir(app_handle).and_then(fs::read_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let _ = fs::remove_file(entry.path());
+    }
+}
+
+// The frontend reads a spilled photo or fingerprint (the `file` of
+// get_facial_biometry and get_fingerprints) here, as base64. Only files in the
+// downloads folder are served, and each only once.
+#[tauri::command]
+pub fn read_downloaded_file(app_handle: AppHandle, path: String) -> Result<String, String> {
+    let dir = downloads_dir(&app_handle)
+        .and_then(|dir| dir.canonicalize())
+        .map_err(|e| format!("Falha ao preparar pasta de downloads: {e}"))?;
+    let path = Path::new(&path)
+        .canonicalize()
+        .map_err(|e| format!("Download não encontrado: {e}"))?;
+    if path.parent() != Some(dir.as_path()) {
+        return Err("O arquivo não é um download do emulador.".into());
+    }
+    take_file(&path)
+}
+
+// Fingerprints whose decoded template passes the threshold are written to
+// disk, so the frontend gets a path instead of the base64 string
+pub fn spill_fingerprints(app_handle: &AppHandle, card_number: &str, fingerprints: Vec<Fingerprint>) -> Vec<Fingerprint> {
+    let limit = threshold(app_handle);
+    fingerprints
+        .into_iter()
+        .map(|mut fingerprint| {
+            let Some(biometry) = fingerprint.biometry.as_deref() else {
+                return fingerprint;
+            };
+            // Base64 takes 4 characters for every 3 bytes
+            if (biometry.len() as u64) * 3 / 4 <= limit {
+                return fingerprint;
+            }
+            let name = format!("fingerprint-{}-{}", card_number, fingerprint.finger_code.unwrap_or(0));
+            let written = LENIENT
+                .decode(biometry.split_whitespace().collect::<String>())
+                .map_err(|e| format!("Base64 inválido: {e}"))
+                .and_then(|data| {
+                    let dir = downloads_dir(app_handle).map_err(|e| format!("Falha ao preparar pasta de downloads: {e}"))?;
+                    let path = dir.join(file_name(&name)).with_extension(extension(&data));
+                    biometric_crypto::write_encrypted_file(&path, &data).map_err(|e| format!("Falha ao gravar digital: {e}"))?;
+                    Ok(path)
+                });
+            match written {
+                Ok(path) => {
+                    fingerprint.file = Some(path.to_string_lossy().into_owned());
+                    fingerprint.biometry = None;
+                }
+                Err(e) => tracing::warn!("Digital {} mantida em memória: {}", name, e),
+            }
+            fingerprint
+        })
+        .collect()
+}
@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::process::Command;
+use tauri::{AppHandle, Emitter};
+
+use crate::keystroke::quote_ahk;
+use crate::patient;
+
+// Card text is only typed when the foreground window matches
+// `keystroke_target` in the app config, so a misfocused chat never gets it.
+// Patterns are case-insensitive; `*` matches any run of characters and `?`
+// one, and a pattern without them only has to appear in the text. A blocked
+// send emits `keystroke-blocked`; the AutoHotkey scripts check the window
+// themselves and show a tray tip, a one-shot one also exiting with
+// `AHK_BLOCKED_EXIT`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TargetWindow {
+    pub enabled: bool,
+    pub title_pattern: String,
+    // Window class on Windows and X11, application name on macOS
+    pub class_pattern: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ForegroundWindow {
+    pub title: String,
+    pub class: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockedSend {
+    pub reason: &'static str,
+    // None when the foreground window couldn't be read
+    pub window: Option<ForegroundWindow>,
+    pub title_pattern: String,
+    pub class_pattern: String,
+}
+
+// Exit code of a one-shot AutoHotkey script that didn't type
+pub const AHK_BLOCKED_EXIT: i32 = 2;
+
+pub fn configured_target(app_handle: &AppHandle) -> TargetWindow {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get("keystroke_target").cloned())
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+fn has_wildcards(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+fn glob(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and of the text it was tried against
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        // Checked first, so a `*` in the text doesn't match it literally
+        if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the `*` take one more character
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn matches(pattern: &str, text: &str) -> bool {
+    let (pattern, text) = (pattern.to_lowercase(), text.to_lowercase());
+    if !has_wildcards(&pattern) {
+        return text.contains(&pattern);
+    }
+    glob(&pattern.chars().collect::<Vec<_>>(), &text.chars().collect::<Vec<_>>())
+}
+
+// The same pattern as an AutoHotkey (PCRE) regex
+fn ahk_regex(pattern: &str) -> String {
+    let mut regex = String::from("i)");
+    let anchored = has_wildcards(pattern);
+    if anchored {
+        regex.push('^');
+    }
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '\\' | '.' | '+' | '[' | ']' | '{' | '}' | '(' | ')' | '|' | '^' | '$' => {
+                regex.push('\\');
+                regex.push(c);
+            }
+            _ => regex.push(c),
+        }
+    }
+    if anchored {
+        regex.push('$');
+    }
+    regex
+}
+
+impl TargetWindow {
+    fn is_active(&self) -> bool {
+        self.enabled && !(self.title_pattern.trim().is_empty() && self.class_pattern.trim().is_empty())
+    }
+
+    fn matches(&self, window: &ForegroundWindow) -> bool {
+        let title = self.title_pattern.trim();
+        let class = self.class_pattern.trim();
+        (title.is_empty() || matches(title, &window.title)) && (class.is_empty() || matches(class, &window.class))
+    }
+
+    // AutoHotkey lines leaving the hotkey (or the script, with `exit` =
+    // "ExitApp") when the active window doesn't match; empty when disabled
+    pub fn ahk_guard(&self, exit: &str) -> String {
+        if !self.is_active() {
+            return String::new();
+        }
+        let mut criteria = Vec::new();
+        if !self.title_pattern.trim().is_empty() {
+            criteria.push(ahk_regex(self.title_pattern.trim()));
+        }
+        if !self.class_pattern.trim().is_empty() {
+            criteria.push(format!("ahk_class {}", ahk_regex(self.class_pattern.trim())));
+        }
+        format!(
+            "SetTitleMatchMode \"RegEx\"\nif !WinActive({}) {{\n    TrayTip \"A janela ativa não é a janela alvo.\", \"Envio bloqueado\"\n    {}\n}}\n",
+            quote_ahk(&criteria.join(" ")),
+            exit
+        )
+    }
+}
+
+#[cfg(windows)]
+fn foreground_window() -> Option<ForegroundWindow> {
+    use std::ffi::c_void;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetForegroundWindow() -> *mut c_void;
+        fn GetWindowTextW(hwnd: *mut c_void, text: *mut u16, max_count: i32) -> i32;
+        fn GetClassNameW(hwnd: *mut c_void, class_name: *mut u16, max_count: i32) -> i32;
+    }
+
+    let hwnd = unsafe { GetForegroundWindow() };
+    if hwnd.is_null() {
+        return None;
+    }
+    let mut title = [0u16; 512];
+    let mut class = [0u16; 256];
+    let title_len = unsafe { GetWindowTextW(hwnd, title.as_mut_ptr(), title.len() as i32) };
+    let class_len = unsafe { GetClassNameW(hwnd, class.as_mut_ptr(), class.len() as i32) };
+    Some(ForegroundWindow {
+        title: String::from_utf16_lossy(&title[..title_len.max(0) as usize]),
+        class: String::from_utf16_lossy(&class[..class_len.max(0) as usize]),
+    })
+}
+
+// X11 only, through xdotool
+#[cfg(target_os = "linux")]
+fn foreground_window() -> Option<ForegroundWindow> {
+    let query = |command: &str| -> Option<String> {
+        let output = Command::new("xdotool").args(["getactivewindow", command]).output().ok()?;
+        output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    Some(ForegroundWindow {
+        title: query("getwindowname")?,
+        class: query("getwindowclassname").unwrap_or_default(),
+    })
+}
+
+// Through System Events, which needs the Accessibility permission typing
+// already asks for
+#[cfg(target_os = "macos")]
+fn foreground_window() -> Option<ForegroundWindow> {
+    let script = [
+        "tell application \"System Events\"",
+        "set frontApp to first application process whose frontmost is true",
+        "set windowTitle to \"\"",
+        "try",
+        "set windowTitle to name of front window of frontApp",
+        "end try",
+        "return (name of frontApp) & linefeed & windowTitle",
+        "end tell",
+    ];
+    let output = Command::new("osascript")
+        .args(script.iter().flat_map(|line| ["-e", *line]))
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let (class, title) = text.trim_end().split_once('\n').unwrap_or((text.trim_end(), ""));
+    Some(ForegroundWindow {
+        title: title.to_string(),
+        class: class.to_string(),
+    })
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+fn foreground_window() -> Option<ForegroundWindow> {
+    None
+}
+
+// Called right before typing. An unreadable foreground window blocks too.
+pub fn check(app_handle: &AppHandle) -> Result<(), String> {
+    let target = configured_target(app_handle);
+    if !target.is_active() {
+        return Ok(());
+    }
+    let window = foreground_window();
+    if window.as_ref().is_some_and(|w| target.matches(w)) {
+        return Ok(());
+    }
+
+    Err(blocked(app_handle, target, window))
+}
+
+// For a one-shot script that exited with `AHK_BLOCKED_EXIT`
+pub fn blocked_by_script(app_handle: &AppHandle) -> String {
+    blocked(app_handle, configured_target(app_handle), foreground_window())
+}
+
+// Emits `keystroke-blocked` and returns the error of the send
+fn blocked(app_handle: &AppHandle, target: TargetWindow, window: Option<ForegroundWindow>) -> String {
+    let message = match &window {
+        Some(w) => format!("Envio bloqueado: a janela ativa \"{}\" ({}) não é a janela alvo.", w.title, w.class),
+        None => "Envio bloqueado: não foi possível identificar a janela ativa.".to_string(),
+    };
+    let _ = app_handle.emit(
+        "keystroke-blocked",
+        BlockedSend {
+            reason: "wrong_window",
+            window,
+            title_pattern: target.title_pattern,
+            class_pattern: target.class_pattern,
+        },
+    );
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+
+    #[test]
+    fn plain_pattern_is_a_substring() {
+        assert!(matches("totvs", "Portal TOTVS Saúde"));
+        assert!(!matches("totvs", "Chrome"));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("portal*saúde", "Portal TOTVS Saúde"));
+        assert!(matches("c?rome", "Chrome"));
+        assert!(!matches("portal*x", "Portal TOTVS Saúde"));
+        assert!(matches("*", ""));
+    }
+
+    #[test]
+    fn star_in_the_text_does_not_end_the_match() {
+        // The `*` of the pattern meeting a `*` of the text must stay a wildcard
+        assert!(matches("a*c", "a*bc"));
+        assert!(matches("*b", "*ab"));
+    }
+}
@@ -0,0 +1,112 @@
+use serde::Serialize;
+use std::fmt;
+
+use super::models::ModelError;
+
+// Failure of a TOTVS command, serialized as { "kind": ..., "message": ... }
+// so the frontend can tell a login problem from a network one while still
+// showing the same message as before.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TotvsError {
+    // Credentials or token refused (401/403)
+    Unauthorized { message: String },
+    // Beneficiary or resource missing (404)
+    NotFound { message: String },
+    // Connection failures, timeouts, 5xx, open circuit
+    Network { message: String },
+    // Response outside the expected format
+    ParseError { message: String },
+    // Missing or invalid settings
+    Config { message: String },
+    // Anything else, e.g. failing to save the imported patients
+    Other { message: String },
+}
+
+impl TotvsError {
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::Unauthorized { message: message.into() }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::NotFound { message: message.into() }
+    }
+
+    pub fn network(message: impl Into<String>) -> Self {
+        Self::Network { message: message.into() }
+    }
+
+    pub fn parse(message: impl Into<String>) -> Self {
+        Self::ParseError { message: message.into() }
+    }
+
+    pub fn config(message: impl Into<String>) -> Self {
+        Self::Config { message: message.into() }
+    }
+
+    // An unsuccessful HTTP status, classified by code
+    pub fn status(status: reqwest::StatusCode, message: impl Into<String>) -> Self {
+        let message = message.into();
+        match status {
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => Self::Unauthorized { message },
+            reqwest::StatusCode::NOT_FOUND => Self::NotFound { message },
+            s if s.is_server_error() => Self::Network { message },
+            _ => Self::Other { message },
+        }
+    }
+
+    // Same kind, with the message prefixed
+    pub fn context(self, prefix: &str) -> Self {
+        let prefixed = |message: String| format!("{}: {}", prefix, message);
+        match self {
+            Self::Unauthorized { message } => Self::Unauthorized { message: prefixed(message) },
+            Self::NotFound { message } => Self::NotFound { message: prefixed(message) },
+            Self::Network { message } => Self::Network { message: prefixed(message) },
+            Self::ParseError { message } => Self::ParseError { message: prefixed(message) },
+            Self::Config { message } => Self::Config { message: prefixed(message) },
+            Self::Other { message } => Self::Other { message: prefixed(message) },
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Self::Unauthorized { message }
+            | Self::NotFound { message }
+            | Self::Network { message }
+            | Self::ParseError { message }
+            | Self::Config { message }
+            | Self::Other { message } => message,
+        }
+    }
+}
+
+impl fmt::Display for TotvsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+// Code outside the TOTVS layer keeps working with plain messages
+impl From<TotvsError> for String {
+    fn from(error: TotvsError) -> Self {
+        error.message().to_string()
+    }
+}
+
+impl From<String> for TotvsError {
+    fn from(message: String) -> Self {
+        Self::Other { message }
+    }
+}
+
+impl From<&str> for TotvsError {
+    fn from(message: &str) -> Self {
+        Self::Other { message: message.to_string() }
+    }
+}
+
+impl From<ModelError> for TotvsError {
+    fn from(error: ModelError) -> Self {
+        Self::ParseError { message: error.to_string() }
+    }
+}
@@ -0,0 +1,256 @@
+use std::fmt;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+
+// Typed views of the Datasul responses the importer relies on. Fields the
+// emulator doesn't use are kept in `extra` and passed through unchanged, and
+// key variants seen across Datasul releases are accepted as aliases.
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Person {
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Beneficiary {
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub health_insurer: Option<String>,
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub card_number: Option<String>,
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub complete_card_number: Option<String>,
+    #[serde(alias = "cardName", alias = "fullName", alias = "nome")]
+    pub name: Option<String>,
+    pub person: Option<Person>,
+    #[serde(default)]
+    pub dependents: Vec<Dependent>,
+    // Set when the response came from the local TOTVS cache
+    #[serde(rename = "from_cache", default, skip_deserializing)]
+    pub from_cache: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dependent {
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub health_insurer: Option<String>,
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub card_number: Option<String>,
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub complete_card_number: Option<String>,
+    #[serde(alias = "cardName", alias = "fullName", alias = "nome")]
+    pub name: Option<String>,
+    pub person: Option<Person>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fingerprint {
+    #[serde(default, alias = "code", deserialize_with = "u32_or_string")]
+    pub finger_code: Option<u32>,
+    #[serde(alias = "data")]
+    pub biometry: Option<String>,
+    #[serde(rename = "from_cache", default, skip_deserializing)]
+    pub from_cache: bool,
+    // Set instead of `biometry` when a large template was written to disk
+    #[serde(default, skip_deserializing)]
+    pub file: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// Answer to a check-in: the protocol identifies the attendance, the
+// authorization code is present when the validation was accepted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkin {
+    #[serde(default, alias = "protocolNumber", alias = "checkinProtocol", deserialize_with = "string_or_number")]
+    pub protocol: Option<String>,
+    #[serde(default, alias = "authorization", alias = "authorizationNumber", deserialize_with = "string_or_number")]
+    pub authorization_code: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// Authorization (guia) requested for a beneficiary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Authorization {
+    #[serde(default, alias = "guideNumber", alias = "authorizationNumber", alias = "numeroGuia", deserialize_with = "string_or_number")]
+    pub number: Option<String>,
+    #[serde(default, alias = "requestDate", alias = "issueDate", alias = "authorizationDate", deserialize_with = "string_or_number")]
+    pub date: Option<String>,
+    #[serde(default, alias = "procedureCode", alias = "procedimento", deserialize_with = "string_or_number")]
+    pub procedure: Option<String>,
+    #[serde(default, alias = "procedureName")]
+    pub procedure_description: Option<String>,
+    #[serde(default, alias = "situation", alias = "situacao", deserialize_with = "string_or_number")]
+    pub status: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FacialPhoto {
+    pub photo: String,
+    pub from_cache: bool,
+    // Path of the decoded image when the download passed the size threshold;
+    // `photo` is then empty
+    pub file: Option<String>,
+}
+
+// A response that doesn't match the model: `path` locates the offending
+// object (e.g. `items[2].dependents[0]`), `missing_fields` lists every
+// required key absent from it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelError {
+    pub path: String,
+    pub missing_fields: Vec<String>,
+    pub detail: Option<String>,
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Resposta do TOTVS fora do formato esperado em {}", self.path)?;
+        if !self.missing_fields.is_empty() {
+            write!(f, ": campos ausentes {}", self.missing_fields.join(", "))?;
+        }
+        if let Some(detail) = &self.detail {
+            write!(f, " ({})", detail)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<ModelError> for String {
+    fn from(error: ModelError) -> Self {
+        error.to_string()
+    }
+}
+
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::String(s)) if !s.trim().is_empty() => Some(s),
+        Some(Value::Number(n)) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+fn u32_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::Number(n)) => n.as_u64().and_then(|n| u32::try_from(n).ok()),
+        Some(Value::String(s)) => s.trim().parse().ok(),
+        _ => None,
+    })
+}
+
+fn has_text(value: &Option<String>) -> bool {
+    value.as_deref().is_some_and(|s| !s.trim().is_empty())
+}
+
+fn missing_identity(
+    card_number: &Option<String>,
+    complete_card_number: &Option<String>,
+    name: &Option<String>,
+    person: &Option<Person>,
+) -> Vec<String> {
+    let mut missing = Vec::new();
+    if !has_text(card_number) && !has_text(complete_card_number) {
+        missing.push("cardNumber".to_string());
+    }
+    if !has_text(name) && !person.as_ref().is_some_and(|p| has_text(&p.name)) {
+        missing.push("name".to_string());
+    }
+    missing
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(value: Value, path: &str) -> Result<T, ModelError> {
+    serde_json::from_value(value).map_err(|e| ModelError {
+        path: path.to_string(),
+        missing_fields: Vec::new(),
+        detail: Some(e.to_string()),
+    })
+}
+
+fn check(path: String, missing_fields: Vec<String>) -> Result<(), ModelError> {
+    if missing_fields.is_empty() {
+        Ok(())
+    } else {
+        Err(ModelError { path, missing_fields, detail: None })
+    }
+}
+
+impl Beneficiary {
+    pub fn from_value(value: Value, path: &str) -> Result<Self, ModelError> {
+        let beneficiary: Self = parse(value, path)?;
+        check(
+            path.to_string(),
+            missing_identity(
+                &beneficiary.card_number,
+                &beneficiary.complete_card_number,
+                &beneficiary.name,
+                &beneficiary.person,
+            ),
+        )?;
+        for (i, dependent) in beneficiary.dependents.iter().enumerate() {
+            check(
+                format!("{}.dependents[{}]", path, i),
+                missing_identity(&dependent.card_number, &dependent.complete_card_number, &dependent.name, &dependent.person),
+            )?;
+        }
+        Ok(beneficiary)
+    }
+}
+
+impl Fingerprint {
+    pub fn from_value(value: Value, path: &str) -> Result<Self, ModelError> {
+        let fingerprint: Self = parse(value, path)?;
+        let mut missing = Vec::new();
+        if fingerprint.finger_code.is_none() {
+            missing.push("fingerCode".to_string());
+        }
+        if !has_text(&fingerprint.biometry) {
+            missing.push("biometry".to_string());
+        }
+        check(path.to_string(), missing)?;
+        Ok(fingerprint)
+    }
+}
+
+impl Authorization {
+    pub fn from_value(value: Value, path: &str) -> Result<Self, ModelError> {
+        let authorization: Self = parse(value, path)?;
+        let missing = if has_text(&authorization.number) { Vec::new() } else { vec!["number".to_string()] };
+        check(path.to_string(), missing)?;
+        Ok(authorization)
+    }
+}
+
+impl Checkin {
+    pub fn from_value(value: Value, path: &str) -> Result<Self, ModelError> {
+        let checkin: Self = parse(value, path)?;
+        let missing = if has_text(&checkin.protocol) { Vec::new() } else { vec!["protocol".to_string()] };
+        check(path.to_string(), missing)?;
+        Ok(checkin)
+    }
+}
+
+// Validates every element of a list response, naming the first one that fails
+pub fn parse_list<T>(
+    items: Vec<Value>,
+    name: &str,
+    parse_item: impl Fn(Value, &str) -> Result<T, ModelError>,
+) -> Result<Vec<T>, ModelError> {
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| parse_item(item, &format!("{}[{}]", name, i)))
+        .collect()
+}
@@ -0,0 +1,211 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Mutex;
+
+use crate::circuit_breaker;
+use crate::config_assistant::TotvsCredentials;
+use crate::patient;
+use crate::totvs::error::TotvsError;
+use crate::totvs_http;
+use crate::totvs_profiles;
+
+const DEFAULT_TOKEN_ENDPOINT: &str = "/api/oauth2/v1/token";
+// Renew a bit before the server-side expiry so in-flight requests don't race it
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+// Used when the token response has no expires_in
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMode {
+    #[default]
+    Basic,
+    Token,
+}
+
+// Token settings, read from the importer config next to base_url/user/password
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct TokenSettings {
+    auth_mode: AuthMode,
+    token_endpoint: Option<String>,
+    token_client_id: Option<String>,
+    token_client_secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    #[serde(alias = "token")]
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<u64>,
+}
+
+struct CachedToken {
+    // Identifies the server and user the token was issued for
+    owner: String,
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_at: Instant,
+}
+
+// Bearer token shared by all TOTVS commands. A tokio mutex so concurrent
+// commands wait for one login instead of each starting their own.
+pub struct TokenCache {
+    token: Option<CachedToken>,
+}
+
+impl TokenCache {
+    pub fn new() -> Self {
+        Self { token: None }
+    }
+}
+
+fn load_settings(app_handle: &AppHandle) -> TokenSettings {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .map(|cfg| totvs_profiles::importer_settings(&cfg))
+        .and_then(|cfg| serde_json::from_value(cfg).ok())
+        .unwrap_or_default()
+}
+
+fn token_url(base_url: &str, settings: &TokenSettings) -> String {
+    let endpoint = settings.token_endpoint.as_deref().unwrap_or(DEFAULT_TOKEN_ENDPOINT);
+    if endpoint.starts_with("http://") || endpoint.starts_with("https://") {
+        endpoint.to_string()
+    } else {
+        format!("{}{}", base_url.trim_end_matches('/'), endpoint)
+    }
+}
+
+async fn request_token(
+    app_handle: &AppHandle,
+    credentials: &TotvsCredentials,
+    settings: &TokenSettings,
+    refresh_token: Option<&str>,
+) -> Result<CachedToken, TotvsError> {
+    let mut form: Vec<(&str, &str)> = match refresh_token {
+        Some(refresh_token) => vec![("grant_type", "refresh_token"), ("refresh_token", refresh_token)],
+        None => vec![
+            ("grant_type", "password"),
+            ("username", credentials.user.as_str()),
+            ("password", credentials.password.as_str()),
+        ],
+    };
+    if let Some(client_id) = settings.token_client_id.as_deref() {
+        form.push(("client_id", client_id));
+    }
+    if let Some(client_secret) = settings.token_client_secret.as_deref() {
+        form.push(("client_secret", client_secret));
+    }
+
+    let request = totvs_http::client(app_handle)?
+        .post(token_url(&credentials.base_url, settings))
+        .form(&form)
+        .header("Accept", "application/json");
+    let response = circuit_breaker::send(app_handle, "auth_token", request).await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let message = format!("Falha ao obter token de acesso: {}", status);
+        // OAuth servers refuse bad credentials with 400 invalid_grant
+        return Err(if status == reqwest::StatusCode::BAD_REQUEST {
+            TotvsError::unauthorized(message)
+        } else {
+            TotvsError::status(status, message)
+        });
+    }
+    let token: TokenResponse = response
+        .json()
+        .await
+        .map_err(|e| TotvsError::parse(format!("Resposta de token inválida: {e}")))?;
+
+    let lifetime = token.expires_in.map(Duration::from_secs).unwrap_or(DEFAULT_TOKEN_LIFETIME);
+    Ok(CachedToken {
+        owner: format!("{}|{}", credentials.base_url, credentials.user),
+        access_token: token.access_token,
+        refresh_token: token.refresh_token,
+        expires_at: Instant::now() + lifetime.saturating_sub(EXPIRY_MARGIN),
+    })
+}
+
+// Returns a valid access token, refreshing or logging in again as needed
+async fn access_token(
+    app_handle: &AppHandle,
+    credentials: &TotvsCredentials,
+    settings: &TokenSettings,
+) -> Result<String, TotvsError> {
+    let cache = app_handle.state::<Arc<Mutex<TokenCache>>>();
+    let mut cache = cache.lock().await;
+    let owner = format!("{}|{}", credentials.base_url, credentials.user);
+
+    if let Some(token) = cache.token.as_ref().filter(|t| t.owner == owner) {
+        if Instant::now() < token.expires_at {
+            return Ok(token.access_token.clone());
+        }
+    }
+
+    let refresh_token = cache
+        .token
+        .take()
+        .filter(|t| t.owner == owner)
+        .and_then(|t| t.refresh_token);
+    let token = match refresh_token {
+        Some(refresh_token) => {
+            match request_token(app_handle, credentials, settings, Some(&refresh_token)).await {
+                Ok(token) => token,
+                // Refresh tokens expire too; fall back to a full login
+                Err(e) => {
+                    tracing::warn!(target: "totvs", "Renovação do token falhou ({}), autenticando novamente", e);
+                    request_token(app_handle, credentials, settings, None).await?
+                }
+            }
+        }
+        None => request_token(app_handle, credentials, settings, None).await?,
+    };
+
+    let access_token = token.access_token.clone();
+    cache.token = Some(token);
+    Ok(access_token)
+}
+
+async fn invalidate(app_handle: &AppHandle) {
+    let cache = app_handle.state::<Arc<Mutex<TokenCache>>>();
+    cache.lock().await.token = None;
+}
+
+// Authenticates a TOTVS request with the configured mode (`auth_mode`: "basic"
+// or "token") and sends it through the endpoint's circuit. In token mode a 401
+// discards the cached token and the request is tried once more with a new one.
+pub async fn send(
+    app_handle: &AppHandle,
+    endpoint: &str,
+    credentials: &TotvsCredentials,
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, TotvsError> {
+    let settings = load_settings(app_handle);
+    let mode = credentials.auth_mode.unwrap_or(settings.auth_mode);
+
+    if mode == AuthMode::Basic {
+        let request = request.basic_auth(&credentials.user, Some(&credentials.password));
+        return circuit_breaker::send(app_handle, endpoint, request).await;
+    }
+
+    let retry = request.try_clone();
+    let token = access_token(app_handle, credentials, &settings).await?;
+    let response = circuit_breaker::send(app_handle, endpoint, request.bearer_auth(token)).await?;
+    if response.status() != reqwest::StatusCode::UNAUTHORIZED {
+        return Ok(response);
+    }
+
+    invalidate(app_handle).await;
+    match retry {
+        Some(request) => {
+            let token = access_token(app_handle, credentials, &settings).await?;
+            circuit_breaker::send(app_handle, endpoint, request.bearer_auth(token)).await
+        }
+        None => Ok(response),
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::biometric_crypto;
+use crate::blob_store::sha256_hex;
+use crate::patient;
+
+const DEFAULT_TTL_SECS: u64 = 600;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    key: String,
+    stored_at: u64,
+    value: serde_json::Value,
+}
+
+// TOTVS responses keyed by endpoint + URL + query. Kept in memory and mirrored
+// under <data dir>/totvs_cache, encrypted as they may carry photos and
+// fingerprints, so they survive restarts; entries older than
+// `totvs_cache_ttl_secs` (0 disables the cache) are ignored.
+pub struct TotvsCache {
+    entries: HashMap<String, CacheEntry>,
+}
+
+impl TotvsCache {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn ttl_secs(app_handle: &AppHandle) -> u64 {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get("totvs_cache_ttl_secs").and_then(|v| v.as_u64()))
+        .unwrap_or(DEFAULT_TTL_SECS)
+}
+
+fn cache_dir(app_handle: &AppHandle) -> io::Result<PathBuf> {
+    let dir = patient::ensure_data_dir(app_handle)?.join("totvs_cache");
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+fn entry_path(app_handle: &AppHandle, key: &str) -> io::Result<PathBuf> {
+    Ok(cache_dir(app_handle)?.join(format!("{}.json", sha256_hex(key.as_bytes()))))
+}
+
+pub fn key(endpoint: &str, url: &str, query: &[(&str, &str)]) -> String {
+    let mut query = query.to_vec();
+    query.sort();
+    let query: Vec<String> = query.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    format!("{} {}?{}", endpoint, url, query.join("&"))
+}
+
+pub fn get(app_handle: &AppHandle, key: &str) -> Option<serde_json::Value> {
+    let ttl = ttl_secs(app_handle);
+    if ttl == 0 {
+        return None;
+    }
+    let fresh = |entry: &CacheEntry| now_secs().saturating_sub(entry.stored_at) < ttl;
+
+    let state = app_handle.state::<Arc<Mutex<TotvsCache>>>();
+    let mut cache = state.lock().unwrap();
+    if let Some(entry) = cache.entries.get(key) {
+        return fresh(entry).then(|| entry.value.clone());
+    }
+
+    // Not in memory yet (e.g. after a restart): look on disk
+    let entry: CacheEntry = entry_path(app_handle, key)
+        .and_then(|path| biometric_crypto::read_encrypted_file(&path))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .filter(|entry: &CacheEntry| entry.key == key)?;
+    let value = fresh(&entry).then(|| entry.value.clone());
+    cache.entries.insert(key.to_string(), entry);
+    value
+}
+
+pub fn put(app_handle: &AppHandle, key: &str, value: &serde_json::Value) {
+    if ttl_secs(app_handle) == 0 {
+        return;
+    }
+    let entry = CacheEntry {
+        key: key.to_string(),
+        stored_at: now_secs(),
+        value: value.clone(),
+    };
+    let written = entry_path(app_handle, key)
+        .and_then(|path| biometric_crypto::write_encrypted_file(&path, &serde_json::to_vec(&entry)?));
+    if let Err(e) = written {
+        tracing::warn!("Falha ao gravar cache TOTVS: {}", e);
+    }
+    let state = app_handle.state::<Arc<Mutex<TotvsCache>>>();
+    state.lock().unwrap().entries.insert(key.to_string(), entry);
+}
+
+// Drops one entry, e.g. after a write made the cached response stale
+pub fn remove(app_handle: &AppHandle, key: &str) {
+    let state = app_handle.state::<Arc<Mutex<TotvsCache>>>();
+    state.lock().unwrap().entries.remove(key);
+    if let Ok(path) = entry_path(app_handle, key) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+// Returns how many entries were removed from disk
+#[tauri::command]
+pub fn clear_totvs_cache(app_handle: AppHandle, state: tauri::State<'_, Arc<Mutex<TotvsCache>>>) -> Result<usize, String> {
+    state
+        .lock()
+        .map_err(|_| "Falha ao obter lock do cache TOTVS".to_string())?
+        .entries
+        .clear();
+
+    let dir = cache_dir(&app_handle).map_err(|e| format!("Falha ao acessar cache: {e}"))?;
+    let mut removed = 0;
+    for entry in fs::read_dir(&dir).map_err(|e| format!("Falha ao listar cache: {e}"))?.flatten() {
+        if fs::remove_file(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+
+use crate::config_assistant::TotvsCredentials;
+use crate::patient;
+use crate::totvs::error::TotvsError;
+use crate::totvs_auth;
+use crate::totvs_http;
+use crate::totvs_profiles;
+
+const DATASUL: &str = "/dts/datasul-rest/resources/prg";
+
+// Path templates per endpoint, in the order they are tried. `{card}` is
+// replaced by the card number. Older Datasul releases only expose the first
+// version, newer ones sometimes only the second.
+fn default_templates(endpoint: &str) -> Vec<String> {
+    let versions: &[&str] = match endpoint {
+        "beneficiary_search" => &["hvp/v2/beneficiaries/subscriber", "hvp/v3/beneficiaries/subscriber"],
+        "beneficiary_details" | "checkin" => &[
+            "portprest/v1/checkin/beneficiaries/{card}",
+            "portprest/v2/checkin/beneficiaries/{card}",
+        ],
+        "fingerprints" | "fingerprint_enrollment" => &[
+            "portprest/v1/checkin/beneficiaries/{card}/fingerPrints",
+            "portprest/v2/checkin/beneficiaries/{card}/fingerPrints",
+        ],
+        "eligibility" => &[
+            "portprest/v1/checkin/beneficiaries/{card}/eligibility",
+            "portprest/v2/checkin/beneficiaries/{card}/eligibility",
+        ],
+        "authorizations" => &[
+            "portprest/v1/checkin/beneficiaries/{card}/authorizations",
+            "portprest/v2/checkin/beneficiaries/{card}/authorizations",
+        ],
+        "facial_photo" => &[
+            "portprest/v1/checkin/beneficiaries/{card}/photo",
+            "portprest/v2/checkin/beneficiaries/{card}/photo",
+        ],
+        _ => &[],
+    };
+    versions.iter().map(|v| format!("{}/{}", DATASUL, v)).collect()
+}
+
+// Template that answered last, per base URL and endpoint, so the probing
+// only happens on the first call against a server
+pub struct EndpointVersions {
+    working: HashMap<String, String>,
+}
+
+impl EndpointVersions {
+    pub fn new() -> Self {
+        Self {
+            working: HashMap::new(),
+        }
+    }
+}
+
+fn memory_key(base_url: &str, endpoint: &str) -> String {
+    format!("{}|{}", base_url.trim_end_matches('/'), endpoint)
+}
+
+// Templates configured under `endpoint_templates` (a string or a list per
+// endpoint, usually set on a connection profile) come before the defaults
+fn configured_templates(app_handle: &AppHandle, endpoint: &str) -> Vec<String> {
+    let config = patient::load_config_from_disk(app_handle).unwrap_or_default();
+    let settings = totvs_profiles::importer_settings(&config);
+    match settings.get("endpoint_templates").and_then(|t| t.get(endpoint)) {
+        Some(serde_json::Value::String(template)) => vec![template.clone()],
+        Some(serde_json::Value::Array(templates)) => templates
+            .iter()
+            .filter_map(|t| t.as_str().map(String::from))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn remembered(app_handle: &AppHandle, base_url: &str, endpoint: &str) -> Option<String> {
+    let state = app_handle.state::<Arc<Mutex<EndpointVersions>>>();
+    let working = state.lock().unwrap().working.get(&memory_key(base_url, endpoint)).cloned();
+    working
+}
+
+fn candidates(app_handle: &AppHandle, base_url: &str, endpoint: &str) -> Vec<String> {
+    let mut templates = configured_templates(app_handle, endpoint);
+    templates.extend(default_templates(endpoint));
+
+    if let Some(working) = remembered(app_handle, base_url, endpoint) {
+        templates.insert(0, working);
+    }
+
+    let mut unique = Vec::with_capacity(templates.len());
+    for template in templates {
+        if !template.trim().is_empty() && !unique.contains(&template) {
+            unique.push(template);
+        }
+    }
+    unique
+}
+
+fn render(base_url: &str, template: &str, card_number: Option<&str>) -> String {
+    let path = template.replace("{card}", card_number.unwrap_or_default());
+    format!("{}{}", base_url.trim_end_matches('/'), path)
+}
+
+// URL of the preferred version, e.g. to key cached responses
+pub fn url(app_handle: &AppHandle, base_url: &str, endpoint: &str, card_number: Option<&str>) -> String {
+    let template = candidates(app_handle, base_url, endpoint).into_iter().next().unwrap_or_default();
+    render(base_url, &template, card_number)
+}
+
+// HEAD request with the URL, query and headers of `request`, to find out
+// whether a version exists without sending its body
+fn probe_request(app_handle: &AppHandle, request: reqwest::RequestBuilder) -> Result<reqwest::RequestBuilder, TotvsError> {
+    let mut request = request
+        .build()
+        .map_err(|e| TotvsError::config(format!("Requisição inválida: {e}")))?;
+    *request.method_mut() = reqwest::Method::HEAD;
+    *request.body_mut() = None;
+    Ok(reqwest::RequestBuilder::from_parts(totvs_http::client(app_handle)?, request))
+}
+
+// Sends the request built by `build` for each version of the endpoint until
+// one doesn't answer 404. The version that answered is remembered; if all
+// of them 404, the last response is returned to the caller. Only GET and
+// HEAD are sent more than once: for other methods each untried version is
+// probed with HEAD first, and the request itself goes out a single time.
+pub async fn send(
+    app_handle: &AppHandle,
+    endpoint: &str,
+    credentials: &TotvsCredentials,
+    card_number: Option<&str>,
+    build: impl Fn(&str) -> reqwest::RequestBuilder,
+) -> Result<reqwest::Response, TotvsError> {
+    let templates = candidates(app_handle, &credentials.base_url, endpoint);
+    let working = remembered(app_handle, &credentials.base_url, endpoint);
+    let repeatable = templates.first().is_some_and(|template| {
+        let method = build(&render(&credentials.base_url, template, card_number)).build().map(|r| r.method().clone());
+        matches!(method, Ok(reqwest::Method::GET | reqwest::Method::HEAD))
+    });
+    let mut last_response = None;
+
+    let count = templates.len();
+    for (index, template) in templates.into_iter().enumerate() {
+        let url = render(&credentials.base_url, &template, card_number);
+        let probe = !repeatable && index + 1 < count && working.as_ref() != Some(&template);
+        if probe {
+            let response = totvs_auth::send(app_handle, endpoint, credentials, probe_request(app_handle, build(&url))?).await?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                tracing::info!(target: "totvs", "{} respondeu 404 em {}, tentando próxima versão", endpoint, url);
+                last_response = Some(response);
+                continue;
+            }
+        }
+        let response = totvs_auth::send(app_handle, endpoint, credentials, build(&url)).await?;
+        if repeatable && response.status() == reqwest::StatusCode::NOT_FOUND {
+            tracing::info!(target: "totvs", "{} respondeu 404 em {}, tentando próxima versão", endpoint, url);
+            last_response = Some(response);
+            continue;
+        }
+
+        let state = app_handle.state::<Arc<Mutex<EndpointVersions>>>();
+        state
+            .lock()
+            .unwrap()
+            .working
+            .insert(memory_key(&credentials.base_url, endpoint), template);
+        return Ok(response);
+    }
+
+    last_response.ok_or_else(|| TotvsError::config(format!("Nenhum caminho configurado para o endpoint {}.", endpoint)))
+}
@@ -0,0 +1,110 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+use crate::beneficiary_sync::iso_utc;
+use crate::fingerprint;
+use crate::fingerprint_enrollment::{enrollment_body, patient_prints, prepare_prints};
+use crate::mock_totvs::beneficiary_json;
+use crate::patient::{self, Patient};
+use crate::totvs_endpoints;
+
+// Seed file for a fresh homolog base: per patient, the beneficiary record and
+// the bodies of the fingerprint and photo enrollment requests, each with the
+// Datasul path it goes to. Pushing them is left to the operator's tooling.
+// Datasul has no standard photo enrollment route (the photo one only answers
+// GET), so photos get a path only when `endpoint_templates.facial_enrollment`
+// is configured.
+const FORMAT: &str = "virtual-io-hub/totvs-seed";
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+pub struct SeedExport {
+    pub path: String,
+    pub beneficiaries: usize,
+    pub fingerprints: usize,
+    pub photos: usize,
+    // "<wallet>: <reason>" for what was left out
+    pub skipped: Vec<String>,
+}
+
+// Path of the endpoint for this card, without a base URL; null when the
+// endpoint has no template
+fn request(app_handle: &AppHandle, endpoint: &str, card_number: &str, body: Value) -> Value {
+    let path = totvs_endpoints::url(app_handle, "", endpoint, Some(card_number));
+    json!({
+        "method": "POST",
+        "path": (!path.is_empty()).then_some(path),
+        "body": body,
+    })
+}
+
+fn seed_item(app_handle: &AppHandle, patient: &Patient, bitrate: f32, summary: &mut SeedExport) -> Value {
+    let card_number = patient.wallet.trim();
+    let mut item = json!({ "beneficiary": beneficiary_json(patient) });
+
+    let (mut prints, skipped) = patient_prints(patient);
+    for finger in skipped {
+        summary.skipped.push(format!("{}: dedo '{}' sem código TOTVS ou vazio", card_number, finger));
+    }
+    match prepare_prints(&mut prints, bitrate) {
+        Ok(()) if !prints.is_empty() => {
+            summary.fingerprints += prints.len();
+            item["fingerPrints"] = request(app_handle, "fingerprint_enrollment", card_number, enrollment_body(&prints));
+        }
+        Ok(()) => {}
+        Err(e) => summary.skipped.push(format!("{}: {}", card_number, e)),
+    }
+
+    // Without a data URL prefix, in the same envelope as the fingerprints
+    let photo = patient.main_photo().rsplit(',').next().unwrap_or_default().trim();
+    if !photo.is_empty() {
+        summary.photos += 1;
+        let body = json!({ "items": [{ "angle": "frontal", "photo": photo }] });
+        item["photo"] = request(app_handle, "facial_enrollment", card_number, body);
+    }
+    item
+}
+
+// Writes the patients with these ids (all of them without `ids`) to `path`.
+// Patients without a wallet can't be addressed on TOTVS and are skipped.
+#[tauri::command]
+pub async fn export_patients_totvs(app_handle: AppHandle, path: String, ids: Option<Vec<u32>>) -> Result<SeedExport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let patients = patient::load_patients_from_disk(&app_handle).map_err(|e| format!("Falha ao carregar pacientes: {e}"))?;
+        let selected: Vec<&Patient> = patients
+            .iter()
+            .filter(|p| ids.as_ref().is_none_or(|ids| ids.contains(&p.id)))
+            .collect();
+        if selected.is_empty() {
+            return Err("Nenhum paciente para exportar.".to_string());
+        }
+
+        let path = path.trim().to_string();
+        let mut summary = SeedExport { path: path.clone(), beneficiaries: 0, fingerprints: 0, photos: 0, skipped: Vec::new() };
+        let bitrate = fingerprint::wsq_bitrate(&app_handle);
+        let mut items = Vec::new();
+        for patient in selected {
+            if patient.wallet.trim().is_empty() {
+                summary.skipped.push(format!("{}: sem número de carteira", patient.name));
+                continue;
+            }
+            items.push(seed_item(&app_handle, patient, bitrate, &mut summary));
+            summary.beneficiaries += 1;
+        }
+
+        let document = json!({
+            "format": FORMAT,
+            "version": FORMAT_VERSION,
+            "generatedAt": iso_utc(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)),
+            "items": items,
+        });
+        let json = serde_json::to_string_pretty(&document).map_err(|e| e.to_string())?;
+        fs::write(&path, json).map_err(|e| format!("Falha ao gravar {}: {e}", path))?;
+        Ok(summary)
+    })
+    .await
+    .map_err(|e| format!("Falha ao exportar pacientes: {e}"))?
+}
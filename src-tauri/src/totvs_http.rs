@@ -0,0 +1,381 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use futures_util::StreamExt;
+use tauri::{AppHandle, Manager};
+
+use crate::patient;
+use crate::streamed_download;
+use crate::totvs::error::TotvsError;
+use crate::totvs_log;
+use crate::totvs_profiles;
+use crate::totvs_replay;
+
+// Upper bound for a single backoff wait, whatever the attempt number
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+// Upper bound for a server-requested Retry-After wait
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+// Timeouts and retries for TOTVS calls, tunable via `totvs_http` in the app
+// config. reqwest 0.11 has no separate read timeout, so `request_timeout_secs`
+// bounds the whole request including reading the body. 429 responses have
+// their own retry budget; `requests_per_second` (0 = unlimited) spaces out
+// every TOTVS request sent by the app.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+struct HttpSettings {
+    connect_timeout_secs: u64,
+    request_timeout_secs: u64,
+    max_retries: u32,
+    backoff_ms: u64,
+    max_throttle_retries: u32,
+    requests_per_second: f64,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout_secs: 10,
+            request_timeout_secs: 30,
+            max_retries: 2,
+            backoff_ms: 500,
+            max_throttle_retries: 5,
+            requests_per_second: 0.0,
+        }
+    }
+}
+
+// Shared schedule of TOTVS requests: each request takes the next free slot,
+// and a 429 pushes every slot past the server's Retry-After.
+pub struct RateLimiter {
+    next_slot: Instant,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self { next_slot: Instant::now() }
+    }
+}
+
+// Slowest rate honored, one request every 100 s; a smaller value is taken
+// as this instead of a spacing too long for a Duration
+const MIN_REQUESTS_PER_SECOND: f64 = 0.01;
+
+// Spacing between requests; zero, negative or non-finite rates mean unlimited
+fn slot_interval(requests_per_second: f64) -> Duration {
+    if !requests_per_second.is_finite() || requests_per_second <= 0.0 {
+        return Duration::ZERO;
+    }
+    Duration::from_secs_f64(1.0 / requests_per_second.max(MIN_REQUESTS_PER_SECOND))
+}
+
+// Waits for this request's slot
+async fn throttle(app_handle: &AppHandle, settings: &HttpSettings) {
+    let interval = slot_interval(settings.requests_per_second);
+    let slot = {
+        let state = app_handle.state::<Arc<Mutex<RateLimiter>>>();
+        let mut limiter = state.lock().unwrap();
+        let slot = limiter.next_slot.max(Instant::now());
+        limiter.next_slot = slot + interval;
+        slot
+    };
+    tokio::time::sleep_until(slot.into()).await;
+}
+
+fn pause_all(app_handle: &AppHandle, wait: Duration) {
+    let state = app_handle.state::<Arc<Mutex<RateLimiter>>>();
+    let mut limiter = state.lock().unwrap();
+    limiter.next_slot = limiter.next_slot.max(Instant::now() + wait);
+}
+
+// Only the delay-seconds form; an HTTP date falls back to the backoff
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+fn load_settings(app_handle: &AppHandle) -> HttpSettings {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|cfg| cfg.get("totvs_http").cloned())
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+// Proxy from the importer config (`proxy_host`, `proxy_port`, `proxy_user`,
+// `proxy_password`). Set explicitly because reqwest only reads the system
+// proxy from environment variables, without credentials.
+fn configured_proxy(app_handle: &AppHandle) -> Result<Option<reqwest::Proxy>, String> {
+    let config = patient::load_config_from_disk(app_handle).unwrap_or_default();
+    let cfg = totvs_profiles::importer_settings(&config);
+    let text = |key: &str| {
+        cfg.get(key)
+            .and_then(|v| v.as_str().map(String::from).or_else(|| v.as_u64().map(|n| n.to_string())))
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+
+    let Some(host) = text("proxy_host") else {
+        return Ok(None);
+    };
+    let mut url = if host.contains("://") { host } else { format!("http://{}", host) };
+    if let Some(port) = text("proxy_port") {
+        url = format!("{}:{}", url.trim_end_matches('/'), port);
+    }
+
+    let mut proxy = reqwest::Proxy::all(&url).map_err(|e| format!("Proxy inválido ({}): {}", url, e))?;
+    if let Some(user) = text("proxy_user") {
+        proxy = proxy.basic_auth(&user, &text("proxy_password").unwrap_or_default());
+    }
+    Ok(Some(proxy))
+}
+
+// Extra pairs some installations require on every TOTVS request (tenant id,
+// company code...): `custom_params` in the importer config, so each profile
+// can set its own. Values marked `secret` are masked in the TOTVS log.
+#[derive(Debug, Clone, Deserialize)]
+struct CustomParam {
+    name: String,
+    value: String,
+    #[serde(default)]
+    secret: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct CustomParams {
+    headers: Vec<CustomParam>,
+    query: Vec<CustomParam>,
+}
+
+fn custom_params(app_handle: &AppHandle) -> CustomParams {
+    let config = patient::load_config_from_disk(app_handle).unwrap_or_default();
+    totvs_profiles::importer_settings(&config)
+        .get("custom_params")
+        .cloned()
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+// Headers the request already sets (e.g. authorization) are kept
+fn apply_custom_params(request: &mut reqwest::Request, params: &CustomParams) -> Result<(), String> {
+    for header in &params.headers {
+        let name = reqwest::header::HeaderName::from_bytes(header.name.trim().as_bytes())
+            .map_err(|_| format!("Cabeçalho personalizado inválido: {}", header.name))?;
+        let mut value = reqwest::header::HeaderValue::from_str(header.value.trim())
+            .map_err(|_| format!("Valor inválido no cabeçalho personalizado {}", header.name))?;
+        value.set_sensitive(header.secret);
+        if !request.headers().contains_key(&name) {
+            request.headers_mut().insert(name, value);
+        }
+    }
+    if !params.query.is_empty() {
+        request
+            .url_mut()
+            .query_pairs_mut()
+            .extend_pairs(params.query.iter().map(|p| (p.name.trim(), p.value.trim())));
+    }
+    Ok(())
+}
+
+// The URL as logged, with secret custom query values masked
+fn loggable_url(url: &reqwest::Url, params: &CustomParams) -> reqwest::Url {
+    let secrets: Vec<&str> = params.query.iter().filter(|p| p.secret).map(|p| p.name.trim()).collect();
+    if secrets.is_empty() {
+        return url.clone();
+    }
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(k, v)| {
+            let v = if secrets.contains(&k.as_ref()) { "***".to_string() } else { v.into_owned() };
+            (k.into_owned(), v)
+        })
+        .collect();
+    let mut masked = url.clone();
+    masked.query_pairs_mut().clear().extend_pairs(pairs);
+    masked
+}
+
+fn backoff(settings: &HttpSettings, retry: u32) -> Duration {
+    let factor = 2u64.saturating_pow(retry.saturating_sub(1));
+    Duration::from_millis(settings.backoff_ms.saturating_mul(factor)).min(MAX_BACKOFF)
+}
+
+fn attempts_suffix(attempts: u32) -> String {
+    if attempts > 1 {
+        format!(" (após {} tentativas)", attempts)
+    } else {
+        String::new()
+    }
+}
+
+// Client for TOTVS requests with the configured timeouts and proxy
+pub fn client(app_handle: &AppHandle) -> Result<reqwest::Client, TotvsError> {
+    let settings = load_settings(app_handle);
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(settings.connect_timeout_secs.max(1)))
+        .timeout(Duration::from_secs(settings.request_timeout_secs.max(1)));
+    if let Some(proxy) = configured_proxy(app_handle).map_err(TotvsError::config)? {
+        builder = builder.proxy(proxy);
+    }
+    builder
+        .build()
+        .map_err(|e| TotvsError::config(format!("Falha ao criar cliente HTTP: {e}")))
+}
+
+// Reads the start of the body so it can be logged and hands back an
+// equivalent response, the rest of the body still to be read. The whole
+// body is only read up front when it fits the log or is being captured for
+// replay. The log and the capture both get the URL in `request`, with
+// secret custom parameters already masked.
+async fn logged(
+    app_handle: &AppHandle,
+    endpoint: &str,
+    request: (&reqwest::Method, &reqwest::Url, u32),
+    started: Instant,
+    response: reqwest::Response,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let status = response.status();
+    // Image downloads are read by the caller as they arrive: only their size
+    // is logged, and they aren't captured for replay
+    if streamed_download::STREAMED_ENDPOINTS.contains(&endpoint) {
+        let note = match response.content_length() {
+            Some(len) => format!("[corpo transmitido, {} bytes]", len),
+            None => "[corpo transmitido]".to_string(),
+        };
+        totvs_log::record(app_handle, endpoint, request, started.elapsed(), Ok((status.as_u16(), note)));
+        return Ok(response);
+    }
+    let version = response.version();
+    let headers = response.headers().clone();
+    let total = response.content_length();
+    let limit = if totvs_replay::recording(app_handle) { usize::MAX } else { totvs_log::MAX_BODY_BYTES };
+    let mut response = response;
+    let mut head = Vec::new();
+    let mut read = 0;
+    let complete = loop {
+        if read >= limit {
+            break false;
+        }
+        match response.chunk().await {
+            Ok(Some(chunk)) => {
+                read += chunk.len();
+                head.push(chunk);
+            }
+            Ok(None) => break true,
+            Err(e) => {
+                totvs_log::record(app_handle, endpoint, request, started.elapsed(), Err(&e.to_string()));
+                return Err(e);
+            }
+        }
+    };
+
+    let body = if complete {
+        let body = head.concat();
+        totvs_log::record(app_handle, endpoint, request, started.elapsed(), Ok((status.as_u16(), totvs_log::redact_body(&body))));
+        totvs_replay::capture(app_handle, request.0, request.1, status, &headers, &body);
+        reqwest::Body::from(body)
+    } else {
+        let logged = totvs_log::redact_prefix(&head.concat(), total);
+        totvs_log::record(app_handle, endpoint, request, started.elapsed(), Ok((status.as_u16(), logged)));
+        let rest = futures_util::stream::iter(head.into_iter().map(Ok)).chain(response.bytes_stream());
+        reqwest::Body::wrap_stream(rest)
+    };
+
+    let mut rebuilt = http::Response::new(body);
+    *rebuilt.status_mut() = status;
+    *rebuilt.version_mut() = version;
+    *rebuilt.headers_mut() = headers;
+    Ok(reqwest::Response::from(rebuilt))
+}
+
+// Sends the request, retrying timeouts, connection failures and 5xx with
+// exponential backoff. A 5xx still returned after the last attempt is an
+// error; other statuses are left to the caller. A 429 waits for Retry-After
+// (or the backoff) and pauses the other requests too, so a batch slows down
+// instead of failing. Every attempt is logged.
+pub async fn send_with_retry(
+    app_handle: &AppHandle,
+    endpoint: &str,
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, TotvsError> {
+    let settings = load_settings(app_handle);
+    let (client, request) = request.build_split();
+    let mut request = request.map_err(|e| TotvsError::config(format!("Requisição inválida: {e}")))?;
+    let params = custom_params(app_handle);
+    apply_custom_params(&mut request, &params).map_err(TotvsError::config)?;
+    // Matched against captures by the masked URL they were recorded with
+    let replay_url = loggable_url(request.url(), &params);
+    if let Some(replayed) = totvs_replay::replay(app_handle, request.method(), &replay_url) {
+        return replayed.map_err(TotvsError::from);
+    }
+    let mut attempts = 0;
+    let mut failures = 0;
+    let mut throttled = 0;
+
+    loop {
+        // Requests with a streaming body can't be cloned, so they get one attempt
+        let retry_request = request.try_clone();
+        attempts += 1;
+
+        throttle(app_handle, &settings).await;
+        let method = request.method().clone();
+        let url = request.url().clone();
+        let log_url = loggable_url(&url, &params);
+        let started = Instant::now();
+        let sent = match client.execute(request).await {
+            Ok(response) => logged(app_handle, endpoint, (&method, &log_url, attempts), started, response).await,
+            Err(e) => {
+                totvs_log::record(app_handle, endpoint, (&method, &log_url, attempts), started.elapsed(), Err(&e.to_string()));
+                Err(e)
+            }
+        };
+
+        let error = match sent {
+            Ok(response) if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                let Some(next) = retry_request.filter(|_| throttled < settings.max_throttle_retries) else {
+                    return Ok(response);
+                };
+                throttled += 1;
+                let wait = retry_after(&response)
+                    .unwrap_or_else(|| backoff(&settings, throttled))
+                    .min(MAX_RETRY_AFTER);
+                tracing::warn!(target: "totvs", "{} limitado pelo servidor (429) - aguardando {}ms", endpoint, wait.as_millis());
+                pause_all(app_handle, wait);
+                request = next;
+                continue;
+            }
+            Ok(response) if !response.status().is_server_error() => return Ok(response),
+            Ok(response) => format!("Servidor TOTVS respondeu {}", response.status()),
+            Err(e) if e.is_timeout() => format!("Tempo esgotado na requisição: {e}"),
+            Err(e) if e.is_connect() => format!("Falha de conexão: {e}"),
+            Err(e) => return Err(TotvsError::network(format!("Erro na requisição: {e}{}", attempts_suffix(attempts)))),
+        };
+
+        failures += 1;
+        match retry_request.filter(|_| failures <= settings.max_retries) {
+            Some(next) => {
+                let wait = backoff(&settings, failures);
+                tracing::warn!(target: "totvs", "{} - nova tentativa em {}ms", error, wait.as_millis());
+                tokio::time::sleep(wait).await;
+                request = next;
+            }
+            None => return Err(TotvsError::network(format!("{}{}", error, attempts_suffix(attempts)))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slot_interval_handles_any_rate() {
+        assert_eq!(slot_interval(2.0), Duration::from_millis(500));
+        assert_eq!(slot_interval(0.0), Duration::ZERO);
+        assert_eq!(slot_interval(-1.0), Duration::ZERO);
+        assert_eq!(slot_interval(f64::NAN), Duration::ZERO);
+        assert_eq!(slot_interval(f64::INFINITY), Duration::ZERO);
+        assert_eq!(slot_interval(1e-300), Duration::from_secs(100));
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+use tracing_appender::rolling::{Builder, Rotation};
+
+use crate::patient;
+
+// Entries kept in memory for the UI; the log file has the full history
+const BUFFER_CAPACITY: usize = 500;
+// Response bodies are cut after this many characters
+const MAX_BODY_CHARS: usize = 2000;
+// Enough of a body for MAX_BODY_CHARS characters; a longer body isn't read
+// in full just for the log
+pub const MAX_BODY_BYTES: usize = MAX_BODY_CHARS * 4;
+// Daily log files kept under <data dir>/logs
+const MAX_LOG_FILES: usize = 7;
+const REDACTED: &str = "***";
+// Any key containing one of these has its value replaced before logging
+const SENSITIVE_KEYS: [&str; 3] = ["password", "token", "secret"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TotvsLogEntry {
+    pub timestamp: u64,
+    pub endpoint: String,
+    pub method: String,
+    pub url: String,
+    pub attempt: u32,
+    pub status: Option<u16>,
+    pub latency_ms: u64,
+    pub body: Option<String>,
+    pub error: Option<String>,
+}
+
+pub struct TotvsLogBuffer {
+    entries: VecDeque<TotvsLogEntry>,
+}
+
+impl TotvsLogBuffer {
+    pub fn new() -> Self {
+        Self {
+            entries: VecDeque::with_capacity(BUFFER_CAPACITY),
+        }
+    }
+}
+
+// Routes `tracing` output to a daily rotating file. Called once at startup;
+// a failure only disables the file log.
+pub fn init(app_handle: &AppHandle) {
+    let appender = patient::ensure_data_dir(app_handle)
+        .map_err(|e| e.to_string())
+        .and_then(|dir| {
+            Builder::new()
+                .rotation(Rotation::DAILY)
+                .filename_prefix("totvs")
+                .filename_suffix("log")
+                .max_log_files(MAX_LOG_FILES)
+                .build(dir.join("logs"))
+                .map_err(|e| e.to_string())
+        });
+    match appender {
+        Ok(appender) => {
+            let _ = tracing_subscriber::fmt()
+                .with_writer(appender)
+                .with_ansi(false)
+                .try_init();
+        }
+        Err(e) => tracing::error!("Falha ao iniciar log TOTVS: {}", e),
+    }
+}
+
+fn is_sensitive(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SENSITIVE_KEYS.iter().any(|s| key.contains(s))
+}
+
+fn redact_value(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                if is_sensitive(key) {
+                    *v = Value::String(REDACTED.into());
+                } else {
+                    redact_value(v);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
+
+pub fn redact_url(url: &reqwest::Url) -> String {
+    redacted_url(url).to_string()
+}
+
+// The URL with sensitive query values and its password masked
+pub fn redacted_url(url: &reqwest::Url) -> reqwest::Url {
+    let mut url = url.clone();
+    if url.query().is_some() {
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(k, v)| {
+                let v = if is_sensitive(&k) { REDACTED.to_string() } else { v.into_owned() };
+                (k.into_owned(), v)
+            })
+            .collect();
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    if url.password().is_some() {
+        let _ = url.set_password(Some(REDACTED));
+    }
+    url
+}
+
+// Whole body with sensitive JSON values masked
+pub fn redact_full_body(body: &[u8]) -> String {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(mut json) => {
+            redact_value(&mut json);
+            json.to_string()
+        }
+        Err(_) => String::from_utf8_lossy(body).into_owned(),
+    }
+}
+
+// Redacted and truncated copy of a response body
+pub fn redact_body(body: &[u8]) -> String {
+    let text = redact_full_body(body);
+    match text.char_indices().nth(MAX_BODY_CHARS) {
+        Some((cut, _)) => format!("{}… ({} bytes)", &text[..cut], body.len()),
+        None => text,
+    }
+}
+
+// Text after a sensitive key, up to the end of its value
+fn skip_value(rest: &mut std::iter::Peekable<std::str::Chars>) {
+    if rest.peek() == Some(&'"') {
+        rest.next();
+        while let Some(c) = rest.next() {
+            match c {
+                '\\' => {
+                    rest.next();
+                }
+                '"' => return,
+                _ => {}
+            }
+        }
+    } else {
+        while rest.peek().is_some_and(|c| !matches!(c, ',' | '}' | ']')) {
+            rest.next();
+        }
+    }
+}
+
+// Start of a body that wasn't read in full. As a cut JSON doesn't parse,
+// the values of sensitive keys are masked in the text itself.
+pub fn redact_prefix(prefix: &[u8], total: Option<u64>) -> String {
+    let text = String::from_utf8_lossy(prefix);
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text.chars().peekable();
+    let mut key = String::new();
+    let mut sensitive = false;
+    while let Some(c) = rest.next() {
+        redacted.push(c);
+        match c {
+            '"' => {
+                key.clear();
+                while let Some(c) = rest.next() {
+                    redacted.push(c);
+                    match c {
+                        '\\' => redacted.extend(rest.next()),
+                        '"' => break,
+                        _ => key.push(c),
+                    }
+                }
+                sensitive = is_sensitive(&key);
+            }
+            ':' if sensitive => {
+                while rest.peek().is_some_and(|c| c.is_whitespace()) {
+                    rest.next();
+                }
+                skip_value(&mut rest);
+                redacted.push('"');
+                redacted.push_str(REDACTED);
+                redacted.push('"');
+                sensitive = false;
+            }
+            c if !c.is_whitespace() => sensitive = false,
+            _ => {}
+        }
+    }
+    let cut = redacted.char_indices().nth(MAX_BODY_CHARS).map_or(redacted.len(), |(cut, _)| cut);
+    match total {
+        Some(total) => format!("{}… ({} bytes)", &redacted[..cut], total),
+        None => format!("{}… (mais de {} bytes)", &redacted[..cut], prefix.len()),
+    }
+}
+
+// `outcome` carries the body as it should be logged, already redacted
+pub fn record(
+    app_handle: &AppHandle,
+    endpoint: &str,
+    request: (&reqwest::Method, &reqwest::Url, u32),
+    latency: Duration,
+    outcome: Result<(u16, String), &str>,
+) {
+    let (method, url, attempt) = request;
+    let (status, body, error) = match outcome {
+        Ok((status, body)) => (Some(status), Some(body), None),
+        Err(error) => (None, None, Some(error.to_string())),
+    };
+    let entry = TotvsLogEntry {
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        endpoint: endpoint.to_string(),
+        method: method.to_string(),
+        url: redact_url(url),
+        attempt,
+        status,
+        latency_ms: latency.as_millis() as u64,
+        body,
+        error,
+    };
+
+    match (&entry.status, &entry.error) {
+        (Some(status), _) => tracing::info!(
+            target: "totvs",
+            endpoint = %entry.endpoint,
+            method = %entry.method,
+            url = %entry.url,
+            attempt = entry.attempt,
+            status = *status,
+            latency_ms = entry.latency_ms,
+            body = entry.body.as_deref().unwrap_or_default(),
+        ),
+        (None, error) => tracing::warn!(
+            target: "totvs",
+            endpoint = %entry.endpoint,
+            method = %entry.method,
+            url = %entry.url,
+            attempt = entry.attempt,
+            latency_ms = entry.latency_ms,
+            error = error.as_deref().unwrap_or_default(),
+        ),
+    }
+
+    let state = app_handle.state::<Arc<Mutex<TotvsLogBuffer>>>();
+    let mut buffer = state.lock().unwrap();
+    if buffer.entries.len() == BUFFER_CAPACITY {
+        buffer.entries.pop_front();
+    }
+    buffer.entries.push_back(entry);
+}
+
+// Most recent first
+#[tauri::command]
+pub fn get_recent_totvs_logs(
+    state: tauri::State<'_, Arc<Mutex<TotvsLogBuffer>>>,
+    limit: Option<usize>,
+) -> Result<Vec<TotvsLogEntry>, String> {
+    let buffer = state
+        .lock()
+        .map_err(|_| "Falha ao obter lock do log TOTVS".to_string())?;
+    Ok(buffer
+        .entries
+        .iter()
+        .rev()
+        .take(limit.unwrap_or(BUFFER_CAPACITY))
+        .cloned()
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_prefix_masks_sensitive_values_of_a_cut_json() {
+        let prefix = br#"{"access_token": "abc\"def", "name": "x", "refreshToken":123, "list":[{"secret":"s"}], "tok"#;
+        assert_eq!(
+            redact_prefix(prefix, Some(5000)),
+            r#"{"access_token":"***", "name": "x", "refreshToken":"***", "list":[{"secret":"***"}], "tok… (5000 bytes)"#
+        );
+    }
+
+    #[test]
+    fn redact_prefix_keeps_values_that_only_look_like_keys() {
+        let prefix = br#"{"kind":"token","password" : "p""#;
+        assert_eq!(redact_prefix(prefix, None), r#"{"kind":"token","password" :"***"… (mais de 32 bytes)"#);
+    }
+}
@@ -0,0 +1,168 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::{json, Value};
+use tauri::AppHandle;
+
+use crate::beneficiary_import::{details_key, new_patient, resolve_name, store_imported, to_digital_biometrics};
+use crate::beneficiary_sync::iso_utc;
+use crate::biometric_crypto;
+use crate::patient::{self, Patient};
+use crate::photo_pipeline;
+use crate::totvs::models::{self, Beneficiary, Fingerprint};
+
+// With `mirror_totvs_responses` on in app_config.json, every response fetched
+// from TOTVS is also written to <data dir>/totvs_mirror/<card>/<resource>.json,
+// so the patients can be rebuilt with `reimport_from_mirror` during an outage.
+// The files are encrypted like the biometrics they hold.
+// The folder is named after the details key, which the full wallet and the
+// 13-digit card number have in common.
+const CONFIG_KEY: &str = "mirror_totvs_responses";
+
+pub fn enabled(app_handle: &AppHandle) -> bool {
+    patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|config| config.get(CONFIG_KEY).and_then(|v| v.as_bool()))
+        .unwrap_or(false)
+}
+
+fn mirror_dir(app_handle: &AppHandle) -> io::Result<PathBuf> {
+    let mut dir = patient::ensure_data_dir(app_handle)?;
+    dir.push("totvs_mirror");
+    Ok(dir)
+}
+
+fn card_key(card_number: &str) -> String {
+    details_key(card_number).chars().filter(|c| c.is_ascii_alphanumeric()).collect()
+}
+
+fn card_dir(app_handle: &AppHandle, card_number: &str) -> io::Result<PathBuf> {
+    let dir = mirror_dir(app_handle)?.join(card_key(card_number));
+    if !dir.exists() {
+        fs::create_dir_all(&dir)?;
+    }
+    Ok(dir)
+}
+
+// The payload is kept as TOTVS sent it, next to the card it was asked for
+pub fn save(app_handle: &AppHandle, card_number: &str, resource: &str, payload: &Value) {
+    if !enabled(app_handle) {
+        return;
+    }
+    let saved_at = iso_utc(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0));
+    let result = card_dir(app_handle, card_number).and_then(|dir| {
+        let body = json!({ "card_number": card_number, "saved_at": saved_at, "payload": payload });
+        biometric_crypto::write_encrypted_file(&dir.join(format!("{}.json", resource)), &serde_json::to_vec(&body)?)
+    });
+    if let Err(e) = result {
+        tracing::warn!("Falha ao espelhar {} da carteira {}: {}", resource, card_number, e);
+    }
+}
+
+fn card_of(beneficiary: &Value) -> Option<String> {
+    ["completeCardNumber", "cardNumber"].iter().find_map(|key| match beneficiary.get(*key) {
+        Some(Value::String(s)) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Some(Value::Number(n)) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+// Family imports never ask for details, so each holder and dependent of a
+// search page is kept as well, for the names
+pub fn save_search_page(app_handle: &AppHandle, json: &Value) {
+    if !enabled(app_handle) {
+        return;
+    }
+    let holders = json.get("items").and_then(|v| v.as_array()).into_iter().flatten();
+    for holder in holders {
+        let dependents = holder.get("dependents").and_then(|v| v.as_array()).into_iter().flatten();
+        for beneficiary in std::iter::once(holder).chain(dependents) {
+            if let Some(card_number) = card_of(beneficiary) {
+                save(app_handle, &card_number, "search", beneficiary);
+            }
+        }
+    }
+}
+
+// (card number, payload) of a mirrored resource
+fn read(dir: &Path, resource: &str) -> Option<(String, Value)> {
+    let data = biometric_crypto::read_encrypted_file(&dir.join(format!("{}.json", resource))).ok()?;
+    let mut body: Value = serde_json::from_slice(&data).ok()?;
+    let card_number = body.get("card_number").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    Some((card_number, body.get_mut("payload").map(Value::take).unwrap_or_default()))
+}
+
+fn rebuild(app_handle: &AppHandle, dir: &Path) -> Option<Patient> {
+    let details = read(dir, "details").or_else(|| read(dir, "search"));
+    let fingerprints = read(dir, "fingerprints");
+    let photo = read(dir, "photo");
+    if details.is_none() && fingerprints.is_none() && photo.is_none() {
+        return None;
+    }
+
+    let beneficiary = details.and_then(|(_, json)| Beneficiary::from_value(json, "beneficiário").ok());
+    // The fingerprints and photo were requested with the full wallet
+    let wallet = beneficiary
+        .as_ref()
+        .and_then(|b| b.complete_card_number.clone())
+        .filter(|c| !c.trim().is_empty())
+        .or_else(|| fingerprints.as_ref().map(|(card, _)| card.clone()).filter(|c| !c.is_empty()))
+        .or_else(|| photo.as_ref().map(|(card, _)| card.clone()).filter(|c| !c.is_empty()))
+        .unwrap_or_else(|| dir.file_name().unwrap_or_default().to_string_lossy().into_owned());
+    let name = beneficiary
+        .as_ref()
+        .map(|b| resolve_name(&b.name, &b.person))
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| format!("Carteira {}", wallet));
+
+    let digital_biometrics = fingerprints
+        .and_then(|(_, json)| {
+            let items = json.get("items").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+            models::parse_list(items, "items", Fingerprint::from_value).ok()
+        })
+        .map(to_digital_biometrics)
+        .unwrap_or_default();
+    let facial_biometric = photo
+        .and_then(|(_, json)| json.get("photo").and_then(|v| v.as_str()).map(String::from))
+        .map(|raw| photo_pipeline::prepare_blocking(&photo_pipeline::settings(app_handle), &raw))
+        .unwrap_or_default();
+
+    Some(new_patient(name, wallet, digital_biometrics, facial_biometric))
+}
+
+// Rebuilds imported patients from the mirrored responses, without calling
+// TOTVS. Without `card_numbers`, every mirrored card is imported.
+#[tauri::command]
+pub async fn reimport_from_mirror(app_handle: AppHandle, card_numbers: Option<Vec<String>>) -> Result<Vec<Patient>, String> {
+    // Decrypting and parsing every mirrored card is too slow for the main thread
+    tauri::async_runtime::spawn_blocking(move || {
+        let root = mirror_dir(&app_handle).map_err(|e| format!("Falha ao localizar espelho local: {e}"))?;
+        let dirs: Vec<PathBuf> = match card_numbers {
+            Some(cards) => cards
+                .iter()
+                .map(|c| c.trim())
+                .filter(|c| !c.is_empty())
+                .map(|c| {
+                    let dir = root.join(card_key(c));
+                    if dir.is_dir() {
+                        Ok(dir)
+                    } else {
+                        Err(format!("Nenhuma cópia local da carteira {}.", c))
+                    }
+                })
+                .collect::<Result<_, _>>()?,
+            None => fs::read_dir(&root)
+                .map(|entries| entries.flatten().map(|e| e.path()).filter(|p| p.is_dir()).collect())
+                .unwrap_or_default(),
+        };
+
+        let patients: Vec<Patient> = dirs.iter().filter_map(|dir| rebuild(&app_handle, dir)).collect();
+        if patients.is_empty() {
+            return Err("Nenhum paciente encontrado no espelho local.".into());
+        }
+        store_imported(&app_handle, patients)
+    })
+    .await
+    .map_err(|e| format!("Falha ao reimportar do espelho local: {e}"))?
+}
@@ -0,0 +1,128 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tauri::AppHandle;
+
+use crate::patient;
+
+// Named connection settings (base_url, credentials, clinic...) kept under
+// `totvs_profiles` in app_config.json. The profile named by
+// `active_totvs_profile` is laid over the importer config, so keys it doesn't
+// set fall back to the plain importer settings.
+const PROFILES_KEY: &str = "totvs_profiles";
+const ACTIVE_KEY: &str = "active_totvs_profile";
+
+#[derive(Debug, Serialize)]
+pub struct TotvsProfileInfo {
+    pub name: String,
+    pub active: bool,
+    pub base_url: Option<String>,
+    pub user: Option<String>,
+}
+
+pub fn active_profile_name(config: &Value) -> Option<&str> {
+    config.get(ACTIVE_KEY)?.as_str()
+}
+
+fn active_profile(config: &Value) -> Option<&Map<String, Value>> {
+    let name = active_profile_name(config)?;
+    config.get(PROFILES_KEY)?.get(name)?.as_object()
+}
+
+// A key set on the active profile itself, e.g. the card format its operator
+// uses (`default_card_format`)
+pub fn profile_setting<'a>(config: &'a Value, key: &str) -> Option<&'a Value> {
+    active_profile(config)?.get(key)
+}
+
+// Effective importer settings: `importer_config` (or the root, for older
+// configs) with the active profile applied on top
+pub fn importer_settings(config: &Value) -> Value {
+    let mut settings = config.get("importer_config").unwrap_or(config).clone();
+    if let (Some(map), Some(profile)) = (settings.as_object_mut(), active_profile(config)) {
+        for (key, value) in profile {
+            map.insert(key.clone(), value.clone());
+        }
+    }
+    settings
+}
+
+fn load_config(app_handle: &AppHandle) -> Result<Value, String> {
+    patient::load_config_from_disk(app_handle).map_err(|e| format!("Falha ao ler configurações: {e}"))
+}
+
+fn save_config(app_handle: &AppHandle, config: &Value) -> Result<(), String> {
+    patient::save_config_to_disk(app_handle, config).map_err(|e| format!("Falha ao salvar configurações: {e}"))
+}
+
+#[tauri::command]
+pub fn list_totvs_profiles(app_handle: AppHandle) -> Result<Vec<TotvsProfileInfo>, String> {
+    let config = load_config(&app_handle)?;
+    let active = active_profile_name(&config);
+    let Some(profiles) = config.get(PROFILES_KEY).and_then(|v| v.as_object()) else {
+        return Ok(Vec::new());
+    };
+
+    Ok(profiles
+        .iter()
+        .map(|(name, profile)| {
+            let field = |key: &str| profile.get(key).and_then(|v| v.as_str()).map(String::from);
+            TotvsProfileInfo {
+                name: name.clone(),
+                active: active == Some(name.as_str()),
+                base_url: field("base_url"),
+                user: field("user"),
+            }
+        })
+        .collect())
+}
+
+// Creates or replaces a profile. Without `settings` the current effective
+// importer settings are saved under the new name.
+#[tauri::command]
+pub fn create_totvs_profile(app_handle: AppHandle, name: String, settings: Option<Value>) -> Result<(), String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Nome do perfil não informado.".into());
+    }
+
+    let mut config = load_config(&app_handle)?;
+    let settings = settings.unwrap_or_else(|| importer_settings(&config));
+    if !settings.is_object() {
+        return Err("Configurações do perfil devem ser um objeto.".into());
+    }
+
+    let root = config
+        .as_object_mut()
+        .ok_or("Arquivo de configurações inválido.")?;
+    let profiles = root
+        .entry(PROFILES_KEY)
+        .or_insert_with(|| Value::Object(Map::new()));
+    if !profiles.is_object() {
+        *profiles = Value::Object(Map::new());
+    }
+    profiles.as_object_mut().unwrap().insert(name, settings);
+
+    save_config(&app_handle, &config)
+}
+
+// Activates a profile; `None` goes back to the plain importer settings
+#[tauri::command]
+pub fn switch_totvs_profile(app_handle: AppHandle, name: Option<String>) -> Result<(), String> {
+    let mut config = load_config(&app_handle)?;
+    let name = name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    if let Some(name) = &name {
+        if config.get(PROFILES_KEY).and_then(|p| p.get(name)).is_none() {
+            return Err(format!("Perfil '{}' não encontrado.", name));
+        }
+    }
+
+    let root = config
+        .as_object_mut()
+        .ok_or("Arquivo de configurações inválido.")?;
+    match name {
+        Some(name) => root.insert(ACTIVE_KEY.to_string(), Value::String(name)),
+        None => root.remove(ACTIVE_KEY),
+    };
+
+    save_config(&app_handle, &config)
+}
@@ -0,0 +1,231 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+
+use crate::totvs_log;
+
+// Record: every TOTVS response is appended to the file (one JSON per line).
+// Replay: requests are answered from the file and never reach the network.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrafficMode {
+    #[default]
+    Off,
+    Record,
+    Replay,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ReplayEntry {
+    method: String,
+    // Path and query only, so a capture from a customer's server replays
+    // against any base_url
+    target: String,
+    status: u16,
+    content_type: Option<String>,
+    body: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrafficStatus {
+    pub mode: TrafficMode,
+    pub path: Option<String>,
+    pub entries: usize,
+}
+
+pub struct ReplayState {
+    mode: TrafficMode,
+    path: Option<PathBuf>,
+    entries: Vec<ReplayEntry>,
+}
+
+impl ReplayState {
+    pub fn new() -> Self {
+        Self {
+            mode: TrafficMode::Off,
+            path: None,
+            entries: Vec::new(),
+        }
+    }
+
+    fn status(&self) -> TrafficStatus {
+        TrafficStatus {
+            mode: self.mode,
+            path: self.path.as_ref().map(|p| p.display().to_string()),
+            entries: self.entries.len(),
+        }
+    }
+}
+
+// Keys whose text values are photos or fingerprint templates. Captures are
+// plain files meant to be shared, so biometrics are left out of them.
+const BIOMETRIC_KEYS: [&str; 4] = ["photo", "biometr", "fingerprint", "template"];
+
+fn strip_biometrics(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                let key = key.to_ascii_lowercase();
+                if v.is_string() && BIOMETRIC_KEYS.iter().any(|k| key.contains(k)) {
+                    *v = Value::String("***".into());
+                } else {
+                    strip_biometrics(v);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_biometrics),
+        _ => {}
+    }
+}
+
+// Captures need whole response bodies, which are otherwise only read in part
+// for the log
+pub fn recording(app_handle: &AppHandle) -> bool {
+    let state = app_handle.state::<Arc<Mutex<ReplayState>>>();
+    let recording = state.lock().map(|state| state.mode == TrafficMode::Record).unwrap_or(false);
+    recording
+}
+
+// Sensitive query values are masked, in the capture and when replaying alike
+fn target(url: &reqwest::Url) -> String {
+    let url = totvs_log::redacted_url(url);
+    match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    }
+}
+
+// Appends the response to the capture file when recording. Sensitive values
+// are masked like in the TOTVS log and biometrics left out, as the file gets
+// shared to reproduce issues.
+pub fn capture(
+    app_handle: &AppHandle,
+    method: &reqwest::Method,
+    url: &reqwest::Url,
+    status: reqwest::StatusCode,
+    headers: &reqwest::header::HeaderMap,
+    body: &[u8],
+) {
+    let state = app_handle.state::<Arc<Mutex<ReplayState>>>();
+    let Some(path) = state
+        .lock()
+        .ok()
+        .and_then(|state| state.path.clone().filter(|_| state.mode == TrafficMode::Record))
+    else {
+        return;
+    };
+
+    let entry = ReplayEntry {
+        method: method.to_string(),
+        target: target(url),
+        status: status.as_u16(),
+        content_type: headers
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from),
+        body: match serde_json::from_slice::<Value>(body) {
+            Ok(mut json) => {
+                strip_biometrics(&mut json);
+                totvs_log::redact_full_body(json.to_string().as_bytes())
+            }
+            Err(_) => totvs_log::redact_full_body(body),
+        },
+    };
+    // Written without the lock, so a slow disk doesn't hold up other requests
+    let written = serde_json::to_string(&entry)
+        .map_err(std::io::Error::from)
+        .and_then(|line| {
+            let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+            writeln!(file, "{}", line)
+        });
+    match written {
+        Ok(()) => {
+            if let Ok(mut state) = state.lock() {
+                // Recording may have stopped or moved to another file meanwhile
+                if state.mode == TrafficMode::Record && state.path.as_ref() == Some(&path) {
+                    state.entries.push(entry);
+                }
+            }
+        }
+        Err(e) => tracing::error!("Falha ao gravar tráfego TOTVS em {}: {}", path.display(), e),
+    }
+}
+
+// In replay mode, the recorded answer to this request (the latest one if it
+// was recorded more than once). None when not replaying.
+pub fn replay(
+    app_handle: &AppHandle,
+    method: &reqwest::Method,
+    url: &reqwest::Url,
+) -> Option<Result<reqwest::Response, String>> {
+    let state = app_handle.state::<Arc<Mutex<ReplayState>>>();
+    let state = state.lock().unwrap();
+    if state.mode != TrafficMode::Replay {
+        return None;
+    }
+
+    let method = method.to_string();
+    let target = target(url);
+    let Some(entry) = state.entries.iter().rev().find(|e| e.method == method && e.target == target) else {
+        return Some(Err(format!("Resposta não gravada no arquivo de replay: {} {}", method, target)));
+    };
+
+    let mut response = http::Response::new(entry.body.clone());
+    *response.status_mut() = reqwest::StatusCode::from_u16(entry.status).unwrap_or(reqwest::StatusCode::OK);
+    if let Some(content_type) = entry.content_type.as_deref().and_then(|c| c.parse().ok()) {
+        response.headers_mut().insert(reqwest::header::CONTENT_TYPE, content_type);
+    }
+    Some(Ok(reqwest::Response::from(response)))
+}
+
+fn load_entries(path: &PathBuf) -> Result<Vec<ReplayEntry>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Falha ao ler arquivo de replay {}: {e}", path.display()))?;
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line).map_err(|e| format!("Linha {} do arquivo de replay inválida: {e}", i + 1))
+        })
+        .collect()
+}
+
+// Switches between normal, recording and replaying. Recording starts a new
+// file at `path`; replaying loads the file at `path`.
+#[tauri::command]
+pub fn set_totvs_traffic_mode(
+    state: tauri::State<'_, Arc<Mutex<ReplayState>>>,
+    mode: TrafficMode,
+    path: Option<String>,
+) -> Result<TrafficStatus, String> {
+    let path = path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).map(PathBuf::from);
+    let entries = match (mode, &path) {
+        (TrafficMode::Off, _) => Vec::new(),
+        (_, None) => return Err("Arquivo de gravação não informado.".into()),
+        (TrafficMode::Record, Some(path)) => {
+            fs::write(path, b"").map_err(|e| format!("Falha ao criar arquivo {}: {e}", path.display()))?;
+            Vec::new()
+        }
+        (TrafficMode::Replay, Some(path)) => load_entries(path)?,
+    };
+
+    let mut state = state
+        .lock()
+        .map_err(|_| "Falha ao obter lock do replay TOTVS".to_string())?;
+    state.mode = mode;
+    state.path = if mode == TrafficMode::Off { None } else { path };
+    state.entries = entries;
+    Ok(state.status())
+}
+
+#[tauri::command]
+pub fn get_totvs_traffic_mode(state: tauri::State<'_, Arc<Mutex<ReplayState>>>) -> Result<TrafficStatus, String> {
+    let state = state
+        .lock()
+        .map_err(|_| "Falha ao obter lock do replay TOTVS".to_string())?;
+    Ok(state.status())
+}
@@ -0,0 +1,95 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+use crate::patient::{self, Patient, VerificationEntry};
+
+// Oldest entries are dropped past this, so patient records don't grow forever
+const MAX_HISTORY_PER_PATIENT: usize = 500;
+
+fn has_template(patient: &Patient, code: &str) -> bool {
+    patient.digital_biometrics.iter().any(|d| d.data.trim() == code)
+}
+
+// The presented template identifies the patient when it is one of theirs;
+// otherwise the verification is attributed to whoever's templates the server
+// was loaded with.
+fn attribute(patients: &[Patient], presented: &str, loaded: &[String]) -> Option<u32> {
+    let presented = presented.trim();
+    patients
+        .iter()
+        .find(|p| has_template(p, presented))
+        .or_else(|| {
+            let loaded = loaded.first()?.trim();
+            patients.iter().find(|p| has_template(p, loaded))
+        })
+        .map(|p| p.id)
+}
+
+// Called by the biometry server after each verification. Runs off the request
+// path; failures are only logged.
+pub fn record(
+    app_handle: AppHandle,
+    endpoint: &str,
+    presented: String,
+    loaded: Vec<String>,
+    matched: bool,
+    score: u8,
+) {
+    let entry = VerificationEntry {
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        endpoint: endpoint.to_string(),
+        matched,
+        score,
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        let patients = match patient::load_patients_from_disk(&app_handle) {
+            Ok(patients) => patients,
+            Err(e) => {
+                tracing::error!("Falha ao ler pacientes para auditoria: {}", e);
+                return;
+            }
+        };
+        let Some(patient_id) = attribute(&patients, &presented, &loaded) else {
+            tracing::debug!("Verificação em {} sem paciente correspondente", entry.endpoint);
+            return;
+        };
+        // Appended inside the write, so a save made meanwhile isn't undone
+        let appended = patient::modify_patient(&app_handle, patient_id, "verification_audit", |p| {
+            p.verification_history.push(entry);
+            let excess = p.verification_history.len().saturating_sub(MAX_HISTORY_PER_PATIENT);
+            p.verification_history.drain(..excess);
+        });
+        if let Err(e) = appended {
+            tracing::error!("Falha ao gravar auditoria de verificação: {}", e);
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_patient_verification_history(
+    app_handle: AppHandle,
+    patient_id: u32,
+    limit: Option<usize>,
+) -> Result<Vec<VerificationEntry>, String> {
+    let patient = patient::load_patients_from_disk(&app_handle)
+        .map_err(|e| format!("Falha ao ler pacientes: {e}"))?
+        .into_iter()
+        .find(|p| p.id == patient_id)
+        .ok_or_else(|| format!("Paciente {} não encontrado.", patient_id))?;
+
+    // Most recent first
+    let mut history = patient.verification_history;
+    history.reverse();
+    if let Some(limit) = limit {
+        history.truncate(limit);
+    }
+    Ok(history)
+}
+
+#[tauri::command]
+pub fn clear_patient_verification_history(app_handle: AppHandle, patient_id: u32) -> Result<usize, String> {
+    patient::modify_patient(&app_handle, patient_id, "clear_verification_history", |p| {
+        std::mem::take(&mut p.verification_history).len()
+    })
+    .map_err(|e| e.to_string())
+}
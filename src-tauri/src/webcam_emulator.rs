@@ -1,27 +1,124 @@
 use std::sync::{Arc, Mutex};
 use std::path::PathBuf;
-use std::process::{Command, Child};
-use std::io;
+use std::process::{Command, Child, ChildStdin, Stdio};
+use std::io::{self, Write};
+use base64::{engine::general_purpose as b64, Engine};
 use serde::{Serialize, Deserialize};
+use tauri::AppHandle;
 
+use crate::patient::{self, FaceAngle, Patient};
+
+// One layer of a composite source: "image" (base64), "video" (path) or
+// "camera" (index)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompositeLayer {
+    pub source_type: String,
+    pub source_data: String,
+}
+
+// Overlay drawn over the base, e.g. the patient face over a card image for
+// kiosks that capture both in one frame. `scale` is the overlay width as a
+// fraction of the base width; `x`/`y` place it within the free space
+// (0 = left/top, 1 = right/bottom).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompositeSpec {
+    pub base: CompositeLayer,
+    pub overlay: CompositeLayer,
+    #[serde(default = "default_overlay_position")]
+    pub x: f32,
+    #[serde(default = "default_overlay_position")]
+    pub y: f32,
+    #[serde(default = "default_overlay_scale")]
+    pub scale: f32,
+}
+
+fn default_overlay_position() -> f32 {
+    1.0
+}
+
+fn default_overlay_scale() -> f32 {
+    0.3
+}
+
+impl CompositeLayer {
+    fn validate(&self) -> Result<(), String> {
+        match self.source_type.as_str() {
+            "image" if self.source_data.is_empty() => Err("Dados de imagem vazios".into()),
+            "image" => Ok(()),
+            "video" if !PathBuf::from(&self.source_data).exists() => {
+                Err(format!("Arquivo não encontrado: {:?}", self.source_data))
+            }
+            "video" => Ok(()),
+            "camera" => match self.source_data.parse::<i32>() {
+                Ok(index) if index >= 0 => Ok(()),
+                _ => Err("Índice de câmera inválido".into()),
+            },
+            other => Err(format!("Tipo de camada não suportado na composição: {}", other)),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum WebcamSource {
     Image(String),     // base64 string
     Video(PathBuf),    // file path
     Camera(i32),       // physical camera index
+    Push,              // frames supplied by the frontend via push_webcam_frame
+    Watch(PathBuf),    // image file reloaded whenever it changes on disk
+    Composite(CompositeSpec),
 }
 
 pub struct WebcamEmulator {
     process: Option<Child>,
+    // Pushed frames are written to the script's stdin, one base64 image per line
+    frame_input: Option<ChildStdin>,
     current_source: Option<WebcamSource>,
+    // Virtual camera device to stream to; None lets pyvirtualcam pick one
+    device: Option<String>,
+}
+
+impl WebcamSource {
+    // The patient's photo with this label
+    pub fn facial_photo(patient: &Patient, label: FaceAngle) -> Result<Self, String> {
+        patient
+            .facial_photo(label)
+            .map(|data| WebcamSource::Image(data.to_string()))
+            .ok_or_else(|| format!("{} não tem foto '{}'.", patient.name, label.as_str()))
+    }
+
+    pub fn from_parts(source_type: &str, source_data: &str) -> Result<Self, String> {
+        match source_type {
+            "image" => Ok(WebcamSource::Image(source_data.to_string())),
+            "video" => Ok(WebcamSource::Video(PathBuf::from(source_data))),
+            "camera" => {
+                let index = source_data.parse::<i32>()
+                    .map_err(|_| "Índice de câmera inválido".to_string())?;
+                Ok(WebcamSource::Camera(index))
+            },
+            "push" => Ok(WebcamSource::Push),
+            "watch" => Ok(WebcamSource::Watch(PathBuf::from(source_data))),
+            "composite" => serde_json::from_str(source_data)
+                .map(WebcamSource::Composite)
+                .map_err(|e| format!("Composição inválida: {}", e)),
+            _ => Err("Tipo de fonte desconhecido".into()),
+        }
+    }
 }
 
 impl WebcamEmulator {
     pub fn new() -> Self {
         Self {
             process: None,
+            frame_input: None,
             current_source: None,
+            device: None,
+        }
+    }
+
+    pub fn with_device(device: &str) -> Self {
+        Self {
+            device: Some(device.to_string()),
+            ..Self::new()
         }
     }
 
@@ -45,6 +142,19 @@ impl WebcamEmulator {
                     return Err("Índice de câmera inválido".into());
                 }
             }
+            WebcamSource::Push => {}
+            WebcamSource::Watch(path) => {
+                if !path.is_file() {
+                    return Err(format!("Arquivo não encontrado: {:?}", path));
+                }
+            }
+            WebcamSource::Composite(spec) => {
+                spec.base.validate()?;
+                spec.overlay.validate()?;
+                if !(spec.scale > 0.0 && spec.scale <= 1.0) {
+                    return Err("A escala da sobreposição deve estar entre 0 e 1".into());
+                }
+            }
         }
 
         // Create a temporary script to run the Python webcam emulator
@@ -78,14 +188,44 @@ impl WebcamEmulator {
                 args.push("--camera".to_string());
                 args.push(index.to_string());
             }
+            WebcamSource::Push => {
+                args.push("--push".to_string());
+            }
+            WebcamSource::Watch(path) => {
+                args.push("--watch".to_string());
+                args.push(path.to_string_lossy().to_string());
+            }
+            WebcamSource::Composite(spec) => {
+                // Layers may carry large base64 images, too big for the command line
+                let spec_path = temp_dir.path().join("composite.json");
+                let spec_json = serde_json::to_string(spec)
+                    .map_err(|e| format!("Erro ao serializar composição: {}", e))?;
+                std::fs::write(&spec_path, spec_json)
+                    .map_err(|e| format!("Erro ao escrever composição: {}", e))?;
+                args.push("--composite".to_string());
+                args.push(spec_path.to_string_lossy().to_string());
+            }
+        }
+
+        if let Some(device) = &self.device {
+            args.push("--device".to_string());
+            args.push(device.clone());
         }
 
+        let stdin = if matches!(source, WebcamSource::Push) {
+            Stdio::piped()
+        } else {
+            Stdio::inherit()
+        };
+
         // Start Python process
-        let process = Command::new("python")
+        let mut process = Command::new("python")
             .args(&args)
+            .stdin(stdin)
             .spawn()
             .map_err(|e| format!("Erro ao iniciar o processo Python: {}", e))?;
 
+        self.frame_input = process.stdin.take();
         self.process = Some(process);
         self.current_source = Some(source);
 
@@ -95,7 +235,30 @@ impl WebcamEmulator {
         Ok(true)
     }
 
+    // Replaces the frame shown by a Push source; accepts plain base64 or a data URL
+    pub fn push_frame(&mut self, image_base64: &str) -> Result<bool, String> {
+        let input = self
+            .frame_input
+            .as_mut()
+            .ok_or("A webcam virtual não está no modo de quadros enviados pela interface.")?;
+
+        let data = image_base64
+            .split_once(";base64,")
+            .map(|(_, data)| data)
+            .unwrap_or(image_base64)
+            .trim();
+        b64::STANDARD
+            .decode(data)
+            .map_err(|e| format!("Quadro com base64 inválido: {}", e))?;
+
+        writeln!(input, "{}", data)
+            .and_then(|_| input.flush())
+            .map_err(|e| format!("Falha ao enviar quadro para a webcam virtual: {}", e))?;
+        Ok(true)
+    }
+
     pub fn stop(&mut self) -> Result<bool, String> {
+        self.frame_input = None;
         if let Some(mut process) = self.process.take() {
             match process.kill() {
                 Ok(_) => {},
@@ -118,6 +281,10 @@ impl WebcamEmulator {
         self.process.is_some()
     }
 
+    pub fn process_id(&self) -> Option<u32> {
+        self.process.as_ref().map(|p| p.id())
+    }
+
     fn create_python_script(&self) -> Result<String, io::Error> {
         // This Python script will use pyvirtualcam to create a virtual camera
         // and stream the specified source (image, video, or physical camera)
@@ -125,21 +292,65 @@ impl WebcamEmulator {
 import sys
 import argparse
 import base64
+import json
+import os
 import time
+import threading
 import numpy as np
 from io import BytesIO
 import cv2
 import pyvirtualcam
 
+def open_layer(layer):
+    kind, data = layer['source_type'], layer['source_data']
+    if kind == 'image':
+        img = cv2.imdecode(np.frombuffer(base64.b64decode(data), np.uint8), cv2.IMREAD_COLOR)
+        if img is None:
+            raise ValueError("Invalid image data")
+        return (lambda: img), None
+
+    cap = cv2.VideoCapture(data if kind == 'video' else int(data))
+    if not cap.isOpened():
+        raise ValueError(f"Could not open {kind} {data}")
+
+    def read():
+        ret, layer_frame = cap.read()
+        if not ret and kind == 'video':
+            cap.set(cv2.CAP_PROP_POS_FRAMES, 0)
+            ret, layer_frame = cap.read()
+        return layer_frame if ret else None
+
+    return read, cap
+
+def composite(base, overlay, spec):
+    out = base.copy()
+    if overlay is None:
+        return out
+    h, w = out.shape[:2]
+    ow = min(w, max(1, int(w * spec.get('scale', 0.3))))
+    oh = min(h, max(1, int(overlay.shape[0] * ow / overlay.shape[1])))
+    x = int((w - ow) * min(max(spec.get('x', 1.0), 0.0), 1.0))
+    y = int((h - oh) * min(max(spec.get('y', 1.0), 0.0), 1.0))
+    out[y:y + oh, x:x + ow] = cv2.resize(overlay, (ow, oh))
+    return out
+
 def main():
     parser = argparse.ArgumentParser(description='Webcam Emulator')
     parser.add_argument('--image', type=str, help='Base64 encoded image data')
     parser.add_argument('--video', type=str, help='Path to video file')
     parser.add_argument('--camera', type=int, help='Physical camera index')
+    parser.add_argument('--push', action='store_true', help='Read base64 frames from stdin, one per line')
+    parser.add_argument('--watch', type=str, help='Path to an image file reloaded when it changes')
+    parser.add_argument('--composite', type=str, help='Path to a JSON picture-in-picture spec')
+    parser.add_argument('--device', type=str, help='Virtual camera device to output to')
     args = parser.parse_args()
 
     # Default frame size and rate
     width, height, fps = 640, 480, 30
+    is_push = False
+    is_watch = False
+    is_composite = False
+    layer_caps = []
     
     # Prepare the source
     if args.image:
@@ -185,13 +396,96 @@ def main():
         except Exception as e:
             print(f"Error opening camera: {e}")
             return 1
+    elif args.push:
+        # Black frame until the first one is pushed; pushed frames are resized
+        # to the camera size, which can't change once it is open
+        frame = np.zeros((height, width, 3), np.uint8)
+        pushed = {'frame': frame}
+        is_video = False
+        is_push = True
+
+        def read_pushed_frames():
+            for line in sys.stdin:
+                line = line.strip()
+                if not line:
+                    continue
+                try:
+                    img = cv2.imdecode(np.frombuffer(base64.b64decode(line), np.uint8), cv2.IMREAD_COLOR)
+                    if img is None:
+                        raise ValueError("Invalid image data")
+                    if img.shape[1] != width or img.shape[0] != height:
+                        img = cv2.resize(img, (width, height))
+                    pushed['frame'] = img
+                except Exception as e:
+                    print(f"Error decoding pushed frame: {e}", flush=True)
+
+        threading.Thread(target=read_pushed_frames, daemon=True).start()
+    elif args.watch:
+        # Editors often write the file in several steps; a frame that fails to
+        # decode keeps the previous one and is retried on the next poll
+        def load_watched():
+            img = cv2.imread(args.watch, cv2.IMREAD_COLOR)
+            if img is None:
+                raise ValueError(f"Could not read image: {args.watch}")
+            return img
+
+        def file_signature():
+            try:
+                st = os.stat(args.watch)
+                return (st.st_mtime_ns, st.st_size)
+            except OSError:
+                return None
+
+        try:
+            frame = load_watched()
+            height, width = frame.shape[:2]
+        except Exception as e:
+            print(f"Error loading watched image: {e}")
+            return 1
+        watched = {'frame': frame, 'signature': file_signature()}
+        is_video = False
+        is_watch = True
+
+        def watch_file():
+            while True:
+                time.sleep(0.5)
+                signature = file_signature()
+                if signature is None or signature == watched['signature']:
+                    continue
+                try:
+                    img = load_watched()
+                    if img.shape[1] != width or img.shape[0] != height:
+                        img = cv2.resize(img, (width, height))
+                    watched['frame'] = img
+                    watched['signature'] = signature
+                    print(f"Reloaded {args.watch}", flush=True)
+                except Exception as e:
+                    print(f"Error reloading watched image: {e}", flush=True)
+
+        threading.Thread(target=watch_file, daemon=True).start()
+    elif args.composite:
+        try:
+            with open(args.composite) as spec_file:
+                spec = json.load(spec_file)
+            read_base, base_cap = open_layer(spec['base'])
+            read_overlay, overlay_cap = open_layer(spec['overlay'])
+            layer_caps = [c for c in (base_cap, overlay_cap) if c is not None]
+            first = read_base()
+            if first is None:
+                raise ValueError("Base layer produced no frame")
+            height, width = first.shape[:2]
+            is_video = False
+            is_composite = True
+        except Exception as e:
+            print(f"Error opening composite: {e}")
+            return 1
     else:
         print("No source specified")
         return 1
 
     # Create virtual camera
     try:
-        with pyvirtualcam.Camera(width=width, height=height, fps=fps) as cam:
+        with pyvirtualcam.Camera(width=width, height=height, fps=fps, device=args.device) as cam:
             print(f"Virtual camera created: {cam.device}")
             
             # Main loop
@@ -204,6 +498,17 @@ def main():
                             continue
                         else:  # If it's a camera and we lost the frame, exit
                             break
+                elif is_push:
+                    current_frame = pushed['frame']
+                elif is_watch:
+                    current_frame = watched['frame']
+                elif is_composite:
+                    base = read_base()
+                    if base is None:
+                        break
+                    if base.shape[1] != width or base.shape[0] != height:
+                        base = cv2.resize(base, (width, height))
+                    current_frame = composite(base, read_overlay(), spec)
                 else:
                     current_frame = frame.copy()
                 
@@ -220,6 +525,8 @@ def main():
     finally:
         if is_video and 'cap' in locals():
             cap.release()
+        for layer_cap in layer_caps:
+            layer_cap.release()
     
     return 0
 
@@ -235,21 +542,38 @@ pub fn start_webcam_emulator(
     source_data: &str,
     webcam_emulator: tauri::State<'_, Arc<Mutex<WebcamEmulator>>>
 ) -> Result<bool, String> {
-    let source = match source_type {
-        "image" => WebcamSource::Image(source_data.to_string()),
-        "video" => WebcamSource::Video(PathBuf::from(source_data)),
-        "camera" => {
-            let index = source_data.parse::<i32>()
-                .map_err(|_| "Índice de câmera inválido".to_string())?;
-            WebcamSource::Camera(index)
-        },
-        _ => return Err("Tipo de fonte desconhecido".into()),
-    };
+    let source = WebcamSource::from_parts(source_type, source_data)?;
 
     let mut emulator = webcam_emulator.lock().map_err(|_| "Falha ao obter lock do WebcamEmulator".to_string())?;
     emulator.start(source)
 }
 
+// Streams one of the patient's photos, frontal unless `label` says otherwise
+#[tauri::command]
+pub fn start_webcam_patient_photo(
+    app_handle: AppHandle,
+    patient_id: u32,
+    label: Option<FaceAngle>,
+    webcam_emulator: tauri::State<'_, Arc<Mutex<WebcamEmulator>>>
+) -> Result<bool, String> {
+    let patient = patient::get_patient(&app_handle, patient_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Paciente {} não encontrado.", patient_id))?;
+    let source = WebcamSource::facial_photo(&patient, label.unwrap_or_default())?;
+
+    let mut emulator = webcam_emulator.lock().map_err(|_| "Falha ao obter lock do WebcamEmulator".to_string())?;
+    emulator.start(source)
+}
+
+#[tauri::command]
+pub fn push_webcam_frame(
+    image_base64: &str,
+    webcam_emulator: tauri::State<'_, Arc<Mutex<WebcamEmulator>>>
+) -> Result<bool, String> {
+    let mut emulator = webcam_emulator.lock().map_err(|_| "Falha ao obter lock do WebcamEmulator".to_string())?;
+    emulator.push_frame(image_base64)
+}
+
 #[tauri::command]
 pub fn stop_webcam_emulator(
     webcam_emulator: tauri::State<'_, Arc<Mutex<WebcamEmulator>>>
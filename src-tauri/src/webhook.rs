@@ -0,0 +1,81 @@
+use std::time::Duration;
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::patient;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobFailure {
+    pub item: String,
+    pub error: String,
+}
+
+// Compact outcome of a bulk job, as POSTed to `import_webhook_url`
+#[derive(Debug, Clone, Serialize)]
+pub struct JobReport {
+    pub event: &'static str,
+    pub job_id: String,
+    pub kind: String,
+    pub total: usize,
+    pub succeeded: usize,
+    pub failures: Vec<JobFailure>,
+    pub cancelled: bool,
+    // Set when the job itself failed, e.g. the search didn't answer
+    pub error: Option<String>,
+}
+
+impl JobReport {
+    pub fn new(job_id: &str, kind: &str) -> Self {
+        Self {
+            event: "import_job_finished",
+            job_id: job_id.to_string(),
+            kind: kind.to_string(),
+            total: 0,
+            succeeded: 0,
+            failures: Vec::new(),
+            cancelled: false,
+            error: None,
+        }
+    }
+
+    pub fn failed(job_id: &str, kind: &str, error: &str) -> Self {
+        Self {
+            error: Some(error.to_string()),
+            ..Self::new(job_id, kind)
+        }
+    }
+}
+
+fn webhook_url(app_handle: &AppHandle) -> Option<String> {
+    patient::load_config_from_disk(app_handle)
+        .ok()?
+        .get("import_webhook_url")?
+        .as_str()
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+}
+
+// Fire and forget: the job's result doesn't depend on the webhook answering
+pub fn send_job_report(app_handle: &AppHandle, report: JobReport) {
+    let Some(url) = webhook_url(app_handle) else {
+        return;
+    };
+    tauri::async_runtime::spawn(async move {
+        let client = match reqwest::Client::builder().timeout(TIMEOUT).build() {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("Falha ao criar cliente do webhook: {}", e);
+                return;
+            }
+        };
+        match client.post(&url).json(&report).send().await {
+            Ok(response) if !response.status().is_success() => {
+                eprintln!("Webhook {} respondeu {} para o job {}", url, response.status(), report.job_id)
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Falha ao chamar webhook {}: {}", url, e),
+        }
+    });
+}
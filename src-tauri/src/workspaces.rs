@@ -0,0 +1,194 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use tauri::{AppHandle, Emitter};
+
+use crate::data_lock;
+use crate::patient;
+use crate::patient_history;
+
+// Independent patient lists, one per clinic or environment being tested.
+// The default workspace is the original <data dir>/patients.db; every other
+// one lives in <data dir>/workspaces/<name>/patients.db. The workspace in use
+// is `active_workspace` in app_config.json.
+pub const DEFAULT_WORKSPACE: &str = "default";
+const ACTIVE_KEY: &str = "active_workspace";
+const WORKSPACES_DIR: &str = "workspaces";
+
+#[derive(Debug, Serialize)]
+pub struct WorkspaceInfo {
+    pub name: String,
+    pub active: bool,
+}
+
+// Names become directory names, so only letters, digits, '-' and '_'
+fn validate_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Nome do workspace não informado.".into());
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("Nome do workspace '{}' inválido: use apenas letras, números, '-' e '_'.", name));
+    }
+    Ok(name.to_string())
+}
+
+fn workspaces_dir(app_handle: &AppHandle) -> io::Result<PathBuf> {
+    Ok(patient::ensure_data_dir(app_handle)?.join(WORKSPACES_DIR))
+}
+
+pub fn database_path(app_handle: &AppHandle, name: &str) -> io::Result<PathBuf> {
+    if name == DEFAULT_WORKSPACE {
+        return Ok(patient::ensure_data_dir(app_handle)?.join("patients.db"));
+    }
+    let dir = workspaces_dir(app_handle)?.join(name);
+    if !dir.exists() {
+        fs::create_dir_all(&dir)?;
+    }
+    Ok(dir.join("patients.db"))
+}
+
+// Name used for the workspace's snapshots under backups/
+pub fn backup_stem(app_handle: &AppHandle) -> String {
+    match active(app_handle) {
+        name if name == DEFAULT_WORKSPACE => "patients".into(),
+        name => format!("patients-{}", name),
+    }
+}
+
+// Falls back to the default workspace when the config can't be read or names
+// one that no longer exists
+pub fn active(app_handle: &AppHandle) -> String {
+    let name = patient::load_config_from_disk(app_handle)
+        .ok()
+        .and_then(|config| config.get(ACTIVE_KEY)?.as_str().map(String::from));
+    match name {
+        Some(name) if exists(app_handle, &name) => name,
+        _ => DEFAULT_WORKSPACE.into(),
+    }
+}
+
+fn exists(app_handle: &AppHandle, name: &str) -> bool {
+    name == DEFAULT_WORKSPACE
+        || workspaces_dir(app_handle).is_ok_and(|dir| dir.join(name).join("patients.db").exists())
+}
+
+fn names(app_handle: &AppHandle) -> io::Result<Vec<String>> {
+    let mut names = vec![DEFAULT_WORKSPACE.to_string()];
+    let dir = workspaces_dir(app_handle)?;
+    if dir.exists() {
+        let mut others: Vec<String> = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().join("patients.db").exists())
+            .filter_map(|entry| entry.file_name().to_str().map(String::from))
+            .collect();
+        others.sort();
+        names.extend(others);
+    }
+    Ok(names)
+}
+
+// Whether an attachment of a workspace other than the active one points to
+// the blob. When a workspace can't be read the blob counts as used.
+pub fn blob_used_elsewhere(app_handle: &AppHandle, blob_key: &str) -> bool {
+    let active = active(app_handle);
+    let Ok(names) = names(app_handle) else {
+        return true;
+    };
+    names.iter().filter(|name| **name != active).any(|name| {
+        database_path(app_handle, name)
+            .and_then(|path| patient::load_patients_at(app_handle, &path))
+            .map_or(true, |patients| {
+                patients.iter().flat_map(|p| p.attachments.iter()).any(|a| a.blob_key == blob_key)
+            })
+    })
+}
+
+fn set_active(app_handle: &AppHandle, name: &str) -> Result<(), String> {
+    let mut config = patient::load_config_from_disk(app_handle).map_err(|e| format!("Falha ao ler configurações: {e}"))?;
+    let root = config.as_object_mut().ok_or("Arquivo de configurações inválido.")?;
+    if name == DEFAULT_WORKSPACE {
+        root.remove(ACTIVE_KEY);
+    } else {
+        root.insert(ACTIVE_KEY.to_string(), Value::String(name.to_string()));
+    }
+    patient::save_config_to_disk(app_handle, &config).map_err(|e| format!("Falha ao salvar configurações: {e}"))?;
+    // The history refers to the patients of the previous workspace
+    patient_history::clear(app_handle);
+    let _ = app_handle.emit("patients-changed", ());
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_workspaces(app_handle: AppHandle) -> Result<Vec<WorkspaceInfo>, String> {
+    let active = active(&app_handle);
+    Ok(names(&app_handle)
+        .map_err(|e| format!("Falha ao listar workspaces: {e}"))?
+        .into_iter()
+        .map(|name| WorkspaceInfo { active: name == active, name })
+        .collect())
+}
+
+// Creates an empty workspace; it becomes active only with `switch_to`
+#[tauri::command]
+pub fn create_workspace(app_handle: AppHandle, name: String, switch_to: Option<bool>) -> Result<(), String> {
+    let name = validate_name(&name)?;
+    if exists(&app_handle, &name) {
+        return Err(format!("Workspace '{}' já existe.", name));
+    }
+    let path = database_path(&app_handle, &name).map_err(|e| e.to_string())?;
+    patient::create_empty_database(&path).map_err(|e| format!("Falha ao criar workspace: {e}"))?;
+    if switch_to.unwrap_or(false) {
+        set_active(&app_handle, &name)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn switch_workspace(app_handle: AppHandle, name: String) -> Result<(), String> {
+    let name = validate_name(&name)?;
+    if !exists(&app_handle, &name) {
+        return Err(format!("Workspace '{}' não encontrado.", name));
+    }
+    set_active(&app_handle, &name)
+}
+
+// Copies every patient (and the audit log) of `source` into a new workspace
+#[tauri::command]
+pub fn clone_workspace(app_handle: AppHandle, source: String, name: String) -> Result<(), String> {
+    let source = validate_name(&source)?;
+    let name = validate_name(&name)?;
+    if !exists(&app_handle, &source) {
+        return Err(format!("Workspace '{}' não encontrado.", source));
+    }
+    if exists(&app_handle, &name) {
+        return Err(format!("Workspace '{}' já existe.", name));
+    }
+    let from = database_path(&app_handle, &source).map_err(|e| e.to_string())?;
+    let to = database_path(&app_handle, &name).map_err(|e| e.to_string())?;
+    // Opening the source brings it to the current format before it's copied
+    let conn = patient::open_database_at(&app_handle, &from).map_err(|e| format!("Falha ao abrir workspace: {e}"))?;
+    conn.execute("VACUUM INTO ?1", [to.to_string_lossy().into_owned()])
+        .map_err(|e| format!("Falha ao copiar workspace: {e}"))?;
+    Ok(())
+}
+
+// The default workspace and the active one can't be deleted
+#[tauri::command]
+pub fn delete_workspace(app_handle: AppHandle, name: String) -> Result<(), String> {
+    let name = validate_name(&name)?;
+    if name == DEFAULT_WORKSPACE {
+        return Err("O workspace padrão não pode ser excluído.".into());
+    }
+    if name == active(&app_handle) {
+        return Err(format!("Workspace '{}' está em uso. Troque de workspace antes de excluí-lo.", name));
+    }
+    if !exists(&app_handle, &name) {
+        return Err(format!("Workspace '{}' não encontrado.", name));
+    }
+    let dir = workspaces_dir(&app_handle).map_err(|e| e.to_string())?.join(&name);
+    data_lock::with_write_lock(&app_handle, || fs::remove_dir_all(&dir))
+        .map_err(|e| format!("Falha ao excluir workspace: {e}"))
+}
@@ -0,0 +1,1233 @@
+use image::GrayImage;
+
+// FBI WSQ (Wavelet Scalar Quantization) fingerprint image codec, following
+// the NBIS reference implementation: a fixed 20-node wavelet decomposition
+// into 64 subbands (60 coded), scalar quantization per subband and JPEG-like
+// Huffman coding of the quantized coefficients.
+
+const SOI: u16 = 0xFFA0;
+const EOI: u16 = 0xFFA1;
+const SOF: u16 = 0xFFA2;
+const SOB: u16 = 0xFFA3;
+const DTT: u16 = 0xFFA4;
+const DQT: u16 = 0xFFA5;
+const DHT: u16 = 0xFFA6;
+const DRT: u16 = 0xFFA7;
+const COM: u16 = 0xFFA8;
+// Restart markers may interrupt the entropy coded data
+const RST_FIRST: u16 = 0xFFB0;
+const RST_LAST: u16 = 0xFFB7;
+
+const NUM_SUBBANDS: usize = 60;
+const MAX_SUBBANDS: usize = 64;
+const W_TREE_LEN: usize = 20;
+const MAX_HUFFMAN_TABLES: usize = 8;
+// Largest image decoded, so a corrupted header can't ask for gigabytes; a
+// 1000 ppi four-finger slap is about 3200x3000
+const MAX_PIXELS: usize = 16 * 1024 * 1024;
+
+// Analysis filters of the 9/7 biorthogonal wavelet, used when the file
+// carries no transform table
+const LO_FILTER: [f32; 9] = [
+    0.037_828_457,
+    -0.023_849_465,
+    -0.110_624_4,
+    0.377_402_84,
+    0.852_698_7,
+    0.377_402_84,
+    -0.110_624_4,
+    -0.023_849_465,
+    0.037_828_457,
+];
+const HI_FILTER: [f32; 7] = [
+    0.064_538_88,
+    -0.040_689_416,
+    -0.418_092_28,
+    0.788_485_6,
+    -0.418_092_28,
+    -0.040_689_416,
+    0.064_538_88,
+];
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Band {
+    x: usize,
+    y: usize,
+    lenx: usize,
+    leny: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct WaveletNode {
+    band: Band,
+    // Spectral inversion: the high-pass half comes first
+    inv_rw: bool,
+    inv_cl: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Filters {
+    lo: Vec<f32>,
+    hi: Vec<f32>,
+}
+
+impl Default for Filters {
+    fn default() -> Self {
+        Self {
+            lo: LO_FILTER.to_vec(),
+            hi: HI_FILTER.to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Quantization {
+    bin_center: f32,
+    q_bin: [f32; MAX_SUBBANDS],
+    z_bin: [f32; MAX_SUBBANDS],
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    width: usize,
+    height: usize,
+    m_shift: f32,
+    r_scale: f32,
+}
+
+#[derive(Debug, Clone)]
+struct HuffmanTable {
+    // Indexed by code length (1..=16)
+    min_code: [i32; 17],
+    max_code: [i32; 17],
+    val_ptr: [usize; 17],
+    values: Vec<u8>,
+}
+
+// Canonical JPEG code assignment: `bits[i]` codes of length i + 1
+fn huffman_codes(bits: &[u8; 16]) -> Vec<(u32, u8)> {
+    let mut codes = Vec::new();
+    let mut code = 0u32;
+    for (i, &count) in bits.iter().enumerate() {
+        for _ in 0..count {
+            codes.push((code, i as u8 + 1));
+            code += 1;
+        }
+        code <<= 1;
+    }
+    codes
+}
+
+impl HuffmanTable {
+    fn new(bits: &[u8; 16], values: Vec<u8>) -> Result<Self, String> {
+        let codes = huffman_codes(bits);
+        if codes.len() != values.len() {
+            return Err("Tabela Huffman WSQ inconsistente.".into());
+        }
+        let mut table = Self {
+            min_code: [0; 17],
+            max_code: [-1; 17],
+            val_ptr: [0; 17],
+            values,
+        };
+        let mut k = 0usize;
+        for len in 1..=16 {
+            let count = usize::from(bits[len - 1]);
+            if count == 0 {
+                continue;
+            }
+            table.val_ptr[len] = k;
+            table.min_code[len] = codes[k].0 as i32;
+            k += count;
+            table.max_code[len] = codes[k - 1].0 as i32;
+        }
+        Ok(table)
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.data.len()).ok_or("Arquivo WSQ truncado.")?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.bytes(2)?;
+        Ok(u16::from(b[0]) << 8 | u16::from(b[1]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    // Decimal stored as a power-of-ten exponent and an unsigned integer
+    fn scaled_u16(&mut self) -> Result<f32, String> {
+        let scale = self.u8()?;
+        let value = self.u16()?;
+        Ok(f32::from(value) / 10f32.powi(i32::from(scale)))
+    }
+
+    // Body of a table segment, after its length field
+    fn segment(&mut self) -> Result<Reader<'a>, String> {
+        let len = usize::from(self.u16()?);
+        if len < 2 {
+            return Err("Segmento WSQ com tamanho inválido.".into());
+        }
+        Ok(Reader {
+            data: self.bytes(len - 2)?,
+            pos: 0,
+        })
+    }
+}
+
+// Reads the entropy coded data MSB first, undoing the 0xFF 0x00 stuffing.
+// Stops at the first marker, leaving `pos` on it.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    byte: u8,
+    bits_left: u8,
+}
+
+impl BitReader<'_> {
+    // None when a marker ends the block
+    fn bit(&mut self) -> Result<Option<u32>, String> {
+        if self.bits_left == 0 {
+            let byte = *self.data.get(self.pos).ok_or("Dados WSQ truncados.")?;
+            if byte == 0xFF {
+                match self.data.get(self.pos + 1) {
+                    Some(0x00) => self.pos += 1,
+                    Some(_) => return Ok(None),
+                    None => return Err("Dados WSQ truncados.".into()),
+                }
+            }
+            self.pos += 1;
+            self.byte = byte;
+            self.bits_left = 8;
+        }
+        self.bits_left -= 1;
+        Ok(Some(u32::from(self.byte >> self.bits_left) & 1))
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for _ in 0..count {
+            let bit = self.bit()?.ok_or("Marcador inesperado nos dados WSQ.")?;
+            value = value << 1 | bit;
+        }
+        Ok(value)
+    }
+
+    fn symbol(&mut self, table: &HuffmanTable) -> Result<Option<u8>, String> {
+        let Some(mut code) = self.bit()? else {
+            return Ok(None);
+        };
+        let mut len = 1;
+        while code as i32 > table.max_code[len] {
+            len += 1;
+            if len > 16 {
+                return Err("Código Huffman WSQ inválido.".into());
+            }
+            let Some(bit) = self.bit()? else {
+                return Ok(None);
+            };
+            code = code << 1 | bit;
+        }
+        let index = table.val_ptr[len] + (code as i32 - table.min_code[len]) as usize;
+        table
+            .values
+            .get(index)
+            .copied()
+            .map(Some)
+            .ok_or_else(|| "Código Huffman WSQ inválido.".to_string())
+    }
+}
+
+// Odd lengths split with the larger half first, unless the node is
+// spectrally inverted in that direction
+fn halves(len: usize, inverted: bool) -> (usize, usize) {
+    let first = if inverted { len / 2 } else { len.div_ceil(2) };
+    (first, len - first)
+}
+
+fn build_w_tree4(tree: &mut [WaveletNode; W_TREE_LEN], p1: usize, p2: usize, band: Band, stop1: bool) {
+    let Band { x, y, lenx, leny } = band;
+    tree[p1].band = band;
+
+    let (left, right) = halves(lenx, p1 == 4);
+    let (top, bottom) = halves(leny, p1 == 5);
+    tree[p2].band = Band { x, y, lenx: left, leny: top };
+    tree[p2 + 1].band = Band { x: x + left, y, lenx: right, leny: top };
+    tree[p2 + 2].band = Band { x, y: y + top, lenx: left, leny: bottom };
+    if !stop1 {
+        tree[p2 + 3].band = Band { x: x + left, y: y + top, lenx: right, leny: bottom };
+    }
+}
+
+// Regions split by each of the 20 decomposition steps
+fn build_w_tree(width: usize, height: usize) -> [WaveletNode; W_TREE_LEN] {
+    let mut tree = [WaveletNode::default(); W_TREE_LEN];
+    for node in [2, 4, 7, 9, 11, 13, 16, 18] {
+        tree[node].inv_rw = true;
+    }
+    for node in [3, 5, 8, 9, 12, 13, 17, 18] {
+        tree[node].inv_cl = true;
+    }
+
+    build_w_tree4(&mut tree, 0, 1, Band { x: 0, y: 0, lenx: width, leny: height }, true);
+
+    let (lenx, lenx2) = halves(tree[1].band.lenx, false);
+    let (leny, leny2) = halves(tree[1].band.leny, false);
+    build_w_tree4(&mut tree, 4, 6, Band { x: lenx, y: 0, lenx: lenx2, leny }, false);
+    build_w_tree4(&mut tree, 5, 10, Band { x: 0, y: leny, lenx, leny: leny2 }, false);
+    build_w_tree4(&mut tree, 14, 15, Band { x: 0, y: 0, lenx, leny }, false);
+
+    tree[19].band = Band {
+        x: 0,
+        y: 0,
+        lenx: tree[15].band.lenx.div_ceil(2),
+        leny: tree[15].band.leny.div_ceil(2),
+    };
+    tree
+}
+
+// Four subbands at `p` from the quadrants of `band`, given the widths of
+// its left/right halves and the heights of its top/bottom halves
+fn set_quadrants(tree: &mut [Band; MAX_SUBBANDS], p: usize, band: Band, (left, right): (usize, usize), (top, bottom): (usize, usize)) {
+    let Band { x, y, .. } = band;
+    tree[p] = Band { x, y, lenx: left, leny: top };
+    tree[p + 1] = Band { x: x + left, y, lenx: right, leny: top };
+    tree[p + 2] = Band { x, y: y + top, lenx: left, leny: bottom };
+    tree[p + 3] = Band { x: x + left, y: y + top, lenx: right, leny: bottom };
+}
+
+fn build_q_tree4(tree: &mut [Band; MAX_SUBBANDS], p: usize, band: Band) {
+    set_quadrants(tree, p, band, halves(band.lenx, false), halves(band.leny, false));
+}
+
+// Splits a region in 16 subbands: four quadrants split in four, with the
+// odd halves following the spectral inversion of each quadrant
+fn build_q_tree16(tree: &mut [Band; MAX_SUBBANDS], p: usize, band: Band, rw: bool, cl: bool) {
+    let Band { x, y, lenx, leny } = band;
+    let (tempx, temp2x) = halves(lenx, cl);
+    let (tempy, temp2y) = halves(leny, rw);
+    let (left, right) = (halves(tempx, false), halves(temp2x, true));
+    let (top, bottom) = (halves(tempy, false), halves(temp2y, true));
+
+    set_quadrants(tree, p, Band { x, y, ..band }, left, top);
+    set_quadrants(tree, p + 4, Band { x: x + tempx, y, ..band }, right, top);
+    set_quadrants(tree, p + 8, Band { x, y: y + tempy, ..band }, left, bottom);
+    set_quadrants(tree, p + 12, Band { x: x + tempx, y: y + tempy, ..band }, right, bottom);
+}
+
+// Position and size of the 64 quantization subbands; 60 to 63 (the finest
+// diagonal details) are never coded
+fn build_q_tree(w_tree: &[WaveletNode; W_TREE_LEN]) -> [Band; MAX_SUBBANDS] {
+    let mut tree = [Band::default(); MAX_SUBBANDS];
+    build_q_tree16(&mut tree, 3, w_tree[14].band, false, false);
+    build_q_tree16(&mut tree, 19, w_tree[4].band, false, true);
+    build_q_tree16(&mut tree, 48, w_tree[0].band, false, false);
+    build_q_tree16(&mut tree, 35, w_tree[5].band, true, false);
+    build_q_tree4(&mut tree, 0, w_tree[19].band);
+    tree
+}
+
+// Whole-sample symmetric extension of a signal of `len` samples
+fn reflect(mut i: isize, len: usize) -> usize {
+    let last = len as isize - 1;
+    if last <= 0 {
+        return 0;
+    }
+    while i < 0 || i > last {
+        if i < 0 {
+            i = -i;
+        }
+        if i > last {
+            i = 2 * last - i;
+        }
+    }
+    i as usize
+}
+
+fn split_lengths(len: usize) -> (usize, usize) {
+    (len.div_ceil(2), len / 2)
+}
+
+// Inverse of the one-level split of a line: low-pass samples sit on even
+// positions, high-pass on odd ones, both symmetrically extended. The
+// synthesis filters are the analysis ones modulated by (-1)^n and swapped.
+fn synthesize_line(sub: &[f32], filters: &Filters, inv: bool, out: &mut [f32]) {
+    let len = sub.len();
+    let (low_len, high_len) = split_lengths(len);
+    let (low, high) = if inv {
+        let (high, low) = sub.split_at(high_len);
+        (low, high)
+    } else {
+        sub.split_at(low_len)
+    };
+    let lo_half = (filters.lo.len() / 2) as isize;
+    let hi_half = (filters.hi.len() / 2) as isize;
+    let sign = |n: isize| if n % 2 == 0 { 1.0 } else { -1.0 };
+
+    for (m, sample) in out.iter_mut().enumerate().take(len) {
+        let m = m as isize;
+        let mut sum = 0.0;
+        // Low band through the modulated high-pass filter
+        for n in -hi_half..=hi_half {
+            if (m - n) % 2 != 0 {
+                continue;
+            }
+            let position = reflect(m - n, len);
+            if let Some(value) = low.get(position / 2) {
+                sum += sign(n) * filters.hi[(n + hi_half) as usize] * value;
+            }
+        }
+        // High band through the modulated low-pass filter
+        if high_len > 0 {
+            for n in -lo_half..=lo_half {
+                if (m - n) % 2 == 0 {
+                    continue;
+                }
+                let position = reflect(m - n, len);
+                if let Some(value) = high.get(position / 2) {
+                    sum += sign(n) * filters.lo[(n + lo_half) as usize] * value;
+                }
+            }
+        }
+        *sample = sum;
+    }
+}
+
+// Undoes the split of one tree node, columns first and then rows
+fn join_band(data: &mut [f32], width: usize, node: &WaveletNode, filters: &Filters) {
+    let Band { x, y, lenx, leny } = node.band;
+    if lenx == 0 || leny == 0 {
+        return;
+    }
+    let mut line = vec![0.0f32; lenx.max(leny)];
+    let mut out = vec![0.0f32; lenx.max(leny)];
+
+    for col in 0..lenx {
+        for row in 0..leny {
+            line[row] = data[(y + row) * width + x + col];
+        }
+        synthesize_line(&line[..leny], filters, node.inv_cl, &mut out);
+        for row in 0..leny {
+            data[(y + row) * width + x + col] = out[row];
+        }
+    }
+    for row in 0..leny {
+        let start = (y + row) * width + x;
+        line[..lenx].copy_from_slice(&data[start..start + lenx]);
+        synthesize_line(&line[..lenx], filters, node.inv_rw, &mut out);
+        data[start..start + lenx].copy_from_slice(&out[..lenx]);
+    }
+}
+
+fn read_filters(mut segment: Reader) -> Result<Filters, String> {
+    let hi_len = usize::from(segment.u8()?);
+    let lo_len = usize::from(segment.u8()?);
+    if hi_len.is_multiple_of(2) || lo_len.is_multiple_of(2) || hi_len > 15 || lo_len > 15 {
+        return Err("Filtros de wavelet do arquivo WSQ não suportados.".into());
+    }
+    // Only the centre and right half of each symmetric filter is stored
+    let mut half = |len: usize| -> Result<Vec<f32>, String> {
+        let mut coefficients = Vec::with_capacity(len / 2 + 1);
+        for _ in 0..=len / 2 {
+            let negative = segment.u8()? != 0;
+            let scale = segment.u8()?;
+            let value = segment.u32()? as f64 / 10f64.powi(i32::from(scale));
+            coefficients.push(if negative { -value } else { value } as f32);
+        }
+        let mut filter: Vec<f32> = coefficients[1..].iter().rev().copied().collect();
+        filter.extend(coefficients);
+        Ok(filter)
+    };
+    let lo = half(lo_len)?;
+    let hi = half(hi_len)?;
+    Ok(Filters { lo, hi })
+}
+
+fn read_quantization(mut segment: Reader) -> Result<Quantization, String> {
+    let mut table = Quantization {
+        bin_center: segment.scaled_u16()?,
+        q_bin: [0.0; MAX_SUBBANDS],
+        z_bin: [0.0; MAX_SUBBANDS],
+    };
+    for band in 0..MAX_SUBBANDS {
+        table.q_bin[band] = segment.scaled_u16()?;
+        table.z_bin[band] = segment.scaled_u16()?;
+    }
+    Ok(table)
+}
+
+fn read_huffman_tables(mut segment: Reader, tables: &mut [Option<HuffmanTable>]) -> Result<(), String> {
+    while segment.pos < segment.data.len() {
+        let id = usize::from(segment.u8()?);
+        let mut bits = [0u8; 16];
+        bits.copy_from_slice(segment.bytes(16)?);
+        let count = bits.iter().map(|&b| usize::from(b)).sum();
+        let values = segment.bytes(count)?.to_vec();
+        let slot = tables.get_mut(id).ok_or_else(|| format!("Tabela Huffman WSQ {} inválida.", id))?;
+        *slot = Some(HuffmanTable::new(&bits, values)?);
+    }
+    Ok(())
+}
+
+fn read_frame(mut segment: Reader) -> Result<Frame, String> {
+    let _black = segment.u8()?;
+    let _white = segment.u8()?;
+    let height = usize::from(segment.u16()?);
+    let width = usize::from(segment.u16()?);
+    let m_shift = segment.scaled_u16()?;
+    let r_scale = segment.scaled_u16()?;
+    if width == 0 || height == 0 {
+        return Err("Imagem WSQ sem dimensões.".into());
+    }
+    if width * height > MAX_PIXELS {
+        return Err(format!("Imagem WSQ grande demais: {}x{}.", width, height));
+    }
+    Ok(Frame {
+        width,
+        height,
+        m_shift,
+        r_scale,
+    })
+}
+
+// Decodes one block of coefficients into `coefficients[*filled..]`. Returns
+// the position of the marker that ends it.
+fn decode_block(
+    data: &[u8],
+    start: usize,
+    table: &HuffmanTable,
+    coefficients: &mut [i32],
+    filled: &mut usize,
+) -> Result<usize, String> {
+    let mut bits = BitReader {
+        data,
+        pos: start,
+        byte: 0,
+        bits_left: 0,
+    };
+    let overflow = || "Dados WSQ excedem o tamanho da imagem.".to_string();
+    let push_zeros = |coefficients: &mut [i32], filled: &mut usize, count: usize| -> Result<(), String> {
+        let end = filled.checked_add(count).filter(|&end| end <= coefficients.len()).ok_or_else(overflow)?;
+        coefficients[*filled..end].fill(0);
+        *filled = end;
+        Ok(())
+    };
+
+    loop {
+        let Some(symbol) = bits.symbol(table)? else {
+            let marker = u16::from(data[bits.pos]) << 8 | u16::from(data[bits.pos + 1]);
+            if (RST_FIRST..=RST_LAST).contains(&marker) {
+                bits.pos += 2;
+                bits.bits_left = 0;
+                continue;
+            }
+            return Ok(bits.pos);
+        };
+        let value = match symbol {
+            1..=100 => {
+                push_zeros(coefficients, filled, usize::from(symbol))?;
+                continue;
+            }
+            105 => {
+                let run = bits.bits(8)? as usize;
+                push_zeros(coefficients, filled, run)?;
+                continue;
+            }
+            106 => {
+                let run = bits.bits(16)? as usize;
+                push_zeros(coefficients, filled, run)?;
+                continue;
+            }
+            101 => bits.bits(8)? as i32,
+            102 => -(bits.bits(8)? as i32),
+            103 => bits.bits(16)? as i32,
+            104 => -(bits.bits(16)? as i32),
+            107..=254 => i32::from(symbol) - 180,
+            other => return Err(format!("Símbolo Huffman WSQ inválido: {}", other)),
+        };
+        let slot = coefficients.get_mut(*filled).ok_or_else(overflow)?;
+        *slot = value;
+        *filled += 1;
+    }
+}
+
+fn dequantize(coefficients: &[i32], frame: &Frame, q_tree: &[Band; MAX_SUBBANDS], quant: &Quantization) -> Vec<f32> {
+    let mut data = vec![0.0f32; frame.width * frame.height];
+    let mut next = coefficients.iter();
+    for (band, region) in q_tree.iter().enumerate().take(NUM_SUBBANDS) {
+        let (q_bin, z_bin) = (quant.q_bin[band], quant.z_bin[band]);
+        if q_bin == 0.0 {
+            continue;
+        }
+        for row in 0..region.leny {
+            let start = (region.y + row) * frame.width + region.x;
+            for value in &mut data[start..start + region.lenx] {
+                let q = *next.next().unwrap_or(&0) as f32;
+                *value = if q > 0.0 {
+                    q_bin * (q - quant.bin_center) + z_bin / 2.0
+                } else if q < 0.0 {
+                    q_bin * (q + quant.bin_center) - z_bin / 2.0
+                } else {
+                    0.0
+                };
+            }
+        }
+    }
+    data
+}
+
+fn coded_len(q_tree: &[Band; MAX_SUBBANDS], quant: &Quantization) -> usize {
+    q_tree
+        .iter()
+        .zip(quant.q_bin.iter())
+        .take(NUM_SUBBANDS)
+        .filter(|(_, &q_bin)| q_bin != 0.0)
+        .map(|(band, _)| band.lenx * band.leny)
+        .sum()
+}
+
+pub fn decode(wsq: &[u8]) -> Result<GrayImage, String> {
+    let mut reader = Reader { data: wsq, pos: 0 };
+    if reader.u16()? != SOI {
+        return Err("Arquivo não é uma imagem WSQ.".into());
+    }
+
+    let mut filters = Filters::default();
+    let mut quant: Option<Quantization> = None;
+    let mut tables: Vec<Option<HuffmanTable>> = vec![None; MAX_HUFFMAN_TABLES];
+    let mut frame: Option<(Frame, [Band; MAX_SUBBANDS], [WaveletNode; W_TREE_LEN])> = None;
+    let mut coefficients: Vec<i32> = Vec::new();
+    let mut filled = 0usize;
+
+    loop {
+        match reader.u16()? {
+            DTT => filters = read_filters(reader.segment()?)?,
+            DQT => quant = Some(read_quantization(reader.segment()?)?),
+            DHT => read_huffman_tables(reader.segment()?, &mut tables)?,
+            DRT | COM => {
+                reader.segment()?;
+            }
+            SOF => {
+                let header = read_frame(reader.segment()?)?;
+                let w_tree = build_w_tree(header.width, header.height);
+                frame = Some((header, build_q_tree(&w_tree), w_tree));
+            }
+            SOB => {
+                let mut segment = reader.segment()?;
+                let id = usize::from(segment.u8()?);
+                let (_, q_tree, _) = frame.as_ref().ok_or("Bloco WSQ antes do cabeçalho da imagem.")?;
+                let quant = quant.as_ref().ok_or("Tabela de quantização WSQ ausente.")?;
+                if coefficients.is_empty() {
+                    coefficients = vec![0; coded_len(q_tree, quant)];
+                }
+                let table = tables
+                    .get(id)
+                    .and_then(|t| t.as_ref())
+                    .ok_or_else(|| format!("Tabela Huffman WSQ {} ausente.", id))?;
+                reader.pos = decode_block(wsq, reader.pos, table, &mut coefficients, &mut filled)?;
+            }
+            EOI => break,
+            other => return Err(format!("Marcador WSQ inesperado: {:04X}", other)),
+        }
+    }
+
+    let (header, q_tree, w_tree) = frame.ok_or("Cabeçalho da imagem WSQ ausente.")?;
+    let quant = quant.ok_or("Tabela de quantização WSQ ausente.")?;
+    let mut data = dequantize(&coefficients, &header, &q_tree, &quant);
+    for node in w_tree.iter().rev() {
+        join_band(&mut data, header.width, node, &filters);
+    }
+
+    let pixels = data
+        .iter()
+        .map(|&value| (value * header.r_scale + header.m_shift + 0.5).clamp(0.0, 255.0) as u8)
+        .collect();
+    GrayImage::from_raw(header.width as u32, header.height as u32, pixels)
+        .ok_or_else(|| "Falha ao montar imagem WSQ decodificada.".to_string())
+}
+
+// Bitrate (bits per pixel) giving the usual 15:1 compression for 500 ppi
+// fingerprints
+pub const DEFAULT_BITRATE: f32 = 0.75;
+
+// Bin center written in the quantization table (0.44)
+const BIN_CENTER: (u8, u16) = (2, 44);
+// Subbands below the variance threshold are not coded at all
+const VARIANCE_THRESHOLD: f64 = 1.01;
+// Coefficient blocks, each a run of subbands; the last two share a table
+const BLOCKS: [(usize, usize, u8); 3] = [(0, 19, 0), (19, 52, 1), (52, NUM_SUBBANDS, 1)];
+
+// Splits a line in its low-pass (even centred) and high-pass (odd centred)
+// halves, with whole-sample symmetric extension at both ends
+fn analyze_line(line: &[f32], filters: &Filters, inv: bool, out: &mut [f32]) {
+    let len = line.len();
+    let (low_len, high_len) = split_lengths(len);
+    let lo_half = (filters.lo.len() / 2) as isize;
+    let hi_half = (filters.hi.len() / 2) as isize;
+    let (low_start, high_start) = if inv { (high_len, 0) } else { (0, low_len) };
+
+    for k in 0..low_len {
+        let center = 2 * k as isize;
+        out[low_start + k] = (-lo_half..=lo_half)
+            .map(|n| filters.lo[(n + lo_half) as usize] * line[reflect(center + n, len)])
+            .sum();
+    }
+    for k in 0..high_len {
+        let center = 2 * k as isize + 1;
+        out[high_start + k] = (-hi_half..=hi_half)
+            .map(|n| filters.hi[(n + hi_half) as usize] * line[reflect(center + n, len)])
+            .sum();
+    }
+}
+
+// One decomposition step of a tree node, rows first and then columns
+fn split_band(data: &mut [f32], width: usize, node: &WaveletNode, filters: &Filters) {
+    let Band { x, y, lenx, leny } = node.band;
+    if lenx == 0 || leny == 0 {
+        return;
+    }
+    let mut line = vec![0.0f32; lenx.max(leny)];
+    let mut out = vec![0.0f32; lenx.max(leny)];
+
+    for row in 0..leny {
+        let start = (y + row) * width + x;
+        line[..lenx].copy_from_slice(&data[start..start + lenx]);
+        analyze_line(&line[..lenx], filters, node.inv_rw, &mut out);
+        data[start..start + lenx].copy_from_slice(&out[..lenx]);
+    }
+    for col in 0..lenx {
+        for row in 0..leny {
+            line[row] = data[(y + row) * width + x + col];
+        }
+        analyze_line(&line[..leny], filters, node.inv_cl, &mut out);
+        for row in 0..leny {
+            data[(y + row) * width + x + col] = out[row];
+        }
+    }
+}
+
+// Exponent and integer the value is written as; encoding uses the value read
+// back from them, so encoder and decoder agree exactly
+fn to_scaled(value: f32) -> (u8, u16) {
+    if value <= 0.0 {
+        return (0, 0);
+    }
+    if value >= f32::from(u16::MAX) {
+        return (0, u16::MAX);
+    }
+    let mut scaled = f64::from(value);
+    let mut scale = 0u8;
+    while scaled < f64::from(u16::MAX) {
+        scaled *= 10.0;
+        scale += 1;
+    }
+    (scale - 1, (scaled / 10.0).round() as u16)
+}
+
+fn from_scaled((scale, value): (u8, u16)) -> f32 {
+    f32::from(value) / 10f32.powi(i32::from(scale))
+}
+
+fn region_variance(data: &[f32], width: usize, band: Band, cropped: bool) -> f64 {
+    let (x, y, lenx, leny) = if cropped {
+        (
+            band.x + band.lenx / 8,
+            band.y + band.leny / 9,
+            3 * band.lenx / 4,
+            7 * band.leny / 9,
+        )
+    } else {
+        (band.x, band.y, band.lenx, band.leny)
+    };
+    let count = (lenx * leny) as f64;
+    if count < 2.0 {
+        return 0.0;
+    }
+    let (mut sum, mut squares) = (0.0f64, 0.0f64);
+    for row in 0..leny {
+        for &value in &data[(y + row) * width + x..][..lenx] {
+            sum += f64::from(value);
+            squares += f64::from(value) * f64::from(value);
+        }
+    }
+    (squares - sum * sum / count) / (count - 1.0)
+}
+
+// Subband variances; the borders are left out unless the image is so smooth
+// that the coarsest subbands barely vary
+fn variances(data: &[f32], width: usize, q_tree: &[Band; MAX_SUBBANDS]) -> [f64; NUM_SUBBANDS] {
+    let mut variances = [0.0; NUM_SUBBANDS];
+    for band in 0..4 {
+        variances[band] = region_variance(data, width, q_tree[band], true);
+    }
+    let cropped = variances[..4].iter().sum::<f64>() >= 20_000.0;
+    let first = if cropped { 4 } else { 0 };
+    for band in first..NUM_SUBBANDS {
+        variances[band] = region_variance(data, width, q_tree[band], cropped);
+    }
+    variances
+}
+
+// Bin widths that spend `bitrate` bits per pixel across the subbands worth
+// coding, as in the FBI specification's bit allocation
+fn bin_widths(variances: &[f64; NUM_SUBBANDS], bitrate: f32) -> [f32; NUM_SUBBANDS] {
+    // Fraction of the image area covered by each subband
+    let area = |band: usize| match band {
+        0..=3 => 1.0 / 1024.0,
+        4..=50 => 1.0 / 256.0,
+        _ => 1.0 / 16.0,
+    };
+    // Relative weight of the finest subbands
+    let weight = |band: usize| match band {
+        52 | 56 => 1.32,
+        53 | 55 | 58 | 59 => 1.08,
+        54 | 57 => 1.42,
+        _ => 1.0,
+    };
+
+    let mut relative = [0.0f64; NUM_SUBBANDS];
+    for band in 0..NUM_SUBBANDS {
+        relative[band] = if variances[band] < VARIANCE_THRESHOLD {
+            0.0
+        } else if band < 4 {
+            1.0
+        } else {
+            10.0 / (weight(band) * variances[band].ln())
+        };
+    }
+
+    let mut coded: Vec<usize> = (0..NUM_SUBBANDS).filter(|&b| relative[b] != 0.0).collect();
+    let mut q = 1.0f64;
+    while !coded.is_empty() {
+        let s: f64 = coded.iter().map(|&b| area(b)).sum();
+        let p: f64 = coded
+            .iter()
+            .map(|&b| (variances[b].sqrt() / relative[b]).powf(area(b)))
+            .product();
+        q = (2f64.powf(f64::from(bitrate) / s - 1.0) / 2.5) / p.powf(1.0 / s);
+        // Subbands whose bin would dwarf their spread are dropped
+        let before = coded.len();
+        coded.retain(|&b| relative[b] / q < 5.0 * variances[b].sqrt());
+        if coded.len() == before {
+            break;
+        }
+    }
+
+    let mut widths = [0.0f32; NUM_SUBBANDS];
+    for &band in &coded {
+        widths[band] = (relative[band] / q) as f32;
+    }
+    widths
+}
+
+fn quantize_value(value: f32, q_bin: f32, z_bin: f32) -> i32 {
+    let limit = f32::from(u16::MAX);
+    if value.abs() <= z_bin / 2.0 {
+        0
+    } else if value > 0.0 {
+        ((value - z_bin / 2.0) / q_bin + 1.0).min(limit) as i32
+    } else {
+        ((value + z_bin / 2.0) / q_bin - 1.0).max(-limit) as i32
+    }
+}
+
+// Huffman symbol with the extra bits (value, length) that follow it
+type Symbol = (u8, u32, u8);
+
+fn block_symbols(coefficients: &[i32]) -> Vec<Symbol> {
+    fn zero_run(symbols: &mut Vec<Symbol>, run: u32) {
+        match run {
+            0 => {}
+            1..=100 => symbols.push((run as u8, 0, 0)),
+            101..=255 => symbols.push((105, run, 8)),
+            _ => symbols.push((106, run, 16)),
+        }
+    }
+
+    let mut symbols = Vec::new();
+    let mut run = 0u32;
+    for &value in coefficients {
+        if value == 0 {
+            run += 1;
+            if run == u32::from(u16::MAX) {
+                zero_run(&mut symbols, run);
+                run = 0;
+            }
+            continue;
+        }
+        zero_run(&mut symbols, run);
+        run = 0;
+        let magnitude = value.unsigned_abs();
+        symbols.push(match value {
+            -73..=74 => ((value + 180) as u8, 0, 0),
+            _ if value > 0 && magnitude <= 255 => (101, magnitude, 8),
+            _ if value > 0 => (103, magnitude, 16),
+            _ if magnitude <= 255 => (102, magnitude, 8),
+            _ => (104, magnitude, 16),
+        });
+    }
+    zero_run(&mut symbols, run);
+    symbols
+}
+
+// Code lengths per symbol limited to 16 bits (JPEG annex K.2 and K.3); a
+// reserved symbol keeps any code from being all ones
+fn huffman_table(symbols: &[Symbol]) -> ([u8; 16], Vec<u8>) {
+    const RESERVED: usize = 256;
+    let mut freq = [0u64; 257];
+    for &(symbol, _, _) in symbols {
+        freq[usize::from(symbol)] += 1;
+    }
+    freq[RESERVED] = 1;
+
+    let mut code_size = [0usize; 257];
+    let mut others = [None::<usize>; 257];
+    loop {
+        let least = |exclude: Option<usize>| {
+            (0..257)
+                .filter(|&i| freq[i] > 0 && Some(i) != exclude)
+                .min_by(|&a, &b| freq[a].cmp(&freq[b]).then(b.cmp(&a)))
+        };
+        let Some(v1) = least(None) else { break };
+        let Some(v2) = least(Some(v1)) else { break };
+
+        freq[v1] += freq[v2];
+        freq[v2] = 0;
+        let mut v = v1;
+        code_size[v] += 1;
+        while let Some(next) = others[v] {
+            v = next;
+            code_size[v] += 1;
+        }
+        others[v] = Some(v2);
+        let mut v = v2;
+        code_size[v] += 1;
+        while let Some(next) = others[v] {
+            v = next;
+            code_size[v] += 1;
+        }
+    }
+
+    let mut bits = [0usize; 33];
+    for &size in code_size.iter().filter(|&&s| s > 0) {
+        bits[size] += 1;
+    }
+    for i in (17..=32).rev() {
+        while bits[i] > 0 {
+            let mut j = i - 2;
+            while bits[j] == 0 {
+                j -= 1;
+            }
+            bits[i] -= 2;
+            bits[i - 1] += 1;
+            bits[j + 1] += 2;
+            bits[j] -= 1;
+        }
+    }
+    // Drop the reserved symbol's code, the longest one
+    if let Some(longest) = (1..=16).rev().find(|&i| bits[i] > 0) {
+        bits[longest] -= 1;
+    }
+
+    let mut values: Vec<(usize, u8)> = (0..RESERVED)
+        .filter(|&s| code_size[s] > 0)
+        .map(|s| (code_size[s], s as u8))
+        .collect();
+    values.sort();
+    let mut counts = [0u8; 16];
+    for i in 0..16 {
+        counts[i] = bits[i + 1] as u8;
+    }
+    (counts, values.into_iter().map(|(_, s)| s).collect())
+}
+
+// MSB first, with a 0x00 stuffed after every 0xFF and 1-bits padding
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u8,
+    count: u8,
+}
+
+impl BitWriter {
+    fn put(&mut self, value: u32, len: u8) {
+        for i in (0..len).rev() {
+            self.acc = self.acc << 1 | ((value >> i) & 1) as u8;
+            self.count += 1;
+            if self.count == 8 {
+                self.out.push(self.acc);
+                if self.acc == 0xFF {
+                    self.out.push(0x00);
+                }
+                self.acc = 0;
+                self.count = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        while self.count != 0 {
+            self.put(1, 1);
+        }
+        self.out
+    }
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_scaled(out: &mut Vec<u8>, (scale, value): (u8, u16)) {
+    out.push(scale);
+    put_u16(out, value);
+}
+
+fn put_filters(out: &mut Vec<u8>, filters: &Filters) {
+    put_u16(out, DTT);
+    let stored = filters.lo.len() / 2 + 1 + filters.hi.len() / 2 + 1;
+    put_u16(out, (4 + stored * 6) as u16);
+    out.push(filters.hi.len() as u8);
+    out.push(filters.lo.len() as u8);
+    for filter in [&filters.lo, &filters.hi] {
+        for &coefficient in &filter[filter.len() / 2..] {
+            let mut value = f64::from(coefficient).abs();
+            let mut scale = 0u8;
+            if value > 0.0 {
+                while value < f64::from(u32::MAX) {
+                    value *= 10.0;
+                    scale += 1;
+                }
+                scale -= 1;
+                value = (value / 10.0).round();
+            }
+            out.push(u8::from(coefficient < 0.0));
+            out.push(scale);
+            out.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+    }
+}
+
+fn put_huffman_table(out: &mut Vec<u8>, id: u8, bits: &[u8; 16], values: &[u8]) {
+    put_u16(out, DHT);
+    put_u16(out, (3 + 16 + values.len()) as u16);
+    out.push(id);
+    out.extend_from_slice(bits);
+    out.extend_from_slice(values);
+}
+
+// Compresses an 8-bit grayscale image at about `bitrate` bits per pixel
+pub fn encode(image: &GrayImage, bitrate: f32) -> Result<Vec<u8>, String> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    if width == 0 || height == 0 || width > usize::from(u16::MAX) || height > usize::from(u16::MAX) {
+        return Err(format!("Dimensões {}x{} não suportadas em WSQ.", width, height));
+    }
+    if !bitrate.is_finite() || bitrate <= 0.0 {
+        return Err(format!("Taxa de bits WSQ inválida: {}", bitrate));
+    }
+
+    // Centre on the mean and scale the largest excursion to ±128
+    let pixels = image.as_raw();
+    let mean = pixels.iter().map(|&p| f64::from(p)).sum::<f64>() / pixels.len() as f64;
+    let (min, max) = pixels
+        .iter()
+        .fold((u8::MAX, u8::MIN), |(lo, hi), &p| (lo.min(p), hi.max(p)));
+    let excursion = (mean - f64::from(min)).max(f64::from(max) - mean);
+    let m_shift = to_scaled(mean as f32);
+    let r_scale = to_scaled(if excursion > 0.0 { (excursion / 128.0) as f32 } else { 1.0 });
+    let (shift, scale) = (from_scaled(m_shift), from_scaled(r_scale));
+    let mut data: Vec<f32> = pixels.iter().map(|&p| (f32::from(p) - shift) / scale).collect();
+
+    let filters = Filters::default();
+    let w_tree = build_w_tree(width, height);
+    let q_tree = build_q_tree(&w_tree);
+    for node in &w_tree {
+        split_band(&mut data, width, node, &filters);
+    }
+
+    let widths = bin_widths(&variances(&data, width, &q_tree), bitrate);
+    let mut q_bins = [(0u8, 0u16); MAX_SUBBANDS];
+    let mut z_bins = [(0u8, 0u16); MAX_SUBBANDS];
+    for band in 0..NUM_SUBBANDS {
+        // Bins so fine that coefficients would overflow 16 bits are widened
+        let region = q_tree[band];
+        let peak = (0..region.leny)
+            .flat_map(|row| &data[(region.y + row) * width + region.x..][..region.lenx])
+            .fold(0.0f32, |peak, v| peak.max(v.abs()));
+        let width_floor = peak / f32::from(i16::MAX);
+        q_bins[band] = to_scaled(if widths[band] > 0.0 { widths[band].max(width_floor) } else { 0.0 });
+        z_bins[band] = to_scaled(1.2 * from_scaled(q_bins[band]));
+    }
+
+    // Quantized coefficients of each block, subband after subband
+    let mut blocks: Vec<(u8, Vec<Symbol>)> = Vec::new();
+    for (first, last, table) in BLOCKS {
+        let mut coefficients = Vec::new();
+        for band in first..last {
+            let (q_bin, z_bin) = (from_scaled(q_bins[band]), from_scaled(z_bins[band]));
+            if q_bin == 0.0 {
+                continue;
+            }
+            let region = q_tree[band];
+            for row in 0..region.leny {
+                let start = (region.y + row) * width + region.x;
+                coefficients.extend(data[start..start + region.lenx].iter().map(|&v| quantize_value(v, q_bin, z_bin)));
+            }
+        }
+        if !coefficients.is_empty() {
+            blocks.push((table, block_symbols(&coefficients)));
+        }
+    }
+
+    let mut out = Vec::new();
+    put_u16(&mut out, SOI);
+    put_filters(&mut out, &filters);
+
+    put_u16(&mut out, DQT);
+    put_u16(&mut out, (2 + 3 + MAX_SUBBANDS * 6) as u16);
+    put_scaled(&mut out, BIN_CENTER);
+    for band in 0..MAX_SUBBANDS {
+        put_scaled(&mut out, q_bins[band]);
+        put_scaled(&mut out, z_bins[band]);
+    }
+
+    put_u16(&mut out, SOF);
+    put_u16(&mut out, 17);
+    out.extend_from_slice(&[0, 255]);
+    put_u16(&mut out, height as u16);
+    put_u16(&mut out, width as u16);
+    put_scaled(&mut out, m_shift);
+    put_scaled(&mut out, r_scale);
+    // Encoder number and software implementation
+    out.push(2);
+    put_u16(&mut out, 0);
+
+    let mut current: Option<(u8, [(u32, u8); 256])> = None;
+    for (table, symbols) in &blocks {
+        if current.as_ref().map(|(id, _)| id) != Some(table) {
+            // Blocks sharing a table get one built from all their symbols
+            let shared: Vec<Symbol> = blocks
+                .iter()
+                .filter(|(t, _)| t == table)
+                .flat_map(|(_, s)| s.iter().copied())
+                .collect();
+            let (bits, values) = huffman_table(&shared);
+            put_huffman_table(&mut out, *table, &bits, &values);
+            let mut codes = [(0u32, 0u8); 256];
+            for (code, &symbol) in huffman_codes(&bits).into_iter().zip(&values) {
+                codes[usize::from(symbol)] = code;
+            }
+            current = Some((*table, codes));
+        }
+        let Some((_, codes)) = current.as_ref() else { continue };
+
+        put_u16(&mut out, SOB);
+        put_u16(&mut out, 3);
+        out.push(*table);
+        let mut writer = BitWriter {
+            out: Vec::new(),
+            acc: 0,
+            count: 0,
+        };
+        for &(symbol, extra, extra_len) in symbols {
+            let (code, len) = codes[usize::from(symbol)];
+            writer.put(code, len);
+            writer.put(extra, extra_len);
+        }
+        out.extend(writer.finish());
+    }
+    put_u16(&mut out, EOI);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fingerprint-like ridges: a sine pattern whose direction turns across the image
+    fn ridges(width: u32, height: u32) -> GrayImage {
+        GrayImage::from_fn(width, height, |x, y| {
+            let (x, y) = (x as f32, y as f32);
+            let angle = (x + y) / (width + height) as f32 * std::f32::consts::PI;
+            let t = x * angle.cos() + y * angle.sin();
+            image::Luma([(128.0 + 100.0 * (t / 3.0).sin()) as u8])
+        })
+    }
+
+    fn mean_abs_error(a: &GrayImage, b: &GrayImage) -> f64 {
+        let total: u64 = a.as_raw().iter().zip(b.as_raw()).map(|(&p, &q)| u64::from(p.abs_diff(q))).sum();
+        total as f64 / a.as_raw().len() as f64
+    }
+
+    #[test]
+    fn round_trip_keeps_the_image() {
+        for (width, height) in [(256, 256), (300, 411), (97, 64)] {
+            let original = ridges(width, height);
+            let wsq = encode(&original, DEFAULT_BITRATE).unwrap();
+            assert!(wsq.len() < original.as_raw().len() / 4, "{}x{} not compressed", width, height);
+
+            let decoded = decode(&wsq).unwrap();
+            assert_eq!(decoded.dimensions(), (width, height));
+            let error = mean_abs_error(&original, &decoded);
+            assert!(error < 12.0, "{}x{}: mean error {}", width, height, error);
+        }
+    }
+
+    #[test]
+    fn higher_bitrate_is_more_faithful() {
+        let original = ridges(256, 256);
+        let error = |bitrate| mean_abs_error(&original, &decode(&encode(&original, bitrate).unwrap()).unwrap());
+        assert!(error(2.25) < error(0.5));
+    }
+
+    #[test]
+    fn flat_image() {
+        let original = GrayImage::from_pixel(128, 128, image::Luma([200]));
+        let decoded = decode(&encode(&original, DEFAULT_BITRATE).unwrap()).unwrap();
+        assert!(mean_abs_error(&original, &decoded) < 2.0);
+    }
+
+    // Written once by `encode` from `ridges(256, 256)`, so a change to the
+    // encoder doesn't hide a change to the decoder
+    #[test]
+    fn decodes_fixture() {
+        let decoded = decode(include_bytes!("../tests/fixtures/ridges_256.wsq")).unwrap();
+        assert_eq!(decoded.dimensions(), (256, 256));
+        assert!(mean_abs_error(&ridges(256, 256), &decoded) < 12.0);
+    }
+
+    #[test]
+    fn rejects_oversized_frame() {
+        let mut wsq = Vec::new();
+        put_u16(&mut wsq, SOI);
+        put_u16(&mut wsq, SOF);
+        put_u16(&mut wsq, 17);
+        wsq.extend_from_slice(&[0, 255]);
+        put_u16(&mut wsq, u16::MAX);
+        put_u16(&mut wsq, u16::MAX);
+        wsq.extend_from_slice(&[0; 9]);
+        assert!(decode(&wsq).unwrap_err().contains("grande demais"));
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let wsq = encode(&ridges(64, 64), DEFAULT_BITRATE).unwrap();
+        assert!(decode(&wsq[..wsq.len() / 2]).is_err());
+        assert!(decode(b"not a wsq").is_err());
+    }
+}
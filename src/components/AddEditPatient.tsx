@@ -1,5 +1,6 @@
-import { useState } from "react";
-import { Patient, DigitalBiometric } from "../types/patient";
+import { useEffect, useState } from "react";
+import { Patient, DigitalBiometric, Scenario } from "../types/patient";
+import { getPatientPhoto } from "../services/patientsService";
 import BiometricUploader from "./BiometricUploader";
 
 interface AddEditPatientProps {
@@ -12,12 +13,27 @@ export default function AddEditPatient({ patient, onSave, onCancel }: AddEditPat
   const [name, setName] = useState(patient?.name || "");
   const [wallet, setWallet] = useState(patient?.wallet || "");
   const [facialBiometric, setFacialBiometric] = useState(patient?.facialBiometric || "");
+  // patients from the list only carry a thumbnail; the photo is loaded here
+  const [photoLoaded, setPhotoLoaded] = useState(!patient?.facialThumbnail || !!patient?.facialBiometric);
   const [digitalBiometrics, setDigitalBiometrics] = useState<DigitalBiometric[]>(
     patient?.digitalBiometrics || []
   );
-  const [activeTab, setActiveTab] = useState<"facial" | "digital">("facial");
+  const [notes, setNotes] = useState(patient?.notes || "");
+  const [scenario, setScenario] = useState<Scenario>(patient?.scenario || {});
+  const [graceProcedures, setGraceProcedures] = useState((patient?.scenario?.grace_procedures ?? []).join(", "));
+  const [activeTab, setActiveTab] = useState<"facial" | "digital" | "scenario">("facial");
   const [errors, setErrors] = useState<{name?: string; wallet?: string}>({});
 
+  useEffect(() => {
+    if (photoLoaded || !patient) return;
+    getPatientPhoto(patient.id)
+      .then((photo) => {
+        setFacialBiometric(photo);
+        setPhotoLoaded(true);
+      })
+      .catch((err) => console.error("Erro ao carregar foto:", err));
+  }, []);
+
   const validateForm = (): boolean => {
     const newErrors: {name?: string; wallet?: string} = {};
     
@@ -37,11 +53,24 @@ export default function AddEditPatient({ patient, onSave, onCancel }: AddEditPat
     if (!validateForm()) return;
     
     onSave({
+      // keeps tags, attachments and the other photos, which aren't edited here
+      ...patient,
+      // a cleared photo removes the frontal one the list had
+      facialPhotos: facialBiometric || !photoLoaded
+        ? patient?.facialPhotos
+        : patient?.facialPhotos?.filter((f) => f.label !== "frontal"),
       id: patient?.id || 0,
       name: name.trim(),
       wallet: wallet.trim(),
       facialBiometric,
       digitalBiometrics,
+      notes,
+      scenario: {
+        ...scenario,
+        grace_procedures: scenario.grace_period
+          ? graceProcedures.split(",").map((p) => p.trim()).filter(Boolean)
+          : [],
+      },
       imported: patient?.imported || false
     });
   };
@@ -165,6 +194,23 @@ export default function AddEditPatient({ patient, onSave, onCancel }: AddEditPat
           >
             Biometrias Digitais
           </div>
+          <div 
+            className={`tab ${activeTab === "scenario" ? "active" : ""}`}
+            onClick={() => setActiveTab("scenario")}
+            style={{ 
+              flex: 1,
+              padding: "12px 16px", 
+              cursor: "pointer",
+              backgroundColor: activeTab === "scenario" ? "var(--color-primary)" : "transparent",
+              color: activeTab === "scenario" ? "#000" : "var(--text-secondary)",
+              fontWeight: activeTab === "scenario" ? "600" : "normal",
+              borderRadius: "6px",
+              textAlign: "center",
+              transition: "all 0.2s ease"
+            }}
+          >
+            Cenário de Teste
+          </div>
         </div>
         
         {/* Tab Content */}
@@ -257,6 +303,93 @@ export default function AddEditPatient({ patient, onSave, onCancel }: AddEditPat
               </div>
             </div>
           )}
+
+          {activeTab === "scenario" && (
+            <div className="scenario-tab">
+              <div className="form-group" style={{ marginBottom: "16px" }}>
+                <label htmlFor="expected-checkin" className="form-label">
+                  Resultado esperado do check-in:
+                </label>
+                <select
+                  id="expected-checkin"
+                  className="form-input"
+                  value={scenario.expected_checkin ?? ""}
+                  onChange={(e) => setScenario({ ...scenario, expected_checkin: (e.target.value || null) as Scenario["expected_checkin"] })}
+                >
+                  <option value="">Não definido</option>
+                  <option value="approved">Aprovado</option>
+                  <option value="denied">Negado</option>
+                  <option value="pending_authorization">Pendente de autorização</option>
+                </select>
+              </div>
+
+              <div className="form-group" style={{ marginBottom: "16px" }}>
+                <label htmlFor="plan-status" className="form-label">
+                  Situação do plano:
+                </label>
+                <select
+                  id="plan-status"
+                  className="form-input"
+                  value={scenario.plan_status ?? ""}
+                  onChange={(e) => setScenario({ ...scenario, plan_status: (e.target.value || null) as Scenario["plan_status"] })}
+                >
+                  <option value="">Não definida</option>
+                  <option value="active">Ativo</option>
+                  <option value="suspended">Suspenso</option>
+                  <option value="cancelled">Cancelado</option>
+                  <option value="defaulting">Inadimplente</option>
+                </select>
+              </div>
+
+              <div className="form-group" style={{ display: "flex", gap: "24px", marginBottom: "16px" }}>
+                <label>
+                  <input
+                    type="checkbox"
+                    checked={!!scenario.grace_period}
+                    onChange={(e) => setScenario({ ...scenario, grace_period: e.target.checked })}
+                  />{" "}
+                  Em carência
+                </label>
+                <label>
+                  <input
+                    type="checkbox"
+                    checked={!!scenario.partial_coverage}
+                    onChange={(e) => setScenario({ ...scenario, partial_coverage: e.target.checked })}
+                  />{" "}
+                  Cobertura parcial temporária (CPT)
+                </label>
+              </div>
+
+              {scenario.grace_period && (
+                <div className="form-group" style={{ marginBottom: "16px" }}>
+                  <label htmlFor="grace-procedures" className="form-label">
+                    Procedimentos em carência (separados por vírgula):
+                  </label>
+                  <input
+                    id="grace-procedures"
+                    type="text"
+                    className="form-input"
+                    value={graceProcedures}
+                    onChange={(e) => setGraceProcedures(e.target.value)}
+                  />
+                </div>
+              )}
+
+              <div className="form-group">
+                <label htmlFor="notes" className="form-label">
+                  Observações:
+                </label>
+                <textarea
+                  id="notes"
+                  className="form-input"
+                  rows={5}
+                  value={notes}
+                  onChange={(e) => setNotes(e.target.value)}
+                  placeholder="O que este paciente de teste deve demonstrar"
+                />
+              </div>
+            </div>
+          )}
         </div>
         
         <div className="dialog-actions" style={{ display: "flex", justifyContent: "flex-end", gap: "12px", marginTop: "16px" }}>
@@ -1,17 +1,29 @@
 import { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 
-interface AppConfig {
+// TOTVS connection settings, kept under `importer_config`
+interface ImporterConfig {
   base_url?: string;
   user?: string;
   password?: string;
   clinic?: string;
   provider_code?: string;
   health_insurer_code?: string;
-  server_host?: string;
-  server_port?: number;
   portal_user?: string;
   portal_password?: string;
+  proxy_host?: string;
+  proxy_port?: number;
+  proxy_user?: string;
+  proxy_password?: string;
+}
+
+interface AppConfig {
+  importer_config?: ImporterConfig;
+  server_host?: string;
+  server_port?: number;
+  // Other settings are saved back untouched
+  [key: string]: unknown;
 }
 
 export default function AppSettings() {
@@ -19,9 +31,22 @@ export default function AppSettings() {
   const [loading, setLoading] = useState(true);
   const [saving, setSaving] = useState(false);
   const [message, setMessage] = useState<{ text: string; type: "success" | "error" } | null>(null);
+  const importer = config.importer_config ?? {};
+
+  function setImporter(changes: Partial<ImporterConfig>) {
+    setConfig({ ...config, importer_config: { ...importer, ...changes } });
+  }
 
   useEffect(() => {
     loadConfig();
+    // app_config.json changed outside the app (another instance or an editor)
+    const unlisten = listen("config-changed", async () => {
+      await loadConfig();
+      setMessage({ text: "Configurações alteradas fora do aplicativo; recarregadas.", type: "success" });
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
   }, []);
 
   async function loadConfig() {
@@ -89,8 +114,8 @@ export default function AppSettings() {
               <input
                 type="text"
                 className="form-input"
-                value={config.base_url || ""}
-                onChange={(e) => setConfig({ ...config, base_url: e.target.value })}
+                value={importer.base_url || ""}
+                onChange={(e) => setImporter({ base_url: e.target.value })}
                 placeholder="http://es-datasul.sp01.local:8880"
               />
             </div>
@@ -101,8 +126,8 @@ export default function AppSettings() {
                 <input
                   type="text"
                   className="form-input"
-                  value={config.user || ""}
-                  onChange={(e) => setConfig({ ...config, user: e.target.value })}
+                  value={importer.user || ""}
+                  onChange={(e) => setImporter({ user: e.target.value })}
                   placeholder="lucas.hsilva"
                 />
               </div>
@@ -111,8 +136,8 @@ export default function AppSettings() {
                 <input
                   type="password"
                   className="form-input"
-                  value={config.password || ""}
-                  onChange={(e) => setConfig({ ...config, password: e.target.value })}
+                  value={importer.password || ""}
+                  onChange={(e) => setImporter({ password: e.target.value })}
                   placeholder="totvs@123@"
                 />
               </div>
@@ -126,8 +151,8 @@ export default function AppSettings() {
                 <input
                   type="text"
                   className="form-input"
-                  value={config.portal_user || ""}
-                  onChange={(e) => setConfig({ ...config, portal_user: e.target.value })}
+                  value={importer.portal_user || ""}
+                  onChange={(e) => setImporter({ portal_user: e.target.value })}
                   placeholder="usuário do portal"
                 />
               </div>
@@ -136,8 +161,8 @@ export default function AppSettings() {
                 <input
                   type="password"
                   className="form-input"
-                  value={config.portal_password || ""}
-                  onChange={(e) => setConfig({ ...config, portal_password: e.target.value })}
+                  value={importer.portal_password || ""}
+                  onChange={(e) => setImporter({ portal_password: e.target.value })}
                   placeholder="senha do portal"
                 />
               </div>
@@ -149,8 +174,8 @@ export default function AppSettings() {
                 <input
                   type="text"
                   className="form-input"
-                  value={config.clinic || ""}
-                  onChange={(e) => setConfig({ ...config, clinic: e.target.value })}
+                  value={importer.clinic || ""}
+                  onChange={(e) => setImporter({ clinic: e.target.value })}
                   placeholder="Código da clínica"
                 />
               </div>
@@ -159,8 +184,8 @@ export default function AppSettings() {
                 <input
                   type="text"
                   className="form-input"
-                  value={config.provider_code || ""}
-                  onChange={(e) => setConfig({ ...config, provider_code: e.target.value })}
+                  value={importer.provider_code || ""}
+                  onChange={(e) => setImporter({ provider_code: e.target.value })}
                   placeholder="Código do prestador"
                 />
               </div>
@@ -171,14 +196,68 @@ export default function AppSettings() {
               <input
                 type="text"
                 className="form-input"
-                value={config.health_insurer_code || ""}
-                onChange={(e) => setConfig({ ...config, health_insurer_code: e.target.value })}
+                value={importer.health_insurer_code || ""}
+                onChange={(e) => setImporter({ health_insurer_code: e.target.value })}
                 placeholder="Código da unidade"
               />
             </div>
           </div>
         </div>
 
+        {/* Proxy Configuration */}
+        <div className="config-section">
+          <h2 className="text-subtitle" style={{ margin: "0 0 20px 0" }}>Proxy (opcional)</h2>
+          <div style={{ display: "grid", gap: "20px" }}>
+            <div style={{ display: "grid", gridTemplateColumns: "1fr 1fr", gap: "20px" }}>
+              <div>
+                <label className="form-label">Host do Proxy</label>
+                <input
+                  type="text"
+                  className="form-input"
+                  value={importer.proxy_host || ""}
+                  onChange={(e) => setImporter({ proxy_host: e.target.value })}
+                  placeholder="proxy.empresa.local"
+                />
+              </div>
+              <div>
+                <label className="form-label">Porta do Proxy</label>
+                <input
+                  type="number"
+                  className="form-input"
+                  value={importer.proxy_port || ""}
+                  onChange={(e) => setImporter({ proxy_port: parseInt(e.target.value) || undefined })}
+                  placeholder="3128"
+                  min="1"
+                  max="65535"
+                />
+              </div>
+            </div>
+
+            <div style={{ display: "grid", gridTemplateColumns: "1fr 1fr", gap: "20px" }}>
+              <div>
+                <label className="form-label">Usuário do Proxy</label>
+                <input
+                  type="text"
+                  className="form-input"
+                  value={importer.proxy_user || ""}
+                  onChange={(e) => setImporter({ proxy_user: e.target.value })}
+                  placeholder="usuário"
+                />
+              </div>
+              <div>
+                <label className="form-label">Senha do Proxy</label>
+                <input
+                  type="password"
+                  className="form-input"
+                  value={importer.proxy_password || ""}
+                  onChange={(e) => setImporter({ proxy_password: e.target.value })}
+                  placeholder="senha"
+                />
+              </div>
+            </div>
+          </div>
+        </div>
+
         {/* Local Server Configuration */}
         <div className="config-section">
           <h2 className="text-subtitle" style={{ margin: "0 0 20px 0" }}>Servidor Local de Biometria</h2>
@@ -1,5 +1,6 @@
 import { useEffect, useState } from "react";
-import { startHotkey, stopHotkey, checkHotkeyStatus, diagnoseHotkeySystem } from "../services/hotkeyService";
+import { listen } from "@tauri-apps/api/event";
+import { startHotkey, stopHotkey, checkHotkeyStatus, diagnoseHotkeySystem, checkInputPermission, HotkeyDied } from "../services/hotkeyService";
 import { Patient } from "../types/patient";
 
 interface HotkeyManagerProps {
@@ -36,6 +37,19 @@ export default function HotkeyManager({ patients }: HotkeyManagerProps) {
     checkStatus();
   }, []);
 
+  // The AutoHotkey process died (crash, antivirus); it may have been restarted
+  useEffect(() => {
+    const unlisten = listen<HotkeyDied>("hotkey-died", ({ payload }) => {
+      setIsHotkeyActive(payload.restarted);
+      setStatusMessage(payload.restarted
+        ? { text: `Hotkey Ctrl+Q: AutoHotkey reiniciado após encerramento inesperado (${payload.status})`, isError: false }
+        : { text: `Hotkey Ctrl+Q: AutoHotkey encerrado inesperadamente (${payload.status})${payload.restart_error ? ` - ${payload.restart_error}` : ""}`, isError: true });
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
   const handlePatientSelect = (e: React.ChangeEvent<HTMLSelectElement>) => {
     const value = e.target.value;
     setSelectedPatientId(value ? parseInt(value, 10) : null);
@@ -60,9 +74,9 @@ export default function HotkeyManager({ patients }: HotkeyManagerProps) {
           return;
         }
 
-        // Show installation message if needed
+        // AutoHotkey may need to be installed when it's the configured backend
         setStatusMessage({
-          text: "Verificando AutoHotkey V2...",
+          text: "Ativando hotkey...",
           isError: false
         });
 
@@ -210,7 +224,21 @@ export default function HotkeyManager({ patients }: HotkeyManagerProps) {
           </h3>
           
           {/* AutoHotkey Status Summary */}
-          {diagnosticInfo.autohotkey_paths && (
+          {diagnosticInfo.keystroke_backend === "native" && (
+            <div style={{ color: "var(--color-success)", fontWeight: "bold", marginBottom: 16 }}>
+              ✅ Digitação nativa em uso - o AutoHotkey não é necessário
+            </div>
+          )}
+
+          {diagnosticInfo.accessibility_trusted === false && (
+            <div style={{ color: "var(--color-warning)", fontWeight: "bold", marginBottom: 16 }}>
+              ⚠️ O macOS ainda não permite que o aplicativo digite. Autorize-o em Ajustes do Sistema &gt; Privacidade e
+              Segurança &gt; Acessibilidade.{" "}
+              <button onClick={() => checkInputPermission(true)}>Solicitar permissão</button>
+            </div>
+          )}
+
+          {diagnosticInfo.keystroke_backend === "autohotkey" && diagnosticInfo.autohotkey_paths && (
             <div style={{ 
               backgroundColor: "var(--bg-main-alt)", 
               padding: 12, 
@@ -299,7 +327,8 @@ export default function HotkeyManager({ patients }: HotkeyManagerProps) {
           do paciente em sistemas que suportam leitura de cartões.
         </p>
         <p style={{ marginBottom: 0 }}>
-          <strong>Nota:</strong> Requer que o AutoHotkey V2 esteja instalado e acessível pelo aplicativo.
+          <strong>Nota:</strong> O texto é digitado pelo próprio aplicativo. O AutoHotkey V2 só é usado
+          quando <code>keystroke_backend</code> é <code>autohotkey</code> nas configurações.
         </p>
         <p style={{ 
           marginTop: 12, 
@@ -310,7 +339,7 @@ export default function HotkeyManager({ patients }: HotkeyManagerProps) {
           border: "1px solid var(--color-info-border)",
           color: "var(--color-info-text)"
         }}>
-          <strong>💡 Instalação Automática:</strong> Se o AutoHotkey V2 não estiver instalado, 
+          <strong>💡 Instalação Automática:</strong> Com o AutoHotkey configurado, se ele não estiver instalado, 
           o sistema baixará e instalará automaticamente o instalador oficial do site da AutoHotkey. 
           Isso pode levar alguns minutos dependendo da sua conexão com a internet.
         </p>
@@ -1,5 +1,6 @@
 import { useEffect, useState } from "react";
-import { loadPatients, savePatients } from "../services/patientsService";
+import { listen } from "@tauri-apps/api/event";
+import { addPatient, deletePatient, loadPatients, updatePatient } from "../services/patientsService";
 import { Patient } from "../types/patient";
 import AddEditPatient from "./AddEditPatient";
 import PatientImporter from "./PatientImporter";
@@ -32,28 +33,33 @@ export default function PatientManager({ onPatientsChanged }: PatientManagerProp
       }
     }
     init();
+    // patients.db changed outside the app (another instance or tool)
+    const unlisten = listen("patients-changed", async () => {
+      try {
+        const data = await loadPatients();
+        if (isMounted) setPatients(data);
+      } catch (err: any) {
+        if (isMounted) setError(err.message ?? "Erro ao carregar pacientes");
+      }
+    });
     return () => {
       isMounted = false;
+      unlisten.then((stop) => stop());
     };
   }, []);
 
+  // Replaces the patient in the list with the copy the backend stored
+  function withStored(list: Patient[], stored: Patient): Patient[] {
+    return list.some(p => p.id === stored.id)
+      ? list.map(p => (p.id === stored.id ? stored : p))
+      : [...list, stored];
+  }
+
   async function handleSavePatient(patient: Patient) {
     try {
-      let updatedPatients: Patient[];
-      
-      if (patient.id === 0) {
-        // New patient
-        const maxId = patients.reduce((max, p) => Math.max(max, p.id), 0);
-        const newPatient = { ...patient, id: maxId + 1 };
-        updatedPatients = [...patients, newPatient];
-      } else {
-        // Edit existing patient
-        updatedPatients = patients.map(p => 
-          p.id === patient.id ? patient : p
-        );
-      }
-      
-      await savePatients(updatedPatients);
+      // The backend assigns new ids and refuses edits of an outdated copy
+      const stored = patient.id === 0 ? await addPatient(patient) : await updatePatient(patient);
+      const updatedPatients = withStored(patients, stored);
       setPatients(updatedPatients);
       onPatientsChanged?.(updatedPatients);
       setIsAddEditOpen(false);
@@ -65,11 +71,8 @@ export default function PatientManager({ onPatientsChanged }: PatientManagerProp
 
   async function handleImportPatient(patient: Patient) {
     try {
-      const maxId = patients.reduce((max, p) => Math.max(max, p.id), 0);
-      const newPatient = { ...patient, id: maxId + 1 };
-      const updatedPatients = [...patients, newPatient];
-      
-      await savePatients(updatedPatients);
+      const stored = await addPatient(patient);
+      const updatedPatients = withStored(patients, stored);
       setPatients(updatedPatients);
       onPatientsChanged?.(updatedPatients);
       setIsImportOpen(false);
@@ -84,8 +87,8 @@ export default function PatientManager({ onPatientsChanged }: PatientManagerProp
     }
 
     try {
+      await deletePatient(patient.id, patient.version);
       const updatedPatients = patients.filter(p => p.id !== patient.id);
-      await savePatients(updatedPatients);
       setPatients(updatedPatients);
       onPatientsChanged?.(updatedPatients);
       setSelectedPatient(null);
@@ -106,11 +109,8 @@ export default function PatientManager({ onPatientsChanged }: PatientManagerProp
       
       if (result.success && result.updatedPatient) {
         // Atualizar dados do paciente
-        const updatedPatients = patients.map(p => 
-          p.id === patient.id ? result.updatedPatient! : p
-        );
-        
-        await savePatients(updatedPatients);
+        const stored = await updatePatient(result.updatedPatient);
+        const updatedPatients = withStored(patients, stored);
         setPatients(updatedPatients);
         onPatientsChanged?.(updatedPatients);
         alert(result.message);
@@ -142,20 +142,22 @@ export default function PatientManager({ onPatientsChanged }: PatientManagerProp
       // Processar resultados e atualizar pacientes
       let updatedCount = 0;
       let errorCount = 0;
+      let updatedPatients = patients;
       
       for (const result of results) {
         if (result.success && result.updatedPatient) {
-          const updatedPatients = patients.map(p => 
-            p.id === result.updatedPatient!.id ? result.updatedPatient! : p
-          );
-          await savePatients(updatedPatients);
-          setPatients(updatedPatients);
-          onPatientsChanged?.(updatedPatients);
-          updatedCount++;
+          try {
+            updatedPatients = withStored(updatedPatients, await updatePatient(result.updatedPatient));
+            updatedCount++;
+          } catch {
+            errorCount++;
+          }
         } else {
           errorCount++;
         }
       }
+      setPatients(updatedPatients);
+      onPatientsChanged?.(updatedPatients);
       
       const message = `Sincronização concluída.\n\nPacientes atualizados: ${updatedCount}\nFalhas: ${errorCount}`;
       alert(message);
@@ -239,7 +241,7 @@ export default function PatientManager({ onPatientsChanged }: PatientManagerProp
                     <td style={{ textAlign: "left" }}>{p.name}</td>
                     <td>{p.wallet}</td>
                     <td>{p.digitalBiometrics.length ? "Sim" : "Não"}</td>
-                    <td>{p.facialBiometric ? "Sim" : "Não"}</td>
+                    <td>{p.facialBiometric || p.facialThumbnail ? "Sim" : "Não"}</td>
                     <td>{p.imported ? "Importado" : "Local"}</td>
                     <td>
                       <div style={{ display: "flex", gap: "8px", justifyContent: "center" }}>
@@ -3,9 +3,17 @@ import {
   startWebcamEmulator, 
   stopWebcamEmulator, 
   checkWebcamEmulatorStatus,
+  startWebcamPatientPhoto,
   WebcamSourceType 
 } from "../services/webcamEmulatorService";
-import { Patient } from "../types/patient";
+import { FaceAngle, Patient } from "../types/patient";
+
+const FACE_ANGLE_LABELS: Record<FaceAngle, string> = {
+  frontal: "Frontal",
+  left: "Esquerda",
+  right: "Direita",
+  "with-mask": "Com máscara",
+};
 
 interface WebcamEmulatorManagerProps {
   patients: Patient[];
@@ -15,6 +23,7 @@ export default function WebcamEmulatorManager({ patients }: WebcamEmulatorManage
   const [isEmulatorActive, setIsEmulatorActive] = useState(false);
   const [sourceType, setSourceType] = useState<WebcamSourceType>("image");
   const [selectedPatientId, setSelectedPatientId] = useState<number | null>(null);
+  const [faceAngle, setFaceAngle] = useState<FaceAngle>("frontal");
   const [videoFilePath, setVideoFilePath] = useState("");
   const [cameraIndex, setCameraIndex] = useState<number>(0);
   const [availableCameras, setAvailableCameras] = useState<string[]>([]);
@@ -55,6 +64,7 @@ export default function WebcamEmulatorManager({ patients }: WebcamEmulatorManage
   const handlePatientSelect = (e: React.ChangeEvent<HTMLSelectElement>) => {
     const value = e.target.value;
     setSelectedPatientId(value ? parseInt(value, 10) : null);
+    setFaceAngle("frontal");
     setSourceType("image");
   };
 
@@ -99,7 +109,7 @@ export default function WebcamEmulatorManager({ patients }: WebcamEmulatorManage
             }
             
             const patient = patients.find(p => p.id === selectedPatientId);
-            if (!patient || !patient.facialBiometric) {
+            if (!patient || !(patient.facialPhotos ?? []).some(f => f.label === faceAngle)) {
               setStatusMessage({
                 text: "Erro: O paciente selecionado não possui biometria facial",
                 isError: true
@@ -107,7 +117,6 @@ export default function WebcamEmulatorManager({ patients }: WebcamEmulatorManage
               return;
             }
             
-            sourceData = patient.facialBiometric;
             break;
             
           case "video":
@@ -127,7 +136,12 @@ export default function WebcamEmulatorManager({ patients }: WebcamEmulatorManage
             break;
         }
         
-        await startWebcamEmulator(sourceType, sourceData);
+        if (sourceType === "image") {
+          // the backend reads the photo, so it's the stored one
+          await startWebcamPatientPhoto(selectedPatientId!, faceAngle);
+        } else {
+          await startWebcamEmulator(sourceType, sourceData);
+        }
         setIsEmulatorActive(true);
         
         let sourceName = "";
@@ -206,7 +220,7 @@ export default function WebcamEmulatorManager({ patients }: WebcamEmulatorManage
                   >
                     <option value="">-- Selecione um paciente --</option>
                     {patients
-                      .filter(p => p.facialBiometric)
+                      .filter(p => (p.facialPhotos ?? []).length > 0)
                       .map(patient => (
                         <option key={patient.id} value={patient.id}>
                           {patient.name}
@@ -214,6 +228,27 @@ export default function WebcamEmulatorManager({ patients }: WebcamEmulatorManage
                       ))
                     }
                   </select>
+                  {selectedPatientId && (
+                    <select
+                      value={faceAngle}
+                      onChange={(e) => setFaceAngle(e.target.value as FaceAngle)}
+                      style={{
+                        width: "100%",
+                        marginTop: 8,
+                        padding: "8px 12px",
+                        backgroundColor: "var(--bg-main-alt)",
+                        color: "var(--text-primary)",
+                        border: "1px solid var(--bg-main)",
+                        borderRadius: 4
+                      }}
+                    >
+                      {(patients.find(p => p.id === selectedPatientId)?.facialPhotos ?? []).map(photo => (
+                        <option key={photo.label} value={photo.label}>
+                          {FACE_ANGLE_LABELS[photo.label]}
+                        </option>
+                      ))}
+                    </select>
+                  )}
                 </div>
               )}
             </div>
@@ -27,6 +27,13 @@ export async function stopHotkey(): Promise<boolean> {
   }
 }
 
+/** Payload of the "hotkey-died" event */
+export interface HotkeyDied {
+  status: string;
+  restarted: boolean;
+  restart_error?: string | null;
+}
+
 /**
  * Checks if the hotkey is currently active
  * @returns Promise resolving to true if hotkey is active
@@ -51,4 +58,191 @@ export async function diagnoseHotkeySystem(): Promise<any> {
     console.error("Failed to run hotkey diagnostics:", error);
     throw error;
   }
-}
\ No newline at end of file
+}
+/**
+ * Checks whether the app may simulate typing (the Accessibility permission
+ * on macOS; always true elsewhere)
+ * @param prompt Shows the macOS dialog leading to the settings when missing
+ */
+export async function checkInputPermission(prompt = false): Promise<boolean> {
+  try {
+    return await invoke("check_input_permission", { prompt });
+  } catch (error) {
+    console.error("Failed to check input permission:", error);
+    return false;
+  }
+}
+
+export interface HotkeyBinding {
+  hotkey: string; // AutoHotkey syntax, e.g. "^1" for Ctrl+1
+  patient_id?: number | null;
+  wallet: string;
+  text: string; // as typed, with the card format applied
+  mode: string;
+}
+
+/**
+ * Binds a hotkey to a patient's card (or to a card text), keeping the others
+ * @returns Promise resolving to every binding
+ */
+export async function registerHotkeyBinding(
+  hotkey: string,
+  target: { patientId: number } | { cardText: string },
+): Promise<HotkeyBinding[]> {
+  try {
+    return await invoke("register_hotkey_binding", { hotkey, ...target });
+  } catch (error) {
+    console.error("Failed to register hotkey binding:", error);
+    throw error;
+  }
+}
+
+export async function listHotkeyBindings(): Promise<HotkeyBinding[]> {
+  try {
+    return await invoke("list_hotkey_bindings");
+  } catch (error) {
+    console.error("Failed to list hotkey bindings:", error);
+    return [];
+  }
+}
+
+/**
+ * @returns Promise resolving to false if the hotkey wasn't bound
+ */
+export async function unregisterHotkeyBinding(hotkey: string): Promise<boolean> {
+  try {
+    return await invoke("unregister_hotkey_binding", { hotkey });
+  } catch (error) {
+    console.error("Failed to unregister hotkey binding:", error);
+    throw error;
+  }
+}
+
+export interface QueuedCard {
+  patient_id: number;
+  name: string;
+  wallet: string;
+  text: string;
+}
+
+export interface SendQueue {
+  hotkey: string;
+  cards: QueuedCard[];
+  next: number; // index of the card the next press types
+}
+
+/**
+ * Loads patients into the send queue: each press of the hotkey (Ctrl+Q by
+ * default) types the next card, starting over after the last
+ */
+export async function loadSendQueue(patientIds: number[], hotkey?: string): Promise<SendQueue> {
+  try {
+    return await invoke("load_send_queue", { patientIds, hotkey });
+  } catch (error) {
+    console.error("Failed to load send queue:", error);
+    throw error;
+  }
+}
+
+export async function getSendQueue(): Promise<SendQueue | null> {
+  try {
+    return await invoke("get_send_queue");
+  } catch (error) {
+    console.error("Failed to get send queue:", error);
+    return null;
+  }
+}
+
+/**
+ * @param patientIds The queued patients in their new order
+ * @param next Index of the card the next press types; keeps the current one when omitted
+ */
+export async function reorderSendQueue(patientIds: number[], next?: number): Promise<SendQueue> {
+  try {
+    return await invoke("reorder_send_queue", { patientIds, next });
+  } catch (error) {
+    console.error("Failed to reorder send queue:", error);
+    throw error;
+  }
+}
+
+export async function clearSendQueue(): Promise<boolean> {
+  try {
+    return await invoke("clear_send_queue");
+  } catch (error) {
+    console.error("Failed to clear send queue:", error);
+    throw error;
+  }
+}
+
+export interface AutoSendStatus {
+  running: boolean;
+  wallet: string;
+  interval_secs: number;
+  iterations: number;
+  sent: number;
+  failed: number;
+  last_error?: string | null;
+  started_at?: number | null;
+  last_send_at?: number | null;
+}
+
+/**
+ * Types the card every `intervalSecs` seconds, `iterations` times, without a
+ * key press; each send emits an "auto-send" event
+ */
+export async function startAutoSend(
+  target: { patientId: number } | { cardText: string },
+  intervalSecs: number,
+  iterations: number,
+): Promise<AutoSendStatus> {
+  const request = {
+    patient_id: "patientId" in target ? target.patientId : null,
+    card_text: "cardText" in target ? target.cardText : null,
+    interval_secs: intervalSecs,
+    iterations,
+  };
+  try {
+    return await invoke("start_auto_send", { request });
+  } catch (error) {
+    console.error("Failed to start auto send:", error);
+    throw error;
+  }
+}
+
+export async function stopAutoSend(): Promise<boolean> {
+  try {
+    return await invoke("stop_auto_send");
+  } catch (error) {
+    console.error("Failed to stop auto send:", error);
+    throw error;
+  }
+}
+
+export async function getAutoSendStatus(): Promise<AutoSendStatus | null> {
+  try {
+    return await invoke("get_auto_send_status");
+  } catch (error) {
+    console.error("Failed to get auto send status:", error);
+    return null;
+  }
+}
+
+export interface AhkScriptTemplateCheck {
+  path: string;
+  placeholders: string[];
+}
+
+/**
+ * Validates an AutoHotkey script template ({{directives}} and {{hotkeys}} are
+ * required, {{script_dir}} is optional)
+ * @param path Template file; the one in "ahk_script_template" when omitted
+ */
+export async function checkAhkScriptTemplate(path?: string): Promise<AhkScriptTemplateCheck> {
+  try {
+    return await invoke("check_ahk_script_template", { path });
+  } catch (error) {
+    console.error("Failed to check AutoHotkey script template:", error);
+    throw error;
+  }
+}
@@ -12,6 +12,11 @@ export interface BeneficiarySearchParams {
   modality?: string;
   proposal?: string;
   contract?: string;
+  page?: number;
+  page_size?: number;
+  // busca todas as páginas (eventos "beneficiary-search-progress")
+  all_pages?: boolean;
+  changed_after?: string;
 }
 
 export interface Beneficiary {
@@ -23,6 +28,11 @@ export interface Beneficiary {
   completeCardNumber?: string;
 }
 
+// Lê (e remove) um download grande gravado em disco, em base64
+function readDownloadedFile(path: string): Promise<string> {
+  return invoke("read_downloaded_file", { path });
+}
+
 export class PatientSyncService {
   private config: any = null;
 
@@ -123,11 +133,11 @@ export class PatientSyncService {
       // Garantir que é um array
       const fingerprintsArray = Array.isArray(fingerprints) ? fingerprints : [];
       
-      // Converter para o formato esperado
-      return fingerprintsArray.map((fp: any) => ({
+      // Converter para o formato esperado; digitais grandes vêm em arquivo
+      return await Promise.all(fingerprintsArray.map(async (fp: any) => ({
         fingerCode: fp.fingerCode || fp.code || 0,
-        biometry: fp.biometry || fp.data || ""
-      }));
+        biometry: fp.file ? await readDownloadedFile(fp.file) : (fp.biometry || fp.data || "")
+      })));
     } catch (error) {
       console.error("Erro ao buscar biometria digital:", error);
       return []; // Retorna array vazio em caso de erro
@@ -150,6 +160,9 @@ export class PatientSyncService {
 
       if (typeof raw === "string") {
         base64 = raw;
+      } else if (raw && typeof raw === "object" && (raw as any).file) {
+        // Fotos grandes são gravadas em disco pelo backend
+        base64 = await readDownloadedFile(String((raw as any).file));
       } else if (raw && typeof raw === "object" && (raw as any).photo) {
         base64 = String((raw as any).photo);
       }
@@ -1,5 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
-import { Patient } from "../types/patient";
+import { ExpectedCheckin, FaceAngle, FacialPhoto, Patient, PlanStatus } from "../types/patient";
 
 export async function loadPatients(): Promise<Patient[]> {
   const raw = (await invoke("load_patients")) as any;
@@ -7,24 +7,129 @@ export async function loadPatients(): Promise<Patient[]> {
   return (raw as any[]).map(toPatientCamel);
 }
 
+// Saves edits of several loaded patients at once; refused if any changed
+// since it was loaded. Patients missing from the list are kept.
 export async function savePatients(patients: Patient[]): Promise<void> {
   // map back to snake_case
   const snake = patients.map(toPatientSnake);
   await invoke("save_patients", { patients: snake });
 }
 
+export interface PatientQuery {
+  filter?: {
+    ids?: number[];
+    imported?: boolean;
+    tag?: string;
+    nameContains?: string;
+    walletPrefix?: string;
+    notesContains?: string;
+    expectedCheckin?: ExpectedCheckin;
+    planStatus?: PlanStatus;
+    gracePeriod?: boolean;
+  };
+  sort?: { field: "id" | "name" | "wallet"; descending?: boolean };
+  page?: number;
+  pageSize?: number;
+}
+
+export interface PatientPage {
+  items: Patient[];
+  total: number;
+  page: number;
+  pageSize: number;
+}
+
+// filtering, sorting and paging happen in the backend
+export async function queryPatients(query: PatientQuery = {}): Promise<PatientPage> {
+  const f = query.filter ?? {};
+  const raw = (await invoke("query_patients", {
+    filter: {
+      ids: f.ids,
+      imported: f.imported,
+      tag: f.tag,
+      name_contains: f.nameContains,
+      wallet_prefix: f.walletPrefix,
+      notes_contains: f.notesContains,
+      expected_checkin: f.expectedCheckin,
+      plan_status: f.planStatus,
+      grace_period: f.gracePeriod,
+    },
+    sort: query.sort,
+    page: query.page,
+    pageSize: query.pageSize,
+  })) as any;
+  return {
+    items: (raw.items as any[]).map(toPatientCamel),
+    total: raw.total,
+    page: raw.page,
+    pageSize: raw.page_size,
+  };
+}
+
+// full photo; lists only carry thumbnails
+export async function getPatientPhoto(id: number, label?: FaceAngle): Promise<string> {
+  return await invoke("get_patient_photo", { patientId: id, label });
+}
+
+export async function getPatient(id: number): Promise<Patient> {
+  return toPatientCamel(await invoke("get_patient", { id }));
+}
+
+// the backend assigns the id
+export async function addPatient(patient: Patient): Promise<Patient> {
+  return toPatientCamel(await invoke("add_patient", { patient: toPatientSnake(patient) }));
+}
+
+// fails if the patient changed since `patient.version` was loaded
+export async function updatePatient(patient: Patient): Promise<Patient> {
+  return toPatientCamel(await invoke("update_patient", { patient: toPatientSnake(patient) }));
+}
+
+export async function deletePatient(id: number, version?: number): Promise<void> {
+  await invoke("delete_patient", { id, version });
+}
+
+function frontal(photos: FacialPhoto[]): FacialPhoto | undefined {
+  return photos.find((f) => f.label === "frontal") ?? photos[0];
+}
+
+function frontalPhoto(photos: FacialPhoto[]): string {
+  return frontal(photos)?.data ?? "";
+}
+
+// facialBiometric is the one edited by most screens and wins over the list.
+// Photos that came from a list without data are sent back as they came; the
+// backend keeps the stored ones.
+function toFacialPhotos(p: Patient): FacialPhoto[] {
+  const photos = p.facialPhotos ?? [];
+  const others = photos.filter((f) => f.label !== "frontal");
+  if (p.facialBiometric) {
+    return [{ label: "frontal", data: p.facialBiometric }, ...others];
+  }
+  const listed = photos.find((f) => f.label === "frontal" && !f.data && f.thumbnail);
+  return listed ? [listed, ...others] : others;
+}
+
 function toPatientCamel(raw: any): Patient {
   return {
     id: raw.id,
     // Tentar múltiplas chaves possíveis para nome
     name: (raw.name ?? raw.full_name ?? raw.fullName ?? raw.nome ?? "") as string,
     wallet: raw.wallet,
-    facialBiometric: raw.facial_biometric ?? "",
+    facialBiometric: frontalPhoto(raw.facial_biometrics ?? []),
+    facialThumbnail: frontal(raw.facial_biometrics ?? [])?.thumbnail ?? "",
+    facialPhotos: raw.facial_biometrics ?? [],
     digitalBiometrics: (raw.digital_biometrics ?? []).map((d: any) => ({
       finger: d.finger,
       data: d.data,
     })),
     imported: !!raw.imported,
+    tags: raw.tags ?? [],
+    attachments: raw.attachments ?? [],
+    verificationHistory: raw.verification_history ?? [],
+    notes: raw.notes ?? "",
+    scenario: raw.scenario ?? {},
+    version: raw.version ?? 0,
   } as Patient;
 }
 
@@ -33,8 +138,15 @@ function toPatientSnake(p: Patient): any {
     id: p.id,
     name: p.name,
     wallet: p.wallet,
-    facial_biometric: p.facialBiometric,
+    facial_biometrics: toFacialPhotos(p),
     digital_biometrics: p.digitalBiometrics.map(({ finger, data }) => ({ finger, data })),
     imported: p.imported,
+    // kept so a save-all doesn't drop data managed by backend commands
+    tags: p.tags ?? [],
+    attachments: p.attachments ?? [],
+    verification_history: p.verificationHistory ?? [],
+    notes: p.notes ?? "",
+    scenario: p.scenario ?? {},
+    version: p.version ?? 0,
   };
 }
\ No newline at end of file
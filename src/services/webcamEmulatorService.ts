@@ -1,4 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
+import { FaceAngle } from "../types/patient";
 
 /**
  * Source type for the webcam emulator
@@ -29,6 +30,27 @@ export async function startWebcamEmulator(
   }
 }
 
+/**
+ * Starts the webcam emulator streaming one of the patient's photos
+ * @param patientId Patient whose photo is streamed
+ * @param label Which photo; the frontal one when omitted
+ * @returns Promise resolving to true if successful
+ */
+export async function startWebcamPatientPhoto(
+  patientId: number,
+  label?: FaceAngle
+): Promise<boolean> {
+  try {
+    return await invoke("start_webcam_patient_photo", {
+      patientId,
+      label
+    });
+  } catch (error) {
+    console.error("Failed to start webcam emulator:", error);
+    throw error;
+  }
+}
+
 /**
  * Stops the webcam emulator
  * @returns Promise resolving to true if successful
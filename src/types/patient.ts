@@ -3,11 +3,58 @@ export interface DigitalBiometric {
   data: string;   // base64 PNG ou outro formato
 }
 
+export type FaceAngle = "frontal" | "left" | "right" | "with-mask";
+
+export interface FacialPhoto {
+  label: FaceAngle;
+  data: string; // base64; empty in lists, see getPatientPhoto
+  thumbnail?: string; // small JPEG, base64
+}
+
+export interface Attachment {
+  id: number;
+  file_name: string;
+  mime_type: string;
+  size: number;
+  blob_key: string;
+  added_at: number;
+}
+
+export interface VerificationEntry {
+  timestamp: number;
+  endpoint: string;
+  matched: boolean;
+  score: number;
+}
+
+export type ExpectedCheckin = "approved" | "denied" | "pending_authorization";
+
+export type PlanStatus = "active" | "suspended" | "cancelled" | "defaulting";
+
+// what the test case is meant to prove; documentation only
+export interface Scenario {
+  expected_checkin?: ExpectedCheckin | null;
+  plan_status?: PlanStatus | null;
+  grace_period?: boolean; // em carência
+  partial_coverage?: boolean; // CPT
+  grace_procedures?: string[];
+}
+
 export interface Patient {
   id: number;
   name: string;
   wallet: string;
-  facialBiometric: string;
+  facialBiometric: string; // the frontal photo; empty in lists
+  facialThumbnail?: string;
+  // every photo, frontal included; liveness tests use the other angles
+  facialPhotos?: FacialPhoto[];
   digitalBiometrics: DigitalBiometric[];
   imported: boolean;
+  tags?: string[];
+  attachments?: Attachment[];
+  verificationHistory?: VerificationEntry[];
+  notes?: string;
+  scenario?: Scenario;
+  // set by the backend; update_patient rejects a stale one
+  version?: number;
 }
\ No newline at end of file